@@ -0,0 +1,53 @@
+use dioxus::prelude::*;
+use crate::service::LocalStorage;
+
+type StorageResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 整个应用共用的本地存储，由根组件打开一次后通过上下文提供给各组件
+// 数据库连接在副本之间共享，后台任务取一份副本使用
+#[derive(Clone, Copy)]
+pub struct SharedStorage(Signal<Option<LocalStorage>>);
+
+impl SharedStorage {
+    // 在渲染中读取存储，存储变化（如修改设置）时重新渲染
+    pub fn read_with<T>(&self, f: impl FnOnce(&LocalStorage) -> T) -> Option<T> {
+        self.0.read().as_ref().map(f)
+    }
+
+    // 取一份存储副本，用于事件处理和后台任务，不订阅变化
+    pub fn handle(&self) -> StorageResult<LocalStorage> {
+        self.0.peek().clone().ok_or_else(|| "本地存储不可用".into())
+    }
+
+    // 修改存储中的应用设置，并通知读取设置的组件
+    pub fn update<T>(&mut self, f: impl FnOnce(&mut LocalStorage) -> StorageResult<T>) -> StorageResult<T> {
+        match self.0.write().as_mut() {
+            Some(storage) => f(storage),
+            None => Err("本地存储不可用".into()),
+        }
+    }
+
+    // 用后台任务修改过的副本替换共享存储
+    pub fn replace(&mut self, storage: LocalStorage) {
+        self.0.set(Some(storage));
+    }
+}
+
+// 在根组件中打开本地存储
+pub fn provide_storage() -> SharedStorage {
+    use_context_provider(|| {
+        let storage = match LocalStorage::new() {
+            Ok(storage) => Some(storage),
+            Err(e) => {
+                println!("初始化本地存储失败: {}", e);
+                None
+            }
+        };
+        SharedStorage(Signal::new(storage))
+    })
+}
+
+// 获取根组件提供的本地存储
+pub fn use_storage() -> SharedStorage {
+    use_context()
+}
@@ -1,25 +1,34 @@
 use dioxus::prelude::*;
-use crate::models::email::{EmailAccount, Email, EmailStatus};
+use crate::models::email::{parse_address_list, EmailAccount, Email, EmailStatus, Mailbox};
 use crate::service::smtp_client; // 添加 SMTP 客户端模块导入
+use crate::service::{imap_client, Suggestion};
+use crate::components::use_storage;
+use crate::service::mail_db::FLAG_ANSWERED;
 
 #[derive(Props, PartialEq, Clone)]
 pub struct ComposerProps {
     pub account: Option<EmailAccount>,
     #[props(default = false)]
     pub full_width: bool,
+    // 回复的原邮件，用于预填收件人、主题和引用正文
+    #[props(default)]
+    pub reply_to: Option<Email>,
 }
 
 // 恢复美化后的写邮件界面组件
 pub fn Composer(props: ComposerProps) -> Element {
     let account = props.account.clone();
     
-    let mut to = use_signal(|| String::new());
-    let mut cc = use_signal(|| String::new());
-    let mut bcc = use_signal(|| String::new());
-    let mut subject = use_signal(|| String::new());
-    let mut body = use_signal(|| String::new());
-    let mut attachments = use_signal(|| Vec::new());
+    let reply_to = props.reply_to.clone();
+    
+    let to = use_signal(|| reply_to.as_ref().map_or(String::new(), |e| e.from.to_string()));
+    let cc = use_signal(String::new);
+    let bcc = use_signal(String::new);
+    let mut subject = use_signal(|| reply_to.as_ref().map_or(String::new(), |e| reply_subject(&e.subject)));
+    let mut body = use_signal(|| reply_to.as_ref().map_or(String::new(), quote_body));
+    let attachments = use_signal(Vec::new);
     let mut sending = use_signal(|| false);
+    let shared_storage = use_storage();
     let mut send_status = use_signal(|| EmailStatus::Draft);
 
     // 处理发送
@@ -34,30 +43,52 @@ pub fn Composer(props: ComposerProps) -> Element {
         
         // 异步发送
         spawn({
-            let mut to = to.clone();
-            let mut cc = cc.clone();
-            let mut bcc = bcc.clone();
-            let mut subject = subject.clone();
-            let mut body = body.clone();
-            let mut attachments = attachments.clone();
-            let mut send_status = send_status.clone();
-            let mut sending = sending.clone();
+            let mut to = to;
+            let mut cc = cc;
+            let mut bcc = bcc;
+            let mut subject = subject;
+            let mut body = body;
+            let mut attachments = attachments;
+            let mut send_status = send_status;
+            let mut sending = sending;
             let account = account.clone();
+            let replied = reply_to.clone();
             
             async move {
                 // 构建邮件
                 if let Some(account) = account.as_ref() {
+                    // 收件人中的通讯录分组展开为成员
+                    let storage = shared_storage.handle().ok();
+                    let expand = |text: &str| {
+                        let list = parse_address_list(text);
+                        match storage.as_ref() {
+                            Some(storage) => storage.address_book().expand_groups(list.clone()).unwrap_or(list),
+                            None => list,
+                        }
+                    };
                     let email = Email {
                         id: "".to_string(),
-                        from: account.address.clone(),
-                        to: to.read().split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
-                        cc: cc.read().split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
-                        bcc: bcc.read().split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                        from: Mailbox::new(account.address.clone()),
+                        to: expand(to.read().as_str()),
+                        cc: expand(cc.read().as_str()),
+                        bcc: expand(bcc.read().as_str()),
                         subject: subject.read().clone(),
                         body: body.read().clone(),
+                        html: None,
+                        body_view: None,
                         date: chrono::Local::now().to_rfc3339(),
                         attachments: attachments.read().clone(),
                         status: EmailStatus::Draft, // 初始状态为草稿
+                        seen: true,
+                        account: account.address.clone(),
+                        folder: String::new(),
+                        labels: Vec::new(),
+                        flagged: false,
+                        answered: false,
+                        snippet: String::new(),
+                        raw: None,
+                        received: None,
+                        server_folder: None,
                     };
                     
                     // 使用SMTP客户端发送邮件 - 移除 .await
@@ -67,13 +98,35 @@ pub fn Composer(props: ComposerProps) -> Element {
                         &account.address,
                         &account.password,
                         &email,
-                        account.use_tls,
+                        account.smtp_tls(),
                     );
                     
                     match result {
                         Ok(_) => {
                             // 发送成功
                             send_status.set(EmailStatus::Sent);
+
+                            // 收件人加入通讯录，之后自动补全时排在前面
+                            if let Some(storage) = storage.as_ref() {
+                                if let Err(e) = storage.address_book().record_sent(&email) {
+                                    println!("更新通讯录失败: {}", e);
+                                }
+                            }
+
+                            // 原邮件标记为已回复，并同步到服务器上原邮件所在的文件夹
+                            if let Some(original) = replied.as_ref().filter(|e| e.account == account.address) {
+                                let folder = if original.folder.is_empty() { "INBOX" } else { original.folder.as_str() };
+                                if let Some(storage) = storage.as_ref() {
+                                    if let Err(e) = storage.set_flag(account, folder, &original.id, FLAG_ANSWERED, true) {
+                                        println!("标记已回复失败: {}", e);
+                                    }
+                                }
+                                if let Some((mailbox, uid)) = original.server_location() {
+                                    if let Err(e) = imap_client::store_flags(account, mailbox, &[uid], &[FLAG_ANSWERED], &[]).await {
+                                        println!("同步已回复标记失败: {}", e);
+                                    }
+                                }
+                            }
                             
                             // 清空表单
                             to.set(String::new());
@@ -120,45 +173,27 @@ pub fn Composer(props: ComposerProps) -> Element {
                 class: "composer-form",
                 
                 // 收件人
-                div {
-                    class: "form-group",
-                    label { r#for: "to", "收件人:" }
-                    input {
-                        id: "to",
-                        class: "form-control",
-                        r#type: "text",
-                        value: "{to}",
-                        oninput: move |e| to.set(e.value().to_string()),
-                        placeholder: "多个收件人请用逗号分隔"
-                    }
+                RecipientInput {
+                    id: "to".to_string(),
+                    label: "收件人:".to_string(),
+                    placeholder: "多个收件人请用逗号分隔".to_string(),
+                    value: to,
                 }
                 
                 // 抄送
-                div {
-                    class: "form-group",
-                    label { r#for: "cc", "抄送:" }
-                    input {
-                        id: "cc",
-                        class: "form-control",
-                        r#type: "text",
-                        value: "{cc}",
-                        oninput: move |e| cc.set(e.value().to_string()),
-                        placeholder: "多个抄送请用逗号分隔"
-                    }
+                RecipientInput {
+                    id: "cc".to_string(),
+                    label: "抄送:".to_string(),
+                    placeholder: "多个抄送请用逗号分隔".to_string(),
+                    value: cc,
                 }
                 
                 // 密送
-                div {
-                    class: "form-group",
-                    label { r#for: "bcc", "密送:" }
-                    input {
-                        id: "bcc",
-                        class: "form-control",
-                        r#type: "text",
-                        value: "{bcc}",
-                        oninput: move |e| bcc.set(e.value().to_string()),
-                        placeholder: "多个密送请用逗号分隔"
-                    }
+                RecipientInput {
+                    id: "bcc".to_string(),
+                    label: "密送:".to_string(),
+                    placeholder: "多个密送请用逗号分隔".to_string(),
+                    value: bcc,
                 }
                 
                 // 主题
@@ -236,4 +271,121 @@ pub fn Composer(props: ComposerProps) -> Element {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// 收件人输入框，根据正在输入的地址从通讯录中给出候选
+#[component]
+fn RecipientInput(id: String, label: String, placeholder: String, value: Signal<String>) -> Element {
+    let storage = use_storage();
+    let mut suggestions = use_signal(Vec::<Suggestion>::new);
+    let mut highlighted = use_signal(|| 0usize);
+
+    let on_input = move |e: FormEvent| {
+        let text = e.value();
+        let list = storage
+            .handle()
+            .ok()
+            .and_then(|s| s.address_book().suggest(current_entry(&text)).ok())
+            .unwrap_or_default();
+        value.set(text);
+        highlighted.set(0);
+        suggestions.set(list);
+    };
+
+    // 用候选替换正在输入的地址
+    let mut choose = move |suggestion: Suggestion| {
+        let text = value.read().clone();
+        let kept = text.rfind(',').map_or("", |i| &text[..=i]);
+        let separator = if kept.is_empty() { "" } else { " " };
+        value.set(format!("{}{}{}, ", kept, separator, suggestion.text));
+        suggestions.set(Vec::new());
+    };
+
+    let on_keydown = move |e: KeyboardEvent| {
+        let count = suggestions.read().len();
+        if count == 0 {
+            return;
+        }
+        match e.key() {
+            Key::ArrowDown => {
+                e.prevent_default();
+                let next = (*highlighted.read() + 1) % count;
+                highlighted.set(next);
+            }
+            Key::ArrowUp => {
+                e.prevent_default();
+                let previous = (*highlighted.read() + count - 1) % count;
+                highlighted.set(previous);
+            }
+            Key::Enter | Key::Tab => {
+                e.prevent_default();
+                let selected = suggestions.read().get(*highlighted.read()).cloned();
+                if let Some(suggestion) = selected {
+                    choose(suggestion);
+                }
+            }
+            Key::Escape => suggestions.set(Vec::new()),
+            _ => {}
+        }
+    };
+
+    rsx! {
+        div {
+            class: "form-group recipient-input",
+            label { r#for: "{id}", "{label}" }
+            input {
+                id: "{id}",
+                class: "form-control",
+                r#type: "text",
+                autocomplete: "off",
+                value: "{value}",
+                oninput: on_input,
+                onkeydown: on_keydown,
+                onblur: move |_| suggestions.set(Vec::new()),
+                placeholder: "{placeholder}"
+            }
+            if !suggestions.read().is_empty() {
+                ul {
+                    class: "autocomplete-list",
+                    for (index, suggestion) in suggestions.read().iter().cloned().enumerate() {
+                        li {
+                            class: if index == *highlighted.read() { "autocomplete-item active" } else { "autocomplete-item" },
+                            // 在输入框失去焦点之前选中
+                            onmousedown: move |e: MouseEvent| {
+                                e.prevent_default();
+                                choose(suggestion.clone());
+                            },
+                            span {
+                                class: "autocomplete-label",
+                                if suggestion.is_group { "👥 " }
+                                "{suggestion.label}"
+                            }
+                            span { class: "autocomplete-detail", "{suggestion.detail}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 输入框中最后一个逗号之后正在输入的地址
+fn current_entry(text: &str) -> &str {
+    text.rsplit(',').next().unwrap_or(text).trim()
+}
+
+// 回复主题加上 "Re: " 前缀，已有前缀时不重复添加
+fn reply_subject(subject: &str) -> String {
+    let trimmed = subject.trim();
+    if trimmed.to_lowercase().starts_with("re:") || trimmed.starts_with("回复：") {
+        trimmed.to_string()
+    } else {
+        format!("Re: {}", trimmed)
+    }
+}
+
+// 引用原邮件正文
+fn quote_body(email: &Email) -> String {
+    let quoted: Vec<String> = email.body.lines().map(|line| format!("> {}", line)).collect();
+    format!("\n\n在 {}，{} 写道：\n{}", email.full_date(), email.from.display_name(), quoted.join("\n"))
+}
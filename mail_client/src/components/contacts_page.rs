@@ -0,0 +1,436 @@
+use dioxus::prelude::*;
+use crate::models::{format_address_list, parse_address_list, Address, Contact, ContactGroup};
+use crate::service::VCardVersion;
+use crate::components::use_storage;
+
+// 正在编辑的联系人，多个地址和电话每行一个
+#[derive(Clone, Default, PartialEq)]
+struct ContactForm {
+    uid: String,
+    name: String,
+    emails: String,
+    phones: String,
+    organization: String,
+    note: String,
+}
+
+impl ContactForm {
+    fn from_contact(contact: &Contact) -> Self {
+        ContactForm {
+            uid: contact.uid.clone(),
+            name: contact.name.clone(),
+            emails: contact.emails.join("\n"),
+            phones: contact.phones.join("\n"),
+            organization: contact.organization.clone(),
+            note: contact.note.clone(),
+        }
+    }
+
+    // 手动保存后不再是自动收集的联系人
+    fn to_contact(&self) -> Contact {
+        let lines = |text: &str| -> Vec<String> {
+            text.split(['\n', ',', ';'])
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        };
+        Contact {
+            uid: self.uid.clone(),
+            name: self.name.trim().to_string(),
+            emails: lines(&self.emails),
+            phones: self.phones.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect(),
+            organization: self.organization.trim().to_string(),
+            note: self.note.clone(),
+            harvested: false,
+        }
+    }
+}
+
+// 正在编辑的分组，成员为逗号分隔的地址列表
+#[derive(Clone, Default, PartialEq)]
+struct GroupForm {
+    uid: String,
+    name: String,
+    members: String,
+}
+
+pub fn ContactsPage() -> Element {
+    let shared_storage = use_storage();
+    let storage = use_hook(|| shared_storage.handle().map_err(|e| e.to_string()));
+    // 通讯录修改后加一，重新读取列表
+    let mut revision = use_signal(|| 0u64);
+    let mut search = use_signal(String::new);
+    let mut editing = use_signal(|| None::<ContactForm>);
+    let mut editing_group = use_signal(|| None::<GroupForm>);
+    let mut export_version = use_signal(|| VCardVersion::V4);
+    let mut status = use_signal(String::new);
+
+    let storage = match storage {
+        Ok(storage) => storage,
+        Err(e) => return rsx! { div { class: "empty-state", "无法打开通讯录: {e}" } },
+    };
+
+    let _ = revision.read();
+    let query = search.read().trim().to_lowercase();
+    let contacts: Vec<Contact> = storage
+        .address_book()
+        .contacts()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| {
+            query.is_empty()
+                || c.name.to_lowercase().contains(&query)
+                || c.emails.iter().any(|e| e.to_lowercase().contains(&query))
+        })
+        .collect();
+    let groups = storage.address_book().groups().unwrap_or_default();
+
+    let save_storage = storage.clone();
+    let save_contact = move |_| {
+        let Some(form) = editing.read().clone() else {
+            return;
+        };
+        let contact = form.to_contact();
+        if contact.name.is_empty() && contact.emails.is_empty() {
+            status.set("请填写姓名或邮箱地址".to_string());
+            return;
+        }
+        match save_storage.address_book().save_contact(&contact) {
+            Ok(_) => {
+                status.set(format!("已保存 {}", contact.display_name()));
+                editing.set(None);
+                revision += 1;
+            }
+            Err(e) => status.set(format!("保存失败: {}", e)),
+        }
+    };
+
+    let delete_storage = storage.clone();
+    let delete_contact = move |_| {
+        let Some(form) = editing.read().clone() else {
+            return;
+        };
+        if !form.uid.is_empty() {
+            if let Err(e) = delete_storage.address_book().delete_contact(&form.uid) {
+                status.set(format!("删除失败: {}", e));
+                return;
+            }
+        }
+        editing.set(None);
+        revision += 1;
+    };
+
+    let group_storage = storage.clone();
+    let save_group = move |_| {
+        let Some(form) = editing_group.read().clone() else {
+            return;
+        };
+        let group = ContactGroup {
+            uid: form.uid.clone(),
+            name: form.name.trim().to_string(),
+            members: parse_address_list(&form.members)
+                .iter()
+                .flat_map(|a| a.mailboxes().to_vec())
+                .collect(),
+        };
+        match group_storage.address_book().save_group(&group) {
+            Ok(_) => {
+                status.set(format!("已保存分组 {}", group.name));
+                editing_group.set(None);
+                revision += 1;
+            }
+            Err(e) => status.set(format!("保存失败: {}", e)),
+        }
+    };
+
+    let delete_group_storage = storage.clone();
+    let delete_group = move |_| {
+        let Some(form) = editing_group.read().clone() else {
+            return;
+        };
+        if !form.uid.is_empty() {
+            if let Err(e) = delete_group_storage.address_book().delete_group(&form.uid) {
+                status.set(format!("删除失败: {}", e));
+                return;
+            }
+        }
+        editing_group.set(None);
+        revision += 1;
+    };
+
+    let import_storage = storage.clone();
+    let import_vcards = move |_| {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("导入 vCard")
+            .add_filter("vCard", &["vcf", "vcard"])
+            .pick_file()
+        else {
+            return;
+        };
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| e.into())
+            .and_then(|text| import_storage.address_book().import_vcards(&text));
+        match result {
+            Ok(count) => status.set(format!("已导入 {} 个联系人和分组", count)),
+            Err(e) => status.set(format!("导入失败: {}", e)),
+        }
+        revision += 1;
+    };
+
+    let export_storage = storage.clone();
+    let export_vcards = move |_| {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("导出 vCard")
+            .set_file_name("通讯录.vcf")
+            .add_filter("vCard", &["vcf"])
+            .save_file()
+        else {
+            return;
+        };
+        let result = export_storage
+            .address_book()
+            .export_vcards(*export_version.read())
+            .and_then(|text| std::fs::write(&path, text).map_err(|e| e.into()));
+        match result {
+            Ok(()) => status.set(format!("已导出到 {}", path.display())),
+            Err(e) => status.set(format!("导出失败: {}", e)),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "settings-container",
+            div {
+                class: "composer-header",
+                h2 { "通讯录" }
+            }
+            div {
+                class: "settings-body",
+                div {
+                    class: "contacts-toolbar",
+                    input {
+                        class: "form-control contacts-search",
+                        r#type: "text",
+                        placeholder: "搜索姓名或地址",
+                        value: "{search}",
+                        oninput: move |e| search.set(e.value()),
+                    }
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| {
+                            editing_group.set(None);
+                            editing.set(Some(ContactForm::default()));
+                        },
+                        "新建联系人"
+                    }
+                    button {
+                        class: "btn btn-secondary",
+                        onclick: move |_| {
+                            editing.set(None);
+                            editing_group.set(Some(GroupForm::default()));
+                        },
+                        "新建分组"
+                    }
+                    button { class: "btn btn-secondary", onclick: import_vcards, "导入 vCard" }
+                    select {
+                        class: "form-control",
+                        value: if *export_version.read() == VCardVersion::V3 { "3" } else { "4" },
+                        onchange: move |e| export_version.set(if e.value() == "3" { VCardVersion::V3 } else { VCardVersion::V4 }),
+                        option { value: "4", "vCard 4.0" }
+                        option { value: "3", "vCard 3.0" }
+                    }
+                    button { class: "btn btn-secondary", onclick: export_vcards, "导出 vCard" }
+                    span { class: "status-message", "{status}" }
+                }
+
+                // 联系人编辑表单
+                if let Some(form) = editing.read().clone() {
+                    section {
+                        class: "settings-section",
+                        h3 { if form.uid.is_empty() { "新建联系人" } else { "编辑联系人" } }
+                        div {
+                            class: "form-group",
+                            label { "姓名" }
+                            input {
+                                class: "form-control",
+                                r#type: "text",
+                                value: "{form.name}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing.write().as_mut() {
+                                        form.name = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "form-group",
+                            label { "邮箱地址（每行一个，第一个为默认地址）" }
+                            textarea {
+                                class: "form-textarea",
+                                rows: "3",
+                                value: "{form.emails}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing.write().as_mut() {
+                                        form.emails = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "form-group",
+                            label { "电话（每行一个）" }
+                            textarea {
+                                class: "form-textarea",
+                                rows: "2",
+                                value: "{form.phones}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing.write().as_mut() {
+                                        form.phones = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "form-group",
+                            label { "单位" }
+                            input {
+                                class: "form-control",
+                                r#type: "text",
+                                value: "{form.organization}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing.write().as_mut() {
+                                        form.organization = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "form-group",
+                            label { "备注" }
+                            textarea {
+                                class: "form-textarea",
+                                rows: "3",
+                                value: "{form.note}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing.write().as_mut() {
+                                        form.note = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "settings-actions",
+                            button { class: "btn btn-primary", onclick: save_contact, "保存" }
+                            button { class: "btn btn-secondary", onclick: move |_| editing.set(None), "取消" }
+                            if !form.uid.is_empty() {
+                                button { class: "btn btn-danger", onclick: delete_contact, "删除" }
+                            }
+                        }
+                    }
+                }
+
+                // 分组编辑表单
+                if let Some(form) = editing_group.read().clone() {
+                    section {
+                        class: "settings-section",
+                        h3 { if form.uid.is_empty() { "新建分组" } else { "编辑分组" } }
+                        div {
+                            class: "form-group",
+                            label { "分组名称（写信时输入分组名称即可发送给所有成员）" }
+                            input {
+                                class: "form-control",
+                                r#type: "text",
+                                value: "{form.name}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing_group.write().as_mut() {
+                                        form.name = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "form-group",
+                            label { "成员（逗号分隔）" }
+                            textarea {
+                                class: "form-textarea",
+                                rows: "4",
+                                value: "{form.members}",
+                                oninput: move |e| {
+                                    if let Some(form) = editing_group.write().as_mut() {
+                                        form.members = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        div {
+                            class: "settings-actions",
+                            button { class: "btn btn-primary", onclick: save_group, "保存" }
+                            button { class: "btn btn-secondary", onclick: move |_| editing_group.set(None), "取消" }
+                            if !form.uid.is_empty() {
+                                button { class: "btn btn-danger", onclick: delete_group, "删除" }
+                            }
+                        }
+                    }
+                }
+
+                if !groups.is_empty() {
+                    section {
+                        class: "settings-section",
+                        h3 { "分组" }
+                        ul {
+                            class: "contact-list",
+                            for group in groups {
+                                li {
+                                    class: "contact-item",
+                                    onclick: {
+                                        let form = GroupForm {
+                                            uid: group.uid.clone(),
+                                            name: group.name.clone(),
+                                            members: format_address_list(
+                                                &group.members.iter().cloned().map(Address::Mailbox).collect::<Vec<_>>(),
+                                            ),
+                                        };
+                                        move |_| {
+                                            editing.set(None);
+                                            editing_group.set(Some(form.clone()));
+                                        }
+                                    },
+                                    span { class: "contact-name", "👥 {group.name}" }
+                                    span { class: "contact-email", "{group.members.len()} 位成员" }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                section {
+                    class: "settings-section",
+                    h3 { "联系人（{contacts.len()}）" }
+                    if contacts.is_empty() {
+                        p { class: "settings-hint", "没有联系人，收发邮件后会自动加入" }
+                    }
+                    ul {
+                        class: "contact-list",
+                        for contact in contacts {
+                            li {
+                                class: "contact-item",
+                                onclick: {
+                                    let form = ContactForm::from_contact(&contact);
+                                    move |_| {
+                                        editing_group.set(None);
+                                        editing.set(Some(form.clone()));
+                                    }
+                                },
+                                span { class: "contact-name", "{contact.display_name()}" }
+                                span { class: "contact-email", {contact.emails.join(", ")} }
+                                if contact.harvested {
+                                    span { class: "contact-tag", "自动收集" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,271 @@
+use std::collections::HashMap;
+use std::io::Read;
 use dioxus::prelude::*;
-use crate::models::Email;
+use crate::components::plain_text::PlainTextBody;
+use crate::components::{LabelChips, LabelPicker, use_storage};
+use crate::models::{format_address_list, BodyView, Email, Label, MimePart};
+use crate::models::email::EmailAccount;
+use crate::service::{caldav, eml, Invitation};
+use crate::service::spam::{self, JUNK_FOLDER, KEYWORD_JUNK, KEYWORD_NOT_JUNK};
+use crate::service::label;
 
 #[derive(Props, PartialEq, Clone)]
 pub struct EmailDetailProps {
     pub email: Email,
+    pub on_reply: Option<EventHandler<Email>>,
+    // 接收该邮件的账户，用于从本地存储读取邮件原文
+    #[props(default)]
+    pub account: Option<EmailAccount>,
+    // 从文件或附件打开的邮件的原始内容
+    #[props(default)]
+    pub raw: Option<Vec<u8>>,
+    // 打开附带的邮件（message/rfc822），参数为解析后的邮件和原始内容
+    #[props(default)]
+    pub on_open_message: Option<EventHandler<(Email, Vec<u8>)>>,
+    // 邮件所在的本地文件夹，邮件本身记录了文件夹时以邮件为准
+    #[props(default = "INBOX".to_string())]
+    pub folder: String,
+    // 邮件被移到其他文件夹（如标记为垃圾邮件）后通知父组件刷新列表
+    #[props(default)]
+    pub on_moved: Option<EventHandler<()>>,
+    // 标签有变化时通知父组件，参数为更新后的邮件
+    #[props(default)]
+    pub on_labels_changed: Option<EventHandler<Email>>,
+    // 标签定义，由根组件读取
+    #[props(default)]
+    pub labels: Vec<Label>,
 }
 
-pub fn EmailDetail(props: EmailDetailProps) -> Element {
+// 会议邀请最多读取的字节数
+const MAX_INVITATION_SIZE: u64 = 1024 * 1024;
+
+// 原文面板显示的内容
+#[derive(Clone, Copy, PartialEq)]
+enum SourceView {
+    Headers, // 完整的邮件头
+    Raw,     // 完整原文
+    Structure, // MIME 结构
+}
+
+// 邮件头部分（第一个空行之前）
+fn header_block(raw: &str) -> &str {
+    let end = raw
+        .find("\r\n\r\n")
+        .or_else(|| raw.find("\n\n"))
+        .unwrap_or(raw.len());
+    &raw[..end]
+}
+
+pub fn EmailDetail(mut props: EmailDetailProps) -> Element {
+    if !props.email.folder.is_empty() {
+        props.folder = props.email.folder.clone();
+    }
     let email = &props.email;
+    let reply_email = props.email.clone();
+    let mut save_status = use_signal(|| None::<String>);
+    let mut source_view = use_signal(|| None::<SourceView>);
+    let mut copied = use_signal(|| false);
+    let mut accepting = use_signal(|| false);
+    // 本次打开后切换过显示方式的邮件，列表中的邮件副本不会随之更新
+    let mut view_overrides = use_signal(HashMap::<(String, String), BodyView>::new);
+    let storage = use_storage();
+
+    // 正文显示方式：单封邮件的选择优先，其次为默认设置
+    let default_view = storage.read_with(|storage| storage.body_view()).unwrap_or_default();
+    let view_key = (email.account.clone(), email.id.clone());
+    let body_view = view_overrides
+        .read()
+        .get(&view_key)
+        .copied()
+        .or(email.body_view)
+        .unwrap_or(default_view);
+    let set_body_view = {
+        let account = props.account.clone();
+        let folder = props.folder.clone();
+        let from_file = props.raw.is_some();
+        move |view: BodyView| {
+            view_overrides.write().insert(view_key.clone(), view);
+            let Some(account) = account.as_ref().filter(|_| !from_file) else {
+                return;
+            };
+            // 与默认设置相同时不单独记录，之后修改默认设置对该邮件也生效
+            let stored = (view != default_view).then_some(view);
+            if let Err(e) = storage
+                .handle()
+                .and_then(|storage| storage.set_message_body_view(account, &folder, &view_key.1, stored))
+            {
+                println!("保存显示方式失败: {}", e);
+            }
+        }
+    };
+
+    // 附带的会议邀请，只有 REQUEST 可以接受；在后台读取和解析，邀请文件很小，过大的附件只读取开头部分
+    let calendar = email.attachments.iter().find(|a| caldav::is_calendar_attachment(a)).cloned();
+    let invitation = use_resource(use_reactive((&email.id, &calendar), move |(_, calendar)| {
+        let storage = storage.handle();
+        async move {
+            let attachment = calendar?;
+            async_std::task::spawn_blocking(move || {
+                let mut data = Vec::new();
+                storage.ok()?.open_attachment(&attachment).ok()?.take(MAX_INVITATION_SIZE).read_to_end(&mut data).ok()?;
+                let ics = String::from_utf8_lossy(&data).into_owned();
+                Invitation::parse(&ics).filter(Invitation::is_request).map(|invitation| (invitation, ics))
+            })
+            .await
+        }
+    }));
+    let invitation = invitation.read().clone().flatten();
+
+    // 打开原文面板时在后台读取邮件原文，切换邮件或视图时重新读取
+    let source = use_resource(use_reactive(
+        (&props.email, &props.folder, &props.account, &props.raw),
+        move |(email, folder, account, raw)| {
+            let view = *source_view.read();
+            let storage = storage.handle();
+            async move {
+                let view = view?;
+                let source = async_std::task::spawn_blocking(move || {
+                    let raw = match (raw, account) {
+                        (Some(raw), _) => Ok(Some(raw)),
+                        (None, Some(account)) => storage
+                            .and_then(|storage| storage.raw_source(&account, &folder, &email))
+                            .map_err(|e| e.to_string()),
+                        (None, None) => Err("找不到邮件所属的账户".to_string()),
+                    };
+                    match raw {
+                        Ok(Some(raw)) => {
+                            let text = String::from_utf8_lossy(&raw).into_owned();
+                            Ok(match view {
+                                SourceView::Headers => header_block(&text).to_string(),
+                                SourceView::Raw => text,
+                                SourceView::Structure => MimePart::parse(&raw)
+                                    .map(|root| root.outline())
+                                    .unwrap_or_else(|e| format!("无法解析邮件结构: {}", e)),
+                            })
+                        }
+                        Ok(None) => Err("该邮件由旧版本同步，没有保存原文".to_string()),
+                        Err(e) => Err(format!("读取邮件原文失败: {}", e)),
+                    }
+                })
+                .await;
+                Some(source)
+            }
+        },
+    ));
+    let source: Option<Result<String, String>> = source.read().clone().flatten();
+    let copy_text = source.clone().and_then(|s| s.ok());
+
+    // 另存为 .eml，保存邮件原文
+    let save_email = props.email.clone();
+    let save_account = props.account.clone();
+    let save_raw = props.raw.clone();
+    let save_folder = props.folder.clone();
+    let on_save = move |_| {
+        let raw = match (&save_raw, &save_account) {
+            (Some(raw), _) => Ok(raw.clone()),
+            (None, Some(account)) => storage
+                .handle()
+                .and_then(|storage| storage.message_source(account, &save_folder, &save_email)),
+            (None, None) => Err("找不到邮件所属的账户".into()),
+        };
+        let raw = match raw {
+            Ok(raw) => raw,
+            Err(e) => {
+                save_status.set(Some(format!("读取邮件原文失败: {}", e)));
+                return;
+            }
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("另存为")
+            .set_file_name(eml::file_name_for(&save_email.subject))
+            .add_filter("邮件", &["eml"])
+            .save_file()
+        else {
+            return;
+        };
+        save_status.set(Some(match std::fs::write(&path, &raw) {
+            Ok(()) => format!("已保存到 {}", path.display()),
+            Err(e) => format!("保存失败: {}", e),
+        }));
+    };
     
+    // 标记为垃圾邮件或不是垃圾邮件：训练模型并移动邮件，再在服务器上设置关键字
+    let in_junk = props.folder == JUNK_FOLDER;
+    let can_mark_spam = props.raw.is_none() && props.account.is_some();
+    let spam_email = props.email.clone();
+    let spam_account = props.account.clone();
+    let spam_folder = props.folder.clone();
+    let on_moved = props.on_moved;
+    let on_mark_spam = move |_| {
+        let Some(account) = spam_account.clone() else {
+            return;
+        };
+        let email = spam_email.clone();
+        let folder = spam_folder.clone();
+        let spam = !in_junk;
+        spawn(async move {
+            let local = {
+                let (account, email, storage) = (account.clone(), email.clone(), storage.handle());
+                async_std::task::spawn_blocking(move || {
+                    spam::mark_spam(&storage?, &account, &folder, &email, spam)
+                })
+                .await
+            };
+            match local {
+                Ok(_) => {
+                    if let Some(callback) = on_moved {
+                        callback.call(());
+                    }
+                }
+                Err(e) => {
+                    save_status.set(Some(format!("标记失败: {}", e)));
+                    return;
+                }
+            }
+            if let Err(e) = spam::store_keywords(&account, &email, spam).await {
+                println!("在服务器上设置 {} 失败: {}", if spam { KEYWORD_JUNK } else { KEYWORD_NOT_JUNK }, e);
+            }
+        });
+    };
+
+    // 标签：从文件打开的邮件不能添加
+    let labels = &props.labels;
+    let can_label = props.raw.is_none() && props.account.is_some();
+    let addable: Vec<_> = labels.iter().filter(|l| !email.labels.contains(&l.keyword)).cloned().collect();
+    let set_label = {
+        let account = props.account.clone();
+        let email = props.email.clone();
+        let on_labels_changed = props.on_labels_changed;
+        move |keyword: String, set: bool| {
+            let Some(account) = account.clone() else {
+                return;
+            };
+            let accounts = vec![account];
+            let emails = vec![email.clone()];
+            let updated = storage
+                .handle()
+                .and_then(|storage| label::apply_label(&storage, &accounts, &emails, &keyword, set));
+            match updated {
+                Ok(updated) => {
+                    if let (Some(callback), Some(email)) = (on_labels_changed, updated.into_iter().next()) {
+                        callback.call(email);
+                    }
+                }
+                Err(e) => {
+                    save_status.set(Some(format!("修改标签失败: {}", e)));
+                    return;
+                }
+            }
+            spawn(async move {
+                if !label::store_label(&accounts, &emails, &keyword, set).await.is_empty() {
+                    save_status.set(Some("服务器不允许自定义关键字，标签只保存在本地".to_string()));
+                }
+            });
+        }
+    };
+    let mut add_label = set_label.clone();
+    let mut remove_label = set_label;
+
     rsx! {
         div {
             class: "email-detail",
@@ -32,21 +289,37 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                     div {
                         class: "email-to",
                         strong { "收件人: " }
-                        span { "{email.to.join(\", \")}" }
+                        span { "{format_address_list(&email.to)}" }
                     }
                     
                     if !email.cc.is_empty() {
                         div {
                             class: "email-cc",
                             strong { "抄送: " }
-                            span { "{email.cc.join(\", \")}" }
+                            span { "{format_address_list(&email.cc)}" }
                         }
                     }
                     
                     div {
                         class: "email-date",
                         strong { "日期: " }
-                        span { "{email.date}" }
+                        span { title: "{email.date}", "{email.full_date()}" }
+                    }
+                }
+
+                if can_label {
+                    div {
+                        class: "email-labels",
+                        LabelChips {
+                            labels: labels.clone(),
+                            keywords: email.labels.clone(),
+                            on_remove: move |keyword| remove_label(keyword, false),
+                        }
+                        LabelPicker {
+                            labels: addable,
+                            placeholder: "🏷 添加标签",
+                            on_pick: move |keyword| add_label(keyword, true),
+                        }
                     }
                 }
                 
@@ -56,7 +329,9 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                     button {
                         class: "btn btn-primary",
                         onclick: move |_| {
-                            // TODO: 实现回复功能
+                            if let Some(ref callback) = props.on_reply {
+                                callback.call(reply_email.clone());
+                            }
                         },
                         "回复"
                     }
@@ -67,6 +342,27 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                         },
                         "转发"
                     }
+                    button {
+                        class: "btn btn-secondary",
+                        onclick: on_save,
+                        "另存为"
+                    }
+                    button {
+                        class: if source_view.read().is_some() { "btn btn-secondary active" } else { "btn btn-secondary" },
+                        onclick: move |_| {
+                            let next = if source_view.read().is_some() { None } else { Some(SourceView::Headers) };
+                            source_view.set(next);
+                            copied.set(false);
+                        },
+                        "查看原文"
+                    }
+                    if can_mark_spam {
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: on_mark_spam,
+                            if in_junk { "不是垃圾邮件" } else { "垃圾邮件" }
+                        }
+                    }
                     button {
                         class: "btn btn-danger",
                         onclick: move |_| {
@@ -75,14 +371,143 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                         "删除"
                     }
                 }
+                if let Some(status) = save_status.read().as_ref() {
+                    div { class: "status-message", "{status}" }
+                }
+            }
+
+            // 会议邀请
+            if let Some((invitation, ics)) = invitation {
+                div {
+                    class: "invitation-banner",
+                    span { class: "invitation-icon", "📅" }
+                    div {
+                        class: "invitation-info",
+                        strong { "{invitation.summary}" }
+                        span { "时间: {invitation.start}" }
+                        if !invitation.location.is_empty() {
+                            span { "地点: {invitation.location}" }
+                        }
+                        span { "组织者: {invitation.organizer}" }
+                    }
+                    button {
+                        class: "btn btn-primary",
+                        disabled: *accepting.read() || props.account.is_none(),
+                        onclick: {
+                            let account = props.account.clone();
+                            move |_| {
+                                let Some(account) = account.clone() else {
+                                    return;
+                                };
+                                let ics = ics.clone();
+                                accepting.set(true);
+                                let storage = storage.handle();
+                                spawn(async move {
+                                    let result = async_std::task::spawn_blocking(move || {
+                                        let storage = storage?;
+                                        caldav::accept_invitation(storage.address_book(), &account, &ics)
+                                    })
+                                    .await;
+                                    save_status.set(Some(match result {
+                                        Ok(calendar) => format!("已接受邀请，加入日历「{}」", calendar),
+                                        Err(e) => format!("接受邀请失败: {}", e),
+                                    }));
+                                    accepting.set(false);
+                                });
+                            }
+                        },
+                        if *accepting.read() { "正在加入..." } else { "接受并加入日历" }
+                    }
+                }
             }
             
+            // 邮件头 / 原文面板
+            if let Some(view) = *source_view.read() {
+                div {
+                    class: "email-source",
+                    div {
+                        class: "email-source-toolbar",
+                        button {
+                            class: if view == SourceView::Headers { "tab active" } else { "tab" },
+                            onclick: move |_| {
+                                source_view.set(Some(SourceView::Headers));
+                                copied.set(false);
+                            },
+                            "全部邮件头"
+                        }
+                        button {
+                            class: if view == SourceView::Raw { "tab active" } else { "tab" },
+                            onclick: move |_| {
+                                source_view.set(Some(SourceView::Raw));
+                                copied.set(false);
+                            },
+                            "原文"
+                        }
+                        button {
+                            class: if view == SourceView::Structure { "tab active" } else { "tab" },
+                            onclick: move |_| {
+                                source_view.set(Some(SourceView::Structure));
+                                copied.set(false);
+                            },
+                            "MIME 结构"
+                        }
+                        button {
+                            class: "btn btn-link",
+                            disabled: copy_text.is_none(),
+                            onclick: move |_| {
+                                if let Some(text) = copy_text.as_ref() {
+                                    let script = format!(
+                                        "navigator.clipboard.writeText({})",
+                                        serde_json::to_string(text).unwrap_or_default()
+                                    );
+                                    document::eval(&script);
+                                    copied.set(true);
+                                }
+                            },
+                            if *copied.read() { "已复制" } else { "复制" }
+                        }
+                    }
+                    match source {
+                        Some(Ok(text)) => rsx! { pre { class: "email-source-text", "{text}" } },
+                        Some(Err(e)) => rsx! { div { class: "status-message", "{e}" } },
+                        None => rsx! { div { class: "status-message", "正在读取邮件原文..." } },
+                    }
+                }
+            }
+
             // 邮件正文
             div {
                 class: "email-body",
-                div {
-                    class: "email-content",
-                    dangerous_inner_html: "{email.body}"
+                if email.html.is_some() {
+                    div {
+                        class: "body-view-toggle",
+                        button {
+                            class: if body_view == BodyView::Html { "tab active" } else { "tab" },
+                            onclick: {
+                                let mut set_body_view = set_body_view.clone();
+                                move |_| set_body_view(BodyView::Html)
+                            },
+                            "HTML"
+                        }
+                        button {
+                            class: if body_view == BodyView::Plain { "tab active" } else { "tab" },
+                            onclick: {
+                                let mut set_body_view = set_body_view.clone();
+                                move |_| set_body_view(BodyView::Plain)
+                            },
+                            "纯文本"
+                        }
+                    }
+                }
+                // 纯文本不能作为 HTML 插入，否则 < 等字符和换行会被破坏
+                match email.html.as_ref().filter(|_| body_view == BodyView::Html) {
+                    Some(html) => rsx! {
+                        div {
+                            class: "email-content",
+                            dangerous_inner_html: "{html}"
+                        }
+                    },
+                    None => rsx! { PlainTextBody { text: email.body.clone() } },
                 }
             }
             
@@ -93,7 +518,7 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                     h3 { "附件:" }
                     div {
                         class: "attachment-list",
-                        for attachment in &email.attachments {
+                        for attachment in email.attachments.iter().cloned() {
                             div {
                                 class: "attachment-item",
                                 span {
@@ -106,12 +531,65 @@ pub fn EmailDetail(props: EmailDetailProps) -> Element {
                                 }
                                 span {
                                     class: "attachment-size",
-                                    "({attachment.data.len()} bytes)"
+                                    "({attachment.byte_len()} bytes)"
+                                }
+                                // 附带的邮件可以直接打开
+                                if eml::is_message_attachment(&attachment) {
+                                    button {
+                                        class: "btn btn-link",
+                                        onclick: {
+                                            let attachment = attachment.clone();
+                                            let on_open_message = props.on_open_message;
+                                            move |_| {
+                                                // 附件内容在打开时才在后台从附件存储读取
+                                                let storage = storage.handle();
+                                                let attachment = attachment.clone();
+                                                spawn(async move {
+                                                    let data = async_std::task::spawn_blocking(move || {
+                                                        storage?.attachment_data(&attachment)
+                                                    })
+                                                    .await;
+                                                    let data = match data {
+                                                        Ok(data) => data,
+                                                        Err(e) => {
+                                                            save_status.set(Some(format!("读取附件失败: {}", e)));
+                                                            return;
+                                                        }
+                                                    };
+                                                    match eml::parse_eml(&data) {
+                                                        Ok(email) => {
+                                                            if let Some(ref callback) = on_open_message {
+                                                                callback.call((email, data.clone()));
+                                                            }
+                                                        }
+                                                        Err(e) => save_status.set(Some(format!("无法打开附带的邮件: {}", e))),
+                                                    }
+                                                });
+                                            }
+                                        },
+                                        "打开"
+                                    }
                                 }
                                 button {
                                     class: "btn btn-link",
-                                    onclick: move |_| {
-                                        // TODO: 实现下载功能
+                                    onclick: {
+                                        let attachment = attachment.clone();
+                                        move |_| {
+                                            let Some(path) = rfd::FileDialog::new()
+                                                .set_title("保存附件")
+                                                .set_file_name(&attachment.filename)
+                                                .save_file()
+                                            else {
+                                                return;
+                                            };
+                                            let result = storage
+                                                .handle()
+                                                .and_then(|storage| storage.save_attachment(&attachment, &path));
+                                            save_status.set(Some(match result {
+                                                Ok(_) => format!("已保存到 {}", path.display()),
+                                                Err(e) => format!("保存附件失败: {}", e),
+                                            }));
+                                        }
                                     },
                                     "下载"
                                 }
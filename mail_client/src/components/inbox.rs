@@ -1,156 +1,432 @@
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
 use dioxus::prelude::*;
+use crate::components::{LabelChips, LabelPicker, use_storage};
 use crate::models::email::EmailAccount;
-use crate::service::{imap_client, local_storage::LocalStorage};
-use crate::models::Email;
+use crate::service::{local_storage::LocalStorage, BatchAction, ListDensity, ListView, MboxFormat, MessageScope, SortField, SEARCH_EXCLUDED};
+use crate::service::batch::{self, ARCHIVE_FOLDER};
+use crate::service::filter::TRASH_FOLDER;
+use crate::service::mail_db::FLAG_FLAGGED;
+use crate::service::spam::JUNK_FOLDER;
+use crate::models::{Email, Label, SearchQuery};
 
 #[derive(Props, PartialEq, Clone)]
 pub struct InboxProps {
-    pub account: Option<EmailAccount>,
+    // 要显示的账户；“全部收件箱”时包含所有账户
+    pub accounts: Vec<EmailAccount>,
+    // 是否为合并显示多个账户的“全部收件箱”
+    #[props(default = false)]
+    pub unified: bool,
+    // 父组件的邮件变化计数，变化时从本地重新加载
+    #[props(default = 0)]
+    pub revision: u64,
+    // 父组件是否正在与服务器同步
+    #[props(default = false)]
+    pub syncing: bool,
     pub on_email_selected: Option<EventHandler<Email>>,
+    // 本地邮件有变化（新邮件、已读状态）时通知父组件，用于刷新未读数
+    pub on_mailbox_changed: Option<EventHandler<()>>,
+    // 点击刷新按钮时请求父组件同步
+    pub on_refresh: Option<EventHandler<()>>,
+    // 顶部搜索框的内容，语法见 SearchQuery，如 from:boss is:unread
+    #[props(default)]
+    pub search: String,
+    // 显示的本地文件夹，如垃圾邮件页面显示 Junk
+    #[props(default = "INBOX".to_string())]
+    pub folder: String,
+    // 标签视图：显示所有文件夹中带有该标签关键字的邮件，此时忽略 folder
+    #[props(default)]
+    pub label: Option<String>,
+    // 保存的搜索（虚拟文件夹）：在所有文件夹中搜索，此时忽略 folder
+    #[props(default)]
+    pub query: Option<String>,
+}
+
+// 列表每行的固定高度（像素），与样式表中 .email-item 的高度一致
+fn row_height(density: ListDensity) -> f64 {
+    match density {
+        ListDensity::Comfortable => 80.0,
+        ListDensity::Compact => 56.0,
+    }
+}
+// 可见区域上下额外渲染的行数，快速滚动时不出现空白
+const OVERSCAN: usize = 8;
+// 还没有测量到列表高度时假定的可见高度
+const DEFAULT_VIEWPORT: f64 = 800.0;
+const LIST_ELEMENT_ID: &str = "email-items";
+
+// 滚动到 scroll_top 时需要渲染的行
+fn window(scroll_top: f64, viewport: f64, total: usize, row_height: f64) -> Range<usize> {
+    let first = (scroll_top.max(0.0) / row_height) as usize;
+    let count = (viewport / row_height).ceil() as usize + 1;
+    first.saturating_sub(OVERSCAN).min(total)..(first + count + OVERSCAN).min(total)
+}
+
+// 设置列表的滚动位置
+fn scroll_list_to(top: f64) {
+    document::eval(&format!("document.getElementById('{}').scrollTop = {}", LIST_ELEMENT_ID, top));
 }
 
 pub fn Inbox(props: InboxProps) -> Element {
-    let mut emails = use_signal(|| None);
-    let mut selected_index = use_signal(|| 0);
-    let mut is_loading = use_signal(|| true);
+    // 当前列表中邮件的行 ID，按显示顺序；邮件内容只在滚动到附近时从数据库读取
+    let mut keys = use_signal(|| None::<Vec<i64>>);
+    let mut load_failed = use_signal(|| false);
+    // 已读取的可见区域：(第一行在列表中的位置, [(行 ID, 不含正文的邮件)])
+    let mut window_rows = use_signal(|| (0usize, Vec::<(i64, Email)>::new()));
+    // 选中的邮件的行 ID
+    let mut selected_row = use_signal(|| None::<i64>);
+    let shared_storage = use_storage();
     let mut local_storage = use_signal(|| None::<LocalStorage>);
     let mut last_sync_time = use_signal(|| None::<String>);
+    let mut export_status = use_signal(|| None::<String>);
+    let mut labels = use_signal(Vec::<Label>::new);
+    // 勾选的邮件的行 ID，用于批量操作
+    let mut checked = use_signal(HashSet::<i64>::new);
+    // 勾选的邮件中各标记出现的次数，用于星标按钮和可移除的标签
+    let mut checked_flags = use_signal(HashMap::<String, usize>::new);
+    // 上一次点击的邮件，Shift 点击时从这里选到点击的邮件
+    let mut anchor = use_signal(|| None::<i64>);
+    // 批量移动可选的文件夹
+    let mut folders = use_signal(Vec::<String>::new);
+    let mut batch_running = use_signal(|| false);
+    // 当前文件夹的排序与快速筛选，以及保存时使用的键
+    let mut view = use_signal(ListView::default);
+    let mut view_key = use_signal(String::new);
+    // 列表的范围：文件夹、标签或保存的搜索
+    let mut scope = use_signal(|| None::<MessageScope>);
+    // 通讯录中联系人的地址，用于“联系人”筛选
+    let mut contact_addresses = use_signal(HashSet::<String>::new);
+    // 正在后台进行的查询，开始新的查询时取消旧的
+    let mut load_task = use_signal(|| None::<Task>);
+    let mut flags_task = use_signal(|| None::<Task>);
+    // 列表只渲染可见的行：滚动位置与可见高度
+    let mut list_element = use_signal(|| None::<Rc<MountedData>>);
+    let mut scroll_top = use_signal(|| 0.0);
+    let mut viewport = use_signal(|| DEFAULT_VIEWPORT);
+    // 重新加载前位于顶部的邮件及其露出的偏移，加载后滚动到它的新位置，新邮件到达时画面不跳动
+    let mut scroll_anchor = use_signal(|| None::<(i64, f64)>);
+    let mut density = use_signal(ListDensity::default);
+
+    // 重新加载后恢复到原来位于顶部的邮件
+    use_effect(move || {
+        let list = keys.read();
+        let Some(list) = list.as_ref() else {
+            return;
+        };
+        let Some((row, offset)) = scroll_anchor.write().take() else {
+            return;
+        };
+        if let Some(index) = list.iter().position(|&r| r == row) {
+            let height = row_height(*density.peek());
+            let top = index as f64 * height + offset.min(height);
+            if top != *scroll_top.peek() {
+                scroll_top.set(top);
+                scroll_list_to(top);
+            }
+        }
+    });
+
+    // 读取列表的设置和范围，账户或邮件计数变化时重新读取
+    use_effect(use_reactive((&props.accounts, &props.revision, &props.folder, &props.label, &props.query), move |(accounts, _revision, folder, label, query)| {
+        // 同步状态保存在数据库中，共用的存储即可读到最新的结果
+        let storage = match shared_storage.handle() {
+            Ok(storage) => storage,
+            Err(e) => {
+                println!("初始化本地存储失败: {}", e);
+                load_failed.set(true);
+                return;
+            }
+        };
+
+        // 显示最近一次同步时间
+        let last_sync = accounts.iter().filter_map(|acc| storage.get_last_sync(acc)).max();
+        last_sync_time.set(last_sync.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()));
 
-    // 初始化本地存储
-    {
-        let mut local_storage_setter = local_storage.clone();
-        
-        use_effect(move || {
-            match LocalStorage::new() {
-                Ok(storage) => {
-                    local_storage_setter.set(Some(storage));
+        let label_list = storage.labels().unwrap_or_default();
+        // 标签和保存的搜索与文件夹分开记住排序与筛选
+        let key = match (label.as_deref(), query.as_deref()) {
+            (Some(keyword), _) => format!("label:{}", keyword),
+            (None, Some(query)) => format!("search:{}", query),
+            (None, None) => folder.clone(),
+        };
+        let list_view = storage.list_view(&key);
+        if *view_key.peek() != key {
+            // 切换文件夹时回到顶部
+            scroll_anchor.set(None);
+            scroll_top.set(0.0);
+            scroll_list_to(0.0);
+        } else if *scroll_top.peek() > 0.0 {
+            let top = *scroll_top.peek();
+            let height = row_height(*density.peek());
+            let index = (top / height) as usize;
+            let anchor = keys.peek().as_ref().and_then(|list| list.get(index).copied()).map(|row| (row, top - index as f64 * height));
+            scroll_anchor.set(anchor);
+        }
+        let list_scope = match (label, query) {
+            (Some(keyword), _) => MessageScope::Flag(keyword),
+            (None, Some(query)) => MessageScope::Search(
+                SearchQuery::parse(&query).with_labels(&label_list),
+                SEARCH_EXCLUDED.map(String::from).to_vec(),
+            ),
+            (None, None) => MessageScope::Folder(folder.clone()),
+        };
+        contact_addresses.set(storage.address_book().known_addresses().unwrap_or_default());
+        density.set(storage.density());
+        view.set(list_view);
+        view_key.set(key);
+        labels.set(label_list);
+        let mut folder_list: Vec<String> = accounts
+            .iter()
+            .flat_map(|acc| storage.list_folders(acc))
+            .chain(["INBOX", ARCHIVE_FOLDER, TRASH_FOLDER].map(String::from))
+            .collect();
+        folder_list.sort();
+        folder_list.dedup();
+        folders.set(folder_list);
+        scope.set(Some(list_scope));
+        local_storage.set(Some(storage));
+    }));
+
+    // 按范围、排序和筛选在后台查询列表，只保留行 ID
+    use_effect(use_reactive((&props.accounts, &props.search), move |(accounts, search)| {
+        let (Some(storage), Some(list_scope)) = (local_storage.read().clone(), scope.read().clone()) else {
+            return;
+        };
+        let query = view.read().query(SearchQuery::parse(&search).with_labels(&labels.read()), &contact_addresses.read());
+        if let Some(task) = load_task.take() {
+            task.cancel();
+        }
+        let task = spawn(async move {
+            let result = async_std::task::spawn_blocking(move || storage.list_keys(&accounts, &list_scope, &query)).await;
+            match result {
+                Ok(list) => {
+                    println!("从本地加载了 {} 封邮件", list.len());
+                    // 只保留仍在列表中的勾选
+                    if !checked.peek().is_empty() {
+                        let present: HashSet<i64> = list.iter().copied().collect();
+                        checked.write().retain(|row| present.contains(row));
+                    }
+                    load_failed.set(false);
+                    keys.set(Some(list));
                 }
                 Err(e) => {
-                    println!("初始化本地存储失败: {}", e);
+                    println!("从本地加载邮件失败: {}", e);
+                    load_failed.set(true);
+                    keys.set(None);
                 }
             }
-            
-            ()
         });
-    }
+        load_task.set(Some(task));
+    }));
 
-    // 加载本地邮件
-    {
-        let mut emails_setter = emails.clone();
-        let mut loading_setter = is_loading.clone();
-        let account = props.account.clone();
-        let local_storage_reader = local_storage.clone();
-        let mut last_sync_setter = last_sync_time.clone();
-        
-        use_effect(move || {
-            loading_setter.set(true);
-            
-            if let (Some(acc), Some(storage)) = (account.clone(), local_storage_reader.read().as_ref()) {
-                // 更新最后同步时间
-                if let Some(last_sync) = storage.get_last_sync() {
-                    last_sync_setter.set(Some(last_sync.format("%Y-%m-%d %H:%M:%S").to_string()));
-                }
-                
-                // 尝试从本地加载
-                match storage.load_emails(&acc, "INBOX") {
-                    Ok(local_emails) => {
-                        if !local_emails.is_empty() {
-                            println!("从本地加载了 {} 封邮件", local_emails.len());
-                            emails_setter.set(Some(local_emails));
-                            loading_setter.set(false);
-                            
-                            // 然后在后台刷新
-                            sync_with_server(acc.clone(), storage, emails_setter.clone(), last_sync_setter.clone());
-                        } else {
-                            // 本地没有邮件，直接从服务器获取
-                            sync_with_server(acc.clone(), storage, emails_setter.clone(), last_sync_setter.clone());
-                        }
-                    }
-                    Err(e) => {
-                        println!("从本地加载邮件失败: {}", e);
-                        // 从服务器获取
-                        sync_with_server(acc.clone(), storage, emails_setter.clone(), last_sync_setter.clone());
-                    }
-                }
-            } else {
-                loading_setter.set(false);
-                println!("没有配置邮箱账户或本地存储");
+    // 需要渲染的行在列表中的范围
+    let rows = use_memo(move || {
+        let total = keys.read().as_ref().map_or(0, Vec::len);
+        window(scroll_top(), viewport(), total, row_height(density()))
+    });
+
+    // 滚动或列表变化时读取可见区域附近的邮件，每次只读取几十行
+    use_effect(move || {
+        let range = rows();
+        let list = keys.read();
+        let ids = list.as_ref().map_or(&[][..], |list| &list[range.start.min(list.len())..range.end.min(list.len())]);
+        let storage = local_storage.peek();
+        let Some(storage) = storage.as_ref() else {
+            return;
+        };
+        match storage.list_headers(ids) {
+            Ok(headers) => window_rows.set((range.start, headers)),
+            Err(e) => println!("从本地加载邮件失败: {}", e),
+        }
+    });
+
+    // 勾选变化时在后台统计各标记的数量
+    use_effect(move || {
+        let targets: Vec<i64> = checked.read().iter().copied().collect();
+        if let Some(task) = flags_task.take() {
+            task.cancel();
+        }
+        if targets.is_empty() {
+            checked_flags.set(HashMap::new());
+            return;
+        }
+        let Some(storage) = local_storage.peek().clone() else {
+            return;
+        };
+        let task = spawn(async move {
+            let counts = async_std::task::spawn_blocking(move || storage.flag_counts(&targets)).await;
+            checked_flags.set(counts);
+        });
+        flags_task.set(Some(task));
+    });
+
+    let visible_count = keys.read().as_ref().map_or(0, Vec::len);
+    let is_loading = (keys.read().is_none() && !load_failed()) || (props.syncing && visible_count == 0);
+
+    // 修改排序或筛选：保存到当前文件夹，列表随之重新查询
+    let mut update_view = move |change: &dyn Fn(&mut ListView)| {
+        let mut list_view = view();
+        change(&mut list_view);
+        if let Some(storage) = local_storage.read().as_ref() {
+            if let Err(e) = storage.save_list_view(&view_key.read(), &list_view) {
+                println!("保存排序设置失败: {}", e);
             }
-            
-            ()
+        }
+        view.set(list_view);
+        scroll_anchor.set(None);
+        scroll_top.set(0.0);
+        scroll_list_to(0.0);
+    };
+    let current_view = view();
+
+    // 把当前显示的邮件导出为 mbox 文件
+    let export_accounts = props.accounts.clone();
+    let export_folder = props.folder.clone();
+    let export_name = if props.search.trim().is_empty() { props.folder.clone() } else { format!("搜索-{}", props.search.trim()) };
+    let on_export = move |_| {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("导出为 mbox")
+            .set_file_name(format!("{}.mbox", export_name))
+            .add_filter("mbox", &["mbox"])
+            .save_file()
+        else {
+            return;
+        };
+        let accounts = export_accounts.clone();
+        let targets = keys.read().clone().unwrap_or_default();
+        let folder = export_folder.clone();
+        export_status.set(Some("正在导出...".to_string()));
+        let storage = shared_storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                let storage = storage?;
+                // 没有原文的邮件由完整的邮件重新生成，需要正文、收件人和附件
+                let emails = storage.load_messages(&targets)?;
+                storage.export_mbox(&accounts, &folder, &emails, &path, MboxFormat::Mboxrd)
+            })
+            .await;
+            export_status.set(Some(match result {
+                Ok(count) => format!("已导出 {} 封邮件", count),
+                Err(e) => format!("导出失败: {}", e),
+            }));
         });
-    }
+    };
 
-    // 使用 use_future 进行定期同步
-    {
-        let account = props.account.clone();
-        let local_storage_reader = local_storage.clone();
-        let emails_setter = emails.clone();
-        let mut last_sync_setter = last_sync_time.clone();
-        
-        // 使用 use_future 创建一个异步任务
-        use_future(move || {
-            let account_clone = account.clone();
-            let local_storage_reader_clone = local_storage_reader.clone();
-            let emails_setter_clone = emails_setter.clone();
-            let last_sync_setter_clone = last_sync_setter.clone();
-            
-            async move {
-                loop {
-                    // 等待10分钟
-                    async_std::task::sleep(Duration::from_secs(600)).await;
-                    
-                    if let (Some(acc), Some(storage)) = (account_clone.clone(), local_storage_reader_clone.read().as_ref()) {
-                        println!("执行定时同步...");
-                        sync_with_server(acc.clone(), storage, emails_setter_clone.clone(), last_sync_setter_clone.clone()).await;
-                    }
+    let checked_count = checked.read().len();
+    let all_flagged = checked_flags.read().get(FLAG_FLAGGED).copied().unwrap_or(0) >= checked_count;
+    let removable: Vec<Label> = labels
+        .read()
+        .iter()
+        .filter(|l| checked_flags.read().get(&l.keyword).is_some_and(|&count| count > 0))
+        .cloned()
+        .collect();
+    let current_folder = if props.label.is_none() && props.query.is_none() { Some(props.folder.clone()) } else { None };
+    let move_targets: Vec<String> = folders
+        .read()
+        .iter()
+        .filter(|f| Some(*f) != current_folder.as_ref())
+        .cloned()
+        .collect();
+
+    // 对勾选的邮件执行批量操作：先在本地修改并刷新列表，再同步到服务器
+    let batch_accounts = props.accounts.clone();
+    let on_mailbox_changed = props.on_mailbox_changed;
+    let run_batch = use_callback(move |action: BatchAction| {
+        // 按列表中的顺序
+        let targets: Vec<i64> = keys.read().iter().flatten().filter(|row| checked.read().contains(row)).copied().collect();
+        let accounts = batch_accounts.clone();
+        batch_running.set(true);
+        spawn(async move {
+            let local = {
+                let (accounts, action, storage) = (accounts.clone(), action.clone(), shared_storage.handle());
+                async_std::task::spawn_blocking(move || {
+                    let storage = storage?;
+                    let emails = storage.load_messages(&targets)?;
+                    let count = batch::apply_local(&storage, &accounts, &emails, &action)?;
+                    Ok::<_, Box<dyn std::error::Error + Send + Sync>>((count, emails, storage))
+                })
+                .await
+            };
+            batch_running.set(false);
+            let (emails, storage) = match local {
+                Ok((count, emails, storage)) => {
+                    export_status.set(Some(format!("已处理 {} 封邮件", count)));
+                    (emails, storage)
                 }
+                Err(e) => {
+                    export_status.set(Some(format!("操作失败: {}", e)));
+                    return;
+                }
+            };
+            if let Some(callback) = on_mailbox_changed {
+                callback.call(());
+            }
+            if !batch::apply_remote(&storage, &accounts, &emails, &action).await.is_empty() {
+                export_status.set(Some("服务器不允许保存该标记，修改只在本地".to_string()));
             }
         });
-    }
+    });
 
-    // 使用 use_callback 创建刷新函数
-    let refresh_emails = use_callback(move |_| {
-        is_loading.set(true);
-        
-        let account = props.account.clone();
-        // 正确获取本地存储
-        let mut local_storage_reader = local_storage.clone();
-        let mut emails_setter = emails.clone();
-        let mut loading_setter = is_loading.clone();
-        let mut last_sync_setter = last_sync_time.clone();
-        
-        if let Some(acc) = account {
-            // 从本地存储中获取实例
-            if let Some(storage) = local_storage_reader.read().as_ref() {
-                let storage_clone = storage.clone();
-                // 直接从服务器同步最新邮件
-                spawn(async move {
-                    sync_with_server(acc, &storage_clone, emails_setter.clone(), last_sync_setter.clone()).await;
-                    loading_setter.set(false);
-                });
-            } else {
-                loading_setter.set(false);
+    // 全选：勾选数不少于显示的邮件数时才逐一检查
+    let all_checked = visible_count > 0
+        && checked_count >= visible_count
+        && keys.read().iter().flatten().all(|row| checked.read().contains(row));
+    let select_range = use_callback(move |row: i64| {
+        let list = keys.read();
+        let list = list.as_deref().unwrap_or_default();
+        let start = anchor.read().and_then(|a| list.iter().position(|&r| r == a));
+        let end = list.iter().position(|&r| r == row);
+        match (start, end) {
+            (Some(start), Some(end)) => {
+                let (from, to) = (start.min(end), start.max(end));
+                checked.write().extend(list[from..=to].iter().copied());
+            }
+            _ => {
+                checked.write().insert(row);
+                anchor.set(Some(row));
             }
-        } else {
-            loading_setter.set(false);
         }
     });
+    let mut toggle_checked = move |row: i64| {
+        if !checked.write().remove(&row) {
+            checked.write().insert(row);
+        }
+        anchor.set(Some(row));
+    };
+
+    // 只渲染已读取的可见区域，上方用 padding 占位，总高度保持与全部邮件相同
+    let comfortable = density() == ListDensity::Comfortable;
+    let height = row_height(density());
+    let (window_start, window_emails) = window_rows();
+    let padding_top = window_start as f64 * height;
+    let total_height = visible_count as f64 * height;
+
+    // 账户地址 -> 标识色
+    let account_colors: Vec<(String, &'static str)> = props
+        .accounts
+        .iter()
+        .map(|acc| (acc.address.clone(), acc.color()))
+        .collect();
 
     // 主渲染函数
     rsx! {
         div {
             class: "inbox-container",
-            
+
             // 修改后的邮件列表工具栏
             div {
                 class: "email-list-toolbar",
-                
+
                 // 显示最后同步时间 (居左)
                 div {
                     class: "sync-info",
-                    if let Some(time) = last_sync_time.read().as_ref() {
+                    if props.syncing {
+                        span {
+                            class: "sync-time",
+                            "正在同步..."
+                        }
+                    } else if let Some(time) = last_sync_time.read().as_ref() {
                         span {
                             class: "sync-time",
                             "上次同步: {time}"
@@ -161,28 +437,205 @@ pub fn Inbox(props: InboxProps) -> Element {
                             "未同步"
                         }
                     }
+                    if let Some(status) = export_status.read().as_ref() {
+                        span { class: "sync-time", " · {status}" }
+                    }
                 }
-                
+
+                // 导出当前列表（收件箱或搜索结果）
+                button {
+                    class: "action-btn",
+                    disabled: visible_count == 0,
+                    onclick: on_export,
+                    "📦 导出"
+                }
+
                 // 刷新按钮 (居右)
                 button {
                     class: "action-btn refresh-btn",
-                    onclick: move |evt| refresh_emails.call(evt),
+                    disabled: props.syncing,
+                    onclick: move |_| {
+                        if let Some(ref callback) = props.on_refresh {
+                            callback.call(());
+                        }
+                    },
                     "🔄 刷新"
                 }
             }
-            
-            // 邮件列表 (不变)
+
+            // 排序与快速筛选，按文件夹分别记住
+            div {
+                class: "list-controls",
+                select {
+                    class: "sort-select",
+                    title: "排序方式",
+                    onchange: move |e| {
+                        if let Some(sort) = SortField::ALL.into_iter().find(|f| format!("{:?}", f) == e.value()) {
+                            update_view(&|v| v.sort = sort);
+                        }
+                    },
+                    for field in SortField::ALL {
+                        option {
+                            key: "{field:?}",
+                            value: "{field:?}",
+                            selected: field == current_view.sort,
+                            "按{field.label()}"
+                        }
+                    }
+                }
+                button {
+                    class: "action-btn",
+                    title: if current_view.descending { "降序" } else { "升序" },
+                    onclick: move |_| update_view(&|v| v.descending = !v.descending),
+                    if current_view.descending { "↓" } else { "↑" }
+                }
+                span { class: "list-controls-sep" }
+                button {
+                    class: if current_view.unread { "filter-toggle active" } else { "filter-toggle" },
+                    onclick: move |_| update_view(&|v| v.unread = !v.unread),
+                    "未读"
+                }
+                button {
+                    class: if current_view.flagged { "filter-toggle active" } else { "filter-toggle" },
+                    onclick: move |_| update_view(&|v| v.flagged = !v.flagged),
+                    "★ 星标"
+                }
+                button {
+                    class: if current_view.attachment { "filter-toggle active" } else { "filter-toggle" },
+                    onclick: move |_| update_view(&|v| v.attachment = !v.attachment),
+                    "📎 附件"
+                }
+                button {
+                    class: if current_view.contacts { "filter-toggle active" } else { "filter-toggle" },
+                    title: "只显示通讯录中联系人或发过邮件的地址发来的邮件",
+                    onclick: move |_| update_view(&|v| v.contacts = !v.contacts),
+                    "👤 联系人"
+                }
+            }
+
+            // 全选和批量操作，Shift 点击选择范围，Ctrl 点击单独勾选
+            if visible_count > 0 {
+                div {
+                    class: "batch-toolbar",
+                    input {
+                        r#type: "checkbox",
+                        title: "全选",
+                        checked: all_checked,
+                        onchange: move |_| {
+                            if all_checked {
+                                checked.write().clear();
+                            } else {
+                                checked.write().extend(keys.read().iter().flatten().copied());
+                            }
+                        },
+                    }
+                    if checked_count == 0 {
+                        span { class: "sync-time", "全选" }
+                    } else {
+                        span { class: "sync-time", "已选 {checked_count} 封" }
+                        button {
+                            class: "action-btn",
+                            disabled: *batch_running.read(),
+                            onclick: move |_| run_batch.call(BatchAction::MarkSeen(true)),
+                            "已读"
+                        }
+                        button {
+                            class: "action-btn",
+                            disabled: *batch_running.read(),
+                            onclick: move |_| run_batch.call(BatchAction::MarkSeen(false)),
+                            "未读"
+                        }
+                        button {
+                            class: "action-btn",
+                            disabled: *batch_running.read(),
+                            onclick: move |_| run_batch.call(BatchAction::Flag(!all_flagged)),
+                            if all_flagged { "取消星标" } else { "⭐ 星标" }
+                        }
+                        if current_folder.as_deref() != Some(ARCHIVE_FOLDER) {
+                            button {
+                                class: "action-btn",
+                                disabled: *batch_running.read(),
+                                onclick: move |_| run_batch.call(BatchAction::Archive),
+                                "归档"
+                            }
+                        }
+                        if current_folder.as_deref() != Some(TRASH_FOLDER) {
+                            button {
+                                class: "action-btn",
+                                disabled: *batch_running.read(),
+                                onclick: move |_| run_batch.call(BatchAction::Delete),
+                                "🗑 删除"
+                            }
+                        }
+                        select {
+                            class: "label-picker",
+                            value: "",
+                            disabled: *batch_running.read(),
+                            onchange: move |e| {
+                                let folder = e.value();
+                                if !folder.is_empty() {
+                                    run_batch.call(BatchAction::Move(folder));
+                                }
+                            },
+                            option { value: "", selected: true, "移动到..." }
+                            for folder in move_targets {
+                                option { key: "{folder}", value: "{folder}", "{folder}" }
+                            }
+                        }
+                        LabelPicker {
+                            labels: labels.read().clone(),
+                            placeholder: "🏷 添加标签",
+                            on_pick: move |keyword| run_batch.call(BatchAction::Label(keyword, true)),
+                        }
+                        LabelPicker {
+                            labels: removable,
+                            placeholder: "移除标签",
+                            on_pick: move |keyword| run_batch.call(BatchAction::Label(keyword, false)),
+                        }
+                    }
+                }
+            }
+
+            // 邮件列表
             div {
-                class: "email-items",
-                
-                if *is_loading.read() {
+                id: LIST_ELEMENT_ID,
+                class: if comfortable { "email-items" } else { "email-items compact" },
+                onmounted: move |e: MountedEvent| async move {
+                    let element = e.data();
+                    if let Ok(rect) = element.get_client_rect().await {
+                        viewport.set(rect.size.height);
+                    }
+                    list_element.set(Some(element));
+                },
+                onscroll: move |_| async move {
+                    let Some(element) = list_element() else {
+                        return;
+                    };
+                    if let Ok(offset) = element.get_scroll_offset().await {
+                        // 用户滚动后不再恢复之前记下的位置
+                        scroll_anchor.set(None);
+                        scroll_top.set(offset.y);
+                    }
+                },
+                onresize: move |e: ResizeEvent| {
+                    if let Ok(size) = e.get_content_box_size() {
+                        viewport.set(size.height);
+                    }
+                },
+
+                if is_loading {
                     div {
                         class: "loading-indicator",
                         "加载中..."
                     }
-                } else if let Some(email_list) = emails.read().as_ref() {
-                    if email_list.is_empty() {
-                        // 空状态显示 (不变)
+                } else if keys.read().is_some() {
+                    if visible_count == 0 && (!props.search.trim().is_empty() || current_view.is_filtered()) {
+                        div {
+                            class: "empty-state",
+                            p { "没有找到匹配的邮件" }
+                        }
+                    } else if visible_count == 0 {
+                        // 空状态显示
                         div {
                             class: "empty-state",
                             div {
@@ -190,49 +643,162 @@ pub fn Inbox(props: InboxProps) -> Element {
                                 "📭"
                             }
                             p { "没有邮件" }
-                            p { class: "empty-hint", "收到的邮件会显示在这里" }
+                            p {
+                                class: "empty-hint",
+                                if props.label.is_some() {
+                                    "添加了该标签的邮件会显示在这里"
+                                } else if props.query.is_some() {
+                                    "符合搜索条件的邮件会显示在这里"
+                                } else if props.folder == JUNK_FOLDER {
+                                    "被判为垃圾邮件的邮件会显示在这里"
+                                } else {
+                                    "收到的邮件会显示在这里"
+                                }
+                            }
                         }
                     } else {
-                        // 邮件列表项 (不变)
-                        {email_list.iter().enumerate().map(|(index, email)| {
-                            let is_selected = *selected_index.read() == index;
-                            let email_clone = email.clone();
-                            let on_email_selected = props.on_email_selected.clone();
-                            
-                            rsx! {
-                                div {
-                                    key: "{index}",
-                                    class: if is_selected { "email-item selected" } else { "email-item" },
-                                    onclick: move |_| {
-                                        selected_index.set(index);
-                                        // 当邮件被选中时，通知父组件
-                                        if let Some(ref callback) = on_email_selected {
-                                            callback.call(email_clone.clone());
-                                        }
-                                    },
-                                    
-                                    div {
-                                        class: "email-checkbox-wrapper",
-                                        input { r#type: "checkbox" }
-                                    }
+                        div {
+                            class: "email-items-window",
+                            style: "height: {total_height}px; padding-top: {padding_top}px;",
+                            {window_emails.iter().map(|(row, email)| {
+                                let row = *row;
+                                let is_selected = selected_row() == Some(row);
+                                let (id, seen) = (email.id.clone(), email.seen);
+                                let on_email_selected = props.on_email_selected;
+                                let on_mailbox_changed = props.on_mailbox_changed;
+                                let account = props.accounts.iter().find(|acc| acc.address == email.account).cloned();
+                                // 标签视图中的邮件来自不同文件夹
+                                let folder = if email.folder.is_empty() { props.folder.clone() } else { email.folder.clone() };
+                                let is_checked = checked.read().contains(&row);
+                                let color = account_colors
+                                    .iter()
+                                    .find(|(addr, _)| addr == &email.account)
+                                    .map_or("transparent", |(_, color)| *color);
+                                let item_class = match (is_selected, email.seen) {
+                                    (true, true) => "email-item selected",
+                                    (true, false) => "email-item selected unread",
+                                    (false, true) => "email-item",
+                                    (false, false) => "email-item unread",
+                                };
+                                let item_class = if is_checked { format!("{} checked", item_class) } else { item_class.to_string() };
+
+                                rsx! {
                                     div {
-                                        class: "email-content",
+                                        key: "{row}",
+                                        class: item_class,
+                                        onclick: move |e: MouseEvent| {
+                                            let modifiers = e.modifiers();
+                                            if modifiers.shift() {
+                                                select_range.call(row);
+                                                return;
+                                            }
+                                            if modifiers.ctrl() || modifiers.meta() {
+                                                toggle_checked(row);
+                                                return;
+                                            }
+                                            anchor.set(Some(row));
+                                            selected_row.set(Some(row));
+
+                                            // 打开即标记为已读，列表中只有表头，打开时读取完整的邮件
+                                            let opened = local_storage.read().as_ref().map(|storage| {
+                                                if let (false, Some(acc)) = (seen, account.as_ref()) {
+                                                    if let Err(e) = storage.mark_seen(acc, &folder, &id, true) {
+                                                        println!("标记已读失败: {}", e);
+                                                    }
+                                                }
+                                                storage.load_messages(&[row])
+                                            });
+                                            if !seen {
+                                                if let Some((_, item)) = window_rows.write().1.iter_mut().find(|(r, _)| *r == row) {
+                                                    item.seen = true;
+                                                }
+                                                if let Some(ref callback) = on_mailbox_changed {
+                                                    callback.call(());
+                                                }
+                                            }
+
+                                            // 当邮件被选中时，通知父组件
+                                            match opened {
+                                                Some(Ok(mut list)) => {
+                                                    if let (Some(mut selected), Some(callback)) = (list.pop(), on_email_selected.as_ref()) {
+                                                        selected.seen = true;
+                                                        callback.call(selected);
+                                                    }
+                                                }
+                                                Some(Err(e)) => println!("读取邮件失败: {}", e),
+                                                None => {}
+                                            }
+                                        },
+
+                                        // 全部收件箱中用颜色标识邮件所属账户
+                                        if props.unified {
+                                            div {
+                                                class: "account-tag",
+                                                style: "background-color: {color}",
+                                                title: "{email.account}",
+                                            }
+                                        }
                                         div {
-                                            class: "email-row",
-                                            div { class: "email-sender", "{email.from}" }
-                                            div { class: "email-date", "{email.date}" }
+                                            class: "email-checkbox-wrapper",
+                                            input {
+                                                r#type: "checkbox",
+                                                checked: is_checked,
+                                                onclick: move |e: MouseEvent| {
+                                                    e.stop_propagation();
+                                                    if e.modifiers().shift() {
+                                                        select_range.call(row);
+                                                    } else {
+                                                        toggle_checked(row);
+                                                    }
+                                                },
+                                            }
+                                        }
+                                        // 发件人首字母头像，颜色按地址固定
+                                        if comfortable {
+                                            div {
+                                                class: "email-avatar",
+                                                style: "background-color: {email.from.avatar_color()}",
+                                                "{email.from.initial()}"
+                                            }
                                         }
                                         div {
-                                            class: "email-subject",
-                                            "{email.subject}"
+                                            class: "email-content",
+                                            div {
+                                                class: "email-row",
+                                                div { class: "email-sender", title: "{email.from.display_address()}", "{email.from.display_name()}" }
+                                                div {
+                                                    class: "email-icons",
+                                                    if email.answered {
+                                                        span { title: "已回复", "↩" }
+                                                    }
+                                                    if !email.attachments.is_empty() {
+                                                        span { title: "{email.attachments.len()} 个附件", "📎" }
+                                                    }
+                                                    if email.flagged {
+                                                        span { class: "email-flag", title: "星标", "★" }
+                                                    }
+                                                }
+                                                div { class: "email-date", title: "{email.full_date()}", "{email.short_date()}" }
+                                            }
+                                            div {
+                                                class: "email-subject-row",
+                                                div {
+                                                    class: "email-subject",
+                                                    "{email.subject}"
+                                                }
+                                                LabelChips { labels: labels.read().clone(), keywords: email.labels.clone() }
+                                            }
+                                            if comfortable && !email.snippet.is_empty() {
+                                                div { class: "email-snippet", "{email.snippet}" }
+                                            }
                                         }
                                     }
                                 }
-                            }
-                        })}
+                            })}
+                        }
                     }
                 } else {
-                    // 错误状态显示 (不变)
+                    // 错误状态显示
                     div {
                         class: "error-state",
                         div {
@@ -243,7 +809,11 @@ pub fn Inbox(props: InboxProps) -> Element {
                         p { class: "error-hint", "请检查你的网络连接和邮箱设置" }
                         button {
                             class: "btn btn-secondary",
-                            onclick: move |evt| refresh_emails.call(evt),
+                            onclick: move |_| {
+                                if let Some(ref callback) = props.on_refresh {
+                                    callback.call(());
+                                }
+                            },
                             "重试"
                         }
                     }
@@ -252,70 +822,3 @@ pub fn Inbox(props: InboxProps) -> Element {
         }
     }
 }
-
-// 帮助函数：与服务器同步
-async fn sync_with_server(
-    account: EmailAccount,
-    storage: &LocalStorage,
-    mut emails_setter: Signal<Option<Vec<Email>>>,
-    mut last_sync_setter: Signal<Option<String>>,
-) {
-    println!("从服务器同步邮件...");
-    
-    // 获取已同步的邮件ID
-    let synced_ids = storage.get_synced_ids(&account);
-    // 获取最后同步时间
-    let since_date = storage.get_last_sync();
-    
-    // 只获取新邮件
-    match imap_client::fetch_new_emails(
-        &account.imap_server,
-        account.imap_port,
-        &account.address,
-        &account.password,
-        account.use_tls,
-        since_date,
-        synced_ids,
-    ).await {
-        Ok(new_emails) => {
-            println!("从服务器获取到 {} 封新邮件", new_emails.len());
-            
-            // 保存到本地
-            if !new_emails.is_empty() {
-                // 创建一个可变的本地存储副本
-                // 使用 try_clone 方法而不是 clone
-                let mut storage_mut = storage.clone();
-                
-                if let Err(e) = storage_mut.save_emails(&account, "INBOX", &new_emails) {
-                    println!("保存邮件到本地失败: {}", e);
-                }
-                
-                // 更新最后同步时间显示
-                if let Some(last_sync) = storage_mut.get_last_sync() {
-                    last_sync_setter.set(Some(last_sync.format("%Y-%m-%d %H:%M:%S").to_string()));
-                }
-            }
-            
-            // 合并本地邮件
-            match storage.load_emails(&account, "INBOX") {
-                Ok(all_emails) => {
-                    emails_setter.set(Some(all_emails));
-                },
-                Err(e) => {
-                    println!("加载合并后的邮件失败: {}", e);
-                    // 至少显示新获取的邮件
-                    if !new_emails.is_empty() {
-                        emails_setter.set(Some(new_emails));
-                    }
-                }
-            }
-        },
-        Err(e) => {
-            println!("从服务器获取邮件失败: {}", e);
-            // 仍然尝试加载本地邮件
-            if let Ok(local_emails) = storage.load_emails(&account, "INBOX") {
-                emails_setter.set(Some(local_emails));
-            }
-        }
-    }
-}
\ No newline at end of file
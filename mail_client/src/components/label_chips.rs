@@ -0,0 +1,64 @@
+use dioxus::prelude::*;
+use crate::models::{find_label, Label};
+
+// 邮件的标签，按标签定义的颜色显示；有 on_remove 时每个标签带删除按钮
+#[component]
+pub fn LabelChips(labels: Vec<Label>, keywords: Vec<String>, on_remove: Option<EventHandler<String>>) -> Element {
+    if keywords.is_empty() {
+        return rsx! {};
+    }
+    rsx! {
+        div {
+            class: "label-chips",
+            for keyword in keywords {
+                {
+                    let label = find_label(&labels, &keyword);
+                    rsx! {
+                        span {
+                            key: "{keyword}",
+                            class: "label-chip",
+                            style: "background-color: {label.color}",
+                            title: "{label.keyword}",
+                            "{label.name}"
+                            if let Some(on_remove) = on_remove {
+                                span {
+                                    class: "label-chip-remove",
+                                    title: "移除标签",
+                                    onclick: move |e: MouseEvent| {
+                                        e.stop_propagation();
+                                        on_remove.call(keyword.clone());
+                                    },
+                                    "×"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 给选中的邮件添加或移除标签的下拉框，选择后立即执行
+#[component]
+pub fn LabelPicker(labels: Vec<Label>, placeholder: String, on_pick: EventHandler<String>) -> Element {
+    if labels.is_empty() {
+        return rsx! {};
+    }
+    rsx! {
+        select {
+            class: "label-picker",
+            value: "",
+            onchange: move |e| {
+                let keyword = e.value();
+                if !keyword.is_empty() {
+                    on_pick.call(keyword);
+                }
+            },
+            option { value: "", selected: true, "{placeholder}" }
+            for label in labels {
+                option { key: "{label.keyword}", value: "{label.keyword}", "{label.name}" }
+            }
+        }
+    }
+}
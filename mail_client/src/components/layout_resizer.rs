@@ -33,7 +33,7 @@ pub fn ResizeHandle(
         let element_id = id.clone();
         
         // 对于初始宽度，我们使用当前设置的百分比宽度
-        let (sidebar_width, email_list_width, content_width) = *column_widths.read();
+        let (sidebar_width, email_list_width, _content_width) = *column_widths.read();
         
         let initial_width_percent = if element_id == "sidebar" {
             sidebar_width
@@ -4,8 +4,11 @@ use serde::{Serialize, Deserialize};
 use std::fs;
 use std::path::PathBuf;
 use crate::models::email::EmailAccount;
+use crate::service::{verify_account, discover_settings, AccountSettings};
+use super::app_storage::{SharedStorage, use_storage};
 use serde_json;
 
+// 旧版本只保存一个账户的 login_info.json，仅用于首次导入
 #[derive(Serialize, Deserialize, Default)]
 struct LoginInfo {
     address: String,
@@ -28,43 +31,59 @@ pub enum LoginStatus {
 #[derive(Props, PartialEq, Clone)]
 pub struct LoginPageProps {
     pub on_login: EventHandler<EmailAccount>,
+    // 编辑账户时预填的信息
+    #[props(default)]
+    pub initial: Option<EmailAccount>,
+    // 作为添加/编辑账户对话框使用时的取消回调
+    #[props(default)]
+    pub on_cancel: Option<EventHandler<()>>,
+    #[props(default = true)]
+    pub auto_login: bool,
 }
 
 pub fn LoginPage(props: LoginPageProps) -> Element {
-    let mut address = use_signal(|| String::new());
-    let mut password = use_signal(|| String::new());
-    let mut imap_server = use_signal(|| "imap.example.com".to_string());
-    let mut imap_port = use_signal(|| 993u16);
-    let mut smtp_server = use_signal(|| "smtp.example.com".to_string());
-    let mut smtp_port = use_signal(|| 587u16);
-    let mut use_tls = use_signal(|| true);
-    let mut status = use_signal(|| LoginStatus::Idle);
-    let mut show_form = use_signal(|| true);
-    let mut auto_login_attempted = use_signal(|| false);
+    let initial = props.initial.clone();
+    // 邮件、标签和视图都以地址为键，编辑已有账户时地址不可修改
+    let editing = initial.is_some();
+    let mut address = use_signal(|| initial.as_ref().map_or(String::new(), |a| a.address.clone()));
+    let mut password = use_signal(|| initial.as_ref().map_or(String::new(), |a| a.password.clone()));
+    let mut imap_server = use_signal(|| initial.as_ref().map_or("imap.example.com".to_string(), |a| a.imap_server.clone()));
+    let mut imap_port = use_signal(|| initial.as_ref().map_or(993u16, |a| a.imap_port));
+    let mut smtp_server = use_signal(|| initial.as_ref().map_or("smtp.example.com".to_string(), |a| a.smtp_server.clone()));
+    let mut smtp_port = use_signal(|| initial.as_ref().map_or(587u16, |a| a.smtp_port));
+    let mut use_tls = use_signal(|| initial.as_ref().is_none_or(|a| a.use_tls));
+    let mut smtp_tls = use_signal(|| initial.as_ref().is_none_or(|a| a.smtp_tls()));
+    let status = use_signal(|| LoginStatus::Idle);
+    let show_form = use_signal(|| true);
+    let auto_login_attempted = use_signal(|| false);
+    let mut discovering = use_signal(|| false);
+    let mut discovery_message = use_signal(|| None::<String>);
+    let storage = use_storage();
 
     // 尝试自动登录
     let auto_login = {
-        let mut auto_login_attempted = auto_login_attempted.clone();
-        let mut address = address.clone();
-        let mut password = password.clone();
-        let mut imap_server = imap_server.clone();
-        let mut imap_port = imap_port.clone();
-        let mut smtp_server = smtp_server.clone();
-        let mut smtp_port = smtp_port.clone();
-        let mut use_tls = use_tls.clone();
-        let mut status = status.clone();
-        let mut show_form = show_form.clone();
-        let mut on_login = props.on_login.clone();
+        let mut auto_login_attempted = auto_login_attempted;
+        let mut address = address;
+        let mut password = password;
+        let mut imap_server = imap_server;
+        let mut imap_port = imap_port;
+        let mut smtp_server = smtp_server;
+        let mut smtp_port = smtp_port;
+        let mut use_tls = use_tls;
+        let mut status = status;
+        let mut show_form = show_form;
+        let on_login = props.on_login;
+        let enabled = props.auto_login;
         
         move || {
             // 防止重复尝试自动登录
-            if *auto_login_attempted.read() {
+            if !enabled || *auto_login_attempted.read() {
                 return;
             }
             auto_login_attempted.set(true);
             
-            // 尝试加载保存的账户信息
-            if let Some(info) = load_login_info() {
+            // 尝试加载保存的当前账户
+            if let Some(info) = load_account_settings(storage).active_account() {
                 // 如果所有必要信息都已填写，则自动登录
                 if !info.address.is_empty() && !info.password.is_empty() {
                     // 设置状态
@@ -78,20 +97,26 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                     smtp_server.set(info.smtp_server.clone());
                     smtp_port.set(info.smtp_port);
                     use_tls.set(info.use_tls);
+                    smtp_tls.set(info.smtp_tls());
                     
-                    // 调用登录回调
-                    on_login.call(EmailAccount {
-                        address: info.address,
-                        password: info.password,
-                        imap_server: info.imap_server,
-                        imap_port: info.imap_port,
-                        smtp_server: info.smtp_server,
-                        smtp_port: info.smtp_port,
-                        use_tls: info.use_tls,
+                    // 先连接服务器校验账户，通过后再登录
+                    let account = info;
+                    show_form.set(false); // 校验期间显示“正在登录”
+                    spawn(async move {
+                        let report = verify_account(&account).await;
+                        // 网络暂时不可用时仍使用保存的账户，之后同步时会重试
+                        if report.is_ok() || report.is_retryable() {
+                            if !report.is_ok() {
+                                println!("账户校验暂时失败，继续使用保存的设置: {}", report.summary());
+                            }
+                            on_login.call(account);
+                            status.set(LoginStatus::Success);
+                        } else {
+                            // 保存的信息已失效，显示表单让用户修改
+                            status.set(LoginStatus::Failed(report.summary()));
+                            show_form.set(true);
+                        }
                     });
-                    
-                    status.set(LoginStatus::Success);
-                    show_form.set(false); // 隐藏登录表单，因为我们已经自动登录
                 } else {
                     // 如果信息不完整，则显示登录表单
                     show_form.set(true);
@@ -107,16 +132,12 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
     use_hook(auto_login);
 
     let try_login = {
-        let mut address = address.clone();
-        let mut password = password.clone();
-        let mut imap_server = imap_server.clone();
-        let mut imap_port = imap_port.clone();
-        let mut smtp_server = smtp_server.clone();
-        let mut smtp_port = smtp_port.clone();
-        let mut use_tls = use_tls.clone();
-        let mut status = status.clone();
-        let mut on_login = props.on_login.clone();
+        let mut status = status;
+        let on_login = props.on_login;
         move |_| {
+            if *status.read() == LoginStatus::Checking {
+                return;
+            }
             status.set(LoginStatus::Checking);
             let address = address.read().clone();
             let password = password.read().clone();
@@ -125,27 +146,14 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
             let smtp_server = smtp_server.read().clone();
             let smtp_port = *smtp_port.read();
             let use_tls = *use_tls.read();
+            let smtp_use_tls = Some(*smtp_tls.read());
 
-            // 这里可以加异步校验逻辑
             if address.is_empty() || password.is_empty() {
                 status.set(LoginStatus::Failed("邮箱和密码不能为空".to_string()));
                 return;
             }
 
-            // 保存登录信息到本地
-            let info = LoginInfo {
-                address: address.clone(),
-                password: password.clone(),
-                imap_server: imap_server.clone(),
-                imap_port,
-                smtp_server: smtp_server.clone(),
-                smtp_port,
-                use_tls,
-            };
-            save_login_info(&info);
-
-            // 这里直接调用 on_login
-            on_login.call(EmailAccount {
+            let account = EmailAccount {
                 address,
                 password,
                 imap_server,
@@ -153,14 +161,57 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                 smtp_server,
                 smtp_port,
                 use_tls,
+                smtp_use_tls,
+            };
+
+            // 同时校验 IMAP 和 SMTP，两者都通过才接受账户
+            spawn(async move {
+                let report = verify_account(&account).await;
+                if !report.is_ok() {
+                    status.set(LoginStatus::Failed(report.summary()));
+                    return;
+                }
+
+                // 账户列表由父组件负责保存
+                on_login.call(account);
+                status.set(LoginStatus::Success);
             });
-            status.set(LoginStatus::Success);
         }
     };
 
+    // 根据邮箱地址自动发现服务器设置并填入表单
+    let mut run_discovery = move || {
+        let addr = address.read().trim().to_string();
+        if *discovering.read() || !addr.contains('@') {
+            return;
+        }
+        discovering.set(true);
+        discovery_message.set(Some("正在检测服务器设置...".to_string()));
+
+        spawn(async move {
+            match discover_settings(addr).await {
+                Some(config) => {
+                    let mut account = EmailAccount::default();
+                    config.apply_to(&mut account);
+                    use_tls.set(account.use_tls);
+                    smtp_tls.set(account.smtp_tls());
+                    imap_server.set(account.imap_server);
+                    imap_port.set(account.imap_port);
+                    smtp_server.set(account.smtp_server);
+                    smtp_port.set(account.smtp_port);
+                    discovery_message.set(Some(format!("已通过{}填入服务器设置", config.source)));
+                }
+                None => {
+                    discovery_message.set(Some("未能自动检测到服务器设置，请手动填写".to_string()));
+                }
+            }
+            discovering.set(false);
+        });
+    };
+
     // 清除保存的登录信息并显示表单
-    let show_login_form = {
-        let mut show_form = show_form.clone();
+    let _show_login_form = {
+        let mut show_form = show_form;
         move |_: MouseEvent| {  // 添加 MouseEvent 类型
             show_form.set(true);
         }
@@ -176,7 +227,11 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                     class: "w-full max-w-md bg-white rounded-3xl shadow-2xl p-10 space-y-8",
                     h2 {
                         class: "text-4xl font-bold text-center text-blue-700 mb-4",
-                        "邮箱账户登录"
+                        match (props.initial.is_some(), props.on_cancel.is_some()) {
+                            (true, _) => "编辑账户",
+                            (false, true) => "添加账户",
+                            _ => "邮箱账户登录",
+                        }
                     }
                     hr { class: "mb-6 border-blue-200" }
                     div { class: "space-y-6",
@@ -185,7 +240,15 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                             input {
                                 class: "w-full px-4 py-3 border border-gray-300 rounded-full focus:outline-none focus:ring-2 focus:ring-blue-400 text-lg",
                                 value: "{address}",
+                                readonly: editing,
+                                title: if editing { "账户地址不可修改，如需更换请添加新账户" } else { "" },
                                 oninput: move |e| address.set(e.value().to_string()),
+                                // 服务器仍是示例地址时，填完邮箱自动检测
+                                onchange: move |_| {
+                                    if imap_server.read().ends_with("example.com") && smtp_server.read().ends_with("example.com") {
+                                        run_discovery();
+                                    }
+                                },
                                 placeholder: "your@email.com"
                             }
                         }
@@ -199,6 +262,19 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                                 placeholder: "请输入密码"
                             }
                         }
+                        div { class: "flex items-center justify-between",
+                            span { class: "text-gray-500 text-sm",
+                                if let Some(msg) = discovery_message.read().as_ref() {
+                                    "{msg}"
+                                }
+                            }
+                            button {
+                                class: "text-blue-600 hover:underline text-sm",
+                                disabled: *discovering.read(),
+                                onclick: move |_| run_discovery(),
+                                "自动检测服务器设置"
+                            }
+                        }
                         div { class: "flex space-x-4",
                             div { class: "flex-1",
                                 label { class: "block text-gray-700 font-semibold mb-2", "IMAP服务器" }
@@ -246,22 +322,37 @@ pub fn LoginPage(props: LoginPageProps) -> Element {
                                 oninput: move |e| use_tls.set(e.value() == "on"),
                                 class: "rounded-full border-gray-300 focus:ring-blue-400"
                             }
-                            label { class: "text-gray-700 text-lg", "使用TLS" }
+                            label { class: "text-gray-700 text-lg", "IMAP 使用 SSL/TLS" }
+                            input {
+                                r#type: "checkbox",
+                                checked: *smtp_tls.read(),
+                                oninput: move |e| smtp_tls.set(e.value() == "on"),
+                                class: "rounded-full border-gray-300 focus:ring-blue-400"
+                            }
+                            label { class: "text-gray-700 text-lg", "SMTP 使用 TLS" }
                         }
                     }
                     div {
                         button {
                             class: "w-full py-3 mt-4 bg-blue-600 hover:bg-blue-700 text-white font-bold rounded-full shadow-lg text-lg transition",
                             onclick: try_login,
-                            "登录"
+                            disabled: *status.read() == LoginStatus::Checking,
+                            if *status.read() == LoginStatus::Checking { "正在连接服务器..." } else if props.on_cancel.is_some() { "保存" } else { "登录" }
+                        }
+                        if let Some(on_cancel) = props.on_cancel {
+                            button {
+                                class: "w-full py-3 mt-2 bg-gray-100 hover:bg-gray-200 text-gray-700 rounded-full text-lg transition",
+                                onclick: move |_| on_cancel.call(()),
+                                "取消"
+                            }
                         }
                     }
                     div { class: "text-center mt-4 min-h-[1.5em]",
                         match &*status.read() {
                             LoginStatus::Idle => rsx!(span { "" }),
-                            LoginStatus::Checking => rsx!(span { class: "text-blue-500", "正在校验..." }),
+                            LoginStatus::Checking => rsx!(span { class: "text-blue-500", "正在连接 IMAP 和 SMTP 服务器校验账户..." }),
                             LoginStatus::Success => rsx!(span { class: "text-green-600", "登录成功！" }),
-                            LoginStatus::Failed(e) => rsx!(span { class: "text-red-600", "{e}" }),
+                            LoginStatus::Failed(e) => rsx!(span { class: "text-red-600 whitespace-pre-line", "{e}" }),
                         }
                     }
                 }
@@ -290,9 +381,47 @@ fn load_login_info() -> Option<LoginInfo> {
     }
 }
 
-fn save_login_info(info: &LoginInfo) {
-    let path = get_login_info_path();
-    if let Ok(data) = serde_json::to_string(info) {
-        let _ = fs::write(path, data);
+// 加载已保存的账户列表；首次运行时导入旧版 login_info.json
+pub fn load_account_settings(shared: SharedStorage) -> AccountSettings {
+    let storage = match shared.handle() {
+        Ok(storage) => storage,
+        Err(e) => {
+            println!("初始化本地存储失败: {}", e);
+            return AccountSettings::default();
+        }
+    };
+
+    match storage.load_accounts() {
+        Ok(Some(settings)) => return settings,
+        Ok(None) => {}
+        Err(e) => {
+            println!("加载账户列表失败: {}", e);
+            return AccountSettings::default();
+        }
     }
-}
\ No newline at end of file
+
+    let mut settings = AccountSettings::default();
+    if let Some(info) = load_login_info() {
+        if !info.address.is_empty() {
+            settings.active = Some(info.address.clone());
+            settings.accounts.push(EmailAccount {
+                address: info.address,
+                password: info.password,
+                imap_server: info.imap_server,
+                imap_port: info.imap_port,
+                smtp_server: info.smtp_server,
+                smtp_port: info.smtp_port,
+                use_tls: info.use_tls,
+                smtp_use_tls: None,
+            });
+            save_account_settings(shared, &settings);
+        }
+    }
+    settings
+}
+
+pub fn save_account_settings(storage: SharedStorage, settings: &AccountSettings) {
+    if let Err(e) = storage.handle().and_then(|storage| storage.save_accounts(settings)) {
+        println!("保存账户列表失败: {}", e);
+    }
+}
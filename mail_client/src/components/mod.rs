@@ -4,10 +4,18 @@ pub mod composer;
 pub mod login_page;
 pub mod layout_resizer;
 pub mod email_detail;
+pub mod settings_page;
+pub mod contacts_page;
+pub mod plain_text;
+pub mod sieve_settings;
+pub mod label_chips;
+pub mod app_storage;
 
 pub use inbox::*;
 pub use sidebar::*;
 pub use composer::*;
-pub use login_page::LoginPage;
-pub use layout_resizer::*;
 pub use email_detail::EmailDetail;
+pub use settings_page::SettingsPage;
+pub use contacts_page::ContactsPage;
+pub use label_chips::{LabelChips, LabelPicker};
+pub use app_storage::{provide_storage, use_storage};
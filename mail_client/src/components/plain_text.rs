@@ -0,0 +1,157 @@
+use dioxus::prelude::*;
+
+// 纯文本正文中的一行：引用层数（行首 > 的个数）和去掉引用标记后的内容
+#[derive(Clone, Debug, PartialEq)]
+struct Line {
+    level: usize,
+    text: String,
+}
+
+// 行内的一段：普通文字或链接
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Link { text: String, href: String },
+}
+
+fn parse_line(line: &str) -> Line {
+    let mut rest = line;
+    let mut level = 0;
+    loop {
+        let trimmed = rest.trim_start_matches(' ');
+        match trimmed.strip_prefix('>') {
+            Some(inner) => {
+                level += 1;
+                rest = inner;
+            }
+            None => break,
+        }
+    }
+    // 引用标记后的一个空格属于标记
+    let text = if level > 0 { rest.strip_prefix(' ').unwrap_or(rest) } else { rest };
+    Line { level, text: text.trim_end_matches('\r').to_string() }
+}
+
+// 签名分隔行（RFC 3676 为 "-- "，很多客户端会去掉末尾空格）
+fn is_signature_separator(line: &Line) -> bool {
+    line.level == 0 && line.text.trim_end() == "--"
+}
+
+// 拆分正文和签名，以最后一个分隔行为准；签名过长时不当作签名
+fn split_signature(lines: Vec<Line>) -> (Vec<Line>, Vec<Line>) {
+    match lines.iter().rposition(is_signature_separator) {
+        Some(index) if lines.len() - index <= 15 => {
+            let mut content = lines;
+            let signature = content.split_off(index + 1);
+            content.pop();
+            (content, signature)
+        }
+        _ => (lines, Vec::new()),
+    }
+}
+
+// 链接结束的位置：空白、全角字符和常见的结尾标点不属于链接
+fn link_end(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || !c.is_ascii() || matches!(c, '<' | '>' | '"'))
+        .unwrap_or(text.len());
+    text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'']).len()
+}
+
+// 识别行中的 http(s)://、www. 和 mailto: 链接
+fn segments(text: &str) -> Vec<Segment> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let start = ["https://", "http://", "www.", "mailto:"]
+            .iter()
+            .filter_map(|prefix| lower.find(prefix))
+            // www. 必须在单词开头
+            .filter(|&i| i == 0 || !rest.as_bytes()[i - 1].is_ascii_alphanumeric())
+            .min();
+        let Some(start) = start else {
+            break;
+        };
+        let len = link_end(&rest[start..]);
+        let link = &rest[start..start + len];
+        if link.len() <= "https://".len() || (!link.contains('.') && !link.to_ascii_lowercase().starts_with("mailto:")) {
+            out.push(Segment::Text(rest[..start + len.max(1)].to_string()));
+            rest = &rest[start + len.max(1)..];
+            continue;
+        }
+        if start > 0 {
+            out.push(Segment::Text(rest[..start].to_string()));
+        }
+        let href = if link.to_ascii_lowercase().starts_with("www.") { format!("https://{}", link) } else { link.to_string() };
+        out.push(Segment::Link { text: link.to_string(), href });
+        rest = &rest[start + len..];
+    }
+    if !rest.is_empty() {
+        out.push(Segment::Text(rest.to_string()));
+    }
+    out
+}
+
+fn render_text(text: &str) -> Element {
+    rsx! {
+        for segment in segments(text) {
+            match segment {
+                Segment::Text(text) => rsx! { "{text}" },
+                Segment::Link { text, href } => rsx! {
+                    a { href: "{href}", target: "_blank", "{text}" }
+                },
+            }
+        }
+        "\n"
+    }
+}
+
+// 按引用层数分组，更深的引用折叠在可展开的块中
+fn render_lines(lines: &[Line], level: usize) -> Element {
+    let mut groups: Vec<(bool, &[Line])> = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let quoted = lines[start].level > level;
+        let len = lines[start..].iter().take_while(|l| (l.level > level) == quoted).count();
+        groups.push((quoted, &lines[start..start + len]));
+        start += len;
+    }
+    rsx! {
+        for (quoted, group) in groups {
+            if quoted {
+                details {
+                    class: "plain-quote",
+                    summary { "引用内容（{group.len()} 行）" }
+                    {render_lines(group, level + 1)}
+                }
+            } else {
+                for line in group {
+                    {render_text(&line.text)}
+                }
+            }
+        }
+    }
+}
+
+// 纯文本正文：保留换行，链接可点击，引用可折叠，签名单独显示
+#[component]
+pub fn PlainTextBody(text: String) -> Element {
+    let lines: Vec<Line> = text.lines().map(parse_line).collect();
+    let (content, signature) = split_signature(lines);
+    rsx! {
+        div {
+            class: "email-content email-plain",
+            {render_lines(&content, 0)}
+            if !signature.is_empty() {
+                div {
+                    class: "plain-signature",
+                    "-- \n"
+                    for line in signature.iter() {
+                        {render_text(&line.text)}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1399 @@
+use std::path::PathBuf;
+use dioxus::prelude::*;
+use crate::models::email::EmailAccount;
+use crate::models::{ActionKind, BodyView, FilterRule, Label, RuleAction, RuleCondition, RuleField, SavedSearch, LABEL_COLORS};
+use crate::service::{ListDensity, StorageBackend, MboxFormat};
+use crate::service::{filter, imap_client, mbox};
+use crate::service::{carddav, dav, DavService};
+use crate::components::sieve_settings::SieveSettings;
+use crate::components::use_storage;
+
+#[derive(Props, PartialEq, Clone)]
+pub struct SettingsPageProps {
+    pub accounts: Vec<EmailAccount>,
+    pub current_account: Option<EmailAccount>,
+    // 导入邮件等操作修改了本地邮件时通知父组件刷新
+    pub on_mail_changed: EventHandler<()>,
+}
+
+pub fn SettingsPage(props: SettingsPageProps) -> Element {
+    rsx! {
+        div {
+            class: "settings-container",
+            div {
+                class: "composer-header",
+                h2 { "设置" }
+            }
+            div {
+                class: "settings-body",
+                ReadingSettings { on_mail_changed: props.on_mail_changed }
+                LabelSettings {
+                    accounts: props.accounts.clone(),
+                    on_mail_changed: props.on_mail_changed,
+                }
+                SearchSettings { on_mail_changed: props.on_mail_changed }
+                RulesSettings {
+                    accounts: props.accounts.clone(),
+                    current_account: props.current_account.clone(),
+                    on_mail_changed: props.on_mail_changed,
+                }
+                SieveSettings {
+                    accounts: props.accounts.clone(),
+                    current_account: props.current_account.clone(),
+                }
+                StorageSettings {
+                    accounts: props.accounts.clone(),
+                    current_account: props.current_account.clone(),
+                    on_mail_changed: props.on_mail_changed,
+                }
+                MboxSettings {
+                    accounts: props.accounts.clone(),
+                    current_account: props.current_account.clone(),
+                    on_mail_changed: props.on_mail_changed,
+                }
+                DavSettings {
+                    accounts: props.accounts.clone(),
+                    current_account: props.current_account.clone(),
+                }
+            }
+        }
+    }
+}
+
+// 阅读邮件时的默认显示方式，单封邮件可以在邮件详情中切换；邮件列表的显示密度
+#[component]
+fn ReadingSettings(on_mail_changed: EventHandler<()>) -> Element {
+    let mut storage = use_storage();
+    let mut body_view = use_signal(|| storage.handle().map(|s| s.body_view()).unwrap_or_default());
+    let mut density = use_signal(|| storage.handle().map(|s| s.density()).unwrap_or_default());
+    let mut status = use_signal(String::new);
+
+    let choose_view = move |e: FormEvent| {
+        let view = BodyView::parse(&e.value()).unwrap_or_default();
+        match storage.update(|s| s.set_body_view(view)) {
+            Ok(()) => {
+                body_view.set(view);
+                status.set("已保存".to_string());
+            }
+            Err(e) => status.set(format!("保存失败: {}", e)),
+        }
+    };
+
+    // 邮件列表重新加载后按新的密度显示
+    let choose_density = move |e: FormEvent| {
+        let value = ListDensity::parse(&e.value()).unwrap_or_default();
+        match storage.update(|s| s.set_density(value)) {
+            Ok(()) => {
+                density.set(value);
+                status.set("已保存".to_string());
+                on_mail_changed.call(());
+            }
+            Err(e) => status.set(format!("保存失败: {}", e)),
+        }
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "阅读" }
+            div {
+                class: "form-group",
+                label { "默认显示" }
+                select {
+                    class: "form-control",
+                    value: body_view.read().as_str(),
+                    onchange: choose_view,
+                    option { value: "html", "HTML" }
+                    option { value: "plain", "纯文本" }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "邮件列表" }
+                select {
+                    class: "form-control",
+                    value: density.read().as_str(),
+                    onchange: choose_density,
+                    option { value: "comfortable", "舒适：显示头像和摘要" }
+                    option { value: "compact", "紧凑" }
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+    }
+}
+
+// 标签的名称和颜色；关键字在创建时确定，改名不影响已加标签的邮件
+#[component]
+fn LabelSettings(accounts: Vec<EmailAccount>, on_mail_changed: EventHandler<()>) -> Element {
+    let storage = use_storage();
+    let mut labels = use_signal(|| storage.handle().and_then(|s| s.labels()).unwrap_or_default());
+    let mut new_name = use_signal(String::new);
+    let mut new_color = use_signal(|| LABEL_COLORS[0].to_string());
+    let mut status = use_signal(String::new);
+
+    let mut save_labels = move |list: Vec<Label>| match storage.handle().and_then(|s| s.save_labels(&list)) {
+        Ok(()) => {
+            labels.set(list);
+            status.set("已保存".to_string());
+            on_mail_changed.call(());
+        }
+        Err(e) => status.set(format!("保存失败: {}", e)),
+    };
+
+    let add_label = move |_| {
+        let name = new_name.read().trim().to_string();
+        if name.is_empty() {
+            status.set("请输入标签名称".to_string());
+            return;
+        }
+        let mut list = labels.read().clone();
+        if list.iter().any(|l| l.name == name) {
+            status.set(format!("标签「{}」已存在", name));
+            return;
+        }
+        let mut label = Label::new(&name, &list);
+        label.color = new_color.read().clone();
+        list.push(label);
+        save_labels(list);
+        new_name.set(String::new());
+        let next = LABEL_COLORS[labels.read().len() % LABEL_COLORS.len()];
+        new_color.set(next.to_string());
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "标签" }
+            if labels.read().is_empty() {
+                p { class: "settings-hint", "还没有标签。标签可以加在任意文件夹的邮件上，服务器允许时会同步为 IMAP 关键字。" }
+            }
+            ul {
+                class: "rule-list",
+                for (i, label) in labels.read().iter().cloned().enumerate() {
+                    li {
+                        key: "{label.keyword}",
+                        class: "rule-item",
+                        input {
+                            r#type: "color",
+                            class: "label-color-input",
+                            value: "{label.color}",
+                            onchange: move |e| {
+                                let mut list = labels.read().clone();
+                                list[i].color = e.value();
+                                save_labels(list);
+                            },
+                        }
+                        input {
+                            class: "form-control",
+                            value: "{label.name}",
+                            onchange: move |e| {
+                                let name = e.value().trim().to_string();
+                                if name.is_empty() {
+                                    return;
+                                }
+                                let mut list = labels.read().clone();
+                                list[i].name = name;
+                                save_labels(list);
+                            },
+                        }
+                        span { class: "rule-summary", "{label.keyword}" }
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: {
+                                let accounts = accounts.clone();
+                                move |_| {
+                                    let keyword = labels.read()[i].keyword.clone();
+                                    match storage.handle().and_then(|s| s.delete_label(&accounts, &keyword)) {
+                                        Ok(()) => {
+                                            labels.write().remove(i);
+                                            status.set("已删除".to_string());
+                                            on_mail_changed.call(());
+                                        }
+                                        Err(e) => status.set(format!("删除失败: {}", e)),
+                                    }
+                                }
+                            },
+                            "删除"
+                        }
+                    }
+                }
+            }
+            div {
+                class: "rule-row label-add-row",
+                input {
+                    r#type: "color",
+                    class: "label-color-input",
+                    value: "{new_color}",
+                    oninput: move |e| new_color.set(e.value()),
+                }
+                input {
+                    class: "form-control",
+                    placeholder: "新标签名称",
+                    value: "{new_name}",
+                    oninput: move |e| new_name.set(e.value()),
+                }
+                button {
+                    class: "btn btn-primary",
+                    onclick: add_label,
+                    "添加标签"
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+    }
+}
+
+// 保存的搜索：名称显示在侧边栏，搜索内容与顶部搜索框的语法相同
+#[component]
+fn SearchSettings(on_mail_changed: EventHandler<()>) -> Element {
+    let storage = use_storage();
+    let mut searches = use_signal(|| storage.handle().and_then(|s| s.saved_searches()).unwrap_or_default());
+    let mut new_name = use_signal(String::new);
+    let mut new_query = use_signal(String::new);
+    let mut status = use_signal(String::new);
+
+    let mut save_searches = move |list: Vec<SavedSearch>| {
+        match storage.handle().and_then(|s| s.save_saved_searches(&list)) {
+            Ok(()) => {
+                searches.set(list);
+                status.set("已保存".to_string());
+                on_mail_changed.call(());
+            }
+            Err(e) => status.set(format!("保存失败: {}", e)),
+        }
+    };
+
+    let add_search = move |_| {
+        let query = new_query.read().trim().to_string();
+        if query.is_empty() {
+            status.set("请输入搜索内容".to_string());
+            return;
+        }
+        let name = match new_name.read().trim() {
+            "" => query.clone(),
+            name => name.to_string(),
+        };
+        let mut list = searches.read().clone();
+        list.push(SavedSearch { name, query });
+        save_searches(list);
+        new_name.set(String::new());
+        new_query.set(String::new());
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "保存的搜索" }
+            p {
+                class: "settings-hint",
+                "保存的搜索显示在侧边栏中，内容随新邮件更新。可以使用 from: to: subject: is:unread is:read has:attachment after: before: label: in: 等条件，前面加 - 表示排除。"
+            }
+            ul {
+                class: "rule-list",
+                for (i, search) in searches.read().iter().cloned().enumerate() {
+                    li {
+                        key: "{i}",
+                        class: "rule-item",
+                        input {
+                            class: "form-control",
+                            value: "{search.name}",
+                            onchange: move |e| {
+                                let name = e.value().trim().to_string();
+                                if name.is_empty() {
+                                    return;
+                                }
+                                let mut list = searches.read().clone();
+                                list[i].name = name;
+                                save_searches(list);
+                            },
+                        }
+                        input {
+                            class: "form-control",
+                            value: "{search.query}",
+                            onchange: move |e| {
+                                let query = e.value().trim().to_string();
+                                if query.is_empty() {
+                                    return;
+                                }
+                                let mut list = searches.read().clone();
+                                list[i].query = query;
+                                save_searches(list);
+                            },
+                        }
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| {
+                                let mut list = searches.read().clone();
+                                list.remove(i);
+                                save_searches(list);
+                            },
+                            "删除"
+                        }
+                    }
+                }
+            }
+            div {
+                class: "rule-row label-add-row",
+                input {
+                    class: "form-control",
+                    placeholder: "名称",
+                    value: "{new_name}",
+                    oninput: move |e| new_name.set(e.value()),
+                }
+                input {
+                    class: "form-control",
+                    placeholder: "如 from:boss is:unread",
+                    value: "{new_query}",
+                    oninput: move |e| new_query.set(e.value()),
+                }
+                button {
+                    class: "btn btn-primary",
+                    onclick: add_search,
+                    "添加"
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+    }
+}
+
+// 本地存储方式以及 Maildir 导入/导出
+#[component]
+fn StorageSettings(
+    accounts: Vec<EmailAccount>,
+    current_account: Option<EmailAccount>,
+    on_mail_changed: EventHandler<()>,
+) -> Element {
+    let mut storage = use_storage();
+    let initial_backend = use_hook(|| storage.handle().map(|s| s.storage_backend().clone()).unwrap_or_default());
+    let mut use_maildir = use_signal(|| matches!(initial_backend, StorageBackend::Maildir(_)));
+    let mut maildir_root = use_signal(|| match &initial_backend {
+        StorageBackend::Maildir(root) => root.display().to_string(),
+        StorageBackend::Database => String::new(),
+    });
+    let mut backend_status = use_signal(String::new);
+
+    let default_address = current_account
+        .as_ref()
+        .or(accounts.first())
+        .map_or(String::new(), |a| a.address.clone());
+    let mut selected_address = use_signal(|| default_address);
+    let mut import_path = use_signal(String::new);
+    let mut export_path = use_signal(String::new);
+    let mut export_folder = use_signal(|| "INBOX".to_string());
+    let mut transfer_status = use_signal(String::new);
+    let mut busy = use_signal(|| false);
+
+    let selected_account = accounts.iter().find(|a| a.address == *selected_address.read()).cloned();
+    let folders = selected_account
+        .as_ref()
+        .and_then(|acc| storage.handle().ok().map(|s| s.list_folders(acc)))
+        .unwrap_or_default();
+
+    // 保存存储方式，切换到 Maildir 时会写出已有邮件，在后台线程执行
+    let save_backend = move |_| {
+        let backend = if *use_maildir.read() {
+            let root = maildir_root.read().trim().to_string();
+            if root.is_empty() {
+                backend_status.set("请填写 Maildir 目录".to_string());
+                return;
+            }
+            StorageBackend::Maildir(PathBuf::from(root))
+        } else {
+            StorageBackend::Database
+        };
+        busy.set(true);
+        backend_status.set("正在保存...".to_string());
+        let shared = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                let mut storage = shared?;
+                let written = storage.set_storage_backend(backend)?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>((storage, written))
+            })
+            .await
+            .map(|(updated, written)| {
+                storage.replace(updated);
+                written
+            });
+            match result {
+                Ok(written) if written > 0 => backend_status.set(format!("已保存，写入 Maildir {} 封邮件", written)),
+                Ok(_) => backend_status.set("已保存".to_string()),
+                Err(e) => backend_status.set(format!("保存失败: {}", e)),
+            }
+            busy.set(false);
+            on_mail_changed.call(());
+        });
+    };
+
+    let import_account = selected_account.clone();
+    let run_import = move |_| {
+        let Some(account) = import_account.clone() else {
+            return;
+        };
+        let source = import_path.read().trim().to_string();
+        if source.is_empty() {
+            transfer_status.set("请填写要导入的 Maildir 目录".to_string());
+            return;
+        }
+        busy.set(true);
+        transfer_status.set("正在导入...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                storage?.import_maildir(&account, &PathBuf::from(source))
+            })
+            .await;
+            match result {
+                Ok(count) => transfer_status.set(format!("导入完成，共 {} 封新邮件", count)),
+                Err(e) => transfer_status.set(format!("导入失败: {}", e)),
+            }
+            busy.set(false);
+            on_mail_changed.call(());
+        });
+    };
+
+    let export_account = selected_account.clone();
+    let run_export = move |_| {
+        let Some(account) = export_account.clone() else {
+            return;
+        };
+        let destination = export_path.read().trim().to_string();
+        if destination.is_empty() {
+            transfer_status.set("请填写导出目录".to_string());
+            return;
+        }
+        let folder = export_folder.read().clone();
+        busy.set(true);
+        transfer_status.set("正在导出...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                storage?.export_maildir(&account, &folder, &PathBuf::from(destination))
+            })
+            .await;
+            match result {
+                Ok(count) => transfer_status.set(format!("导出完成，共 {} 封邮件", count)),
+                Err(e) => transfer_status.set(format!("导出失败: {}", e)),
+            }
+            busy.set(false);
+        });
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "本地存储" }
+            div {
+                class: "form-group",
+                label { "存储方式" }
+                select {
+                    class: "form-control",
+                    value: if *use_maildir.read() { "maildir" } else { "database" },
+                    onchange: move |e| use_maildir.set(e.value() == "maildir"),
+                    option { value: "database", "仅数据库" }
+                    option { value: "maildir", "数据库 + Maildir（可与 mutt、notmuch 共用）" }
+                }
+            }
+            if *use_maildir.read() {
+                div {
+                    class: "form-group",
+                    label { "Maildir 目录" }
+                    input {
+                        class: "form-control",
+                        r#type: "text",
+                        placeholder: "如 /home/me/Mail，每个账户保存在一个子目录中",
+                        value: "{maildir_root}",
+                        oninput: move |e| maildir_root.set(e.value()),
+                    }
+                }
+            }
+            div {
+                class: "settings-actions",
+                button {
+                    class: "btn btn-primary",
+                    disabled: *busy.read(),
+                    onclick: save_backend,
+                    "保存"
+                }
+                span { class: "status-message", "{backend_status}" }
+            }
+        }
+
+        section {
+            class: "settings-section",
+            h3 { "Maildir 导入/导出" }
+            div {
+                class: "form-group",
+                label { "账户" }
+                select {
+                    class: "form-control",
+                    value: "{selected_address}",
+                    onchange: move |e| selected_address.set(e.value()),
+                    for account in accounts.iter() {
+                        option { key: "{account.address}", value: "{account.address}", "{account.address}" }
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "导入目录" }
+                input {
+                    class: "form-control",
+                    r#type: "text",
+                    placeholder: "包含 cur/new/tmp 的 Maildir 目录，子文件夹会一并导入",
+                    value: "{import_path}",
+                    oninput: move |e| import_path.set(e.value()),
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none(),
+                    onclick: run_import,
+                    "导入"
+                }
+            }
+            div {
+                class: "form-group",
+                label { "导出文件夹" }
+                select {
+                    class: "form-control",
+                    value: "{export_folder}",
+                    onchange: move |e| export_folder.set(e.value()),
+                    for folder in folders.iter() {
+                        option { key: "{folder}", value: "{folder}", "{folder}" }
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "导出到" }
+                input {
+                    class: "form-control",
+                    r#type: "text",
+                    placeholder: "目标 Maildir 目录，不存在时自动创建",
+                    value: "{export_path}",
+                    oninput: move |e| export_path.set(e.value()),
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none() || folders.is_empty(),
+                    onclick: run_export,
+                    "导出"
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{transfer_status}" }
+            }
+        }
+    }
+}
+
+// mbox 文件导入（可同时上传到服务器）和导出
+#[component]
+fn MboxSettings(
+    accounts: Vec<EmailAccount>,
+    current_account: Option<EmailAccount>,
+    on_mail_changed: EventHandler<()>,
+) -> Element {
+    let storage = use_storage();
+    let default_address = current_account
+        .as_ref()
+        .or(accounts.first())
+        .map_or(String::new(), |a| a.address.clone());
+    let mut selected_address = use_signal(|| default_address);
+    let mut import_folder = use_signal(|| "INBOX".to_string());
+    let mut upload = use_signal(|| false);
+    let mut export_folder = use_signal(|| "INBOX".to_string());
+    let mut export_format = use_signal(|| MboxFormat::Mboxrd);
+    let mut status = use_signal(String::new);
+    let mut busy = use_signal(|| false);
+
+    let selected_account = accounts.iter().find(|a| a.address == *selected_address.read()).cloned();
+    let folders = selected_account
+        .as_ref()
+        .and_then(|acc| storage.handle().ok().map(|s| s.list_folders(acc)))
+        .unwrap_or_default();
+
+    let import_account = selected_account.clone();
+    let run_import = move |_| {
+        let Some(account) = import_account.clone() else {
+            return;
+        };
+        let folder = import_folder.read().trim().to_string();
+        if folder.is_empty() {
+            status.set("请填写导入的文件夹".to_string());
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("选择 mbox 文件")
+            .add_filter("mbox", &["mbox", "mbx"])
+            .add_filter("所有文件", &["*"])
+            .pick_file()
+        else {
+            return;
+        };
+        let upload = *upload.read();
+        busy.set(true);
+        status.set("正在导入...".to_string());
+        spawn(async move {
+            let data = match async_std::fs::read(&path).await {
+                Ok(data) => data,
+                Err(e) => {
+                    status.set(format!("读取文件失败: {}", e));
+                    busy.set(false);
+                    return;
+                }
+            };
+
+            // 上传到收件箱的邮件会在下次同步时下载，不在本地重复保存
+            let messages = if upload && folder.eq_ignore_ascii_case("INBOX") {
+                Ok(mbox::parse_mbox(&data).into_iter().map(|m| m.raw).collect::<Vec<_>>())
+            } else {
+                let import_account = account.clone();
+                let import_folder = folder.clone();
+                let storage = storage.handle();
+                async_std::task::spawn_blocking(move || {
+                    storage?.import_mbox(&import_account, &import_folder, &data)
+                })
+                .await
+            };
+            let messages = match messages {
+                Ok(messages) => messages,
+                Err(e) => {
+                    status.set(format!("导入失败: {}", e));
+                    busy.set(false);
+                    return;
+                }
+            };
+
+            if upload && !messages.is_empty() {
+                status.set(format!("正在上传 {} 封邮件到服务器...", messages.len()));
+                let messages: Vec<Vec<u8>> = messages.iter().map(|raw| mbox::to_crlf(raw)).collect();
+                let result = imap_client::append_emails(
+                    &account.imap_server,
+                    account.imap_port,
+                    &account.address,
+                    &account.password,
+                    account.use_tls,
+                    &folder,
+                    &messages,
+                )
+                .await;
+                match result {
+                    Ok(count) => status.set(format!("导入完成，已上传 {} 封邮件到服务器", count)),
+                    Err(e) => status.set(format!("上传失败: {}", e)),
+                }
+            } else {
+                status.set(format!("导入完成，共 {} 封新邮件", messages.len()));
+            }
+            busy.set(false);
+            on_mail_changed.call(());
+        });
+    };
+
+    let export_account = selected_account.clone();
+    let export_accounts = accounts.clone();
+    let run_export = move |_| {
+        let Some(account) = export_account.clone() else {
+            return;
+        };
+        let folder = export_folder.read().clone();
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("导出为 mbox")
+            .set_file_name(format!("{}.mbox", folder.replace('/', "_")))
+            .add_filter("mbox", &["mbox"])
+            .save_file()
+        else {
+            return;
+        };
+        let format = *export_format.read();
+        let accounts = export_accounts.clone();
+        busy.set(true);
+        status.set("正在导出...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                let storage = storage?;
+                let emails = storage.load_emails(&account, &folder)?;
+                storage.export_mbox(&accounts, &folder, &emails, &path, format)
+            })
+            .await;
+            match result {
+                Ok(count) => status.set(format!("导出完成，共 {} 封邮件", count)),
+                Err(e) => status.set(format!("导出失败: {}", e)),
+            }
+            busy.set(false);
+        });
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "mbox 导入/导出" }
+            div {
+                class: "form-group",
+                label { "账户" }
+                select {
+                    class: "form-control",
+                    value: "{selected_address}",
+                    onchange: move |e| selected_address.set(e.value()),
+                    for account in accounts.iter() {
+                        option { key: "{account.address}", value: "{account.address}", "{account.address}" }
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "导入到" }
+                input {
+                    class: "form-control",
+                    r#type: "text",
+                    placeholder: "文件夹名称，如 INBOX 或 Archive",
+                    value: "{import_folder}",
+                    oninput: move |e| import_folder.set(e.value()),
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none(),
+                    onclick: run_import,
+                    "选择文件并导入"
+                }
+            }
+            div {
+                class: "form-group",
+                label { "" }
+                input {
+                    r#type: "checkbox",
+                    checked: *upload.read(),
+                    onchange: move |e| upload.set(e.checked()),
+                }
+                span { class: "settings-hint", "同时上传到服务器（IMAP APPEND）" }
+            }
+            div {
+                class: "form-group",
+                label { "导出文件夹" }
+                select {
+                    class: "form-control",
+                    value: "{export_folder}",
+                    onchange: move |e| export_folder.set(e.value()),
+                    for folder in folders.iter() {
+                        option { key: "{folder}", value: "{folder}", "{folder}" }
+                    }
+                }
+                select {
+                    class: "form-control settings-format",
+                    value: if *export_format.read() == MboxFormat::Mboxcl2 { "mboxcl2" } else { "mboxrd" },
+                    onchange: move |e| export_format.set(if e.value() == "mboxcl2" { MboxFormat::Mboxcl2 } else { MboxFormat::Mboxrd }),
+                    option { value: "mboxrd", "mboxrd" }
+                    option { value: "mboxcl2", "mboxcl2" }
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none() || folders.is_empty(),
+                    onclick: run_export,
+                    "导出"
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+    }
+}
+
+// CardDAV/CalDAV 服务器：发现通讯录和日历、立即同步通讯录、选择接受邀请的日历
+// 使用邮箱账户的用户名和密码登录
+#[component]
+fn DavSettings(accounts: Vec<EmailAccount>, current_account: Option<EmailAccount>) -> Element {
+    let storage = use_storage();
+    let default_address = current_account
+        .as_ref()
+        .or(accounts.first())
+        .map_or(String::new(), |a| a.address.clone());
+    let mut selected_address = use_signal(|| default_address);
+    let mut server = use_signal(String::new);
+    let mut status = use_signal(String::new);
+    let mut busy = use_signal(|| false);
+    // 发现或选择日历后加一，重新读取保存的集合
+    let mut revision = use_signal(|| 0u64);
+
+    // 切换账户时读取已保存的服务器地址
+    use_effect(move || {
+        let address = selected_address.read().clone();
+        let saved = storage
+            .handle()
+            .ok()
+            .and_then(|s| s.address_book().dav_server(&address).ok().flatten())
+            .unwrap_or_default();
+        server.set(saved);
+    });
+
+    let selected_account = accounts.iter().find(|a| a.address == *selected_address.read()).cloned();
+    let _ = revision.read();
+    let (address_books, calendars, default_calendar) = selected_account
+        .as_ref()
+        .and_then(|acc| {
+            let storage = storage.handle().ok()?;
+            let book = storage.address_book();
+            Some((
+                book.dav_collections(&acc.address, DavService::CardDav).unwrap_or_default(),
+                book.dav_collections(&acc.address, DavService::CalDav).unwrap_or_default(),
+                book.default_calendar(&acc.address).ok().flatten().map(|c| c.url),
+            ))
+        })
+        .unwrap_or_default();
+
+    let discover_account = selected_account.clone();
+    let run_discover = move |_| {
+        let Some(account) = discover_account.clone() else {
+            return;
+        };
+        let server = server.read().trim().to_string();
+        busy.set(true);
+        status.set("正在查找通讯录和日历...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                let storage = storage?;
+                dav::discover_account(storage.address_book(), &account, &server)
+            })
+            .await;
+            match result {
+                Ok((books, calendars)) => {
+                    status.set(format!("找到 {} 个通讯录、{} 个日历", books.len(), calendars.len()))
+                }
+                Err(e) => status.set(format!("查找失败: {}", e)),
+            }
+            busy.set(false);
+            revision += 1;
+        });
+    };
+
+    let sync_account = selected_account.clone();
+    let run_sync = move |_| {
+        let Some(account) = sync_account.clone() else {
+            return;
+        };
+        busy.set(true);
+        status.set("正在同步通讯录...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                let storage = storage?;
+                carddav::sync_account_contacts(storage.address_book(), &account)
+            })
+            .await;
+            match result {
+                Ok(report) => status.set(format!(
+                    "同步完成：下载 {}，上传 {}，删除 {}，合并冲突 {}",
+                    report.downloaded, report.uploaded, report.deleted, report.conflicts
+                )),
+                Err(e) => status.set(format!("同步失败: {}", e)),
+            }
+            busy.set(false);
+        });
+    };
+
+    let calendar_account = selected_address.read().clone();
+    let choose_calendar = move |e: FormEvent| {
+        let result = storage.handle().and_then(|s| s.address_book().set_default_calendar(&calendar_account, &e.value()));
+        if let Err(e) = result {
+            status.set(format!("保存失败: {}", e));
+        }
+        revision += 1;
+    };
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "通讯录和日历同步（CardDAV / CalDAV）" }
+            div {
+                class: "form-group",
+                label { "账户" }
+                select {
+                    class: "form-control",
+                    value: "{selected_address}",
+                    onchange: move |e| selected_address.set(e.value()),
+                    for account in accounts.iter() {
+                        option { key: "{account.address}", value: "{account.address}", "{account.address}" }
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "服务器地址" }
+                input {
+                    class: "form-control",
+                    r#type: "text",
+                    placeholder: "如 https://dav.example.com/，留空时根据邮箱域名自动查找",
+                    value: "{server}",
+                    oninput: move |e| server.set(e.value()),
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none(),
+                    onclick: run_discover,
+                    "查找"
+                }
+            }
+            div {
+                class: "form-group",
+                label { "通讯录" }
+                if address_books.is_empty() {
+                    span { class: "settings-hint", "未设置" }
+                } else {
+                    span {
+                        class: "settings-hint",
+                        {address_books.iter().map(|c| c.name.clone()).collect::<Vec<_>>().join("、")}
+                    }
+                    button {
+                        class: "btn btn-secondary",
+                        disabled: *busy.read(),
+                        onclick: run_sync,
+                        "立即同步"
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "邀请加入到" }
+                if calendars.is_empty() {
+                    span { class: "settings-hint", "未设置" }
+                } else {
+                    select {
+                        class: "form-control",
+                        value: default_calendar.unwrap_or_default(),
+                        onchange: choose_calendar,
+                        for calendar in calendars.iter().filter(|c| c.supports_events()) {
+                            option { key: "{calendar.url}", value: "{calendar.url}", "{calendar.name}" }
+                        }
+                    }
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+    }
+}
+
+// 邮件规则：同步到的新邮件按顺序执行，也可以对已有邮件执行一次
+#[component]
+fn RulesSettings(
+    accounts: Vec<EmailAccount>,
+    current_account: Option<EmailAccount>,
+    on_mail_changed: EventHandler<()>,
+) -> Element {
+    let storage = use_storage();
+    let mut rules = use_signal(|| storage.handle().and_then(|s| s.filter_rules()).unwrap_or_default());
+    // 正在编辑的规则及其位置，位置为 None 时是新规则
+    let mut draft = use_signal(|| None::<(Option<usize>, FilterRule)>);
+    let mut status = use_signal(String::new);
+
+    let default_address = current_account
+        .as_ref()
+        .or(accounts.first())
+        .map_or(String::new(), |a| a.address.clone());
+    let mut selected_address = use_signal(|| default_address);
+    let mut apply_folder = use_signal(|| "INBOX".to_string());
+    let mut apply_status = use_signal(String::new);
+    let mut busy = use_signal(|| false);
+
+    let selected_account = accounts.iter().find(|a| a.address == *selected_address.read()).cloned();
+    let folders = selected_account
+        .as_ref()
+        .and_then(|acc| storage.handle().ok().map(|s| s.list_folders(acc)))
+        .unwrap_or_default();
+
+    let mut save_rules = move |list: Vec<FilterRule>| match storage.handle().and_then(|s| s.save_filter_rules(&list)) {
+        Ok(()) => {
+            rules.set(list);
+            status.set("已保存".to_string());
+        }
+        Err(e) => status.set(format!("保存失败: {}", e)),
+    };
+
+    let save_draft = move |_| {
+        let Some((index, mut rule)) = draft.read().clone() else {
+            return;
+        };
+        if let Err(e) = validate_rule(&rule) {
+            status.set(e);
+            return;
+        }
+        let mut list = rules.read().clone();
+        if rule.name.trim().is_empty() {
+            rule.name = format!("规则 {}", index.map_or(list.len(), |i| i) + 1);
+        }
+        match index {
+            Some(i) if i < list.len() => list[i] = rule,
+            _ => list.push(rule),
+        }
+        save_rules(list);
+        draft.set(None);
+    };
+
+    let apply_account = selected_account.clone();
+    let run_apply = move |_| {
+        let Some(account) = apply_account.clone() else {
+            return;
+        };
+        let folder = apply_folder.read().clone();
+        let list = rules.read().clone();
+        busy.set(true);
+        apply_status.set("正在执行规则...".to_string());
+        let storage = storage.handle();
+        spawn(async move {
+            let result = async_std::task::spawn_blocking(move || {
+                filter::apply_to_folder(&storage?, &account, &folder, &list)
+            })
+            .await;
+            match result {
+                Ok(count) => apply_status.set(format!("完成，{} 封邮件匹配规则", count)),
+                Err(e) => apply_status.set(format!("执行失败: {}", e)),
+            }
+            busy.set(false);
+            on_mail_changed.call(());
+        });
+    };
+
+    let editing = draft.read().clone();
+    let rule_count = rules.read().len();
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "邮件规则" }
+            if rules.read().is_empty() {
+                p { class: "settings-hint", "还没有规则。规则按顺序对新收到的邮件执行。" }
+            }
+            ul {
+                class: "rule-list",
+                for (i, rule) in rules.read().iter().cloned().enumerate() {
+                    li {
+                        key: "{i}",
+                        class: if rule.enabled { "rule-item" } else { "rule-item disabled" },
+                        input {
+                            r#type: "checkbox",
+                            checked: rule.enabled,
+                            onchange: move |e| {
+                                let mut list = rules.read().clone();
+                                list[i].enabled = e.checked();
+                                save_rules(list);
+                            },
+                        }
+                        div {
+                            class: "rule-text",
+                            div { class: "rule-name", "{rule.name}" }
+                            div { class: "rule-summary", {rule_summary(&rule)} }
+                        }
+                        button {
+                            class: "btn btn-secondary",
+                            disabled: i == 0,
+                            onclick: move |_| {
+                                let mut list = rules.read().clone();
+                                list.swap(i - 1, i);
+                                save_rules(list);
+                            },
+                            "上移"
+                        }
+                        button {
+                            class: "btn btn-secondary",
+                            disabled: i + 1 >= rule_count,
+                            onclick: move |_| {
+                                let mut list = rules.read().clone();
+                                list.swap(i, i + 1);
+                                save_rules(list);
+                            },
+                            "下移"
+                        }
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| draft.set(Some((Some(i), rules.read()[i].clone()))),
+                            "编辑"
+                        }
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| {
+                                let mut list = rules.read().clone();
+                                list.remove(i);
+                                save_rules(list);
+                                draft.set(None);
+                            },
+                            "删除"
+                        }
+                    }
+                }
+            }
+
+            if let Some((_, rule)) = editing {
+                div {
+                    class: "rule-editor",
+                    div {
+                        class: "form-group",
+                        label { "名称" }
+                        input {
+                            class: "form-control",
+                            r#type: "text",
+                            value: "{rule.name}",
+                            oninput: move |e| edit_draft(draft, |r| r.name = e.value()),
+                        }
+                    }
+                    div {
+                        class: "form-group",
+                        label { "条件" }
+                        select {
+                            class: "form-control",
+                            value: if rule.match_all { "all" } else { "any" },
+                            onchange: move |e| edit_draft(draft, |r| r.match_all = e.value() == "all"),
+                            option { value: "all", "满足所有条件" }
+                            option { value: "any", "满足任意一个条件" }
+                        }
+                    }
+                    for (i, condition) in rule.conditions.iter().cloned().enumerate() {
+                        div {
+                            key: "c{i}",
+                            class: "rule-row",
+                            select {
+                                class: "form-control",
+                                value: position(&RuleField::ALL, &condition.field),
+                                onchange: move |e| edit_draft(draft, |r| {
+                                    let field = RuleField::ALL[e.value().parse::<usize>().unwrap_or(0)];
+                                    let condition = &mut r.conditions[i];
+                                    condition.field = field;
+                                    if !field.ops().contains(&condition.op) {
+                                        condition.op = field.ops()[0];
+                                    }
+                                }),
+                                for (n, field) in RuleField::ALL.iter().enumerate() {
+                                    option { key: "{n}", value: "{n}", "{field.label()}" }
+                                }
+                            }
+                            if condition.field == RuleField::Header {
+                                input {
+                                    class: "form-control",
+                                    r#type: "text",
+                                    placeholder: "邮件头名称，如 List-Id",
+                                    value: "{condition.header}",
+                                    oninput: move |e| edit_draft(draft, |r| r.conditions[i].header = e.value()),
+                                }
+                            }
+                            select {
+                                class: "form-control",
+                                value: position(condition.field.ops(), &condition.op),
+                                onchange: move |e| edit_draft(draft, |r| {
+                                    let ops = r.conditions[i].field.ops();
+                                    r.conditions[i].op = ops[e.value().parse::<usize>().unwrap_or(0).min(ops.len() - 1)];
+                                }),
+                                for (n, op) in condition.field.ops().iter().enumerate() {
+                                    option { key: "{n}", value: "{n}", "{op.label()}" }
+                                }
+                            }
+                            if condition.field != RuleField::Attachment && condition.op.needs_value() {
+                                input {
+                                    class: "form-control",
+                                    r#type: "text",
+                                    value: "{condition.value}",
+                                    oninput: move |e| edit_draft(draft, |r| r.conditions[i].value = e.value()),
+                                }
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                onclick: move |_| edit_draft(draft, |r| {
+                                    r.conditions.remove(i);
+                                }),
+                                "移除"
+                            }
+                        }
+                    }
+                    div {
+                        class: "rule-row",
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| edit_draft(draft, |r| r.conditions.push(RuleCondition::default())),
+                            "添加条件"
+                        }
+                    }
+                    div {
+                        class: "form-group",
+                        label { "动作" }
+                    }
+                    for (i, action) in rule.actions.iter().cloned().enumerate() {
+                        div {
+                            key: "a{i}",
+                            class: "rule-row",
+                            select {
+                                class: "form-control",
+                                value: position(&ActionKind::ALL, &action.kind),
+                                onchange: move |e| edit_draft(draft, |r| {
+                                    r.actions[i].kind = ActionKind::ALL[e.value().parse::<usize>().unwrap_or(0)];
+                                }),
+                                for (n, kind) in ActionKind::ALL.iter().enumerate() {
+                                    option { key: "{n}", value: "{n}", "{kind.label()}" }
+                                }
+                            }
+                            if action.kind.needs_value() {
+                                input {
+                                    class: "form-control",
+                                    r#type: "text",
+                                    placeholder: match action.kind {
+                                        ActionKind::MoveTo => "文件夹名称",
+                                        ActionKind::AddFlag => "如 \\Flagged 或自定义关键字",
+                                        _ => "邮件地址",
+                                    },
+                                    value: "{action.value}",
+                                    oninput: move |e| edit_draft(draft, |r| r.actions[i].value = e.value()),
+                                }
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                onclick: move |_| edit_draft(draft, |r| {
+                                    r.actions.remove(i);
+                                }),
+                                "移除"
+                            }
+                        }
+                    }
+                    div {
+                        class: "rule-row",
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| edit_draft(draft, |r| r.actions.push(RuleAction::default())),
+                            "添加动作"
+                        }
+                    }
+                    div {
+                        class: "rule-row",
+                        label {
+                            input {
+                                r#type: "checkbox",
+                                checked: rule.stop,
+                                onchange: move |e| edit_draft(draft, |r| r.stop = e.checked()),
+                            }
+                            " 匹配后不再执行后面的规则"
+                        }
+                    }
+                }
+            }
+
+            div {
+                class: "settings-actions",
+                if draft.read().is_some() {
+                    button { class: "btn btn-primary", onclick: save_draft, "保存规则" }
+                    button { class: "btn btn-secondary", onclick: move |_| draft.set(None), "取消" }
+                } else {
+                    button {
+                        class: "btn btn-secondary",
+                        onclick: move |_| draft.set(Some((None, FilterRule::default()))),
+                        "新建规则"
+                    }
+                }
+                span { class: "status-message", "{status}" }
+            }
+
+            div {
+                class: "form-group",
+                label { "对已有邮件执行" }
+                select {
+                    class: "form-control",
+                    value: "{selected_address}",
+                    onchange: move |e| selected_address.set(e.value()),
+                    for account in accounts.iter() {
+                        option { key: "{account.address}", value: "{account.address}", "{account.address}" }
+                    }
+                }
+                select {
+                    class: "form-control settings-format",
+                    value: "{apply_folder}",
+                    onchange: move |e| apply_folder.set(e.value()),
+                    for folder in folders.iter() {
+                        option { key: "{folder}", value: "{folder}", "{folder}" }
+                    }
+                }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none() || rule_count == 0,
+                    onclick: run_apply,
+                    "执行"
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "settings-hint", "转发动作只对新收到的邮件执行" }
+                span { class: "status-message", "{apply_status}" }
+            }
+        }
+    }
+}
+
+fn edit_draft(mut draft: Signal<Option<(Option<usize>, FilterRule)>>, edit: impl FnOnce(&mut FilterRule)) {
+    if let Some((_, rule)) = draft.write().as_mut() {
+        edit(rule);
+    }
+}
+
+fn position<T: PartialEq>(items: &[T], item: &T) -> String {
+    items.iter().position(|i| i == item).unwrap_or(0).to_string()
+}
+
+fn validate_rule(rule: &FilterRule) -> Result<(), String> {
+    if rule.conditions.is_empty() {
+        return Err("至少需要一个条件".to_string());
+    }
+    if rule.actions.is_empty() {
+        return Err("至少需要一个动作".to_string());
+    }
+    for condition in &rule.conditions {
+        if condition.field == RuleField::Header && condition.header.trim().is_empty() {
+            return Err("请填写邮件头名称".to_string());
+        }
+        if condition.field != RuleField::Attachment && condition.op.needs_value() && condition.value.trim().is_empty() {
+            return Err(format!("请填写「{}」条件的值", condition.field.label()));
+        }
+        if condition.field == RuleField::Size && condition.value.trim().parse::<f64>().is_err() {
+            return Err("邮件大小需要填写数字".to_string());
+        }
+    }
+    for action in &rule.actions {
+        if action.kind.needs_value() && action.value.trim().is_empty() {
+            return Err(format!("请填写「{}」动作的值", action.kind.label()));
+        }
+    }
+    Ok(())
+}
+
+// 规则列表中显示的简要说明
+fn rule_summary(rule: &FilterRule) -> String {
+    let conditions: Vec<String> = rule
+        .conditions
+        .iter()
+        .map(|c| {
+            let field = if c.field == RuleField::Header { c.header.clone() } else { c.field.label().to_string() };
+            if c.op.needs_value() && c.field != RuleField::Attachment {
+                format!("{}{}「{}」", field, c.op.label(), c.value)
+            } else {
+                format!("{}{}", field, c.op.label())
+            }
+        })
+        .collect();
+    let actions: Vec<String> = rule
+        .actions
+        .iter()
+        .map(|a| if a.kind.needs_value() { format!("{} {}", a.kind.label(), a.value) } else { a.kind.label().to_string() })
+        .collect();
+    let joiner = if rule.match_all { " 且 " } else { " 或 " };
+    format!("如果 {}：{}", conditions.join(joiner), actions.join("，"))
+}
@@ -1,8 +1,8 @@
+use std::collections::HashMap;
 use dioxus::prelude::*;
-use dioxus_desktop::use_window;
 use crate::Page;
 use crate::models::email::EmailAccount;
-use crate::components::Composer;
+use crate::models::{Label, SavedSearch};
 
 #[derive(Props, PartialEq, Clone)]
 pub struct SidebarProps {
@@ -11,9 +11,23 @@ pub struct SidebarProps {
     pub accounts: Vec<EmailAccount>,
     pub current_account: Option<EmailAccount>,
     pub on_switch_account: EventHandler<EmailAccount>,
+    // 各账户收件箱的未读数，键为邮箱地址
+    #[props(default)]
+    pub unread_counts: HashMap<String, usize>,
+    pub on_add_account: EventHandler<()>,
+    pub on_edit_account: EventHandler<EmailAccount>,
+    pub on_remove_account: EventHandler<EmailAccount>,
+    // 标签列表，点击显示带有该标签的邮件
+    #[props(default)]
+    pub labels: Vec<Label>,
+    // 保存的搜索及其未读数
+    #[props(default)]
+    pub saved_searches: Vec<(SavedSearch, usize)>,
 }
 
 pub fn Sidebar(props: SidebarProps) -> Element {
+    let total_unread: usize = props.unread_counts.values().sum();
+
     rsx! {
         div {
             class: "sidebar-content",
@@ -21,6 +35,24 @@ pub fn Sidebar(props: SidebarProps) -> Element {
             // 文件夹菜单
             ul {
                 class: "sidebar-menu",
+                // 多个账户时显示合并的全部收件箱
+                if props.accounts.len() > 1 {
+                    li {
+                        class: "sidebar-menu-item",
+                        div {
+                            class: match props.current_page {
+                                Page::AllInboxes => "sidebar-menu-link active",
+                                _ => "sidebar-menu-link"
+                            },
+                            onclick: move |_| props.on_nav.call(Page::AllInboxes),
+                            span { class: "icon", "📬" }
+                            span { "全部收件箱" }
+                            if total_unread > 0 {
+                                span { class: "unread-badge", "{total_unread}" }
+                            }
+                        }
+                    }
+                }
                 li {
                     class: "sidebar-menu-item",
                     div {
@@ -81,9 +113,148 @@ pub fn Sidebar(props: SidebarProps) -> Element {
                         span { "垃圾邮件" }
                     }
                 }
+                li {
+                    class: "sidebar-menu-item",
+                    div {
+                        class: match props.current_page {
+                            Page::Contacts => "sidebar-menu-link active",
+                            _ => "sidebar-menu-link"
+                        },
+                        onclick: move |_| props.on_nav.call(Page::Contacts),
+                        span { class: "icon", "📇" }
+                        span { "通讯录" }
+                    }
+                }
+                li {
+                    class: "sidebar-menu-item",
+                    div {
+                        class: match props.current_page {
+                            Page::Settings => "sidebar-menu-link active",
+                            _ => "sidebar-menu-link"
+                        },
+                        onclick: move |_| props.on_nav.call(Page::Settings),
+                        span { class: "icon", "⚙️" }
+                        span { "设置" }
+                    }
+                }
             }
             
-            // 移除底部账户信息区域
+            // 保存的搜索
+            if !props.saved_searches.is_empty() {
+                div {
+                    class: "label-section",
+                    div { class: "account-section-title", "搜索" }
+                    ul {
+                        class: "sidebar-menu",
+                        for (search, unread) in props.saved_searches.iter().cloned() {
+                            {
+                                let page = Page::Search(search.query.clone());
+                                let is_current = props.current_page == page;
+                                rsx! {
+                                    li {
+                                        key: "{search.query}",
+                                        class: "sidebar-menu-item",
+                                        div {
+                                            class: if is_current { "sidebar-menu-link active" } else { "sidebar-menu-link" },
+                                            title: "{search.query}",
+                                            onclick: move |_| props.on_nav.call(page.clone()),
+                                            span { class: "icon", "🔍" }
+                                            span { "{search.name}" }
+                                            if unread > 0 {
+                                                span { class: "unread-badge", "{unread}" }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            // 标签
+            if !props.labels.is_empty() {
+                div {
+                    class: "label-section",
+                    div { class: "account-section-title", "标签" }
+                    ul {
+                        class: "sidebar-menu",
+                        for label in props.labels.iter().cloned() {
+                            {
+                                let page = Page::Label(label.keyword.clone());
+                                let is_current = props.current_page == page;
+                                rsx! {
+                                    li {
+                                        key: "{label.keyword}",
+                                        class: "sidebar-menu-item",
+                                        div {
+                                            class: if is_current { "sidebar-menu-link active" } else { "sidebar-menu-link" },
+                                            onclick: move |_| props.on_nav.call(page.clone()),
+                                            span { class: "label-dot", style: "background-color: {label.color}" }
+                                            span { "{label.name}" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            // 账户列表
+            div {
+                class: "account-info",
+                div { class: "account-section-title", "账户" }
+                for account in props.accounts.iter().cloned() {
+                    {
+                        let is_current = props.current_account.as_ref().is_some_and(|c| c.address == account.address);
+                        let unread = props.unread_counts.get(&account.address).copied().unwrap_or(0);
+                        let switch_account = account.clone();
+                        let edit_account = account.clone();
+                        let remove_account = account.clone();
+                        rsx! {
+                            div {
+                                key: "{account.address}",
+                                class: if is_current { "current-account active" } else { "current-account" },
+                                title: "{account.address}",
+                                onclick: move |_| props.on_switch_account.call(switch_account.clone()),
+                                span { class: "account-color", style: "background-color: {account.color()}" }
+                                span { class: "account-address", "{account.address}" }
+                                if unread > 0 {
+                                    span { class: "unread-badge", "{unread}" }
+                                }
+                                span {
+                                    class: "account-actions",
+                                    button {
+                                        class: "account-action-btn",
+                                        title: "编辑账户",
+                                        onclick: move |e: MouseEvent| {
+                                            e.stop_propagation();
+                                            props.on_edit_account.call(edit_account.clone());
+                                        },
+                                        "✎"
+                                    }
+                                    button {
+                                        class: "account-action-btn",
+                                        title: "删除账户",
+                                        onclick: move |e: MouseEvent| {
+                                            e.stop_propagation();
+                                            props.on_remove_account.call(remove_account.clone());
+                                        },
+                                        "✕"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                div {
+                    class: "add-account",
+                    onclick: move |_| props.on_add_account.call(()),
+                    span { class: "account-icon", "＋" }
+                    span { "添加账户" }
+                }
+            }
         }
     }
 }
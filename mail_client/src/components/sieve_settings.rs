@@ -0,0 +1,483 @@
+use chrono::NaiveDate;
+use dioxus::prelude::*;
+use crate::models::EmailAccount;
+use crate::service::managesieve::{self, SieveClient, SieveError, SieveScript};
+use crate::service::sieve::{Vacation, VACATION_SCRIPT};
+
+type SieveResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 对服务器的一次操作，每次操作单独连接
+#[derive(Clone, Debug, PartialEq)]
+enum Request {
+    Refresh,
+    Open(String),
+    Check(String),
+    Save { name: String, content: String, activate: bool },
+    Activate(String), // 名称为空时停用所有脚本
+    Delete(String),
+}
+
+struct Reply {
+    scripts: Vec<SieveScript>,
+    extensions: Vec<String>,
+    text: Option<String>, // 打开的脚本、检查的警告，或刷新时读到的自动回复脚本
+}
+
+async fn perform(account: EmailAccount, port: u16, request: Request) -> SieveResult<Reply> {
+    let mut client = SieveClient::connect_account(&account, port).await?;
+    let text = match &request {
+        Request::Refresh => None,
+        Request::Open(name) => Some(client.get_script(name).await?),
+        Request::Check(content) => client.check_script(content).await?,
+        Request::Save { name, content, activate } => {
+            client.put_script(name, content).await?;
+            if *activate {
+                client.set_active(name).await?;
+            }
+            None
+        }
+        Request::Activate(name) => {
+            client.set_active(name).await?;
+            None
+        }
+        Request::Delete(name) => {
+            client.delete_script(name).await?;
+            None
+        }
+    };
+    let scripts = client.list_scripts().await?;
+    let text = match (&request, text) {
+        (Request::Refresh, _) if scripts.iter().any(|s| s.name == VACATION_SCRIPT) => {
+            Some(client.get_script(VACATION_SCRIPT).await?)
+        }
+        (_, text) => text,
+    };
+    let extensions = client.extensions();
+    client.logout().await;
+    Ok(Reply { scripts, extensions, text })
+}
+
+// 正在编辑的脚本
+#[derive(Clone, Debug, PartialEq)]
+struct ScriptDraft {
+    name: String,
+    content: String,
+    new: bool,
+}
+
+// 自动回复表单，日期和天数以文本保存，保存时再检查
+#[derive(Clone, Debug, Default, PartialEq)]
+struct VacationForm {
+    subject: String,
+    message: String,
+    days: String,
+    addresses: String,
+    start: String,
+    end: String,
+}
+
+impl VacationForm {
+    fn from_vacation(vacation: &Vacation) -> Self {
+        let date = |d: Option<NaiveDate>| d.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+        VacationForm {
+            subject: vacation.subject.clone(),
+            message: vacation.message.clone(),
+            days: vacation.days.to_string(),
+            addresses: vacation.addresses.join(", "),
+            start: date(vacation.start),
+            end: date(vacation.end),
+        }
+    }
+
+    fn to_vacation(&self) -> Result<Vacation, String> {
+        if self.message.trim().is_empty() {
+            return Err("请填写回复内容".to_string());
+        }
+        let days = self.days.trim().parse::<u32>().ok().filter(|d| *d > 0).ok_or("间隔天数需要是正整数")?;
+        let date = |text: &str| -> Result<Option<NaiveDate>, String> {
+            match text.trim() {
+                "" => Ok(None),
+                text => NaiveDate::parse_from_str(text, "%Y-%m-%d").map(Some).map_err(|_| format!("日期无效: {}", text)),
+            }
+        };
+        let (start, end) = (date(&self.start)?, date(&self.end)?);
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                return Err("结束日期早于开始日期".to_string());
+            }
+        }
+        Ok(Vacation {
+            subject: self.subject.trim().to_string(),
+            message: self.message.clone(),
+            days,
+            addresses: self
+                .addresses
+                .split([',', ';', ' '])
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(str::to_string)
+                .collect(),
+            start,
+            end,
+        })
+    }
+}
+
+// 服务器端过滤（ManageSieve）：管理 Sieve 脚本和休假自动回复
+#[component]
+pub fn SieveSettings(accounts: Vec<EmailAccount>, current_account: Option<EmailAccount>) -> Element {
+    let default_address = current_account
+        .as_ref()
+        .or(accounts.first())
+        .map_or(String::new(), |a| a.address.clone());
+    let mut selected_address = use_signal(|| default_address);
+    let mut port = use_signal(|| managesieve::DEFAULT_PORT.to_string());
+    let mut connected = use_signal(|| false);
+    let mut scripts = use_signal(Vec::<SieveScript>::new);
+    let mut extensions = use_signal(Vec::<String>::new);
+    let mut draft = use_signal(|| None::<ScriptDraft>);
+    let mut vacation = use_signal(|| VacationForm::from_vacation(&Vacation::default()));
+    let mut status = use_signal(String::new);
+    let mut busy = use_signal(|| false);
+
+    let selected_account = accounts.iter().find(|a| a.address == *selected_address.read()).cloned();
+
+    let run_account = selected_account.clone();
+    let run = use_callback(move |request: Request| {
+        let Some(account) = run_account.clone() else {
+            return;
+        };
+        let Ok(port) = port.read().trim().parse::<u16>() else {
+            status.set("端口无效".to_string());
+            return;
+        };
+        busy.set(true);
+        status.set("正在连接服务器...".to_string());
+        spawn(async move {
+            match perform(account, port, request.clone()).await {
+                Ok(reply) => {
+                    let count = reply.scripts.len();
+                    scripts.set(reply.scripts);
+                    extensions.set(reply.extensions);
+                    connected.set(true);
+                    match request {
+                        Request::Refresh => {
+                            if let Some(form) = reply.text.as_deref().and_then(Vacation::from_script) {
+                                vacation.set(VacationForm::from_vacation(&form));
+                            }
+                            status.set(format!("已连接，共 {} 个脚本", count));
+                        }
+                        Request::Open(name) => {
+                            draft.set(Some(ScriptDraft { name, content: reply.text.unwrap_or_default(), new: false }));
+                            status.set(String::new());
+                        }
+                        Request::Check(_) => match reply.text {
+                            Some(warnings) => status.set(format!("语法正确，警告: {}", warnings)),
+                            None => status.set("语法正确".to_string()),
+                        },
+                        Request::Save { name, activate, .. } => {
+                            if name != VACATION_SCRIPT {
+                                draft.set(None);
+                            }
+                            let done = if activate { "已保存并启用" } else { "已保存" };
+                            status.set(format!("{} {}", done, name));
+                        }
+                        Request::Activate(name) if name.is_empty() => status.set("已停用所有脚本".to_string()),
+                        Request::Activate(name) => status.set(format!("已启用 {}", name)),
+                        Request::Delete(name) => status.set(format!("已删除 {}", name)),
+                    }
+                }
+                // 检查或保存时服务器返回的 NO 说明脚本有误
+                Err(e) => match request {
+                    Request::Check(_) | Request::Save { .. } if e.is::<SieveError>() => {
+                        status.set(format!("脚本有误: {}", e))
+                    }
+                    _ => status.set(format!("操作失败: {}", e)),
+                },
+            }
+            busy.set(false);
+        });
+    });
+
+    let save_vacation = move |_| match vacation.read().to_vacation() {
+        Ok(form) => run.call(Request::Save { name: VACATION_SCRIPT.to_string(), content: form.to_script(), activate: true }),
+        Err(e) => status.set(e),
+    };
+
+    let vacation_active = scripts.read().iter().any(|s| s.name == VACATION_SCRIPT && s.active);
+    let supports_vacation = extensions.read().is_empty() || extensions.read().iter().any(|e| e == "vacation");
+    let form = vacation.read().clone();
+    let editing = draft.read().clone();
+
+    rsx! {
+        section {
+            class: "settings-section",
+            h3 { "服务器过滤（ManageSieve）" }
+            div {
+                class: "form-group",
+                label { "账户" }
+                select {
+                    class: "form-control",
+                    value: "{selected_address}",
+                    onchange: move |e| {
+                        selected_address.set(e.value());
+                        connected.set(false);
+                        scripts.set(Vec::new());
+                        draft.set(None);
+                    },
+                    for account in accounts.iter() {
+                        option { key: "{account.address}", value: "{account.address}", "{account.address}" }
+                    }
+                }
+            }
+            div {
+                class: "form-group",
+                label { "端口" }
+                input {
+                    class: "form-control settings-format",
+                    r#type: "text",
+                    value: "{port}",
+                    oninput: move |e| port.set(e.value()),
+                }
+                span { class: "settings-hint", "使用账户的 IMAP 服务器、登录信息和加密设置" }
+                button {
+                    class: "btn btn-secondary",
+                    disabled: *busy.read() || selected_account.is_none(),
+                    onclick: move |_| run.call(Request::Refresh),
+                    if *connected.read() { "刷新" } else { "连接" }
+                }
+            }
+
+            if *connected.read() {
+                ul {
+                    class: "rule-list",
+                    for script in scripts.read().iter().cloned() {
+                        li {
+                            key: "{script.name}",
+                            class: "rule-item",
+                            div {
+                                class: "rule-text",
+                                div {
+                                    class: "rule-name",
+                                    "{script.name}"
+                                    if script.active {
+                                        span { class: "settings-hint", "启用中" }
+                                    }
+                                }
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                disabled: *busy.read(),
+                                onclick: {
+                                    let name = script.name.clone();
+                                    move |_| run.call(Request::Open(name.clone()))
+                                },
+                                "编辑"
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                disabled: *busy.read(),
+                                onclick: {
+                                    let name = if script.active { String::new() } else { script.name.clone() };
+                                    move |_| run.call(Request::Activate(name.clone()))
+                                },
+                                if script.active { "停用" } else { "启用" }
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                disabled: *busy.read() || script.active,
+                                onclick: {
+                                    let name = script.name.clone();
+                                    move |_| run.call(Request::Delete(name.clone()))
+                                },
+                                "删除"
+                            }
+                        }
+                    }
+                }
+
+                if let Some(script) = editing {
+                    div {
+                        class: "rule-editor",
+                        div {
+                            class: "form-group",
+                            label { "脚本名称" }
+                            input {
+                                class: "form-control",
+                                r#type: "text",
+                                disabled: !script.new,
+                                value: "{script.name}",
+                                oninput: move |e| {
+                                    if let Some(draft) = draft.write().as_mut() {
+                                        draft.name = e.value();
+                                    }
+                                },
+                            }
+                        }
+                        textarea {
+                            class: "form-textarea sieve-script",
+                            rows: "14",
+                            spellcheck: "false",
+                            value: "{script.content}",
+                            oninput: move |e| {
+                                if let Some(draft) = draft.write().as_mut() {
+                                    draft.content = e.value();
+                                }
+                            },
+                        }
+                        div {
+                            class: "rule-row",
+                            button {
+                                class: "btn btn-secondary",
+                                disabled: *busy.read(),
+                                onclick: {
+                                    let content = script.content.clone();
+                                    move |_| run.call(Request::Check(content.clone()))
+                                },
+                                "检查语法"
+                            }
+                            button {
+                                class: "btn btn-primary",
+                                disabled: *busy.read() || script.name.trim().is_empty(),
+                                onclick: {
+                                    let script = script.clone();
+                                    move |_| run.call(Request::Save {
+                                        name: script.name.trim().to_string(),
+                                        content: script.content.clone(),
+                                        activate: false,
+                                    })
+                                },
+                                "保存"
+                            }
+                            button {
+                                class: "btn btn-secondary",
+                                disabled: *busy.read() || script.name.trim().is_empty(),
+                                onclick: {
+                                    let script = script.clone();
+                                    move |_| run.call(Request::Save {
+                                        name: script.name.trim().to_string(),
+                                        content: script.content.clone(),
+                                        activate: true,
+                                    })
+                                },
+                                "保存并启用"
+                            }
+                            button { class: "btn btn-secondary", onclick: move |_| draft.set(None), "取消" }
+                        }
+                    }
+                } else {
+                    div {
+                        class: "rule-row",
+                        button {
+                            class: "btn btn-secondary",
+                            onclick: move |_| draft.set(Some(ScriptDraft {
+                                name: String::new(),
+                                content: "require [\"fileinto\"];\n\n".to_string(),
+                                new: true,
+                            })),
+                            "新建脚本"
+                        }
+                    }
+                }
+            }
+            div {
+                class: "settings-actions",
+                span { class: "status-message", "{status}" }
+            }
+        }
+
+        if *connected.read() {
+            section {
+                class: "settings-section",
+                h3 { "休假自动回复" }
+                if !supports_vacation {
+                    p { class: "settings-hint", "服务器不支持 vacation 扩展" }
+                }
+                div {
+                    class: "form-group",
+                    label { "状态" }
+                    span { class: "settings-hint", if vacation_active { "已启用" } else { "未启用" } }
+                }
+                div {
+                    class: "form-group",
+                    label { "主题" }
+                    input {
+                        class: "form-control",
+                        r#type: "text",
+                        placeholder: "留空时由服务器生成",
+                        value: "{form.subject}",
+                        oninput: move |e| vacation.write().subject = e.value(),
+                    }
+                }
+                div {
+                    class: "form-group",
+                    label { "回复内容" }
+                    textarea {
+                        class: "form-textarea",
+                        rows: "5",
+                        value: "{form.message}",
+                        oninput: move |e| vacation.write().message = e.value(),
+                    }
+                }
+                div {
+                    class: "form-group",
+                    label { "开始日期" }
+                    input {
+                        class: "form-control",
+                        r#type: "date",
+                        value: "{form.start}",
+                        oninput: move |e| vacation.write().start = e.value(),
+                    }
+                    label { "结束日期" }
+                    input {
+                        class: "form-control",
+                        r#type: "date",
+                        value: "{form.end}",
+                        oninput: move |e| vacation.write().end = e.value(),
+                    }
+                }
+                div {
+                    class: "form-group",
+                    label { "间隔天数" }
+                    input {
+                        class: "form-control settings-format",
+                        r#type: "number",
+                        min: "1",
+                        value: "{form.days}",
+                        oninput: move |e| vacation.write().days = e.value(),
+                    }
+                    span { class: "settings-hint", "同一发件人在这段时间内只回复一次" }
+                }
+                div {
+                    class: "form-group",
+                    label { "其他地址" }
+                    input {
+                        class: "form-control",
+                        r#type: "text",
+                        placeholder: "发到这些别名的邮件也回复，用逗号分隔",
+                        value: "{form.addresses}",
+                        oninput: move |e| vacation.write().addresses = e.value(),
+                    }
+                }
+                div {
+                    class: "settings-actions",
+                    button {
+                        class: "btn btn-primary",
+                        disabled: *busy.read() || !supports_vacation,
+                        onclick: save_vacation,
+                        "保存并启用"
+                    }
+                    if vacation_active {
+                        button {
+                            class: "btn btn-secondary",
+                            disabled: *busy.read(),
+                            onclick: move |_| run.call(Request::Activate(String::new())),
+                            "关闭自动回复"
+                        }
+                    }
+                    span { class: "settings-hint", "服务器同一时间只启用一个脚本，启用自动回复会替换当前启用的脚本" }
+                }
+            }
+        }
+    }
+}
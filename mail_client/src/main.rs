@@ -1,27 +1,44 @@
-#![allow(warnings)]
+#![allow(non_snake_case)]
 #![cfg_attr(not(test), windows_subsystem = "windows")]
 
 use dioxus::prelude::*;
 use dioxus_desktop::{Config, WindowBuilder, LogicalSize};
+use dioxus::html::HasFileData;
 
 mod components;
 mod models;
 mod service;
 
-use components::{Inbox, Composer, Sidebar, EmailDetail};
+use components::{Inbox, Composer, Sidebar, EmailDetail, SettingsPage, ContactsPage, provide_storage};
 use components::layout_resizer::{ResizeData, ResizeHandle, use_resize_state};
-use models::email::{EmailAccount, AccountList};
-use models::Email;
-use components::login_page::LoginPage;
+use models::email::EmailAccount;
+use models::{Email, SavedSearch, SearchQuery};
+use components::login_page::{LoginPage, load_account_settings, save_account_settings};
+use service::{AccountSettings, sync_all_accounts, sync_all_contacts};
+use service::spam::JUNK_FOLDER;
+use std::time::Duration;
+use std::collections::HashMap;
 
 #[derive(Clone, PartialEq)]
 pub enum Page {
     Inbox,
+    AllInboxes, // 所有账户的收件箱合并显示
     Sent,
     Drafts,
     Deleted, // 新增已删除页面
     Spam,    // 新增垃圾邮件页面
     Compose,
+    Settings, // 设置页面
+    Contacts, // 通讯录
+    Label(String), // 带有某个标签的邮件，参数为标签关键字
+    Search(String), // 保存的搜索，参数为搜索内容
+}
+
+// 账户对话框的模式
+#[derive(Clone, PartialEq)]
+pub enum AccountDialog {
+    Add,
+    Edit(EmailAccount), // 保存被编辑账户的原始信息
 }
 
 // 将 CSS 作为静态字符串包含
@@ -47,32 +64,279 @@ fn main() {
 
 #[component]
 pub fn App() -> Element {
+    // 本地存储只在这里打开一次，各组件通过上下文共用
+    let mut storage = provide_storage();
     let mut current_page = use_signal(|| Page::Inbox);
-    let mut accounts = use_signal(|| AccountList::new());
+    let mut accounts = use_signal(|| load_account_settings(storage).accounts);
     let mut current_account = use_signal(|| None::<EmailAccount>);
     let mut show_login = use_signal(|| true);
     let mut show_middle_column = use_signal(|| true);
     let mut selected_email = use_signal(|| None::<Email>);
+    let mut account_dialog = use_signal(|| None::<AccountDialog>);
+    let mut confirm_remove = use_signal(|| None::<EmailAccount>);
+    // 本地邮件变化的计数器，变化时重新统计未读数
+    let mut mail_revision = use_signal(|| 0u64);
+    let mut syncing = use_signal(|| false);
+    // 正在回复的邮件，写信页面据此预填并选择发件账户
+    let mut reply_to = use_signal(|| None::<Email>);
+    // 顶部搜索框内容
+    let mut search_text = use_signal(String::new);
+    // 从 .eml 文件或附件打开的邮件原文，查看本地邮件时为 None
+    let mut opened_raw = use_signal(|| None::<Vec<u8>>);
+
+    // 显示从文件或附件打开的邮件
+    let mut open_message = move |email: Email, raw: Vec<u8>| {
+        selected_email.set(Some(email));
+        opened_raw.set(Some(raw));
+        if matches!(*current_page.peek(), Page::Compose | Page::Settings | Page::Contacts) {
+            current_page.set(Page::Inbox);
+        }
+    };
+
+    let mut open_eml_data = move |raw: Vec<u8>| match service::eml::parse_eml(&raw) {
+        Ok(email) => open_message(email, raw),
+        Err(e) => println!("无法打开邮件文件: {}", e),
+    };
+
+    // 通过文件对话框打开 .eml
+    let open_eml_file = move |_| {
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("打开邮件")
+            .add_filter("邮件", &["eml"])
+            .pick_file()
+        else {
+            return;
+        };
+        match std::fs::read(&path) {
+            Ok(raw) => open_eml_data(raw),
+            Err(e) => println!("读取文件失败: {}", e),
+        }
+    };
+
+    // 拖放 .eml 文件到窗口中打开
+    let on_drop = move |e: DragEvent| {
+        e.prevent_default();
+        let Some(engine) = e.files() else {
+            return;
+        };
+        spawn(async move {
+            for name in engine.files() {
+                if !name.to_lowercase().ends_with(".eml") {
+                    continue;
+                }
+                if let Some(raw) = engine.read_file(&name).await {
+                    open_eml_data(raw);
+                }
+            }
+        });
+    };
+
+    // 同时同步所有账户的邮件和通讯录，邮件同步完成后通知各视图重新加载
+    // 使用 peek 读取，避免在 effect 中调用时订阅这些信号
+    let mut sync_now = move || {
+        if *syncing.peek() || *show_login.peek() {
+            return;
+        }
+        let account_list = accounts.peek().clone();
+        if account_list.is_empty() {
+            return;
+        }
+        let shared = match storage.handle() {
+            Ok(shared) => shared,
+            Err(e) => {
+                println!("同步失败: {}", e);
+                return;
+            }
+        };
+        syncing.set(true);
+        spawn(async move {
+            let outcomes = sync_all_accounts(shared.clone(), &account_list).await;
+            let failed: Vec<&str> = outcomes.iter().filter(|o| o.result.is_err()).map(|o| o.address.as_str()).collect();
+            if !failed.is_empty() {
+                println!("以下账户同步失败: {}", failed.join(", "));
+            }
+            syncing.set(false);
+            mail_revision += 1;
+            // 通讯录在邮件之后同步，不影响邮件列表的刷新
+            sync_all_contacts(shared, account_list).await;
+        });
+    };
+
+    // 登录后及账户列表变化时立即同步
+    use_effect(move || {
+        let logged_in = !*show_login.read();
+        let _ = accounts.read();
+        if logged_in {
+            sync_now();
+        }
+    });
+
+    // 每10分钟定时同步所有账户
+    use_future(move || async move {
+        loop {
+            async_std::task::sleep(Duration::from_secs(600)).await;
+            println!("执行定时同步...");
+            sync_now();
+        }
+    });
+
+    // 各账户收件箱未读数
+    let unread_counts = use_memo(move || {
+        let _ = mail_revision.read();
+        let mut counts = HashMap::new();
+        if let Ok(storage) = storage.handle() {
+            for account in accounts.read().iter() {
+                counts.insert(account.address.clone(), storage.unread_count(account, "INBOX"));
+            }
+        }
+        counts
+    });
+
+    // 标签定义，设置中修改后随邮件计数刷新
+    let labels = use_memo(move || {
+        let _ = mail_revision.read();
+        storage.handle().and_then(|storage| storage.labels()).unwrap_or_default()
+    });
+
+    // 保存的搜索及其未读数，新邮件同步后在后台重新计算
+    let mut saved_searches = use_signal(Vec::<(SavedSearch, usize)>::new);
+    use_effect(move || {
+        let _ = mail_revision.read();
+        let account_list = accounts.read().clone();
+        let storage = storage.handle();
+        spawn(async move {
+            let counts = async_std::task::spawn_blocking(move || {
+                let storage = storage?;
+                let labels = storage.labels().unwrap_or_default();
+                let searches = storage.saved_searches()?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                    searches
+                        .into_iter()
+                        .map(|search| {
+                            let query = SearchQuery::parse(&search.query).with_labels(&labels);
+                            let unread = service::count_search_unread(&storage, &account_list, &query);
+                            (search, unread)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .await;
+            match counts {
+                Ok(counts) => saved_searches.set(counts),
+                Err(e) => println!("计算保存的搜索未读数失败: {}", e),
+            }
+        });
+    });
+
+    // 把搜索框的内容保存为虚拟文件夹并打开
+    let save_search = move |_| {
+        let query = search_text.read().trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+        let result = storage.handle().and_then(|storage| {
+            let mut searches = storage.saved_searches()?;
+            if !searches.iter().any(|s| s.query == query) {
+                searches.push(SavedSearch { name: query.clone(), query: query.clone() });
+                storage.save_saved_searches(&searches)?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                search_text.set(String::new());
+                current_page.set(Page::Search(query));
+                mail_revision += 1;
+            }
+            Err(e) => println!("保存搜索失败: {}", e),
+        }
+    };
+
+    // 保存账户列表和当前账户
+    let persist_accounts = move || {
+        save_account_settings(storage, &AccountSettings {
+            accounts: accounts.read().clone(),
+            active: current_account.read().as_ref().map(|a| a.address.clone()),
+        });
+    };
+
+    let switch_account = move |account: EmailAccount| {
+        if current_account.read().as_ref() != Some(&account) {
+            selected_email.set(None);
+            current_account.set(Some(account));
+            persist_accounts();
+        }
+    };
 
     // 获取拖拽状态 - 添加 mut 关键字
     let (mut resize_data, mut column_widths) = use_resize_state();
 
     let on_login = {
-        let mut accounts = accounts.clone();
-        let mut current_account = current_account.clone();
-        let mut show_login = show_login.clone();
+        let mut accounts = accounts;
+        let mut current_account = current_account;
+        let mut show_login = show_login;
         
         move |account: EmailAccount| {
-            accounts.write().push(account.clone());
+            let mut settings = AccountSettings {
+                accounts: accounts.read().clone(),
+                active: None,
+            };
+            settings.upsert(account.clone());
+            accounts.set(settings.accounts);
             current_account.set(Some(account));
             show_login.set(false);
+            persist_accounts();
         }
     };
 
+    // 添加或编辑账户对话框提交
+    let on_dialog_saved = move |mut account: EmailAccount| {
+        // 本地数据按地址存放，编辑时保持原地址，按地址更新避免出现重复账户
+        if let Some(AccountDialog::Edit(original)) = account_dialog.read().as_ref() {
+            account.address = original.address.clone();
+        }
+        {
+            let mut list = accounts.write();
+            match list.iter_mut().find(|a| a.address == account.address) {
+                Some(existing) => *existing = account.clone(),
+                None => list.push(account.clone()),
+            }
+        }
+        account_dialog.set(None);
+        // 编辑当前账户或新增账户后都切换到该账户
+        selected_email.set(None);
+        current_account.set(Some(account));
+        persist_accounts();
+    };
+
+    // 删除账户并清除其本地数据
+    let on_confirm_remove = move |_| {
+        let Some(account) = confirm_remove.read().clone() else {
+            return;
+        };
+        confirm_remove.set(None);
+
+        if let Err(e) = storage.update(|storage| storage.remove_account_data(&account)) {
+            println!("清除账户本地数据失败: {}", e);
+        }
+
+        accounts.write().retain(|a| a.address != account.address);
+        if current_account.read().as_ref().is_some_and(|c| c.address == account.address) {
+            selected_email.set(None);
+            let next = accounts.read().first().cloned();
+            if next.is_none() {
+                show_login.set(true);
+            }
+            current_account.set(next);
+        }
+        persist_accounts();
+        mail_revision += 1;
+    };
+
     // 在读取 current_page 后更新中间栏可见性
     use_effect(move || {
-        // 当页面为 Compose 时隐藏中间栏
-        show_middle_column.set(*current_page.read() != Page::Compose);
+        // 写邮件和设置页面隐藏中间栏
+        show_middle_column.set(!matches!(*current_page.read(), Page::Compose | Page::Settings | Page::Contacts));
     });
 
     // 处理鼠标移动事件 - 用于实时调整宽度
@@ -94,8 +358,7 @@ pub fn App() -> Element {
                 if element_id == "sidebar" {
                     // 调整侧边栏宽度
                     let new_width = (data.start_width / data.container_width * 100.0 + delta_percent)
-                        .max(10.0)  // 最小宽度10%
-                        .min(20.0); // 最大宽度20%
+                        .clamp(10.0, 20.0); // 宽度限制在10%到20%
                     
                     // 计算变化量
                     let change = new_width - sidebar_width;
@@ -110,8 +373,7 @@ pub fn App() -> Element {
                 } else if element_id == "email-list" {
                     // 调整邮件列表宽度
                     let new_width = (data.start_width / data.container_width * 100.0 + delta_percent)
-                        .max(20.0)  // 最小宽度20%
-                        .min(30.0); // 最大宽度60%
+                        .clamp(20.0, 30.0); // 宽度限制在20%到30%
                     
                     // 计算变化量
                     let change = new_width - email_list_width;
@@ -148,7 +410,52 @@ pub fn App() -> Element {
             // 添加全局鼠标事件监听
             onmousemove: handle_mouse_move,
             onmouseup: handle_mouse_up,
+            ondragover: move |e: DragEvent| e.prevent_default(),
+            ondrop: on_drop,
             
+            // 添加/编辑账户对话框
+            if let Some(dialog) = account_dialog.read().clone() {
+                div {
+                    class: "modal-overlay",
+                    div {
+                        class: "modal-dialog",
+                        LoginPage {
+                            on_login: on_dialog_saved,
+                            initial: match dialog {
+                                AccountDialog::Edit(account) => Some(account),
+                                AccountDialog::Add => None,
+                            },
+                            on_cancel: move |_| account_dialog.set(None),
+                            auto_login: false,
+                        }
+                    }
+                }
+            }
+
+            // 删除账户确认对话框
+            if let Some(account) = confirm_remove.read().clone() {
+                div {
+                    class: "modal-overlay",
+                    div {
+                        class: "modal-dialog confirm-dialog",
+                        p { "确定删除账户 {account.address} 吗？该账户的本地邮件数据也会被清除。" }
+                        div {
+                            class: "dialog-actions",
+                            button {
+                                class: "btn btn-secondary",
+                                onclick: move |_| confirm_remove.set(None),
+                                "取消"
+                            }
+                            button {
+                                class: "btn btn-danger",
+                                onclick: on_confirm_remove,
+                                "删除"
+                            }
+                        }
+                    }
+                }
+            }
+
             if *show_login.read() {
                 div {
                     class: "login-container",
@@ -170,9 +477,28 @@ pub fn App() -> Element {
                         input {
                             type: "text",
                             placeholder: "搜索",
+                            value: "{search_text}",
+                            title: "可以使用 from: to: subject: is:unread has:attachment after:2026-01-01 label: in: 等条件",
+                            oninput: move |e| search_text.set(e.value()),
+                        }
+                        if !search_text.read().trim().is_empty() {
+                            button {
+                                class: "search-save",
+                                title: "保存为侧边栏中的搜索",
+                                onclick: save_search,
+                                "☆"
+                            }
                         }
                     }
                     
+                    // 打开 .eml 文件
+                    button {
+                        class: "action-btn",
+                        title: "打开 .eml 文件（也可以直接拖放到窗口中）",
+                        onclick: open_eml_file,
+                        "📂 打开"
+                    }
+
                     // 用户信息 - 移除头像和下拉箭头
                     div {
                         class: "user-info",
@@ -193,7 +519,10 @@ pub fn App() -> Element {
                         // 写邮件按钮
                         button {
                             class: "compose-button",
-                            onclick: move |_| current_page.set(Page::Compose),
+                            onclick: move |_| {
+                                reply_to.set(None);
+                                current_page.set(Page::Compose);
+                            },
                             span { class: "icon", "✉" }
                             "写信"
                         }
@@ -204,14 +533,20 @@ pub fn App() -> Element {
                             on_nav: move |page| current_page.set(page),
                             accounts: accounts.read().clone(),
                             current_account: current_account.read().clone(),
-                            on_switch_account: move |acc| current_account.set(Some(acc)),
+                            on_switch_account: switch_account,
+                            unread_counts: unread_counts.read().clone(),
+                            labels: labels.read().clone(),
+                            saved_searches: saved_searches.read().clone(),
+                            on_add_account: move |_| account_dialog.set(Some(AccountDialog::Add)),
+                            on_edit_account: move |acc| account_dialog.set(Some(AccountDialog::Edit(acc))),
+                            on_remove_account: move |acc| confirm_remove.set(Some(acc)),
                         }
                         
                         // 添加调整手柄
                         ResizeHandle {
                             id: "sidebar".to_string(),
-                            resize_data: resize_data.clone(),
-                            column_widths: column_widths.clone()
+                            resize_data: resize_data,
+                            column_widths: column_widths
                         }
                     }
                     
@@ -223,25 +558,84 @@ pub fn App() -> Element {
                             style: "width: {column_widths.read().1}%",
                             
                             // 邮件列表内容
-                            match *current_page.read() {
+                            match &*current_page.read() {
                                 Page::Inbox => rsx!(Inbox { 
-                                    account: current_account.read().clone(),
+                                    accounts: current_account.read().clone().into_iter().collect::<Vec<_>>(),
+                                    revision: *mail_revision.read(),
+                                    syncing: *syncing.read(),
                                     on_email_selected: move |email: Email| {
                                         selected_email.set(Some(email));
-                                    }
+                                        opened_raw.set(None);
+                                    },
+                                    on_mailbox_changed: move |_| mail_revision += 1,
+                                    on_refresh: move |_| sync_now(),
+                                    search: search_text.read().clone(),
+                                }),
+                                Page::AllInboxes => rsx!(Inbox { 
+                                    accounts: accounts.read().clone(),
+                                    unified: true,
+                                    revision: *mail_revision.read(),
+                                    syncing: *syncing.read(),
+                                    on_email_selected: move |email: Email| {
+                                        selected_email.set(Some(email));
+                                        opened_raw.set(None);
+                                    },
+                                    on_mailbox_changed: move |_| mail_revision += 1,
+                                    on_refresh: move |_| sync_now(),
+                                    search: search_text.read().clone(),
                                 }),
                                 Page::Sent => rsx!(div { class: "empty-state", "已发送邮件（待实现）" }),
                                 Page::Drafts => rsx!(div { class: "empty-state", "草稿箱（待实现）" }),
                                 Page::Deleted => rsx!(div { class: "empty-state", "已删除邮件（待实现）" }),
-                                Page::Spam => rsx!(div { class: "empty-state", "垃圾邮件（待实现）" }),
+                                Page::Spam => rsx!(Inbox {
+                                    accounts: current_account.read().clone().into_iter().collect::<Vec<_>>(),
+                                    folder: JUNK_FOLDER.to_string(),
+                                    revision: *mail_revision.read(),
+                                    syncing: *syncing.read(),
+                                    on_email_selected: move |email: Email| {
+                                        selected_email.set(Some(email));
+                                        opened_raw.set(None);
+                                    },
+                                    on_mailbox_changed: move |_| mail_revision += 1,
+                                    on_refresh: move |_| sync_now(),
+                                    search: search_text.read().clone(),
+                                }),
+                                Page::Label(keyword) => rsx!(Inbox {
+                                    accounts: accounts.read().clone(),
+                                    unified: accounts.read().len() > 1,
+                                    label: Some(keyword.clone()),
+                                    revision: *mail_revision.read(),
+                                    syncing: *syncing.read(),
+                                    on_email_selected: move |email: Email| {
+                                        selected_email.set(Some(email));
+                                        opened_raw.set(None);
+                                    },
+                                    on_mailbox_changed: move |_| mail_revision += 1,
+                                    on_refresh: move |_| sync_now(),
+                                    search: search_text.read().clone(),
+                                }),
+                                Page::Search(query) => rsx!(Inbox {
+                                    accounts: accounts.read().clone(),
+                                    unified: accounts.read().len() > 1,
+                                    query: Some(query.clone()),
+                                    revision: *mail_revision.read(),
+                                    syncing: *syncing.read(),
+                                    on_email_selected: move |email: Email| {
+                                        selected_email.set(Some(email));
+                                        opened_raw.set(None);
+                                    },
+                                    on_mailbox_changed: move |_| mail_revision += 1,
+                                    on_refresh: move |_| sync_now(),
+                                    search: search_text.read().clone(),
+                                }),
                                 _ => rsx!(div { "" }),
                             }
                             
                             // 添加调整手柄
                             ResizeHandle {
                                 id: "email-list".to_string(),
-                                resize_data: resize_data.clone(),
-                                column_widths: column_widths.clone()
+                                resize_data: resize_data,
+                                column_widths: column_widths
                             }
                         }
                     }
@@ -254,24 +648,60 @@ pub fn App() -> Element {
                             "content-panel expanded full-width"
                         },
                         style: {
-                            // 当为写邮件界面时，设置样式以铺满整个区域
-                            if *current_page.read() == Page::Compose {
+                            // 写邮件、设置和通讯录页面铺满整个区域
+                            if matches!(*current_page.read(), Page::Compose | Page::Settings | Page::Contacts) {
                                 "padding: 0; display: flex;"
                             } else {
                                 ""
                             }
                         },
                         match *current_page.read() {
-                            Page::Compose => rsx!(
-                                Composer { 
-                                    account: current_account.read().clone(),
-                                    full_width: !*show_middle_column.read(),
+                            Page::Compose => {
+                                // 回复时使用接收该邮件的账户发送
+                                let reply = reply_to.read().clone();
+                                let send_account = reply
+                                    .as_ref()
+                                    .and_then(|email| accounts.read().iter().find(|acc| acc.address == email.account).cloned())
+                                    .or_else(|| current_account.read().clone());
+                                rsx!(
+                                    Composer { 
+                                        account: send_account,
+                                        reply_to: reply,
+                                        full_width: !*show_middle_column.read(),
+                                    }
+                                )
+                            },
+                            Page::Settings => rsx!(
+                                SettingsPage {
+                                    accounts: accounts.read().clone(),
+                                    current_account: current_account.read().clone(),
+                                    on_mail_changed: move |_| mail_revision += 1,
                                 }
                             ),
+                            Page::Contacts => rsx!(ContactsPage {}),
                             _ => {
                                 // 显示选中的邮件详情或欢迎信息
                                 if let Some(email) = selected_email.read().as_ref() {
-                                    rsx!(EmailDetail { email: email.clone() })
+                                    rsx!(EmailDetail {
+                                        email: email.clone(),
+                                        account: accounts.read().iter().find(|acc| acc.address == email.account).cloned(),
+                                        raw: opened_raw.read().clone(),
+                                        on_reply: move |email: Email| {
+                                            reply_to.set(Some(email));
+                                            current_page.set(Page::Compose);
+                                        },
+                                        on_open_message: move |(email, raw): (Email, Vec<u8>)| open_message(email, raw),
+                                        folder: (if *current_page.read() == Page::Spam { JUNK_FOLDER } else { "INBOX" }).to_string(),
+                                        on_moved: move |_| {
+                                            selected_email.set(None);
+                                            mail_revision += 1;
+                                        },
+                                        on_labels_changed: move |email: Email| {
+                                            selected_email.set(Some(email));
+                                            mail_revision += 1;
+                                        },
+                                        labels: labels.read().clone(),
+                                    })
                                 } else {
                                     rsx!(
                                         div { 
@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+use crate::models::email::Mailbox;
+
+// 通讯录中的联系人
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    pub uid: String, // vCard UID，新建时为空，保存时生成
+    pub name: String,
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub organization: String,
+    pub note: String,
+    pub harvested: bool, // 从收发的邮件中自动收集，还没有手动编辑过
+}
+
+impl Contact {
+    pub fn primary_email(&self) -> Option<&str> {
+        self.emails.first().map(String::as_str)
+    }
+
+    // 显示的名称，没有姓名时为第一个地址
+    pub fn display_name(&self) -> &str {
+        if self.name.trim().is_empty() {
+            self.primary_email().unwrap_or("未命名联系人")
+        } else {
+            self.name.trim()
+        }
+    }
+
+    // 联系人的第一个地址，用作收件人
+    pub fn mailbox(&self) -> Option<Mailbox> {
+        self.primary_email().map(|email| Mailbox {
+            name: Some(self.name.trim().to_string()).filter(|name| !name.is_empty()),
+            address: email.to_string(),
+        })
+    }
+}
+
+// 联系人分组（邮件列表），发送时展开为组内的地址
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContactGroup {
+    pub uid: String,
+    pub name: String,
+    pub members: Vec<Mailbox>,
+}
@@ -1,4 +1,6 @@
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Datelike, Local, Utc};
+use crate::models::mime::html_to_text;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct EmailAccount {
@@ -8,30 +10,432 @@ pub struct EmailAccount {
     pub smtp_port: u16,
     pub imap_server: String,
     pub imap_port: u16,
-    pub use_tls: bool,
+    pub use_tls: bool, // IMAP 使用 SSL/TLS 直连
+    #[serde(default)]
+    pub smtp_use_tls: Option<bool>, // SMTP 是否加密（465 直连，其他端口 STARTTLS），None 时与 use_tls 相同
 }
 
-pub type AccountList = Vec<EmailAccount>;
+// 账户标识色，用于在全部收件箱中区分邮件来源
+const ACCOUNT_COLORS: [&str; 8] = [
+    "#0066FF", "#16a34a", "#ea580c", "#9333ea",
+    "#db2777", "#0891b2", "#ca8a04", "#4b5563",
+];
+
+// 发件人头像的背景色
+const AVATAR_COLORS: [&str; 10] = [
+    "#ef4444", "#f97316", "#d97706", "#65a30d", "#059669",
+    "#0891b2", "#2563eb", "#7c3aed", "#c026d3", "#e11d48",
+];
+
+// 列表中正文摘要的最大字符数
+const SNIPPET_CHARS: usize = 140;
+
+// 按字符串计算固定的哈希，用于选取颜色
+fn stable_hash(text: &str) -> usize {
+    text.to_lowercase()
+        .bytes()
+        .fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32)) as usize
+}
+
+impl EmailAccount {
+    // SMTP 是否加密，旧版本保存的账户只有一个 use_tls
+    pub fn smtp_tls(&self) -> bool {
+        self.smtp_use_tls.unwrap_or(self.use_tls)
+    }
+
+    // 根据地址计算固定的颜色，账户顺序变化时颜色不变
+    pub fn color(&self) -> &'static str {
+        ACCOUNT_COLORS[stable_hash(&self.address) % ACCOUNT_COLORS.len()]
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Attachment {
     pub filename: String,
     pub content_type: String,
+    // 刚解析或刚添加的附件内容；从本地存储加载时为空，内容在 blob 指向的文件中
+    #[serde(default)]
     pub data: Vec<u8>,
+    // 附件存储中的内容哈希
+    #[serde(default)]
+    pub blob: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+}
+
+impl Attachment {
+    pub fn new(filename: String, content_type: String, data: Vec<u8>) -> Self {
+        let size = data.len() as u64;
+        Self { filename, content_type, data, blob: None, size }
+    }
+
+    // 附件大小，内容不在内存中时使用记录的大小
+    pub fn byte_len(&self) -> u64 {
+        if self.data.is_empty() { self.size } else { self.data.len() as u64 }
+    }
+}
+
+// 单个邮箱，name 为已解码的显示名
+// 旧版本保存的邮件中地址为字符串，读取时解析
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "MailboxRepr")]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MailboxRepr {
+    Text(String),
+    Fields {
+        #[serde(default)]
+        name: Option<String>,
+        address: String,
+    },
+}
+
+impl From<MailboxRepr> for Mailbox {
+    fn from(repr: MailboxRepr) -> Self {
+        match repr {
+            MailboxRepr::Text(text) => Mailbox::parse(&text).unwrap_or_else(|| Mailbox::new(text.trim())),
+            MailboxRepr::Fields { name, address } => Mailbox { name, address },
+        }
+    }
+}
+
+impl Mailbox {
+    pub fn new(address: impl Into<String>) -> Self {
+        Mailbox { name: None, address: address.into() }
+    }
+
+    // 解析单个地址，如 "张三 <zhang@example.com>"，有多个地址时取第一个
+    pub fn parse(text: &str) -> Option<Self> {
+        parse_address_list(text)
+            .into_iter()
+            .flat_map(|address| address.mailboxes().to_vec())
+            .next()
+    }
+
+    // 列表中显示的名称，没有显示名时为地址
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+            _ if !self.address.is_empty() => self.display_address(),
+            _ => "未知发件人".to_string(),
+        }
+    }
+
+    // 头像中显示的字母：显示名或地址的第一个字母或汉字
+    pub fn initial(&self) -> String {
+        self.display_name()
+            .chars()
+            .find(|c| c.is_alphanumeric())
+            .map_or_else(|| "?".to_string(), |c| c.to_uppercase().collect())
+    }
+
+    // 头像颜色，按地址计算，同一发件人的颜色固定
+    pub fn avatar_color(&self) -> &'static str {
+        let key = if self.address.is_empty() { self.display_name() } else { self.address.clone() };
+        AVATAR_COLORS[stable_hash(&key) % AVATAR_COLORS.len()]
+    }
+
+    // 国际化域名（xn--）转换为 Unicode 后的地址，用于显示
+    pub fn display_address(&self) -> String {
+        self.map_domain(|domain| idna::domain_to_unicode(domain).0)
+    }
+
+    // 国际化域名转换为 ASCII（punycode）后的地址，用于发送
+    pub fn ascii_address(&self) -> String {
+        self.map_domain(|domain| idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_string()))
+    }
+
+    fn map_domain(&self, convert: impl Fn(&str) -> String) -> String {
+        match self.address.rsplit_once('@') {
+            Some((user, domain)) => format!("{}@{}", user, convert(domain)),
+            None => self.address.clone(),
+        }
+    }
+}
+
+// 显示和编辑用的格式，可以再次解析：张三 <zhang@example.com>
+impl std::fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => {
+                if name.contains(|c: char| "()<>[]:;@\\,.\"".contains(c)) {
+                    let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                    write!(f, "\"{}\" <{}>", escaped, self.display_address())
+                } else {
+                    write!(f, "{} <{}>", name, self.display_address())
+                }
+            }
+            _ => f.write_str(&self.display_address()),
+        }
+    }
+}
+
+// 地址头中的一项：单个邮箱或地址组（如 "项目组: a@example.com, b@example.com;"）
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Address {
+    Mailbox(Mailbox),
+    Group { name: String, members: Vec<Mailbox> },
+}
+
+impl Address {
+    // 地址包含的所有邮箱
+    pub fn mailboxes(&self) -> &[Mailbox] {
+        match self {
+            Address::Mailbox(mailbox) => std::slice::from_ref(mailbox),
+            Address::Group { members, .. } => members,
+        }
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Address::Mailbox(mailbox) => write!(f, "{}", mailbox),
+            Address::Group { name, members } => {
+                let members: Vec<String> = members.iter().map(|m| m.to_string()).collect();
+                if members.is_empty() {
+                    write!(f, "{}:;", name)
+                } else {
+                    write!(f, "{}: {};", name, members.join(", "))
+                }
+            }
+        }
+    }
+}
+
+impl From<mailparse::MailAddr> for Address {
+    fn from(addr: mailparse::MailAddr) -> Self {
+        let mailbox = |info: mailparse::SingleInfo| Mailbox { name: info.display_name, address: info.addr };
+        match addr {
+            mailparse::MailAddr::Single(info) => Address::Mailbox(mailbox(info)),
+            mailparse::MailAddr::Group(group) => Address::Group {
+                name: group.group_name.trim_matches(|c: char| c == ',' || c.is_whitespace()).to_string(),
+                members: group.addrs.into_iter().map(mailbox).collect(),
+            },
+        }
+    }
+}
+
+// 解析逗号分隔的地址列表
+// 整体无法解析时（如混有分组名）逐项解析，无法解析的项原样作为一个地址
+pub fn parse_address_list(text: &str) -> Vec<Address> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    if let Ok(list) = mailparse::addrparse(text) {
+        return list.iter().cloned().map(Address::from).collect();
+    }
+    split_addresses(text)
+        .into_iter()
+        .flat_map(|item| match mailparse::addrparse(item) {
+            Ok(list) => list.iter().cloned().map(Address::from).collect(),
+            Err(_) => vec![Address::Mailbox(Mailbox::new(item))],
+        })
+        .collect()
+}
+
+// 按逗号拆分地址列表，引号和尖括号中的逗号不拆分
+fn split_addresses(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut start, mut in_quotes, mut in_angle) = (0, false, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' if !in_quotes && !in_angle => {
+                items.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items.into_iter().map(str::trim).filter(|item| !item.is_empty()).collect()
+}
+
+// 地址列表的显示格式
+pub fn format_address_list(addresses: &[Address]) -> String {
+    addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+// 读取地址列表，兼容旧版本保存的字符串（一个字符串中可能有多个地址）
+fn deserialize_address_list<'de, D>(deserializer: D) -> Result<Vec<Address>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Item {
+        Text(String),
+        Address(Address),
+    }
+
+    let items = Vec::<Item>::deserialize(deserializer)?;
+    Ok(items
+        .into_iter()
+        .flat_map(|item| match item {
+            Item::Text(text) => parse_address_list(&text),
+            Item::Address(address) => vec![address],
+        })
+        .collect())
+}
+
+// 同时有 HTML 和纯文本正文时的显示方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyView {
+    #[default]
+    Html,
+    Plain,
+}
+
+impl BodyView {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BodyView::Html => "html",
+            BodyView::Plain => "plain",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "html" => Some(BodyView::Html),
+            "plain" => Some(BodyView::Plain),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Email {
     pub id: String,
-    pub from: String,
-    pub to: Vec<String>,
-    pub cc: Vec<String>,
-    pub bcc: Vec<String>,
+    pub from: Mailbox,
+    #[serde(deserialize_with = "deserialize_address_list")]
+    pub to: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_list")]
+    pub cc: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_list")]
+    pub bcc: Vec<Address>,
     pub subject: String,
     pub date: String,
-    pub body: String,
+    pub body: String, // 纯文本正文，只有 HTML 正文时由 HTML 转换得到
+    #[serde(default)]
+    pub html: Option<String>, // HTML 正文，纯文本邮件为 None
+    #[serde(default)]
+    pub body_view: Option<BodyView>, // 该邮件单独选择的显示方式，None 时使用默认设置
     pub attachments: Vec<Attachment>,
     pub status: EmailStatus,
+    #[serde(default)]
+    pub seen: bool, // 是否已读，旧数据默认为未读
+    #[serde(default)]
+    pub flagged: bool, // 是否加了星标（\Flagged）
+    #[serde(default)]
+    pub answered: bool, // 是否已回复（\Answered）
+    #[serde(default)]
+    pub snippet: String, // 列表中显示的一行正文摘要，同步时生成
+    #[serde(default)]
+    pub account: String, // 接收该邮件的账户地址
+    #[serde(default)]
+    pub folder: String, // 所在的本地文件夹，从本地存储加载时设置
+    #[serde(default)]
+    pub labels: Vec<String>, // 标签的 IMAP 关键字
+    // 原始 RFC822 内容，只在解析邮件后到写入本地存储之间携带，列表加载时为 None
+    #[serde(skip)]
+    pub raw: Option<Vec<u8>>,
+    // 服务器收到邮件的时间（IMAP INTERNALDATE），Date 头无法识别时用于排序和显示
+    #[serde(default)]
+    pub received: Option<DateTime<Utc>>,
+    // 服务器上所在的文件夹，id 为该文件夹中的 UID；只在本地的邮件和旧版本按序号同步的邮件为 None
+    #[serde(default)]
+    pub server_folder: Option<String>,
+}
+
+impl Email {
+    // 生成正文摘要：去掉 HTML、引用的原文和签名，空白合并为一个空格
+    pub fn make_snippet(&self) -> String {
+        let text = match &self.html {
+            Some(html) if self.body.trim().is_empty() => html_to_text(html),
+            _ => self.body.clone(),
+        };
+        let mut words = Vec::new();
+        for line in text.lines() {
+            if line == "-- " {
+                break;
+            }
+            if line.trim_start().starts_with('>') {
+                continue;
+            }
+            words.extend(line.split_whitespace());
+        }
+        words.join(" ").chars().take(SNIPPET_CHARS).collect()
+    }
+
+    // 邮件大小：正文、HTML 与附件的字节数之和
+    pub fn size(&self) -> usize {
+        self.body.len() + self.html.as_ref().map_or(0, String::len) + self.attachments.iter().map(|a| a.byte_len() as usize).sum::<usize>()
+    }
+
+    // 邮件在服务器上的位置 (文件夹, UID)，只在本地的邮件没有
+    pub fn server_location(&self) -> Option<(&str, u32)> {
+        let folder = self.server_folder.as_deref()?;
+        self.id.parse().ok().map(|uid| (folder, uid))
+    }
+
+    // 邮件时间：解析 Date 头，无法识别时使用服务器收到邮件的时间
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        parse_date(&self.date).or(self.received)
+    }
+
+    // 列表中显示的相对日期，如“今天 14:03”、“昨天”、“3月2日”
+    pub fn short_date(&self) -> String {
+        match self.timestamp() {
+            Some(time) => format_relative(time.with_timezone(&Local), Local::now()),
+            None => self.date.clone(),
+        }
+    }
+
+    // 详情中显示的完整本地时间
+    pub fn full_date(&self) -> String {
+        match self.timestamp() {
+            Some(time) => time.with_timezone(&Local).format("%Y年%-m月%-d日 %H:%M:%S").to_string(),
+            None => self.date.clone(),
+        }
+    }
+}
+
+// 解析日期头：本地写的邮件为 RFC3339，收到的邮件为 RFC2822，结果统一为 UTC
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(time.with_timezone(&Utc));
+    }
+    // dateparse 会按邮件中的时区换算，对无法识别的内容（如“未知日期”）返回 0
+    mailparse::dateparse(date)
+        .ok()
+        .filter(|secs| *secs > 0)
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
+
+// 按时间排序，最新的邮件在前面，没有日期的邮件放在最后
+pub fn sort_newest_first(emails: &mut [Email]) {
+    emails.sort_by_key(|email| std::cmp::Reverse(email.timestamp()));
+}
+
+fn format_relative(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let days = now.date_naive().signed_duration_since(time.date_naive()).num_days();
+    if days == 0 {
+        time.format("今天 %H:%M").to_string()
+    } else if days == 1 {
+        "昨天".to_string()
+    } else if time.year() == now.year() {
+        time.format("%-m月%-d日").to_string()
+    } else {
+        time.format("%Y年%-m月%-d日").to_string()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -54,6 +458,151 @@ impl Default for EmailAccount {
             smtp_server: "".to_string(),
             smtp_port: 587,
             use_tls: true,
+            smtp_use_tls: None,
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::service::imap_client::parse_email;
+
+    fn email(id: &str, headers: &str) -> Email {
+        parse_email(id.into(), format!("{}\r\n\r\nbody", headers).as_bytes()).unwrap()
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    // RFC 2822 按邮件中的时区换算为 UTC，本地写的邮件为 RFC 3339
+    #[test]
+    fn parse_date_formats() {
+        assert_eq!(parse_date("Mon, 15 Jan 2024 12:00:00 +0800"), Some(utc("2024-01-15T04:00:00Z")));
+        assert_eq!(parse_date("15 Jan 2024 12:00:00 -0500 (EST)"), Some(utc("2024-01-15T17:00:00Z")));
+        assert_eq!(parse_date("2024-01-15T12:00:00+02:00"), Some(utc("2024-01-15T10:00:00Z")));
+        assert_eq!(parse_date("未知日期"), None);
+        assert_eq!(parse_date(""), None);
+    }
+
+    // Date 头无法识别时按服务器收到的时间排序，都没有的放在最后
+    #[test]
+    fn sort_by_timestamp_with_fallback() {
+        let mut received = email("2", "Subject: no date");
+        received.received = Some(utc("2024-03-01T00:00:00Z"));
+        let mut emails = vec![
+            email("1", "Date: Mon, 15 Jan 2024 12:00:00 +0000"),
+            email("3", "Date: garbage"),
+            received,
+            // 本地时间更晚，换算为 UTC 后更早
+            email("4", "Date: Mon, 15 Jan 2024 18:00:00 +0800"),
+        ];
+        sort_newest_first(&mut emails);
+        assert_eq!(emails.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["2", "1", "4", "3"]);
+        assert_eq!(emails[3].short_date(), "garbage");
+    }
+
+    // 按本地日期而不是相隔的小时数判断今天和昨天
+    #[test]
+    fn relative_dates() {
+        let local = |y, m, d, h, min| Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+        let now = local(2024, 3, 10, 0, 10);
+        assert_eq!(format_relative(local(2024, 3, 10, 0, 3), now), "今天 00:03");
+        assert_eq!(format_relative(local(2024, 3, 9, 23, 50), now), "昨天");
+        assert_eq!(format_relative(local(2024, 3, 2, 14, 3), now), "3月2日");
+        assert_eq!(format_relative(local(2023, 12, 31, 9, 0), now), "2023年12月31日");
+    }
+
+    // 地址列表：引号中的逗号、地址组和无法解析的项
+    #[test]
+    fn address_lists() {
+        let list = parse_address_list("张三 <zhang@example.com>, \"Doe, John\" <john@example.com>, plain@example.com");
+        let mailboxes: Vec<&Mailbox> = list.iter().flat_map(Address::mailboxes).collect();
+        assert_eq!(mailboxes.len(), 3);
+        assert_eq!(mailboxes[0].name.as_deref(), Some("张三"));
+        assert_eq!(mailboxes[1].display_name(), "Doe, John");
+        assert_eq!(mailboxes[2].display_name(), "plain@example.com");
+
+        let groups = parse_address_list("项目组: a@example.com, b@example.com;, Empty:;");
+        assert_eq!(groups[0], Address::Group {
+            name: "项目组".into(),
+            members: vec![Mailbox::new("a@example.com"), Mailbox::new("b@example.com")],
+        });
+        assert_eq!(groups[1].mailboxes(), &[]);
+        assert_eq!(format_address_list(&groups), "项目组: a@example.com, b@example.com;, Empty:;");
+
+        // 显示格式可以再次解析，需要时给显示名加引号
+        let mailbox = Mailbox { name: Some("Doe, \"JD\" John".into()), address: "john@example.com".into() };
+        assert_eq!(mailbox.to_string(), "\"Doe, \\\"JD\\\" John\" <john@example.com>");
+        assert_eq!(Mailbox::parse(&mailbox.to_string()), Some(mailbox));
+        assert!(parse_address_list("  ").is_empty());
+    }
+
+    // 邮件头中的 RFC 2047 编码的显示名
+    #[test]
+    fn encoded_display_names() {
+        let message = email(
+            "1",
+            "From: =?UTF-8?B?5byg5LiJ?= <zhang@example.com>\r\nTo: =?GB2312?B?wO7LxA==?= <li@example.com>, =?UTF-8?Q?Caf=C3=A9?= <cafe@example.com>",
+        );
+        assert_eq!(message.from.display_name(), "张三");
+        let to: Vec<String> = message.to.iter().flat_map(Address::mailboxes).map(Mailbox::display_name).collect();
+        assert_eq!(to, vec!["李四", "Café"]);
+        assert_eq!(message.from.initial(), "张");
+    }
+
+    // 国际化域名显示为 Unicode，发送时转换为 punycode
+    #[test]
+    fn idn_addresses() {
+        let mailbox = Mailbox::new("user@xn--fsqu00a.xn--0zwm56d");
+        assert_eq!(mailbox.display_address(), "user@例子.测试");
+        assert_eq!(mailbox.to_string(), "user@例子.测试");
+        assert_eq!(Mailbox::new("user@例子.测试").ascii_address(), "user@xn--fsqu00a.xn--0zwm56d");
+        assert_eq!(Mailbox::new("not-an-address").ascii_address(), "not-an-address");
+    }
+
+    // 旧版本保存的字符串地址
+    #[test]
+    fn legacy_string_addresses() {
+        let mailbox: Mailbox = serde_json::from_str("\"张三 <zhang@example.com>\"").unwrap();
+        assert_eq!(mailbox, Mailbox { name: Some("张三".into()), address: "zhang@example.com".into() });
+        let fields: Mailbox = serde_json::from_str(r#"{"address": "a@example.com"}"#).unwrap();
+        assert_eq!(fields, Mailbox::new("a@example.com"));
+    }
+
+    // 摘要去掉引用和签名，合并空白，按字符数截断
+    #[test]
+    fn snippets() {
+        let mut message = email("1", "Subject: hi");
+        message.body = "Hi  team,\n\n> quoted reply\n  >> nested\nsee\tattached.\n-- \nsignature".into();
+        assert_eq!(message.make_snippet(), "Hi team, see attached.");
+
+        message.body = "长".repeat(200);
+        assert_eq!(message.make_snippet(), "长".repeat(SNIPPET_CHARS));
+
+        // 只有 HTML 正文时由 HTML 转换
+        message.body = "  ".into();
+        message.html = Some("<html><body><p>Hello <b>world</b></p><p>again</p></body></html>".into());
+        assert_eq!(message.make_snippet(), "Hello world again");
+    }
+
+    // 头像的字母取显示名或地址的第一个字母或汉字，颜色按地址固定
+    #[test]
+    fn avatars() {
+        let zhang = Mailbox { name: Some("张三".into()), address: "zhang@example.com".into() };
+        assert_eq!(zhang.initial(), "张");
+        assert_eq!(Mailbox::new("alice@example.com").initial(), "A");
+        assert_eq!(Mailbox { name: Some("\"(bob)\"".into()), address: String::new() }.initial(), "B");
+        assert_eq!(Mailbox { name: Some("!!!".into()), address: String::new() }.initial(), "?");
+        assert_eq!(Mailbox::default().display_name(), "未知发件人");
+
+        let color = Mailbox::new("Alice@Example.com").avatar_color();
+        assert_eq!(color, Mailbox { name: Some("Alice".into()), address: "alice@example.com".into() }.avatar_color());
+        assert!(AVATAR_COLORS.contains(&color));
+        // 没有地址时按显示名计算
+        let named = |name: &str| Mailbox { name: Some(name.into()), address: String::new() }.avatar_color();
+        assert_eq!(named("张三"), AVATAR_COLORS[stable_hash("张三") % AVATAR_COLORS.len()]);
+        assert_eq!(stable_hash("Ab"), stable_hash("aB"));
+    }
+}
@@ -0,0 +1,332 @@
+use serde::{Serialize, Deserialize};
+use crate::models::email::{Address, Email};
+
+// 条件检查的内容
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleField {
+    #[default]
+    From,
+    To, // 收件人和抄送
+    Subject,
+    Header, // 任意邮件头，名称在 header 中
+    Size,   // 邮件大小，值的单位为 KB
+    Attachment,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleOp {
+    #[default]
+    Contains,
+    NotContains,
+    Is,
+    StartsWith,
+    EndsWith,
+    Greater,
+    Less,
+    Exists,    // 有附件 / 有该邮件头
+    NotExists,
+}
+
+impl RuleField {
+    pub const ALL: [RuleField; 6] =
+        [RuleField::From, RuleField::To, RuleField::Subject, RuleField::Header, RuleField::Size, RuleField::Attachment];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuleField::From => "发件人",
+            RuleField::To => "收件人",
+            RuleField::Subject => "主题",
+            RuleField::Header => "邮件头",
+            RuleField::Size => "大小",
+            RuleField::Attachment => "附件",
+        }
+    }
+
+    // 该内容可以使用的比较方式
+    pub fn ops(&self) -> &'static [RuleOp] {
+        const TEXT: &[RuleOp] = &[RuleOp::Contains, RuleOp::NotContains, RuleOp::Is, RuleOp::StartsWith, RuleOp::EndsWith];
+        match self {
+            RuleField::From | RuleField::To | RuleField::Subject => TEXT,
+            RuleField::Header => &[
+                RuleOp::Contains,
+                RuleOp::NotContains,
+                RuleOp::Is,
+                RuleOp::StartsWith,
+                RuleOp::EndsWith,
+                RuleOp::Exists,
+                RuleOp::NotExists,
+            ],
+            RuleField::Size => &[RuleOp::Greater, RuleOp::Less],
+            RuleField::Attachment => &[RuleOp::Exists, RuleOp::NotExists],
+        }
+    }
+}
+
+impl RuleOp {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuleOp::Contains => "包含",
+            RuleOp::NotContains => "不包含",
+            RuleOp::Is => "等于",
+            RuleOp::StartsWith => "开头是",
+            RuleOp::EndsWith => "结尾是",
+            RuleOp::Greater => "大于 (KB)",
+            RuleOp::Less => "小于 (KB)",
+            RuleOp::Exists => "存在",
+            RuleOp::NotExists => "不存在",
+        }
+    }
+
+    // 不需要填写值的比较方式
+    pub fn needs_value(&self) -> bool {
+        !matches!(self, RuleOp::Exists | RuleOp::NotExists)
+    }
+}
+
+// 规则的一个条件，文本比较不区分大小写
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleCondition {
+    pub field: RuleField,
+    #[serde(default)]
+    pub header: String, // field 为 Header 时的邮件头名称
+    pub op: RuleOp,
+    #[serde(default)]
+    pub value: String,
+}
+
+impl RuleCondition {
+    pub fn matches(&self, email: &Email) -> bool {
+        match self.field {
+            RuleField::From => self.matches_text(&[email.from.to_string()]),
+            RuleField::To => {
+                let recipients: Vec<String> = email
+                    .to
+                    .iter()
+                    .chain(&email.cc)
+                    .flat_map(Address::mailboxes)
+                    .map(|m| m.to_string())
+                    .collect();
+                self.matches_text(&recipients)
+            }
+            RuleField::Subject => self.matches_text(std::slice::from_ref(&email.subject)),
+            RuleField::Header => self.matches_text(&header_values(email, &self.header)),
+            RuleField::Size => {
+                let Ok(limit) = self.value.trim().parse::<f64>() else {
+                    return false;
+                };
+                let size = message_size(email) as f64 / 1024.0;
+                match self.op {
+                    RuleOp::Greater => size > limit,
+                    RuleOp::Less => size < limit,
+                    _ => false,
+                }
+            }
+            RuleField::Attachment => (self.op == RuleOp::Exists) != email.attachments.is_empty(),
+        }
+    }
+
+    // 多个值（如多个收件人）时任意一个满足即可；“不包含”要求所有值都不包含
+    fn matches_text(&self, values: &[String]) -> bool {
+        let pattern = self.value.trim().to_lowercase();
+        let test = |value: &String| {
+            let value = value.to_lowercase();
+            match self.op {
+                RuleOp::Contains | RuleOp::NotContains => value.contains(&pattern),
+                RuleOp::Is => value.trim() == pattern,
+                RuleOp::StartsWith => value.trim_start().starts_with(&pattern),
+                RuleOp::EndsWith => value.trim_end().ends_with(&pattern),
+                _ => false,
+            }
+        };
+        match self.op {
+            RuleOp::Exists => !values.is_empty(),
+            RuleOp::NotExists => values.is_empty(),
+            RuleOp::NotContains => !values.iter().any(test),
+            _ => values.iter().any(test),
+        }
+    }
+}
+
+// 规则动作
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionKind {
+    #[default]
+    MoveTo, // 值为文件夹名称
+    AddFlag, // 值为标记，如 \Flagged 或自定义关键字
+    MarkRead,
+    Forward, // 值为转发地址，只对新收到的邮件执行
+    Delete,  // 移到已删除文件夹
+}
+
+impl ActionKind {
+    pub const ALL: [ActionKind; 5] =
+        [ActionKind::MoveTo, ActionKind::AddFlag, ActionKind::MarkRead, ActionKind::Forward, ActionKind::Delete];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ActionKind::MoveTo => "移动到文件夹",
+            ActionKind::AddFlag => "添加标记",
+            ActionKind::MarkRead => "标记为已读",
+            ActionKind::Forward => "转发到",
+            ActionKind::Delete => "删除",
+        }
+    }
+
+    pub fn needs_value(&self) -> bool {
+        matches!(self, ActionKind::MoveTo | ActionKind::AddFlag | ActionKind::Forward)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleAction {
+    pub kind: ActionKind,
+    #[serde(default)]
+    pub value: String,
+}
+
+// 邮件过滤规则，按列表顺序对新同步的邮件执行
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub name: String,
+    pub enabled: bool,
+    pub match_all: bool, // true 时所有条件都满足才执行（AND），否则满足任意一个（OR）
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    #[serde(default)]
+    pub stop: bool, // 匹配后不再检查后面的规则
+}
+
+impl Default for FilterRule {
+    fn default() -> Self {
+        FilterRule {
+            name: String::new(),
+            enabled: true,
+            match_all: true,
+            conditions: vec![RuleCondition::default()],
+            actions: vec![RuleAction::default()],
+            stop: false,
+        }
+    }
+}
+
+impl FilterRule {
+    // 没有条件的规则不匹配任何邮件，避免误操作所有邮件
+    pub fn matches(&self, email: &Email) -> bool {
+        if !self.enabled || self.conditions.is_empty() {
+            return false;
+        }
+        if self.match_all {
+            self.conditions.iter().all(|c| c.matches(email))
+        } else {
+            self.conditions.iter().any(|c| c.matches(email))
+        }
+    }
+}
+
+// 邮件头的所有值，只有带原文的邮件（新同步的或从原文加载的）才能检查
+fn header_values(email: &Email, name: &str) -> Vec<String> {
+    use mailparse::MailHeaderMap;
+    let Some(raw) = &email.raw else {
+        return Vec::new();
+    };
+    match mailparse::parse_headers(raw) {
+        Ok((headers, _)) => headers.get_all_values(name.trim()),
+        Err(_) => Vec::new(),
+    }
+}
+
+// 邮件大小：有原文时为原文大小，否则为正文和附件大小之和
+fn message_size(email: &Email) -> usize {
+    match &email.raw {
+        Some(raw) => raw.len(),
+        None => {
+            email.body.len()
+                + email.html.as_ref().map_or(0, String::len)
+                + email.attachments.iter().map(|a| a.byte_len() as usize).sum::<usize>()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Attachment;
+    use crate::service::imap_client::parse_email;
+
+    fn email() -> Email {
+        let raw = format!(
+            "From: =?UTF-8?B?5byg5LiJ?= <Boss@Example.com>\r\nTo: me@example.com, Team: a@x.com, b@x.com;\r\n\
+             Cc: cc@example.org\r\nList-Id: <dev.lists.example.com>\r\nSubject: [Weekly] Report\r\n\r\n{}",
+            "x".repeat(2048)
+        );
+        parse_email("1".into(), raw.as_bytes()).unwrap()
+    }
+
+    fn condition(field: RuleField, op: RuleOp, value: &str) -> RuleCondition {
+        RuleCondition { field, op, value: value.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn text_conditions_ignore_case() {
+        let email = email();
+        assert!(condition(RuleField::From, RuleOp::Contains, "boss@example").matches(&email));
+        assert!(condition(RuleField::From, RuleOp::StartsWith, "张三").matches(&email));
+        assert!(condition(RuleField::Subject, RuleOp::Is, " [weekly] report ").matches(&email));
+        assert!(condition(RuleField::Subject, RuleOp::EndsWith, "REPORT").matches(&email));
+        assert!(!condition(RuleField::Subject, RuleOp::StartsWith, "report").matches(&email));
+    }
+
+    #[test]
+    fn recipients_include_cc_and_group_members() {
+        let email = email();
+        assert!(condition(RuleField::To, RuleOp::Is, "b@x.com").matches(&email));
+        assert!(condition(RuleField::To, RuleOp::EndsWith, "example.org").matches(&email));
+        // 不包含要求每个收件人都不包含
+        assert!(!condition(RuleField::To, RuleOp::NotContains, "a@x.com").matches(&email));
+        assert!(condition(RuleField::To, RuleOp::NotContains, "nobody").matches(&email));
+    }
+
+    #[test]
+    fn headers_need_the_raw_message() {
+        let mut email = email();
+        let header = |op, value: &str| RuleCondition { header: "list-id".into(), ..condition(RuleField::Header, op, value) };
+        assert!(header(RuleOp::Contains, "dev.lists").matches(&email));
+        assert!(header(RuleOp::Exists, "").matches(&email));
+        assert!(!header(RuleOp::NotExists, "").matches(&email));
+        email.raw = None;
+        assert!(header(RuleOp::NotExists, "").matches(&email));
+    }
+
+    #[test]
+    fn size_and_attachment_conditions() {
+        let mut email = email();
+        email.raw = None;
+        assert!(condition(RuleField::Size, RuleOp::Greater, "1.5").matches(&email));
+        assert!(condition(RuleField::Size, RuleOp::Less, "3").matches(&email));
+        assert!(!condition(RuleField::Size, RuleOp::Greater, "abc").matches(&email));
+
+        assert!(condition(RuleField::Attachment, RuleOp::NotExists, "").matches(&email));
+        email.attachments.push(Attachment::new("a.txt".into(), "text/plain".into(), b"hi".to_vec()));
+        assert!(condition(RuleField::Attachment, RuleOp::Exists, "").matches(&email));
+    }
+
+    #[test]
+    fn rule_combines_conditions() {
+        let email = email();
+        let mut rule = FilterRule {
+            conditions: vec![
+                condition(RuleField::From, RuleOp::Contains, "boss"),
+                condition(RuleField::Subject, RuleOp::Contains, "invoice"),
+            ],
+            ..Default::default()
+        };
+        assert!(!rule.matches(&email));
+        rule.match_all = false;
+        assert!(rule.matches(&email));
+        rule.enabled = false;
+        assert!(!rule.matches(&email));
+        rule.enabled = true;
+        rule.conditions.clear();
+        assert!(!rule.matches(&email));
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Serialize, Deserialize};
+
+// 可选的标签颜色
+pub const LABEL_COLORS: [&str; 8] = [
+    "#dc2626", "#ea580c", "#ca8a04", "#16a34a",
+    "#0891b2", "#2563eb", "#9333ea", "#db2777",
+];
+
+// 程序自己使用或其他客户端常见的关键字，不当作标签显示
+const SYSTEM_KEYWORDS: [&str; 9] = [
+    "$Junk", "$NotJunk", "Junk", "NonJunk", "$Forwarded", "$MDNSent", "$Phishing", "$Submitted", "$Answered",
+];
+
+// 邮件标签，与文件夹无关，一封邮件可以有多个
+// 服务器允许自定义关键字时作为 IMAP 关键字同步，否则只保存在本地
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+    pub keyword: String, // 对应的 IMAP 关键字，创建后不再改变，改名不影响已有邮件
+    pub color: String,
+}
+
+impl Label {
+    // 新建标签，关键字由名称生成，与已有标签重复时加上序号
+    pub fn new(name: &str, existing: &[Label]) -> Self {
+        let base = keyword_for(name);
+        let mut keyword = base.clone();
+        let mut n = 2;
+        while existing.iter().any(|l| l.keyword.eq_ignore_ascii_case(&keyword)) {
+            keyword = format!("{}{}", base, n);
+            n += 1;
+        }
+        Label {
+            name: name.trim().to_string(),
+            keyword,
+            color: LABEL_COLORS[existing.len() % LABEL_COLORS.len()].to_string(),
+        }
+    }
+}
+
+// IMAP 关键字只能使用 ASCII 原子字符，中文等名称用名称的哈希生成
+fn keyword_for(name: &str) -> String {
+    let name = name.trim();
+    let is_atom = |c: char| c.is_ascii_graphic() && !matches!(c, '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']');
+    if !name.is_empty() && name.chars().all(is_atom) && !name.starts_with('$') {
+        return name.to_string();
+    }
+    let hash = name.bytes().fold(0x811c9dc5u32, |acc, b| (acc ^ b as u32).wrapping_mul(0x01000193));
+    format!("$Label_{:08x}", hash)
+}
+
+// 邮件标记中哪些是标签：系统标记（\Seen 等）和常见的程序关键字除外
+pub fn is_label_keyword(flag: &str) -> bool {
+    !flag.starts_with('\\') && !SYSTEM_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(flag))
+}
+
+// 按关键字找到标签，没有定义的关键字（如其他客户端添加的）用关键字作名称、灰色显示
+pub fn find_label(labels: &[Label], keyword: &str) -> Label {
+    labels
+        .iter()
+        .find(|l| l.keyword.eq_ignore_ascii_case(keyword))
+        .cloned()
+        .unwrap_or_else(|| Label { name: keyword.to_string(), keyword: keyword.to_string(), color: "#6b7280".to_string() })
+}
@@ -0,0 +1,558 @@
+use std::collections::BTreeMap;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use encoding_rs::{Encoding, GB18030, UTF_8};
+use mailparse::body::Body;
+use mailparse::{parse_content_disposition, parse_content_type, parse_mail, DispositionType, MailHeader, MailParseError, ParsedMail};
+
+// 附带邮件的最大嵌套层数，避免构造的邮件导致无限递归
+const MAX_DEPTH: usize = 16;
+
+// Content-Disposition
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Disposition {
+    #[default]
+    None, // 没有该头
+    Inline,
+    Attachment,
+}
+
+// 邮件的 MIME 结构树，保留每个部分的头和参数
+// 正文选择和附件提取都基于这棵树
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MimePart {
+    pub headers: Vec<(String, String)>, // 原始顺序，值已解码
+    pub content_type: String,           // 小写，如 text/plain
+    pub params: BTreeMap<String, String>, // Content-Type 参数，RFC 2231 编码已解码
+    pub disposition: Disposition,
+    pub filename: Option<String>,
+    pub content_id: Option<String>, // 去掉尖括号
+    pub encoding: String,           // Content-Transfer-Encoding，小写，默认 7bit
+    pub body: Vec<u8>,              // 已解除传输编码的内容，multipart 为空，message/rfc822 为附带邮件原文
+    pub children: Vec<MimePart>,    // multipart 的子部分；message/rfc822 时为附带邮件
+}
+
+impl MimePart {
+    pub fn parse(raw: &[u8]) -> Result<Self, MailParseError> {
+        Ok(Self::from(&parse_mail(raw)?))
+    }
+
+    fn from_parsed(parsed: &ParsedMail, depth: usize) -> Self {
+        let headers: Vec<(String, String)> = parsed.headers.iter().map(|h| (h.get_key(), header_value(h))).collect();
+        let first = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+
+        // 没有 Content-Type 时使用 mailparse 的默认值（multipart/digest 中为 message/rfc822）
+        let (content_type, params) = match first("Content-Type") {
+            Some(value) => {
+                let ctype = parse_content_type(&value);
+                (ctype.mimetype, ctype.params)
+            }
+            None => (parsed.ctype.mimetype.clone(), parsed.ctype.params.clone()),
+        };
+        let disposition_header = first("Content-Disposition").map(|value| parse_content_disposition(&value));
+        let disposition = match disposition_header.as_ref().map(|d| &d.disposition) {
+            None => Disposition::None,
+            Some(DispositionType::Attachment) => Disposition::Attachment,
+            Some(_) => Disposition::Inline,
+        };
+        let filename = disposition_header
+            .as_ref()
+            .and_then(|d| d.params.get("filename"))
+            .or_else(|| params.get("name"))
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+
+        let mut part = MimePart {
+            content_type,
+            params,
+            disposition,
+            filename,
+            content_id: first("Content-ID").map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string()),
+            encoding: first("Content-Transfer-Encoding")
+                .map(|e| e.trim().to_lowercase())
+                .unwrap_or_else(|| "7bit".to_string()),
+            headers,
+            ..Default::default()
+        };
+
+        if part.is_multipart() {
+            part.children = parsed.subparts.iter().map(|p| Self::from_parsed(p, depth + 1)).collect();
+            if part.children.is_empty() {
+                part.children = guess_boundary(parsed, &part, depth);
+            }
+            // 找不到任何分隔行时整体作为纯文本显示
+            if part.children.is_empty() {
+                part.content_type = "text/plain".to_string();
+                part.params.remove("boundary");
+                part.body = body_bytes(parsed);
+            }
+        } else {
+            part.body = body_bytes(parsed);
+            if part.is_message() && depth < MAX_DEPTH {
+                if let Ok(inner) = parse_mail(&part.body) {
+                    part.children = vec![Self::from_parsed(&inner, depth + 1)];
+                }
+            }
+        }
+        part
+    }
+
+    pub fn is_multipart(&self) -> bool {
+        self.content_type.starts_with("multipart/")
+    }
+
+    // 附带的完整邮件
+    pub fn is_message(&self) -> bool {
+        self.content_type == "message/rfc822" || self.content_type == "message/global"
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.params.get("charset").map(String::as_str)
+    }
+
+    // 按声明的字符集解码文本内容，声明错误或缺失时依次尝试 UTF-8 和 GB18030
+    pub fn text(&self) -> String {
+        decode_text(&self.body, self.charset())
+    }
+
+    // 叶子部分是否作为附件列出：声明为附件，或不是可以直接显示的正文
+    pub fn is_attachment(&self) -> bool {
+        if self.is_multipart() {
+            return false;
+        }
+        if self.disposition == Disposition::Attachment {
+            return true;
+        }
+        let is_text = self.content_type == "text/plain" || self.content_type == "text/html";
+        !is_text || self.filename.is_some()
+    }
+
+    // 纯文本正文，多个内联文本部分（如邮件列表追加的页脚）依次拼接
+    pub fn text_body(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        self.collect_bodies("text/plain", &mut parts);
+        let texts: Vec<String> = parts.iter().map(|p| p.text()).collect();
+        (!texts.is_empty()).then(|| texts.join("\n"))
+    }
+
+    // HTML 正文，引用的内嵌图片（cid:）替换为 data URI 以便直接显示
+    pub fn html_body(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        self.collect_bodies("text/html", &mut parts);
+        if parts.is_empty() {
+            return None;
+        }
+        let mut html: String = parts.iter().map(|p| p.text()).collect::<Vec<_>>().join("\n");
+        if html.contains("cid:") {
+            for part in self.parts() {
+                if let (Some(id), true) = (&part.content_id, part.content_type.starts_with("image/")) {
+                    let uri = format!("data:{};base64,{}", part.content_type, BASE64_STANDARD.encode(&part.body));
+                    html = html.replace(&format!("cid:{}", id), &uri);
+                }
+            }
+        }
+        Some(html)
+    }
+
+    // 显示用的正文：纯文本和 HTML，只有 HTML 时纯文本由 HTML 转换得到
+    pub fn bodies(&self) -> (String, Option<String>) {
+        let html = self.html_body();
+        let text = self
+            .text_body()
+            .or_else(|| html.as_deref().map(html_to_text))
+            .unwrap_or_default();
+        (text, html)
+    }
+
+    // 按 RFC 2046 选择正文部分
+    fn collect_bodies<'a>(&'a self, content_type: &str, out: &mut Vec<&'a MimePart>) {
+        match self.content_type.as_str() {
+            // 各部分内容相同，取能显示的最后一个（最接近原意）
+            "multipart/alternative" => {
+                for child in self.children.iter().rev() {
+                    let start = out.len();
+                    child.collect_bodies(content_type, out);
+                    if out.len() > start {
+                        return;
+                    }
+                }
+            }
+            "multipart/related" => {
+                if let Some(root) = self.related_root() {
+                    root.collect_bodies(content_type, out);
+                }
+            }
+            // 签名邮件的第一部分是内容；退信报告的第一部分是给人看的说明
+            "multipart/signed" | "multipart/report" => {
+                if let Some(first) = self.children.first() {
+                    first.collect_bodies(content_type, out);
+                }
+            }
+            _ if self.is_multipart() => {
+                for child in &self.children {
+                    child.collect_bodies(content_type, out);
+                }
+            }
+            _ if self.content_type == content_type && !self.is_attachment() => out.push(self),
+            _ => {}
+        }
+    }
+
+    // multipart/related 的根部分：start 参数指定，否则为第一个子部分
+    fn related_root(&self) -> Option<&MimePart> {
+        let start = self.params.get("start").map(|s| s.trim_start_matches('<').trim_end_matches('>'));
+        start
+            .and_then(|id| self.children.iter().find(|c| c.content_id.as_deref() == Some(id)))
+            .or_else(|| self.children.first())
+    }
+
+    // 作为附件列出的部分，不包括签名和被正文引用的内嵌图片，也不进入附带的邮件
+    pub fn attachments(&self) -> Vec<&MimePart> {
+        let mut out = Vec::new();
+        self.collect_attachments(&mut out);
+        out
+    }
+
+    fn collect_attachments<'a>(&'a self, out: &mut Vec<&'a MimePart>) {
+        match self.content_type.as_str() {
+            "multipart/signed" => {
+                if let Some(first) = self.children.first() {
+                    first.collect_attachments(out);
+                }
+            }
+            "multipart/related" => {
+                let root = self.related_root();
+                for child in &self.children {
+                    let is_root = root.is_some_and(|r| std::ptr::eq(r, child));
+                    let resource = child.content_id.is_some() && child.disposition != Disposition::Attachment;
+                    if is_root || !resource {
+                        child.collect_attachments(out);
+                    }
+                }
+            }
+            _ if self.is_multipart() => {
+                for child in &self.children {
+                    child.collect_attachments(out);
+                }
+            }
+            _ if self.is_attachment() => out.push(self),
+            _ => {}
+        }
+    }
+
+    // 深度优先遍历所有部分（包括附带邮件内的部分），第一个为自身
+    pub fn parts(&self) -> Vec<&MimePart> {
+        let mut out = vec![self];
+        let mut index = 0;
+        while index < out.len() {
+            let part = out[index];
+            out.splice(index + 1..index + 1, part.children.iter());
+            index += 1;
+        }
+        out
+    }
+
+    // 结构概览，每行一个部分，用于原文面板
+    pub fn outline(&self) -> String {
+        let mut lines = Vec::new();
+        self.outline_into(0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn outline_into(&self, depth: usize, lines: &mut Vec<String>) {
+        let mut line = format!("{}{}", "  ".repeat(depth), self.content_type);
+        if let Some(charset) = self.charset() {
+            line.push_str(&format!("; charset={}", charset));
+        }
+        if !self.is_multipart() {
+            line.push_str(&format!(" [{}, {} 字节]", self.encoding, self.body.len()));
+        }
+        match self.disposition {
+            Disposition::Attachment => line.push_str(" 附件"),
+            Disposition::Inline => line.push_str(" 内联"),
+            Disposition::None => {}
+        }
+        if let Some(name) = &self.filename {
+            line.push_str(&format!(" \"{}\"", name));
+        }
+        if let Some(id) = &self.content_id {
+            line.push_str(&format!(" <{}>", id));
+        }
+        lines.push(line);
+        for child in &self.children {
+            child.outline_into(depth + 1, lines);
+        }
+    }
+}
+
+impl From<&ParsedMail<'_>> for MimePart {
+    fn from(parsed: &ParsedMail) -> Self {
+        Self::from_parsed(parsed, 0)
+    }
+}
+
+// 头的值：RFC 2047 编码已解码；未编码的 8 位内容不是 UTF-8 时按 GB18030 解码
+fn header_value(header: &MailHeader) -> String {
+    let raw = header.get_value_raw();
+    if raw.is_ascii() || std::str::from_utf8(raw).is_ok() {
+        return header.get_value();
+    }
+    let (text, _, _) = GB18030.decode(raw);
+    text.split(['\r', '\n']).map(str::trim).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+// 解除传输编码，编码损坏时保留原始内容
+fn body_bytes(parsed: &ParsedMail) -> Vec<u8> {
+    parsed.get_body_raw().unwrap_or_else(|_| match parsed.get_body_encoded() {
+        Body::Base64(body) | Body::QuotedPrintable(body) => body.get_raw().to_vec(),
+        Body::SevenBit(body) | Body::EightBit(body) => body.get_raw().to_vec(),
+        Body::Binary(body) => body.get_raw().to_vec(),
+    })
+}
+
+// boundary 参数缺失或与正文中的分隔行不一致时，用正文中第一个 "--" 开头的行作为分隔重新解析
+fn guess_boundary(parsed: &ParsedMail, part: &MimePart, depth: usize) -> Vec<MimePart> {
+    let body = body_bytes(parsed);
+    let Some(boundary) = body
+        .split(|&b| b == b'\n')
+        .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+        .find(|line| line.len() > 2 && line.starts_with("--") && !line.contains('"'))
+        .map(|line| line[2..].to_string())
+    else {
+        return Vec::new();
+    };
+    if part.params.get("boundary") == Some(&boundary) {
+        return Vec::new();
+    }
+    let mut raw = format!("Content-Type: {}; boundary=\"{}\"\r\n\r\n", part.content_type, boundary).into_bytes();
+    raw.extend_from_slice(&body);
+    match parse_mail(&raw) {
+        Ok(reparsed) => reparsed.subparts.iter().map(|p| MimePart::from_parsed(p, depth + 1)).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// 文本解码：us-ascii 或缺失的声明经常是错的，按内容判断
+pub fn decode_text(bytes: &[u8], charset: Option<&str>) -> String {
+    let declared = charset
+        .map(str::trim)
+        .filter(|c| !c.eq_ignore_ascii_case("us-ascii") && !c.eq_ignore_ascii_case("ascii"))
+        .and_then(|c| Encoding::for_label(c.as_bytes()));
+    if let Some(encoding) = declared.filter(|e| *e != UTF_8) {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    match GB18030.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => text.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+// HTML 转为纯文本，用于只有 HTML 正文的邮件的搜索、引用回复和纯文本显示
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_lowercase();
+        let name: String = tag.trim_start_matches('/').chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        rest = &rest[start + end + 1..];
+        match name.as_str() {
+            // 不显示的内容整个跳过
+            "style" | "script" | "head" | "title" if !tag.starts_with('/') => {
+                let close = format!("</{}", name);
+                rest = match rest.to_ascii_lowercase().find(&close) {
+                    Some(i) => &rest[i..],
+                    None => "",
+                };
+            }
+            "br" | "p" | "div" | "tr" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" | "table" => {
+                text.push('\n')
+            }
+            "td" | "th" if !tag.starts_with('/') => text.push('\t'),
+            _ => {}
+        }
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    // 合并多余的空行
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank += 1;
+            if blank > 1 || out.is_empty() {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|&i| i <= 10).map(|i| &rest[1..i + 1]);
+        let decoded = entity.and_then(|name| match name {
+            "nbsp" => Some(' '),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = name.strip_prefix('#')?;
+                let value = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(value)
+            }
+        });
+        match (entity, decoded) {
+            (Some(name), Some(c)) => {
+                out.push(c);
+                rest = &rest[name.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> MimePart {
+        MimePart::parse(raw.replace('\n', "\r\n").as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn alternative_prefers_last_part_and_keeps_text() {
+        let root = parse(
+            "Content-Type: multipart/alternative; boundary=b\n\n\
+             --b\nContent-Type: text/plain; charset=utf-8\n\n纯文本\n\
+             --b\nContent-Type: text/html; charset=utf-8\n\n<p>超文本 &amp; 图片</p>\n\
+             --b--\n",
+        );
+        let (text, html) = root.bodies();
+        assert_eq!(text.trim(), "纯文本");
+        assert_eq!(html.as_deref().map(str::trim), Some("<p>超文本 &amp; 图片</p>"));
+        assert!(root.attachments().is_empty());
+    }
+
+    #[test]
+    fn html_only_body_is_converted_to_text() {
+        let root = parse("Content-Type: text/html\n\n<html><head><title>t</title></head><body>a<br>b&nbsp;&#20320;</body></html>\n");
+        let (text, html) = root.bodies();
+        assert_eq!(text, "a\nb 你");
+        assert!(html.is_some());
+    }
+
+    #[test]
+    fn related_images_are_inlined_not_listed() {
+        let root = parse(
+            "Content-Type: multipart/mixed; boundary=m\n\n\
+             --m\nContent-Type: multipart/related; boundary=r\n\n\
+             --r\nContent-Type: text/html\n\n<img src=\"cid:logo@x\">\n\
+             --r\nContent-Type: image/png\nContent-ID: <logo@x>\nContent-Transfer-Encoding: base64\n\niVBORw==\n\
+             --r--\n\
+             --m\nContent-Type: application/pdf; name=\"=?UTF-8?B?5oql5ZGKLnBkZg==?=\"\nContent-Disposition: attachment\nContent-Transfer-Encoding: base64\n\nJVBERg==\n\
+             --m--\n",
+        );
+        let html = root.html_body().unwrap();
+        assert!(html.contains("src=\"data:image/png;base64,iVBORw==\""));
+        let attachments = root.attachments();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename.as_deref(), Some("报告.pdf"));
+        assert_eq!(attachments[0].body, b"%PDF");
+    }
+
+    #[test]
+    fn signed_message_uses_first_part() {
+        let root = parse(
+            "Content-Type: multipart/signed; boundary=s; protocol=\"application/pgp-signature\"\n\n\
+             --s\nContent-Type: text/plain\n\nsigned text\n\
+             --s\nContent-Type: application/pgp-signature\n\n-----BEGIN PGP SIGNATURE-----\n\
+             --s--\n",
+        );
+        assert_eq!(root.text_body().as_deref().map(str::trim), Some("signed text"));
+        assert!(root.attachments().is_empty());
+    }
+
+    #[test]
+    fn wrong_boundary_is_recovered() {
+        let root = parse(
+            "Content-Type: multipart/mixed; boundary=missing\n\n\
+             --real\nContent-Type: text/plain\n\nbody\n\
+             --real--\n",
+        );
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.text_body().as_deref().map(str::trim), Some("body"));
+    }
+
+    #[test]
+    fn attached_message_is_parsed_as_child() {
+        let root = parse(
+            "Content-Type: multipart/mixed; boundary=m\n\n\
+             --m\nContent-Type: text/plain\n\nouter\n\
+             --m\nContent-Type: message/rfc822\n\nSubject: inner\nContent-Type: text/plain\n\ninner body\n\
+             --m--\n",
+        );
+        assert_eq!(root.text_body().as_deref().map(str::trim), Some("outer"));
+        let attachments = root.attachments();
+        assert_eq!(attachments.len(), 1);
+        assert!(attachments[0].is_message());
+        assert_eq!(attachments[0].children[0].header("Subject"), Some("inner"));
+    }
+
+    #[test]
+    fn text_falls_back_to_gb18030() {
+        let (gbk, _, _) = GB18030.encode("中文");
+        assert_eq!(decode_text(&gbk, None), "中文");
+        assert_eq!(decode_text(&gbk, Some("us-ascii")), "中文");
+        assert_eq!(decode_text("中文".as_bytes(), Some("utf-8")), "中文");
+    }
+
+    #[test]
+    fn declared_big5_and_unencoded_gbk_headers() {
+        let (big5, _, _) = encoding_rs::BIG5.encode("繁體");
+        let mut raw = b"Subject: ".to_vec();
+        raw.extend_from_slice(&GB18030.encode("简体主题").0);
+        raw.extend_from_slice(b"\r\nContent-Type: text/plain; charset=big5\r\n\r\n");
+        raw.extend_from_slice(&big5);
+        let root = MimePart::parse(&raw).unwrap();
+        assert_eq!(root.header("Subject"), Some("简体主题"));
+        assert_eq!(root.text(), "繁體");
+    }
+}
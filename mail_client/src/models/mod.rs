@@ -1,2 +1,12 @@
 pub mod email;
-pub use self::email::*;
\ No newline at end of file
+pub mod contact;
+pub mod mime;
+pub mod filter;
+pub mod label;
+pub mod search;
+pub use self::email::*;
+pub use self::contact::*;
+pub use self::mime::*;
+pub use self::filter::*;
+pub use self::label::*;
+pub use self::search::*;
\ No newline at end of file
@@ -0,0 +1,276 @@
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+use crate::models::email::Mailbox;
+#[cfg(test)]
+use chrono::Local;
+#[cfg(test)]
+use crate::models::email::{Address, Email};
+use crate::models::label::Label;
+
+// 保存的搜索，在侧边栏中作为虚拟文件夹显示
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+}
+
+// 搜索条件中的一项
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Term {
+    Text(String), // 主题、发件人、收件人或正文包含
+    From(String),
+    To(String), // 收件人或抄送
+    Subject(String),
+    Unread(bool),
+    Flagged,
+    HasAttachment,
+    After(NaiveDate), // 该日期及之后
+    Before(NaiveDate), // 该日期之前
+    Label(String),
+    Folder(String),
+    Not(Box<Term>),
+}
+
+// 解析后的搜索，如 from:boss is:unread has:attachment after:2026-01-01
+// 各项同时满足才算匹配，项前加 - 表示排除，不认识的 key:value 按普通文字搜索
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let terms = split_query(query)
+            .into_iter()
+            .filter_map(|(negated, word)| {
+                let term = parse_term(&word)?;
+                Some(if negated { Term::Not(Box::new(term)) } else { term })
+            })
+            .collect();
+        SearchQuery { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // 数据库按同样的条件查询时使用
+    pub(crate) fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    // label: 后面可以写标签名称，换成对应的关键字
+    pub fn with_labels(mut self, labels: &[Label]) -> Self {
+        fn resolve(term: &mut Term, labels: &[Label]) {
+            match term {
+                Term::Label(value) => {
+                    if let Some(label) = labels.iter().find(|l| l.name.eq_ignore_ascii_case(value)) {
+                        *value = label.keyword.clone();
+                    }
+                }
+                Term::Not(inner) => resolve(inner, labels),
+                _ => {}
+            }
+        }
+        for term in &mut self.terms {
+            resolve(term, labels);
+        }
+        self
+    }
+
+    // 用 in: 指定的文件夹，没有指定时为 None
+    pub fn folders(&self) -> Option<Vec<String>> {
+        let folders: Vec<String> = self
+            .terms
+            .iter()
+            .filter_map(|term| match term {
+                Term::Folder(folder) => Some(folder.clone()),
+                _ => None,
+            })
+            .collect();
+        (!folders.is_empty()).then_some(folders)
+    }
+
+    #[cfg(test)]
+    pub fn matches(&self, email: &Email) -> bool {
+        self.terms.iter().all(|term| term_matches(term, email))
+    }
+}
+
+#[cfg(test)]
+fn term_matches(term: &Term, email: &Email) -> bool {
+    match term {
+        Term::Text(text) => {
+            contains(&email.subject, text)
+                || mailbox_matches(&email.from, text)
+                || email.to.iter().chain(&email.cc).any(|a| address_matches(a, text))
+                || contains(&email.body, text)
+        }
+        Term::From(text) => mailbox_matches(&email.from, text),
+        Term::To(text) => email.to.iter().chain(&email.cc).any(|a| address_matches(a, text)),
+        Term::Subject(text) => contains(&email.subject, text),
+        Term::Unread(unread) => email.seen != *unread,
+        Term::Flagged => email.flagged,
+        Term::HasAttachment => !email.attachments.is_empty(),
+        Term::After(date) => local_date(email).is_some_and(|d| d >= *date),
+        Term::Before(date) => local_date(email).is_some_and(|d| d < *date),
+        Term::Label(keyword) => email.labels.iter().any(|l| l.eq_ignore_ascii_case(keyword)),
+        // 邮件没有记录文件夹时（如从文件打开）不做限制
+        Term::Folder(folder) => email.folder.is_empty() || email.folder.eq_ignore_ascii_case(folder),
+        Term::Not(inner) => !term_matches(inner, email),
+    }
+}
+
+pub(crate) fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(needle)
+}
+
+pub(crate) fn mailbox_matches(mailbox: &Mailbox, text: &str) -> bool {
+    mailbox.name.as_deref().is_some_and(|name| contains(name, text))
+        || contains(&mailbox.address, text)
+        || contains(&mailbox.display_address(), text)
+}
+
+// 地址组按组名或任一成员匹配
+#[cfg(test)]
+fn address_matches(address: &Address, text: &str) -> bool {
+    matches!(address, Address::Group { name, .. } if contains(name, text))
+        || address.mailboxes().iter().any(|m| mailbox_matches(m, text))
+}
+
+#[cfg(test)]
+fn local_date(email: &Email) -> Option<NaiveDate> {
+    email.timestamp().map(|t| t.with_timezone(&Local).date_naive())
+}
+
+fn parse_term(word: &str) -> Option<Term> {
+    let lower = word.to_lowercase();
+    let Some((key, value)) = lower.split_once(':').filter(|(_, value)| !value.is_empty()) else {
+        return (!lower.is_empty()).then(|| Term::Text(lower.clone()));
+    };
+    let term = match key {
+        "from" => Term::From(value.to_string()),
+        "to" | "cc" => Term::To(value.to_string()),
+        "subject" => Term::Subject(value.to_string()),
+        "is" if value == "unread" => Term::Unread(true),
+        "is" if value == "read" => Term::Unread(false),
+        "is" if value == "starred" || value == "flagged" => Term::Flagged,
+        "has" if value == "attachment" => Term::HasAttachment,
+        // 无法识别的日期按普通文字搜索，而不是忽略这一项
+        "after" | "since" => parse_date(value).map_or_else(|| Term::Text(lower.clone()), Term::After),
+        "before" => parse_date(value).map_or_else(|| Term::Text(lower.clone()), Term::Before),
+        "label" | "tag" => Term::Label(value.to_string()),
+        // 文件夹名称保留原来的大小写
+        "in" | "folder" => Term::Folder(word[key.len() + 1..].to_string()),
+        _ => Term::Text(lower.clone()),
+    };
+    Some(term)
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y/%m/%d"))
+        .ok()
+}
+
+// 按空格分开各项，引号中的空格不分开；返回 (是否以 - 排除, 去掉引号的内容)
+fn split_query(query: &str) -> Vec<(bool, String)> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+        .into_iter()
+        .map(|word| match word.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+            _ => (false, word),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::imap_client::parse_email;
+
+    fn email() -> Email {
+        let raw = "From: =?UTF-8?B?5byg5LiJ?= <Boss@Example.com>\r\n\
+            To: Team: a@example.com, b@example.com;\r\n\
+            Cc: Carol <carol@example.com>\r\n\
+            Subject: Quarterly Report\r\n\
+            Date: Mon, 15 Jan 2024 12:00:00 +0000\r\n\
+            \r\n\
+            Numbers are attached.";
+        let mut email = parse_email("1".into(), raw.as_bytes()).unwrap();
+        email.folder = "INBOX".into();
+        email.labels = vec!["$work".into()];
+        email
+    }
+
+    fn matches(query: &str) -> bool {
+        SearchQuery::parse(query).matches(&email())
+    }
+
+    #[test]
+    fn parses_keys_quotes_and_negation() {
+        let query = SearchQuery::parse("from:Boss \"quarterly report\" -is:read has:attachment in:Archive/2024 after:2024/01/02 before:bad");
+        assert_eq!(
+            query.terms,
+            vec![
+                Term::From("boss".into()),
+                Term::Text("quarterly report".into()),
+                Term::Not(Box::new(Term::Unread(false))),
+                Term::HasAttachment,
+                Term::Folder("Archive/2024".into()),
+                Term::After(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()),
+                Term::Text("before:bad".into()),
+            ]
+        );
+        assert_eq!(query.folders(), Some(vec!["Archive/2024".to_string()]));
+        assert_eq!(SearchQuery::parse("  - ").terms, vec![Term::Text("-".into())]);
+        assert!(SearchQuery::parse("   ").is_empty());
+    }
+
+    #[test]
+    fn text_matches_headers_and_body() {
+        assert!(matches("quarterly"));
+        assert!(matches("张三"));
+        assert!(matches("attached"));
+        assert!(matches("team"));
+        assert!(matches("to:carol"));
+        assert!(matches("to:b@example.com"));
+        assert!(matches("from:boss@example.com subject:report"));
+        assert!(!matches("from:carol"));
+        assert!(!matches("quarterly -report"));
+    }
+
+    #[test]
+    fn flags_dates_labels_and_folders() {
+        assert!(matches("is:unread"));
+        assert!(!matches("is:read"));
+        assert!(!matches("is:starred"));
+        assert!(!matches("has:attachment"));
+        assert!(matches("after:2024-01-15 before:2024-01-17"));
+        assert!(!matches("after:2024-01-17"));
+        assert!(matches("in:inbox"));
+        assert!(!matches("in:Archive"));
+        assert!(!matches("label:工作"));
+
+        let labels = vec![Label { name: "工作".into(), keyword: "$work".into(), color: String::new() }];
+        assert!(SearchQuery::parse("label:工作").with_labels(&labels).matches(&email()));
+        assert!(!SearchQuery::parse("-label:工作").with_labels(&labels).matches(&email()));
+    }
+}
@@ -0,0 +1,460 @@
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs as StdToSocketAddrs};
+use std::time::Duration;
+use async_std::io;
+use async_std::net::{TcpStream, ToSocketAddrs};
+use async_imap::{self, Client};
+use async_imap::imap_proto::{Response, Status};
+use async_native_tls::TlsConnector;
+use futures_util::future::join;
+use futures_util::io::{AsyncRead, AsyncWrite};
+use lettre::transport::smtp::{
+    authentication::{Credentials, Mechanism},
+    client::{SmtpConnection, TlsParameters},
+    extension::ClientId,
+};
+use crate::models::EmailAccount;
+use crate::service::smtp_client::uses_implicit_tls;
+
+// 每一步网络操作的超时时间
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+// 单台服务器校验失败的具体原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckError {
+    Dns(String),            // 域名无法解析
+    Tcp(String),            // TCP 连接失败（拒绝连接、不可达等）
+    TlsCertificate(String), // 证书校验失败
+    Tls(String),            // 其他 TLS 错误
+    AuthRejected(String),   // 服务器拒绝了用户名或密码
+    AuthMethod(String),     // 服务器没有提供客户端支持的认证方式
+    Transient(String),      // 服务器暂时不可用（4xx）或连接中断，可以稍后重试
+    WrongPort(String),      // 端口上运行的不是预期的协议
+    Timeout(String),        // 某一步骤超时
+    Other(String),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::Dns(msg) => write!(f, "无法解析服务器地址: {}", msg),
+            CheckError::Tcp(msg) => write!(f, "无法连接到服务器: {}", msg),
+            CheckError::TlsCertificate(msg) => write!(f, "服务器证书校验失败: {}", msg),
+            CheckError::Tls(msg) => write!(f, "TLS 加密连接失败: {}", msg),
+            CheckError::AuthRejected(msg) => write!(f, "用户名或密码被拒绝: {}", msg),
+            CheckError::AuthMethod(msg) => write!(f, "没有可用的认证方式: {}", msg),
+            CheckError::Transient(msg) => write!(f, "服务器暂时不可用，请稍后重试: {}", msg),
+            CheckError::WrongPort(msg) => write!(f, "端口可能填写错误: {}", msg),
+            CheckError::Timeout(step) => write!(f, "{}超时", step),
+            CheckError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+impl CheckError {
+    // 稍后重试可能成功的错误，不代表账户设置有误
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, CheckError::Transient(_) | CheckError::Timeout(_))
+    }
+}
+
+// IMAP 和 SMTP 两台服务器的校验结果
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckReport {
+    pub imap: Result<(), CheckError>,
+    pub smtp: Result<(), CheckError>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.imap.is_ok() && self.smtp.is_ok()
+    }
+
+    // 所有失败都是暂时性的，账户设置本身可能没有问题
+    pub fn is_retryable(&self) -> bool {
+        !self.is_ok() && [&self.imap, &self.smtp].iter().all(|r| r.as_ref().err().is_none_or(CheckError::is_retryable))
+    }
+
+    // 生成逐台服务器的失败说明，每台一行
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        if let Err(e) = &self.imap {
+            lines.push(format!("IMAP: {}", e));
+        }
+        if let Err(e) = &self.smtp {
+            lines.push(format!("SMTP: {}", e));
+        }
+        lines.join("\n")
+    }
+}
+
+// 同时校验 IMAP 登录和 SMTP EHLO/AUTH，两者都成功才算账户可用
+pub async fn verify_account(account: &EmailAccount) -> CheckReport {
+    let smtp_account = account.clone();
+    let smtp = async_std::task::spawn_blocking(move || check_smtp(&smtp_account));
+    let (imap, smtp) = join(check_imap(account), smtp).await;
+
+    CheckReport { imap, smtp }
+}
+
+// 校验 IMAP：解析域名 -> TCP -> TLS -> 问候 -> LOGIN
+pub async fn check_imap(account: &EmailAccount) -> Result<(), CheckError> {
+    let host = account.imap_server.trim();
+    let port = account.imap_port;
+    println!("校验 IMAP 服务器: {}:{}", host, port);
+
+    let addrs: Vec<SocketAddr> = match io::timeout(CHECK_TIMEOUT, ToSocketAddrs::to_socket_addrs(&(host, port))).await {
+        Ok(addrs) => addrs.collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Err(CheckError::Timeout("域名解析".to_string())),
+        Err(e) => return Err(CheckError::Dns(format!("{} ({})", host, e))),
+    };
+    if addrs.is_empty() {
+        return Err(CheckError::Dns(format!("{} 没有可用的地址", host)));
+    }
+
+    let tcp_stream = io::timeout(CHECK_TIMEOUT, TcpStream::connect(&addrs[..]))
+        .await
+        .map_err(|e| classify_io_error(e, "TCP 连接"))?;
+
+    if account.use_tls {
+        let tls = TlsConnector::new();
+        let tls_stream = io::timeout(CHECK_TIMEOUT, async {
+            tls.connect(host, tcp_stream)
+                .await
+                .map_err(std::io::Error::other)
+        })
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::TimedOut => CheckError::Timeout("TLS 握手".to_string()),
+            _ => classify_tls_message(&e.to_string(), port),
+        })?;
+        imap_login(tls_stream, &account.address, &account.password).await
+    } else {
+        imap_login(tcp_stream, &account.address, &account.password).await
+    }
+}
+
+async fn imap_login<T>(stream: T, username: &str, password: &str) -> Result<(), CheckError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + fmt::Debug + Send,
+{
+    let mut client = Client::new(stream);
+
+    // 先读取服务器问候，用来判断端口上是否真的是 IMAP 服务
+    match io::timeout(CHECK_TIMEOUT, async { Ok(client.read_response().await) }).await {
+        Ok(Some(Ok(greeting))) => match greeting.parsed() {
+            Response::Data { status: Status::Ok, .. } | Response::Data { status: Status::PreAuth, .. } => {}
+            Response::Data { status: Status::Bye, information, .. } => {
+                return Err(CheckError::Other(format!(
+                    "服务器拒绝连接: {}",
+                    information.as_deref().unwrap_or("")
+                )));
+            }
+            _ => return Err(CheckError::WrongPort("服务器返回的不是 IMAP 问候".to_string())),
+        },
+        Ok(Some(Err(_))) => {
+            return Err(CheckError::WrongPort("无法识别服务器问候，该端口可能不是 IMAP 服务".to_string()));
+        }
+        Ok(None) => return Err(CheckError::WrongPort("服务器在问候前关闭了连接".to_string())),
+        Err(_) => {
+            return Err(CheckError::WrongPort(
+                "等待 IMAP 问候超时，请确认端口和 TLS 设置是否匹配".to_string(),
+            ));
+        }
+    }
+
+    let mut session = match io::timeout(CHECK_TIMEOUT, async { Ok(client.login(username, password).await) }).await {
+        Ok(Ok(session)) => session,
+        Ok(Err((async_imap::error::Error::No(msg), _))) | Ok(Err((async_imap::error::Error::Bad(msg), _))) => {
+            return Err(CheckError::AuthRejected(msg));
+        }
+        Ok(Err((e, _))) => return Err(CheckError::Other(format!("登录失败: {}", e))),
+        Err(_) => return Err(CheckError::Timeout("IMAP 登录".to_string())),
+    };
+
+    let _ = session.logout().await;
+    Ok(())
+}
+
+// 校验 SMTP：解析域名 -> TCP -> TLS/STARTTLS -> EHLO -> AUTH
+// lettre 的同步连接会阻塞，调用方需放到 spawn_blocking 中执行
+pub fn check_smtp(account: &EmailAccount) -> Result<(), CheckError> {
+    let host = account.smtp_server.trim();
+    let port = account.smtp_port;
+    println!("校验 SMTP 服务器: {}:{}", host, port);
+
+    let addrs: Vec<SocketAddr> = StdToSocketAddrs::to_socket_addrs(&(host, port))
+        .map_err(|e| CheckError::Dns(format!("{} ({})", host, e)))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(CheckError::Dns(format!("{} 没有可用的地址", host)));
+    }
+    smtp_login(account, host, &addrs)
+}
+
+// 依次尝试解析到的每个地址，连接成功后在同一个连接上完成 EHLO、STARTTLS 和认证
+fn smtp_login(account: &EmailAccount, host: &str, addrs: &[SocketAddr]) -> Result<(), CheckError> {
+    let port = account.smtp_port;
+    let tls_parameters = if account.smtp_tls() {
+        Some(
+            TlsParameters::new(host.to_string())
+                .map_err(|e| CheckError::Tls(e.to_string()))?,
+        )
+    } else {
+        None
+    };
+    let implicit_tls = account.smtp_tls() && uses_implicit_tls(port);
+    let hello_name = ClientId::default();
+
+    let mut conn = SmtpConnection::connect(
+        addrs,
+        Some(CHECK_TIMEOUT),
+        &hello_name,
+        if implicit_tls { tls_parameters.as_ref() } else { None },
+        None,
+    )
+    .map_err(|e| classify_smtp_error(&e, port, implicit_tls))?;
+
+    if let (Some(params), false) = (tls_parameters.as_ref(), implicit_tls) {
+        if !conn.can_starttls() {
+            conn.abort();
+            return Err(CheckError::Tls(
+                "服务器不支持 STARTTLS，请确认端口（SSL 通常为 465）".to_string(),
+            ));
+        }
+        conn.starttls(params, &hello_name)
+            .map_err(|e| classify_smtp_error(&e, port, true))?;
+    }
+
+    let creds = Credentials::new(account.address.clone(), account.password.clone());
+    let result = conn.auth(&[Mechanism::Plain, Mechanism::Login], &creds);
+    match result {
+        Ok(_) => {
+            let _ = conn.quit();
+            Ok(())
+        }
+        Err(e) => {
+            conn.abort();
+            Err(classify_auth_error(&e, account.smtp_tls()))
+        }
+    }
+}
+
+// AUTH 失败：只有 5xx 才说明用户名或密码错误
+fn classify_auth_error(e: &lettre::transport::smtp::Error, tls: bool) -> CheckError {
+    let message = full_error_message(e);
+    if e.is_permanent() {
+        CheckError::AuthRejected(message)
+    } else if e.is_client() {
+        // 服务器的 EHLO 中没有 PLAIN 或 LOGIN，很多服务器只在加密连接上提供
+        CheckError::AuthMethod(if tls {
+            format!("服务器不支持 PLAIN 或 LOGIN 认证 ({})", message)
+        } else {
+            format!("未加密的连接上服务器不提供 PLAIN 或 LOGIN 认证，请启用 SSL/TLS ({})", message)
+        })
+    } else if e.is_timeout() {
+        CheckError::Timeout("SMTP 认证".to_string())
+    } else {
+        // 4xx 暂时性错误，或认证过程中连接中断
+        CheckError::Transient(message)
+    }
+}
+
+fn classify_io_error(e: std::io::Error, step: &str) -> CheckError {
+    match e.kind() {
+        std::io::ErrorKind::TimedOut => CheckError::Timeout(step.to_string()),
+        _ => CheckError::Tcp(e.to_string()),
+    }
+}
+
+// TLS 握手失败时，证书问题和“对端根本不是 TLS”要分开提示
+fn classify_tls_message(message: &str, port: u16) -> CheckError {
+    let lower = message.to_lowercase();
+    if lower.contains("certificate") || lower.contains("verify") || lower.contains("self signed") {
+        CheckError::TlsCertificate(message.to_string())
+    } else if lower.contains("wrong version number")
+        || lower.contains("unexpected eof")
+        || lower.contains("record")
+        || lower.contains("handshake")
+    {
+        CheckError::WrongPort(format!("端口 {} 不是 SSL/TLS 端口 ({})", port, message))
+    } else {
+        CheckError::Tls(message.to_string())
+    }
+}
+
+fn classify_smtp_error(e: &lettre::transport::smtp::Error, port: u16, tls: bool) -> CheckError {
+    let message = full_error_message(e);
+    if e.is_timeout() {
+        CheckError::Timeout(if tls { "SMTP TLS 握手" } else { "等待 SMTP 问候" }.to_string())
+    } else if e.is_tls() {
+        classify_tls_message(&message, port)
+    } else if e.is_transient() {
+        // 如 421 服务暂时不可用
+        CheckError::Transient(message)
+    } else if e.is_response() || e.is_client() {
+        CheckError::WrongPort(format!("服务器返回的不是 SMTP 响应 ({})", message))
+    } else if std::error::Error::source(e).is_some_and(|source| source.is::<std::io::Error>()) {
+        // 所有地址都连接失败（拒绝连接、不可达等）或连接中断
+        CheckError::Tcp(message)
+    } else {
+        CheckError::Other(message)
+    }
+}
+
+// 把错误链拼成一行，方便识别证书等底层原因
+fn full_error_message(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(inner) = source {
+        message.push_str(": ");
+        message.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // 本地的简易服务器：先发送问候，之后按 respond 回复每一行命令，返回 None 时关闭连接
+    // 结束时返回收到的连接数，用于检查校验过程只连接了一次
+    fn fake_server<F>(greeting: &'static str, respond: F) -> (SocketAddr, thread::JoinHandle<usize>)
+    where
+        F: Fn(&str) -> Option<String> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer.write_all(greeting.as_bytes()).unwrap();
+            for line in BufReader::new(stream).lines() {
+                let Some(reply) = line.ok().and_then(|line| respond(&line)) else {
+                    break;
+                };
+                if writer.write_all(reply.as_bytes()).is_err() {
+                    break;
+                }
+            }
+            listener.set_nonblocking(true).unwrap();
+            1 + listener.incoming().take_while(Result::is_ok).count()
+        });
+        (addr, handle)
+    }
+
+    fn fake_smtp(ehlo: &'static str, auth: &'static str) -> (SocketAddr, thread::JoinHandle<usize>) {
+        fake_server("220 fake ESMTP\r\n", move |line| {
+            let command = line.split(' ').next().unwrap_or("").to_ascii_uppercase();
+            Some(match command.as_str() {
+                "EHLO" => ehlo.to_string(),
+                "AUTH" => auth.to_string(),
+                "QUIT" => "221 bye\r\n".to_string(),
+                _ => "500 unknown\r\n".to_string(),
+            })
+        })
+    }
+
+    fn account(port: u16) -> EmailAccount {
+        EmailAccount {
+            address: "me@example.com".into(),
+            password: "secret".into(),
+            smtp_server: "127.0.0.1".into(),
+            smtp_port: port,
+            imap_server: "127.0.0.1".into(),
+            imap_port: port,
+            use_tls: false,
+            smtp_use_tls: Some(false),
+        }
+    }
+
+    // 已关闭的端口，连接时被拒绝
+    fn closed_addr() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    const EHLO_AUTH: &str = "250-fake\r\n250 AUTH PLAIN LOGIN\r\n";
+
+    // 第一个地址连不上时尝试下一个，EHLO 和认证在同一个连接上完成
+    #[test]
+    fn smtp_tries_every_address_on_one_connection() {
+        let (addr, server) = fake_smtp(EHLO_AUTH, "235 2.7.0 accepted\r\n");
+        assert_eq!(smtp_login(&account(addr.port()), "127.0.0.1", &[closed_addr(), addr]), Ok(()));
+        assert_eq!(server.join().unwrap(), 1);
+
+        let refused = smtp_login(&account(addr.port()), "127.0.0.1", &[closed_addr()]);
+        assert!(matches!(refused, Err(CheckError::Tcp(_))), "{:?}", refused);
+    }
+
+    // 只有 5xx 表示用户名或密码错误，4xx 可以重试，没有 AUTH 时提示认证方式
+    #[test]
+    fn smtp_auth_errors() {
+        for (ehlo, auth, expected) in [
+            (EHLO_AUTH, "535 5.7.8 bad credentials\r\n", CheckError::AuthRejected(String::new())),
+            (EHLO_AUTH, "454 4.7.0 try again later\r\n", CheckError::Transient(String::new())),
+            ("250 fake\r\n", "235 unused\r\n", CheckError::AuthMethod(String::new())),
+        ] {
+            let (addr, server) = fake_smtp(ehlo, auth);
+            let error = smtp_login(&account(addr.port()), "127.0.0.1", &[addr]).unwrap_err();
+            assert_eq!(std::mem::discriminant(&error), std::mem::discriminant(&expected), "{:?}", error);
+            server.join().unwrap();
+        }
+        let method = CheckError::AuthMethod(String::new());
+        assert!(!method.is_retryable());
+        assert!(CheckError::Transient(String::new()).is_retryable());
+    }
+
+    #[test]
+    fn smtp_dns_failure() {
+        let mut account = account(587);
+        account.smtp_server = "no-such-host.invalid".into();
+        assert!(matches!(check_smtp(&account), Err(CheckError::Dns(_))));
+    }
+
+    // IMAP 的 NO 表示登录被拒绝，问候不是 IMAP 时提示端口错误
+    #[test]
+    fn imap_login_errors() {
+        let (addr, server) = fake_server("* OK IMAP ready\r\n", |line| {
+            let tag = line.split(' ').next().unwrap_or("*");
+            Some(format!("{} NO [AUTHENTICATIONFAILED] Invalid credentials\r\n", tag))
+        });
+        let result = async_std::task::block_on(async {
+            imap_login(TcpStream::connect(addr).await.unwrap(), "me", "wrong").await
+        });
+        assert!(matches!(result, Err(CheckError::AuthRejected(ref msg)) if msg.contains("Invalid credentials")), "{:?}", result);
+        server.join().unwrap();
+
+        let (addr, server) = fake_server("220 smtp.example ESMTP\r\n", |_| None);
+        let result = async_std::task::block_on(async {
+            imap_login(TcpStream::connect(addr).await.unwrap(), "me", "secret").await
+        });
+        assert!(matches!(result, Err(CheckError::WrongPort(_))), "{:?}", result);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tls_and_timeout_classification() {
+        assert!(matches!(
+            classify_tls_message("error:0A000086:SSL routines::certificate verify failed", 993),
+            CheckError::TlsCertificate(_)
+        ));
+        assert!(matches!(classify_tls_message("self signed certificate in chain", 993), CheckError::TlsCertificate(_)));
+        assert_eq!(
+            classify_tls_message("SSL routines: wrong version number", 143),
+            CheckError::WrongPort("端口 143 不是 SSL/TLS 端口 (SSL routines: wrong version number)".to_string())
+        );
+        assert!(matches!(classify_tls_message("protocol not supported", 993), CheckError::Tls(_)));
+
+        let timeout = classify_io_error(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"), "TCP 连接");
+        assert_eq!(timeout, CheckError::Timeout("TCP 连接".to_string()));
+        assert_eq!(timeout.to_string(), "TCP 连接超时");
+        let refused = classify_io_error(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused"), "TCP 连接");
+        assert!(matches!(refused, CheckError::Tcp(_)));
+    }
+}
@@ -0,0 +1,884 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use crate::models::{Address, Contact, ContactGroup, Email, Mailbox};
+use crate::service::dav::{DavCollection, DavService};
+use crate::service::mail_db::{run_migrations, DbResult};
+use crate::service::vcard::{self, VCardEntry, VCardVersion};
+
+// 通讯录数据库的迁移，规则与邮件数据库相同：只能在末尾追加
+const MIGRATIONS: &[&str] = &[
+    // 1: 联系人、分组和地址使用次数
+    "CREATE TABLE contacts (
+        id          INTEGER PRIMARY KEY,
+        uid         TEXT NOT NULL UNIQUE,
+        name        TEXT NOT NULL,
+        organization TEXT NOT NULL DEFAULT '',
+        note        TEXT NOT NULL DEFAULT '',
+        harvested   INTEGER NOT NULL DEFAULT 0,
+        updated     TEXT NOT NULL
+    );
+    CREATE TABLE contact_emails (
+        contact_id  INTEGER NOT NULL REFERENCES contacts(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        email       TEXT NOT NULL COLLATE NOCASE UNIQUE
+    );
+    CREATE INDEX idx_contact_emails_contact ON contact_emails(contact_id);
+    CREATE TABLE contact_phones (
+        contact_id  INTEGER NOT NULL REFERENCES contacts(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        phone       TEXT NOT NULL
+    );
+    CREATE TABLE usage (
+        email       TEXT PRIMARY KEY COLLATE NOCASE,
+        sent        INTEGER NOT NULL DEFAULT 0,
+        received    INTEGER NOT NULL DEFAULT 0,
+        last_used   INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE contact_groups (
+        id          INTEGER PRIMARY KEY,
+        uid         TEXT NOT NULL UNIQUE,
+        name        TEXT NOT NULL COLLATE NOCASE UNIQUE
+    );
+    CREATE TABLE group_members (
+        group_id    INTEGER NOT NULL REFERENCES contact_groups(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        name        TEXT,
+        email       TEXT NOT NULL
+    );",
+    // 2: CardDAV/CalDAV 同步状态，dirty 表示本地修改过还没有上传
+    "ALTER TABLE contacts ADD COLUMN dirty INTEGER NOT NULL DEFAULT 1;
+    CREATE TABLE dav_accounts (
+        account     TEXT PRIMARY KEY,
+        server      TEXT NOT NULL
+    );
+    CREATE TABLE dav_collections (
+        url         TEXT PRIMARY KEY,
+        account     TEXT NOT NULL,
+        service     TEXT NOT NULL,
+        name        TEXT NOT NULL,
+        components  TEXT NOT NULL DEFAULT '',
+        sync_token  TEXT,
+        is_default  INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE dav_resources (
+        contact_id  INTEGER NOT NULL UNIQUE REFERENCES contacts(id) ON DELETE CASCADE,
+        collection  TEXT NOT NULL REFERENCES dav_collections(url) ON DELETE CASCADE,
+        href        TEXT NOT NULL UNIQUE,
+        etag        TEXT NOT NULL DEFAULT ''
+    );
+    CREATE TABLE dav_tombstones (
+        collection  TEXT NOT NULL REFERENCES dav_collections(url) ON DELETE CASCADE,
+        href        TEXT PRIMARY KEY,
+        etag        TEXT NOT NULL DEFAULT ''
+    );
+    -- 删除已同步的联系人时记录下来，下次同步时从服务器删除
+    CREATE TRIGGER contacts_tombstone BEFORE DELETE ON contacts BEGIN
+        INSERT OR REPLACE INTO dav_tombstones (collection, href, etag)
+        SELECT collection, href, etag FROM dav_resources WHERE contact_id = OLD.id;
+    END;",
+];
+
+// 自动补全最多显示的候选数
+const SUGGESTION_LIMIT: usize = 8;
+
+// 生成 UID 时区分同一时刻创建的多个联系人
+static UID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// 收件人输入框的自动补全候选
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub text: String,   // 选中后填入输入框的内容
+    pub label: String,  // 显示的名称
+    pub detail: String, // 地址，分组时为成员数
+    pub is_group: bool,
+}
+
+// 已上传的联系人在服务器上的 (地址, ETag)
+pub type DavResourceRef = (String, String);
+
+// 保存在用户配置目录中的通讯录，与邮件数据库分开
+#[derive(Clone)]
+pub struct AddressBook {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl AddressBook {
+    pub fn open(path: &Path) -> DbResult<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        run_migrations(&mut conn, MIGRATIONS)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 所有联系人，按名称排序
+    pub fn contacts(&self) -> DbResult<Vec<Contact>> {
+        let conn = self.lock();
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM contacts ORDER BY name COLLATE NOCASE, id")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        ids.into_iter().map(|id| Self::load_contact(&conn, id)).collect()
+    }
+
+    // 视为联系人的地址（小写）：手动添加或编辑过的联系人，以及给对方发过邮件的地址
+    // 只收到过邮件的自动收集地址不算
+    pub fn known_addresses(&self) -> DbResult<HashSet<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT lower(e.email) FROM contact_emails e
+             JOIN contacts c ON c.id = e.contact_id
+             WHERE c.harvested = 0
+             UNION
+             SELECT lower(email) FROM usage WHERE sent > 0",
+        )?;
+        let addresses = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(addresses)
+    }
+
+    #[cfg(test)]
+    pub fn contact(&self, uid: &str) -> DbResult<Option<Contact>> {
+        let conn = self.lock();
+        match Self::contact_id(&conn, uid)? {
+            Some(id) => Ok(Some(Self::load_contact(&conn, id)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn contact_id(conn: &Connection, uid: &str) -> rusqlite::Result<Option<i64>> {
+        conn.query_row("SELECT id FROM contacts WHERE uid = ?1", params![uid], |row| row.get(0))
+            .optional()
+    }
+
+    fn load_contact(conn: &Connection, id: i64) -> DbResult<Contact> {
+        let mut contact = conn.query_row(
+            "SELECT uid, name, organization, note, harvested FROM contacts WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(Contact {
+                    uid: row.get(0)?,
+                    name: row.get(1)?,
+                    organization: row.get(2)?,
+                    note: row.get(3)?,
+                    harvested: row.get(4)?,
+                    ..Default::default()
+                })
+            },
+        )?;
+        contact.emails = conn
+            .prepare_cached("SELECT email FROM contact_emails WHERE contact_id = ?1 ORDER BY position")?
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        contact.phones = conn
+            .prepare_cached("SELECT phone FROM contact_phones WHERE contact_id = ?1 ORDER BY position")?
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(contact)
+    }
+
+    // 新建或更新联系人（按 UID 匹配），返回 UID
+    // 地址已属于其他联系人时从那个联系人中移除，自动收集的联系人没有地址后被删除
+    pub fn save_contact(&self, contact: &Contact) -> DbResult<String> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let uid = Self::save_contact_tx(&tx, contact)?;
+        tx.commit()?;
+        Ok(uid)
+    }
+
+    fn save_contact_tx(tx: &Transaction, contact: &Contact) -> DbResult<String> {
+        let uid = if contact.uid.is_empty() { new_uid() } else { contact.uid.clone() };
+        let now = Utc::now().to_rfc3339();
+        let id = match Self::contact_id(tx, &uid)? {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE contacts SET name = ?1, organization = ?2, note = ?3, harvested = ?4, updated = ?5, dirty = 1
+                     WHERE id = ?6",
+                    params![contact.name.trim(), contact.organization, contact.note, contact.harvested, now, id],
+                )?;
+                tx.execute("DELETE FROM contact_emails WHERE contact_id = ?1", params![id])?;
+                tx.execute("DELETE FROM contact_phones WHERE contact_id = ?1", params![id])?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO contacts (uid, name, organization, note, harvested, updated)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![uid, contact.name.trim(), contact.organization, contact.note, contact.harvested, now],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        let mut position = 0i64;
+        for email in &contact.emails {
+            let email = email.trim();
+            if email.is_empty() {
+                continue;
+            }
+            tx.execute(
+                "UPDATE contacts SET dirty = 1
+                 WHERE id IN (SELECT contact_id FROM contact_emails WHERE email = ?1) AND id != ?2",
+                params![email, id],
+            )?;
+            tx.execute("DELETE FROM contact_emails WHERE email = ?1", params![email])?;
+            tx.execute(
+                "INSERT INTO contact_emails (contact_id, position, email) VALUES (?1, ?2, ?3)",
+                params![id, position, email],
+            )?;
+            position += 1;
+        }
+        for (position, phone) in contact.phones.iter().filter(|p| !p.trim().is_empty()).enumerate() {
+            tx.execute(
+                "INSERT INTO contact_phones (contact_id, position, phone) VALUES (?1, ?2, ?3)",
+                params![id, position as i64, phone.trim()],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM contacts WHERE harvested = 1 AND id != ?1
+             AND NOT EXISTS (SELECT 1 FROM contact_emails e WHERE e.contact_id = contacts.id)",
+            params![id],
+        )?;
+        Ok(uid)
+    }
+
+    pub fn delete_contact(&self, uid: &str) -> DbResult<()> {
+        self.lock().execute("DELETE FROM contacts WHERE uid = ?1", params![uid])?;
+        Ok(())
+    }
+
+    // 所有分组，按名称排序
+    pub fn groups(&self) -> DbResult<Vec<ContactGroup>> {
+        let conn = self.lock();
+        let rows: Vec<(i64, String, String)> = conn
+            .prepare("SELECT id, uid, name FROM contact_groups ORDER BY name")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let mut groups = Vec::new();
+        for (id, uid, name) in rows {
+            let members = conn
+                .prepare_cached("SELECT name, email FROM group_members WHERE group_id = ?1 ORDER BY position")?
+                .query_map(params![id], |row| Ok(Mailbox { name: row.get(0)?, address: row.get(1)? }))?
+                .collect::<rusqlite::Result<_>>()?;
+            groups.push(ContactGroup { uid, name, members });
+        }
+        Ok(groups)
+    }
+
+    // 新建或更新分组（按 UID 匹配），返回 UID
+    pub fn save_group(&self, group: &ContactGroup) -> DbResult<String> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let uid = Self::save_group_tx(&tx, group)?;
+        tx.commit()?;
+        Ok(uid)
+    }
+
+    fn save_group_tx(tx: &Transaction, group: &ContactGroup) -> DbResult<String> {
+        let name = group.name.trim();
+        if name.is_empty() {
+            return Err("分组名称不能为空".into());
+        }
+        let uid = if group.uid.is_empty() { new_uid() } else { group.uid.clone() };
+        let existing: Option<i64> = tx
+            .query_row("SELECT id FROM contact_groups WHERE uid = ?1", params![uid], |row| row.get(0))
+            .optional()?;
+        let id = match existing {
+            Some(id) => {
+                tx.execute("UPDATE contact_groups SET name = ?1 WHERE id = ?2", params![name, id])?;
+                tx.execute("DELETE FROM group_members WHERE group_id = ?1", params![id])?;
+                id
+            }
+            None => {
+                tx.execute("INSERT INTO contact_groups (uid, name) VALUES (?1, ?2)", params![uid, name])
+                    .map_err(|_| format!("已有名为 {} 的分组", name))?;
+                tx.last_insert_rowid()
+            }
+        };
+        for (position, member) in group.members.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO group_members (group_id, position, name, email) VALUES (?1, ?2, ?3, ?4)",
+                params![id, position as i64, member.name, member.address],
+            )?;
+        }
+        Ok(uid)
+    }
+
+    pub fn delete_group(&self, uid: &str) -> DbResult<()> {
+        self.lock().execute("DELETE FROM contact_groups WHERE uid = ?1", params![uid])?;
+        Ok(())
+    }
+
+    // 记录收到的邮件的发件人
+    pub fn record_received(&self, emails: &[Email]) -> DbResult<()> {
+        let senders: Vec<Mailbox> = emails.iter().map(|e| e.from.clone()).collect();
+        self.harvest(&senders, false)
+    }
+
+    // 记录已发送邮件的所有收件人，发送次数在排序中权重更高
+    pub fn record_sent(&self, email: &Email) -> DbResult<()> {
+        let recipients: Vec<Mailbox> = email
+            .to
+            .iter()
+            .chain(&email.cc)
+            .chain(&email.bcc)
+            .flat_map(|a| a.mailboxes().to_vec())
+            .collect();
+        self.harvest(&recipients, true)
+    }
+
+    // 更新地址使用次数，不在通讯录中的地址作为自动收集的联系人加入
+    fn harvest(&self, mailboxes: &[Mailbox], sent: bool) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let now = Utc::now().timestamp();
+        for mailbox in mailboxes {
+            let email = mailbox.address.trim();
+            if !email.contains('@') {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO usage (email, sent, received, last_used) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(email) DO UPDATE SET
+                    sent = sent + excluded.sent,
+                    received = received + excluded.received,
+                    last_used = MAX(last_used, excluded.last_used)",
+                params![email, sent as i64, !sent as i64, now],
+            )?;
+
+            let name = mailbox.name.as_deref().map(str::trim).unwrap_or_default();
+            let owner: Option<(i64, bool, String)> = tx
+                .query_row(
+                    "SELECT c.id, c.harvested, c.name FROM contacts c
+                     JOIN contact_emails e ON e.contact_id = c.id WHERE e.email = ?1",
+                    params![email],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            match owner {
+                None => {
+                    let contact = Contact {
+                        name: name.to_string(),
+                        emails: vec![email.to_string()],
+                        harvested: true,
+                        ..Default::default()
+                    };
+                    Self::save_contact_tx(&tx, &contact)?;
+                }
+                // 自动收集时没有显示名的联系人，之后遇到显示名时补上
+                Some((id, true, existing)) if existing.is_empty() && !name.is_empty() => {
+                    tx.execute("UPDATE contacts SET name = ?1 WHERE id = ?2", params![name, id])?;
+                }
+                Some(_) => {}
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 按输入内容给出收件人候选：前缀匹配优先，其次按发送/接收次数和最近使用时间
+    pub fn suggest(&self, query: &str) -> DbResult<Vec<Suggestion>> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = format!("%{}%", query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let conn = self.lock();
+
+        let mut ranked: Vec<(u8, i64, Suggestion)> = Vec::new();
+        let mut stmt = conn.prepare(
+            "SELECT c.name, e.email, COALESCE(u.sent, 0), COALESCE(u.received, 0), COALESCE(u.last_used, 0)
+             FROM contacts c
+             JOIN contact_emails e ON e.contact_id = c.id
+             LEFT JOIN usage u ON u.email = e.email
+             WHERE c.name LIKE ?1 ESCAPE '\\' OR e.email LIKE ?1 ESCAPE '\\'",
+        )?;
+        let rows = stmt.query_map(params![pattern], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })?;
+        let now = Utc::now().timestamp();
+        for row in rows {
+            let (name, email, sent, received, last_used) = row?;
+            let mailbox = Mailbox { name: Some(name.clone()).filter(|n| !n.is_empty()), address: email.clone() };
+            // 最近 30 天内使用过的地址额外加分
+            let recent = if now - last_used < 30 * 24 * 3600 { 10 } else { 0 };
+            ranked.push((
+                match_rank(&query, &[&name, &email]),
+                sent * 5 + received + recent,
+                Suggestion { text: mailbox.to_string(), label: mailbox.display_name(), detail: email, is_group: false },
+            ));
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT g.name, COUNT(m.email) FROM contact_groups g
+             LEFT JOIN group_members m ON m.group_id = g.id
+             WHERE g.name LIKE ?1 ESCAPE '\\' GROUP BY g.id",
+        )?;
+        let rows = stmt.query_map(params![pattern], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+        for row in rows {
+            let (name, count) = row?;
+            // 分组排在同等匹配程度的联系人前面
+            ranked.push((
+                match_rank(&query, &[&name]),
+                i64::MAX,
+                Suggestion { text: name.clone(), label: name, detail: format!("{} 位成员", count), is_group: true },
+            ));
+        }
+
+        ranked.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then_with(|| a.2.label.cmp(&b.2.label)));
+        Ok(ranked.into_iter().take(SUGGESTION_LIMIT).map(|(_, _, s)| s).collect())
+    }
+
+    // 把收件人中的分组名（没有 @ 的地址）展开为分组成员
+    pub fn expand_groups(&self, addresses: Vec<Address>) -> DbResult<Vec<Address>> {
+        let groups = self.groups()?;
+        Ok(addresses
+            .into_iter()
+            .map(|address| {
+                let Address::Mailbox(mailbox) = &address else {
+                    return address;
+                };
+                if mailbox.address.contains('@') {
+                    return address;
+                }
+                match groups.iter().find(|g| g.name.eq_ignore_ascii_case(mailbox.address.trim())) {
+                    Some(group) => Address::Group { name: group.name.clone(), members: group.members.clone() },
+                    None => address,
+                }
+            })
+            .collect())
+    }
+
+    // 导入 vCard 文件，UID 或地址相同的联系人会被更新，返回导入的联系人和分组数
+    pub fn import_vcards(&self, text: &str) -> DbResult<usize> {
+        let entries = vcard::parse_vcards(text);
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        let mut groups = Vec::new();
+        for entry in entries {
+            match entry {
+                VCardEntry::Contact(mut contact) => {
+                    if contact.uid.is_empty() || Self::contact_id(&tx, &contact.uid)?.is_none() {
+                        // 没有相同 UID 时按地址合并到已有联系人
+                        let existing: Option<String> = contact
+                            .emails
+                            .iter()
+                            .find_map(|email| {
+                                tx.query_row(
+                                    "SELECT c.uid FROM contacts c JOIN contact_emails e ON e.contact_id = c.id
+                                     WHERE e.email = ?1",
+                                    params![email],
+                                    |row| row.get(0),
+                                )
+                                .optional()
+                                .transpose()
+                            })
+                            .transpose()?;
+                        if let Some(uid) = existing {
+                            contact.uid = uid;
+                        }
+                    }
+                    Self::save_contact_tx(&tx, &contact)?;
+                    imported += 1;
+                }
+                VCardEntry::Group { group, member_uids } => groups.push((group, member_uids)),
+            }
+        }
+        // 分组最后导入，成员引用的联系人已经存在
+        for (mut group, member_uids) in groups {
+            for uid in member_uids {
+                if let Some(id) = Self::contact_id(&tx, &uid)? {
+                    if let Some(mailbox) = Self::load_contact(&tx, id)?.mailbox() {
+                        group.members.push(mailbox);
+                    }
+                }
+            }
+            let same_name: Option<String> = tx
+                .query_row("SELECT uid FROM contact_groups WHERE name = ?1", params![group.name.trim()], |row| row.get(0))
+                .optional()?;
+            if let Some(uid) = same_name {
+                group.uid = uid;
+            }
+            Self::save_group_tx(&tx, &group)?;
+            imported += 1;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    // 把整个通讯录导出为 vCard 文件内容
+    pub fn export_vcards(&self, version: VCardVersion) -> DbResult<String> {
+        Ok(vcard::write_vcards(&self.contacts()?, &self.groups()?, version))
+    }
+
+    // 账户的 CardDAV/CalDAV 服务器地址，为空表示根据邮箱域名自动发现
+    pub fn dav_server(&self, account: &str) -> DbResult<Option<String>> {
+        Ok(self
+            .lock()
+            .query_row("SELECT server FROM dav_accounts WHERE account = ?1", params![account], |row| row.get(0))
+            .optional()?)
+    }
+
+    pub fn set_dav_server(&self, account: &str, server: &str) -> DbResult<()> {
+        self.lock().execute(
+            "INSERT OR REPLACE INTO dav_accounts (account, server) VALUES (?1, ?2)",
+            params![account, server.trim()],
+        )?;
+        Ok(())
+    }
+
+    // 保存账户下发现的通讯录或日历，已有集合的同步状态保留，不再存在的集合连同同步状态一起删除
+    pub fn set_dav_collections(&self, account: &str, service: DavService, collections: &[DavCollection]) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let urls: Vec<&str> = collections.iter().map(|c| c.url.as_str()).collect();
+        let existing: Vec<String> = tx
+            .prepare("SELECT url FROM dav_collections WHERE account = ?1 AND service = ?2")?
+            .query_map(params![account, service.as_str()], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for url in existing.iter().filter(|url| !urls.contains(&url.as_str())) {
+            tx.execute("DELETE FROM dav_collections WHERE url = ?1", params![url])?;
+        }
+        for collection in collections {
+            tx.execute(
+                "INSERT INTO dav_collections (url, account, service, name, components) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(url) DO UPDATE SET account = excluded.account, service = excluded.service,
+                    name = excluded.name, components = excluded.components",
+                params![collection.url, account, service.as_str(), collection.name, collection.components.join(",")],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn dav_collections(&self, account: &str, service: DavService) -> DbResult<Vec<DavCollection>> {
+        let conn = self.lock();
+        let collections = conn
+            .prepare("SELECT url, name, components FROM dav_collections WHERE account = ?1 AND service = ?2 ORDER BY rowid")?
+            .query_map(params![account, service.as_str()], |row| {
+                let components: String = row.get(2)?;
+                Ok(DavCollection {
+                    url: row.get(0)?,
+                    name: row.get(1)?,
+                    service,
+                    components: components.split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(collections)
+    }
+
+    // 接受会议邀请时使用的日历：选定的日历，没有选定时为第一个支持事件的日历
+    pub fn default_calendar(&self, account: &str) -> DbResult<Option<DavCollection>> {
+        let selected: Option<String> = self
+            .lock()
+            .query_row(
+                "SELECT url FROM dav_collections WHERE account = ?1 AND service = 'caldav' AND is_default = 1",
+                params![account],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let calendars = self.dav_collections(account, DavService::CalDav)?;
+        Ok(calendars
+            .iter()
+            .find(|c| selected.as_deref() == Some(c.url.as_str()))
+            .or_else(|| calendars.iter().find(|c| c.supports_events()))
+            .cloned())
+    }
+
+    pub fn set_default_calendar(&self, account: &str, url: &str) -> DbResult<()> {
+        self.lock().execute(
+            "UPDATE dav_collections SET is_default = (url = ?2) WHERE account = ?1 AND service = 'caldav'",
+            params![account, url],
+        )?;
+        Ok(())
+    }
+
+    pub fn dav_sync_token(&self, collection: &str) -> DbResult<Option<String>> {
+        let token = self
+            .lock()
+            .query_row("SELECT sync_token FROM dav_collections WHERE url = ?1", params![collection], |row| row.get(0))
+            .optional()?;
+        Ok(token.flatten())
+    }
+
+    pub fn set_dav_sync_token(&self, collection: &str, token: Option<&str>) -> DbResult<()> {
+        self.lock()
+            .execute("UPDATE dav_collections SET sync_token = ?1 WHERE url = ?2", params![token, collection])?;
+        Ok(())
+    }
+
+    // 集合中已同步的资源：地址 -> ETag
+    pub fn dav_etags(&self, collection: &str) -> DbResult<HashMap<String, String>> {
+        let conn = self.lock();
+        let etags = conn
+            .prepare("SELECT href, etag FROM dav_resources WHERE collection = ?1")?
+            .query_map(params![collection], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(etags)
+    }
+
+    // 资源对应的本地联系人，以及本地是否修改过
+    pub fn dav_contact(&self, href: &str) -> DbResult<Option<(Contact, bool)>> {
+        let conn = self.lock();
+        let row: Option<(i64, bool)> = conn
+            .query_row(
+                "SELECT c.id, c.dirty FROM contacts c JOIN dav_resources r ON r.contact_id = c.id WHERE r.href = ?1",
+                params![href],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        match row {
+            Some((id, dirty)) => Ok(Some((Self::load_contact(&conn, id)?, dirty))),
+            None => Ok(None),
+        }
+    }
+
+    // 保存从服务器下载的联系人，dirty 为 true 时（与本地修改合并后）下次同步仍会上传
+    pub fn save_dav_contact(&self, collection: &str, href: &str, etag: &str, contact: &Contact, dirty: bool) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut contact = Contact { harvested: false, ..contact.clone() };
+        // 已经对应到本地联系人的资源沿用本地的 UID
+        let mapped: Option<String> = tx
+            .query_row(
+                "SELECT c.uid FROM contacts c JOIN dav_resources r ON r.contact_id = c.id WHERE r.href = ?1",
+                params![href],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(uid) = mapped {
+            contact.uid = uid;
+        }
+        let uid = Self::save_contact_tx(&tx, &contact)?;
+        let id = Self::contact_id(&tx, &uid)?.ok_or("联系人保存失败")?;
+        tx.execute("UPDATE contacts SET dirty = ?1 WHERE id = ?2", params![dirty, id])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO dav_resources (contact_id, collection, href, etag) VALUES (?1, ?2, ?3, ?4)",
+            params![id, collection, href, etag],
+        )?;
+        tx.execute("DELETE FROM dav_tombstones WHERE href = ?1", params![href])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 服务器上已删除的联系人，本地也删除且不再记录删除
+    pub fn remove_dav_contact(&self, href: &str) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let id: Option<i64> = tx
+            .query_row("SELECT contact_id FROM dav_resources WHERE href = ?1", params![href], |row| row.get(0))
+            .optional()?;
+        tx.execute("DELETE FROM dav_resources WHERE href = ?1", params![href])?;
+        if let Some(id) = id {
+            tx.execute("DELETE FROM contacts WHERE id = ?1", params![id])?;
+        }
+        tx.execute("DELETE FROM dav_tombstones WHERE href = ?1", params![href])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 去掉联系人与服务器资源的对应关系，联系人之后作为新资源上传
+    pub fn forget_dav_resource(&self, href: &str) -> DbResult<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE contacts SET dirty = 1 WHERE id IN (SELECT contact_id FROM dav_resources WHERE href = ?1)",
+            params![href],
+        )?;
+        conn.execute("DELETE FROM dav_resources WHERE href = ?1", params![href])?;
+        Ok(())
+    }
+
+    // 需要上传到集合的联系人及其已有的 (地址, ETag)
+    // include_new 为 true 时包括还没有上传到任何集合的联系人，自动收集的联系人不上传
+    pub fn dirty_contacts(&self, collection: &str, include_new: bool) -> DbResult<Vec<(Contact, Option<DavResourceRef>)>> {
+        let conn = self.lock();
+        let rows: Vec<(i64, Option<String>, Option<String>)> = conn
+            .prepare(
+                "SELECT c.id, r.href, r.etag FROM contacts c LEFT JOIN dav_resources r ON r.contact_id = c.id
+                 WHERE c.dirty = 1 AND c.harvested = 0 AND (r.collection = ?1 OR (?2 AND r.href IS NULL))",
+            )?
+            .query_map(params![collection, include_new], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows.into_iter()
+            .map(|(id, href, etag)| Ok((Self::load_contact(&conn, id)?, href.zip(etag))))
+            .collect()
+    }
+
+    // 上传成功，记录资源地址和新的 ETag，同步期间被删除的联系人会在下次同步时处理
+    pub fn mark_dav_uploaded(&self, uid: &str, collection: &str, href: &str, etag: &str) -> DbResult<()> {
+        let conn = self.lock();
+        let Some(id) = Self::contact_id(&conn, uid)? else {
+            return Ok(());
+        };
+        conn.execute("UPDATE contacts SET dirty = 0 WHERE id = ?1", params![id])?;
+        conn.execute(
+            "INSERT OR REPLACE INTO dav_resources (contact_id, collection, href, etag) VALUES (?1, ?2, ?3, ?4)",
+            params![id, collection, href, etag],
+        )?;
+        Ok(())
+    }
+
+    // 本地删除、还没有从服务器删除的资源：(地址, ETag)
+    pub fn dav_tombstones(&self, collection: &str) -> DbResult<Vec<(String, String)>> {
+        let conn = self.lock();
+        let tombstones = conn
+            .prepare("SELECT href, etag FROM dav_tombstones WHERE collection = ?1")?
+            .query_map(params![collection], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tombstones)
+    }
+
+    pub fn has_dav_tombstone(&self, href: &str) -> DbResult<bool> {
+        Ok(self
+            .lock()
+            .query_row("SELECT 1 FROM dav_tombstones WHERE href = ?1", params![href], |_| Ok(()))
+            .optional()?
+            .is_some())
+    }
+
+    pub fn clear_dav_tombstone(&self, href: &str) -> DbResult<()> {
+        self.lock().execute("DELETE FROM dav_tombstones WHERE href = ?1", params![href])?;
+        Ok(())
+    }
+}
+
+// 0: 名称或地址以输入开头，1: 名称中的某个词以输入开头，2: 只是包含
+fn match_rank(query: &str, fields: &[&str]) -> u8 {
+    let fields: Vec<String> = fields.iter().map(|f| f.to_lowercase()).collect();
+    if fields.iter().any(|f| f.starts_with(query)) {
+        0
+    } else if fields
+        .iter()
+        .any(|f| f.split(|c: char| c.is_whitespace() || "._-@".contains(c)).any(|w| w.starts_with(query)))
+    {
+        1
+    } else {
+        2
+    }
+}
+
+fn new_uid() -> String {
+    let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    format!(
+        "rustmail-{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        UID_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    const COLLECTION: &str = "https://dav.example.com/addressbooks/me/default/";
+
+    // 每个测试使用单独的数据库文件
+    fn open_book(name: &str) -> (AddressBook, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rustmail-ab-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let book = AddressBook::open(&dir.join("contacts.db")).unwrap();
+        let collection = DavCollection {
+            url: COLLECTION.into(),
+            name: "默认".into(),
+            service: DavService::CardDav,
+            components: Vec::new(),
+        };
+        book.set_dav_collections("me@example.com", DavService::CardDav, &[collection]).unwrap();
+        (book, dir)
+    }
+
+    fn remote(name: &str, email: &str) -> Contact {
+        Contact { uid: "server-uid".into(), name: name.into(), emails: vec![email.into()], ..Default::default() }
+    }
+
+    #[test]
+    fn downloaded_contact_keeps_local_uid_and_clean_state() {
+        let (book, dir) = open_book("download");
+        let href = format!("{}a.vcf", COLLECTION);
+        book.save_dav_contact(COLLECTION, &href, "\"1\"", &remote("A", "a@x.com"), false).unwrap();
+        let (first, dirty) = book.dav_contact(&href).unwrap().unwrap();
+        assert!(!dirty);
+        assert_eq!(first.uid, "server-uid");
+        assert!(book.dirty_contacts(COLLECTION, true).unwrap().is_empty());
+
+        // 服务器上的 UID 变化时仍然更新同一个本地联系人
+        let renamed = Contact { uid: "other-uid".into(), ..remote("A2", "a@x.com") };
+        book.save_dav_contact(COLLECTION, &href, "\"2\"", &renamed, false).unwrap();
+        let contacts = book.contacts().unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!((contacts[0].uid.as_str(), contacts[0].name.as_str()), ("server-uid", "A2"));
+        assert_eq!(book.dav_etags(COLLECTION).unwrap().get(&href).map(String::as_str), Some("\"2\""));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn local_edits_are_uploaded_with_their_etag() {
+        let (book, dir) = open_book("edit");
+        let href = format!("{}a.vcf", COLLECTION);
+        book.save_dav_contact(COLLECTION, &href, "\"1\"", &remote("A", "a@x.com"), false).unwrap();
+
+        let mut edited = book.contact("server-uid").unwrap().unwrap();
+        edited.phones = vec!["123".into()];
+        book.save_contact(&edited).unwrap();
+        let local = book.save_contact(&Contact { name: "New".into(), emails: vec!["n@x.com".into()], ..Default::default() }).unwrap();
+
+        let dirty = book.dirty_contacts(COLLECTION, false).unwrap();
+        assert_eq!(dirty.len(), 1);
+        assert_eq!(dirty[0].1, Some((href.clone(), "\"1\"".to_string())));
+        let with_new = book.dirty_contacts(COLLECTION, true).unwrap();
+        assert!(with_new.iter().any(|(c, r)| c.uid == local && r.is_none()));
+
+        book.mark_dav_uploaded("server-uid", COLLECTION, &href, "\"2\"").unwrap();
+        assert_eq!(book.dav_contact(&href).unwrap().map(|(_, dirty)| dirty), Some(false));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn deleting_a_synced_contact_leaves_a_tombstone() {
+        let (book, dir) = open_book("delete");
+        let href = format!("{}a.vcf", COLLECTION);
+        book.save_dav_contact(COLLECTION, &href, "\"1\"", &remote("A", "a@x.com"), false).unwrap();
+        book.delete_contact("server-uid").unwrap();
+        assert_eq!(book.dav_tombstones(COLLECTION).unwrap(), vec![(href.clone(), "\"1\"".to_string())]);
+
+        // 服务器上又修改过时重新下载，删除记录随之清除
+        book.save_dav_contact(COLLECTION, &href, "\"2\"", &remote("A", "a@x.com"), false).unwrap();
+        assert!(!book.has_dav_tombstone(&href).unwrap());
+
+        book.remove_dav_contact(&href).unwrap();
+        assert!(book.contacts().unwrap().is_empty());
+        assert!(book.dav_tombstones(COLLECTION).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn forgotten_resource_is_uploaded_again() {
+        let (book, dir) = open_book("forget");
+        let href = format!("{}a.vcf", COLLECTION);
+        book.save_dav_contact(COLLECTION, &href, "\"1\"", &remote("A", "a@x.com"), false).unwrap();
+        book.forget_dav_resource(&href).unwrap();
+        assert!(book.dav_contact(&href).unwrap().is_none());
+        let dirty = book.dirty_contacts(COLLECTION, true).unwrap();
+        assert_eq!(dirty.len(), 1);
+        assert_eq!(dirty[0].1, None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,587 @@
+use std::fmt;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use hickory_resolver::Resolver;
+use crate::models::EmailAccount;
+use crate::service::smtp_client::uses_implicit_tls;
+
+// 单次 HTTP 请求/端口探测的超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(8);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Thunderbird 自动配置的默认地址，{domain} 和 {address} 会被替换
+const DEFAULT_AUTOCONFIG_URLS: &[&str] = &[
+    "https://autoconfig.{domain}/mail/config-v1.1.xml?emailaddress={address}",
+    "https://{domain}/.well-known/autoconfig/mail/config-v1.1.xml?emailaddress={address}",
+    "https://autoconfig.thunderbird.net/v1.1/{domain}",
+];
+
+// 常见国内邮箱的内置配置：域名, IMAP 服务器, IMAP 端口, SMTP 服务器, SMTP 端口
+const BUILTIN_PROVIDERS: &[(&str, &str, u16, &str, u16)] = &[
+    ("qq.com", "imap.qq.com", 993, "smtp.qq.com", 465),
+    ("vip.qq.com", "imap.qq.com", 993, "smtp.qq.com", 465),
+    ("foxmail.com", "imap.qq.com", 993, "smtp.qq.com", 465),
+    ("163.com", "imap.163.com", 993, "smtp.163.com", 465),
+    ("126.com", "imap.126.com", 993, "smtp.126.com", 465),
+    ("yeah.net", "imap.yeah.net", 993, "smtp.yeah.net", 465),
+];
+
+// 服务器连接的加密方式，对应 autoconfig 中的 socketType
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SocketType {
+    Ssl,
+    StartTls,
+    Plain,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerSetting {
+    pub host: String,
+    pub port: u16,
+    pub socket: SocketType,
+}
+
+// 自动配置的来源，用于在界面上提示
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource {
+    Builtin,
+    Autoconfig(String),
+    Srv,
+    Guess,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Builtin => write!(f, "内置服务商列表"),
+            ConfigSource::Autoconfig(url) => write!(f, "自动配置文件 {}", url),
+            ConfigSource::Srv => write!(f, "DNS SRV 记录"),
+            ConfigSource::Guess => write!(f, "常见服务器地址探测"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredConfig {
+    pub imap: ServerSetting,
+    pub smtp: ServerSetting,
+    pub source: ConfigSource,
+}
+
+impl DiscoveredConfig {
+    // 把发现的配置填入账户，账户地址和密码保持不变；IMAP 与 SMTP 分别设置是否加密
+    pub fn apply_to(&self, account: &mut EmailAccount) {
+        account.imap_server = self.imap.host.clone();
+        account.imap_port = self.imap.port;
+        account.smtp_server = self.smtp.host.clone();
+        account.smtp_port = self.smtp.port;
+        account.use_tls = self.imap.socket == SocketType::Ssl;
+        account.smtp_use_tls = Some(self.smtp.socket != SocketType::Plain);
+    }
+}
+
+// SRV 记录中与配置相关的字段
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub target: String,
+    pub port: u16,
+}
+
+type DiscoveryResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 获取 autoconfig XML，测试时可替换为本地 HTTP 服务
+pub trait ConfigFetcher: Send + Sync {
+    fn fetch(&self, url: &str) -> DiscoveryResult<String>;
+}
+
+// 查询 SRV 记录，测试时可替换为本地 DNS 服务
+pub trait SrvResolver: Send + Sync {
+    fn lookup_srv(&self, name: &str) -> DiscoveryResult<Vec<SrvRecord>>;
+}
+
+// 探测某个主机端口能否建立 TCP 连接
+pub trait PortProber: Send + Sync {
+    fn probe(&self, host: &str, port: u16) -> bool;
+}
+
+pub struct HttpFetcher;
+
+impl ConfigFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> DiscoveryResult<String> {
+        let response = ureq::get(url).timeout(FETCH_TIMEOUT).call()?;
+        Ok(response.into_string()?)
+    }
+}
+
+// 基于 hickory 的 SRV 查询，默认使用系统 DNS 设置
+pub struct DnsSrvResolver {
+    resolver: Resolver,
+}
+
+impl DnsSrvResolver {
+    pub fn from_system_conf() -> DiscoveryResult<Self> {
+        Ok(Self { resolver: Resolver::from_system_conf()? })
+    }
+}
+
+impl SrvResolver for DnsSrvResolver {
+    fn lookup_srv(&self, name: &str) -> DiscoveryResult<Vec<SrvRecord>> {
+        let lookup = self.resolver.srv_lookup(name)?;
+        Ok(lookup
+            .iter()
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                target: srv.target().to_utf8().trim_end_matches('.').to_string(),
+                port: srv.port(),
+            })
+            .collect())
+    }
+}
+
+pub struct TcpProber;
+
+impl PortProber for TcpProber {
+    fn probe(&self, host: &str, port: u16) -> bool {
+        let addrs: Vec<SocketAddr> = match (host, port).to_socket_addrs() {
+            Ok(addrs) => addrs.collect(),
+            Err(_) => return false,
+        };
+        addrs
+            .iter()
+            .any(|addr| TcpStream::connect_timeout(addr, PROBE_TIMEOUT).is_ok())
+    }
+}
+
+// 根据邮箱地址自动发现服务器设置
+// 依次尝试：内置列表 -> autoconfig XML -> SRV 记录 -> 常见地址探测
+pub struct Discovery {
+    autoconfig_urls: Vec<String>,
+    use_builtin: bool,
+    fetcher: Box<dyn ConfigFetcher>,
+    resolver: Option<Box<dyn SrvResolver>>,
+    prober: Box<dyn PortProber>,
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        let resolver = match DnsSrvResolver::from_system_conf() {
+            Ok(resolver) => Some(Box::new(resolver) as Box<dyn SrvResolver>),
+            Err(e) => {
+                println!("读取系统 DNS 配置失败: {}", e);
+                None
+            }
+        };
+
+        Self {
+            autoconfig_urls: DEFAULT_AUTOCONFIG_URLS.iter().map(|s| s.to_string()).collect(),
+            use_builtin: true,
+            fetcher: Box::new(HttpFetcher),
+            resolver,
+            prober: Box::new(TcpProber),
+        }
+    }
+
+    // 替换 autoconfig 地址模板
+    #[cfg(test)]
+    pub fn with_autoconfig_urls(mut self, urls: Vec<String>) -> Self {
+        self.autoconfig_urls = urls;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_builtin(mut self, use_builtin: bool) -> Self {
+        self.use_builtin = use_builtin;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_fetcher(mut self, fetcher: impl ConfigFetcher + 'static) -> Self {
+        self.fetcher = Box::new(fetcher);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_resolver(mut self, resolver: impl SrvResolver + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_prober(mut self, prober: impl PortProber + 'static) -> Self {
+        self.prober = Box::new(prober);
+        self
+    }
+
+    // 同步执行发现流程，会进行网络请求
+    pub fn discover(&self, address: &str) -> Option<DiscoveredConfig> {
+        let address = address.trim();
+        let domain = address.rsplit_once('@')?.1.trim().to_lowercase();
+        if domain.is_empty() {
+            return None;
+        }
+
+        if self.use_builtin {
+            if let Some(config) = builtin_config(&domain) {
+                return Some(config);
+            }
+        }
+
+        // 地址中可能有 +、& 等字符，放进查询参数前需要编码
+        let encoded: String = url::form_urlencoded::byte_serialize(address.as_bytes()).collect();
+        for template in &self.autoconfig_urls {
+            let url = template
+                .replace("{domain}", &domain)
+                .replace("{address}", &encoded);
+            match self.fetcher.fetch(&url) {
+                Ok(xml) => match parse_autoconfig(&xml, address, &domain) {
+                    Some((imap, smtp)) => {
+                        return Some(DiscoveredConfig { imap, smtp, source: ConfigSource::Autoconfig(url) });
+                    }
+                    None => println!("autoconfig 内容无法识别: {}", url),
+                },
+                Err(e) => println!("获取 autoconfig 失败 {}: {}", url, e),
+            }
+        }
+
+        if let Some(config) = self.discover_srv(&domain) {
+            return Some(config);
+        }
+
+        self.guess(&domain)
+    }
+
+    // RFC 6186：IMAP 客户端只支持 SSL 直连，因此只查 _imaps；SMTP 优先 _submissions
+    fn discover_srv(&self, domain: &str) -> Option<DiscoveredConfig> {
+        let resolver = self.resolver.as_ref()?;
+
+        let lookup = |service: &str, socket: SocketType| -> Option<ServerSetting> {
+            let name = format!("{}._tcp.{}.", service, domain);
+            let mut records = match resolver.lookup_srv(&name) {
+                Ok(records) => records,
+                Err(e) => {
+                    println!("查询 SRV 记录失败 {}: {}", name, e);
+                    return None;
+                }
+            };
+            // target 为 "." 表示该服务不可用
+            records.retain(|r| !r.target.is_empty() && r.target != ".");
+            records.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)));
+            records.first().map(|r| ServerSetting {
+                host: r.target.clone(),
+                port: r.port,
+                socket,
+            })
+        };
+
+        let imap = lookup("_imaps", SocketType::Ssl)?;
+        let smtp = lookup("_submissions", SocketType::Ssl)
+            .or_else(|| lookup("_submission", SocketType::StartTls))?;
+
+        Some(DiscoveredConfig { imap, smtp, source: ConfigSource::Srv })
+    }
+
+    // 按常见命名规则探测，取第一个能连通的地址
+    fn guess(&self, domain: &str) -> Option<DiscoveredConfig> {
+        let imap_candidates = [
+            (format!("imap.{}", domain), 993, SocketType::Ssl),
+            (format!("mail.{}", domain), 993, SocketType::Ssl),
+        ];
+        let smtp_candidates = [
+            (format!("smtp.{}", domain), 465, SocketType::Ssl),
+            (format!("smtp.{}", domain), 587, SocketType::StartTls),
+            (format!("mail.{}", domain), 465, SocketType::Ssl),
+            (format!("mail.{}", domain), 587, SocketType::StartTls),
+        ];
+
+        let (imap, smtp) = std::thread::scope(|scope| {
+            let imap = scope.spawn(|| self.first_reachable(&imap_candidates));
+            let smtp = scope.spawn(|| self.first_reachable(&smtp_candidates));
+            (imap.join().ok().flatten(), smtp.join().ok().flatten())
+        });
+
+        Some(DiscoveredConfig { imap: imap?, smtp: smtp?, source: ConfigSource::Guess })
+    }
+
+    // 并行探测所有候选，按候选顺序返回第一个可连接的
+    fn first_reachable(&self, candidates: &[(String, u16, SocketType)]) -> Option<ServerSetting> {
+        let reachable: Vec<bool> = std::thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .iter()
+                .map(|(host, port, _)| scope.spawn(move || self.prober.probe(host, *port)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap_or(false)).collect()
+        });
+
+        candidates
+            .iter()
+            .zip(reachable)
+            .find(|(_, ok)| *ok)
+            .map(|((host, port, socket), _)| ServerSetting {
+                host: host.clone(),
+                port: *port,
+                socket: *socket,
+            })
+    }
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 在后台线程执行自动发现，供界面调用
+pub async fn discover_settings(address: String) -> Option<DiscoveredConfig> {
+    async_std::task::spawn_blocking(move || Discovery::new().discover(&address)).await
+}
+
+pub fn builtin_config(domain: &str) -> Option<DiscoveredConfig> {
+    BUILTIN_PROVIDERS
+        .iter()
+        .find(|(d, ..)| d.eq_ignore_ascii_case(domain))
+        .map(|(_, imap_host, imap_port, smtp_host, smtp_port)| DiscoveredConfig {
+            imap: ServerSetting {
+                host: imap_host.to_string(),
+                port: *imap_port,
+                socket: SocketType::Ssl,
+            },
+            smtp: ServerSetting {
+                host: smtp_host.to_string(),
+                port: *smtp_port,
+                socket: if uses_implicit_tls(*smtp_port) { SocketType::Ssl } else { SocketType::StartTls },
+            },
+            source: ConfigSource::Builtin,
+        })
+}
+
+// 解析 Thunderbird autoconfig (config-v1.1.xml)，返回首选的 IMAP 与 SMTP 服务器
+// IMAP 客户端只支持 SSL/TLS 直连或不加密，只有 STARTTLS 的 IMAP 配置无法使用，返回 None
+pub fn parse_autoconfig(xml: &str, address: &str, domain: &str) -> Option<(ServerSetting, ServerSetting)> {
+    let doc = roxmltree::Document::parse(xml).ok()?;
+    let provider = doc
+        .descendants()
+        .find(|n| n.has_tag_name("emailProvider"))?;
+
+    let local_part = address.split('@').next().unwrap_or_default();
+    let expand = |value: &str| {
+        value
+            .replace("%EMAILADDRESS%", address)
+            .replace("%EMAILLOCALPART%", local_part)
+            .replace("%EMAILDOMAIN%", domain)
+    };
+
+    let read_server = |node: roxmltree::Node| -> Option<ServerSetting> {
+        let text = |tag: &str| {
+            node.children()
+                .find(|c| c.has_tag_name(tag))
+                .and_then(|c| c.text())
+                .map(|t| t.trim().to_string())
+        };
+        let host = expand(&text("hostname")?);
+        let port = text("port")?.parse().ok()?;
+        let socket = match text("socketType").as_deref() {
+            Some("SSL") => SocketType::Ssl,
+            Some("STARTTLS") => SocketType::StartTls,
+            _ => SocketType::Plain,
+        };
+        Some(ServerSetting { host, port, socket })
+    };
+
+    // 同类服务器有多个时，优先选择 SSL/TLS 直连
+    let pick = |element: &str, kind: &str, usable: &dyn Fn(SocketType) -> bool| -> Option<ServerSetting> {
+        let mut servers: Vec<ServerSetting> = provider
+            .children()
+            .filter(|n| n.has_tag_name(element) && n.attribute("type") == Some(kind))
+            .filter_map(read_server)
+            .filter(|s| usable(s.socket))
+            .collect();
+        servers.sort_by_key(|s| match s.socket {
+            SocketType::Ssl => 0,
+            SocketType::StartTls => 1,
+            SocketType::Plain => 2,
+        });
+        servers.into_iter().next()
+    };
+
+    // 同时有 STARTTLS 和明文时不退回明文，以免在用户不知情时关闭加密
+    let imap_starttls_only = pick("incomingServer", "imap", &|s| s == SocketType::StartTls).is_some()
+        && pick("incomingServer", "imap", &|s| s == SocketType::Ssl).is_none();
+    if imap_starttls_only {
+        return None;
+    }
+    let imap = pick("incomingServer", "imap", &|s| s != SocketType::StartTls)?;
+    Some((imap, pick("outgoingServer", "smtp", &|_| true)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    // 按地址返回固定内容，并记录请求过的地址
+    struct FakeFetcher {
+        responses: HashMap<String, String>,
+        requested: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ConfigFetcher for FakeFetcher {
+        fn fetch(&self, url: &str) -> DiscoveryResult<String> {
+            self.requested.lock().unwrap().push(url.to_string());
+            self.responses.get(url).cloned().ok_or_else(|| "404".into())
+        }
+    }
+
+    struct FakeResolver(HashMap<String, Vec<SrvRecord>>);
+
+    impl SrvResolver for FakeResolver {
+        fn lookup_srv(&self, name: &str) -> DiscoveryResult<Vec<SrvRecord>> {
+            self.0.get(name).cloned().ok_or_else(|| "NXDOMAIN".into())
+        }
+    }
+
+    struct FakeProber(Vec<(String, u16)>);
+
+    impl PortProber for FakeProber {
+        fn probe(&self, host: &str, port: u16) -> bool {
+            self.0.iter().any(|(h, p)| h == host && *p == port)
+        }
+    }
+
+    fn server(element: &str, kind: &str, host: &str, port: u16, socket: &str) -> String {
+        format!(
+            "<{e} type=\"{k}\"><hostname>{h}</hostname><port>{p}</port><socketType>{s}</socketType></{e}>",
+            e = element, k = kind, h = host, p = port, s = socket
+        )
+    }
+
+    fn config_xml(servers: &[String]) -> String {
+        format!(
+            "<?xml version=\"1.0\"?><clientConfig version=\"1.1\"><emailProvider id=\"example.com\">{}</emailProvider></clientConfig>",
+            servers.concat()
+        )
+    }
+
+    fn srv(priority: u16, weight: u16, target: &str, port: u16) -> SrvRecord {
+        SrvRecord { priority, weight, target: target.to_string(), port }
+    }
+
+    fn discovery(fetcher: FakeFetcher) -> Discovery {
+        Discovery::new()
+            .with_builtin(false)
+            .with_autoconfig_urls(vec!["https://autoconfig.{domain}/mail?emailaddress={address}".to_string()])
+            .with_fetcher(fetcher)
+            .with_resolver(FakeResolver(HashMap::new()))
+            .with_prober(FakeProber(Vec::new()))
+    }
+
+    #[test]
+    fn autoconfig_prefers_ssl_and_expands_placeholders() {
+        let xml = config_xml(&[
+            server("incomingServer", "imap", "imap.example.com", 143, "STARTTLS"),
+            server("incomingServer", "imap", "%EMAILLOCALPART%.imap.%EMAILDOMAIN%", 993, "SSL"),
+            server("outgoingServer", "smtp", "smtp.example.com", 587, "STARTTLS"),
+            server("outgoingServer", "smtp", "smtp.example.com", 465, "SSL"),
+        ]);
+        let (imap, smtp) = parse_autoconfig(&xml, "alice@example.com", "example.com").unwrap();
+        assert_eq!(imap, ServerSetting { host: "alice.imap.example.com".into(), port: 993, socket: SocketType::Ssl });
+        assert_eq!(smtp.port, 465);
+        assert_eq!(smtp.socket, SocketType::Ssl);
+    }
+
+    #[test]
+    fn autoconfig_rejects_starttls_only_imap() {
+        let xml = config_xml(&[
+            server("incomingServer", "imap", "imap.example.com", 143, "STARTTLS"),
+            server("incomingServer", "imap", "imap.example.com", 143, "plain"),
+            server("outgoingServer", "smtp", "smtp.example.com", 587, "STARTTLS"),
+        ]);
+        assert_eq!(parse_autoconfig(&xml, "alice@example.com", "example.com"), None);
+        assert_eq!(parse_autoconfig("<not xml", "alice@example.com", "example.com"), None);
+    }
+
+    #[test]
+    fn apply_sets_tls_per_protocol() {
+        let config = DiscoveredConfig {
+            imap: ServerSetting { host: "imap.example.com".into(), port: 143, socket: SocketType::Plain },
+            smtp: ServerSetting { host: "smtp.example.com".into(), port: 587, socket: SocketType::StartTls },
+            source: ConfigSource::Guess,
+        };
+        let mut account = EmailAccount { address: "alice@example.com".into(), ..Default::default() };
+        config.apply_to(&mut account);
+        assert!(!account.use_tls);
+        assert!(account.smtp_tls());
+        assert_eq!((account.imap_port, account.smtp_port), (143, 587));
+        assert_eq!(account.address, "alice@example.com");
+    }
+
+    #[test]
+    fn discovery_encodes_address_in_autoconfig_url() {
+        let url = "https://autoconfig.example.com/mail?emailaddress=a%2Bb%40example.com".to_string();
+        let xml = config_xml(&[
+            server("incomingServer", "imap", "imap.example.com", 993, "SSL"),
+            server("outgoingServer", "smtp", "smtp.example.com", 587, "STARTTLS"),
+        ]);
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let fetcher = FakeFetcher { responses: HashMap::from([(url.clone(), xml)]), requested: requested.clone() };
+
+        let config = discovery(fetcher).discover(" a+b@example.com ").unwrap();
+        assert_eq!(*requested.lock().unwrap(), vec![url.clone()]);
+        assert_eq!(config.source, ConfigSource::Autoconfig(url));
+        assert_eq!(config.imap.host, "imap.example.com");
+    }
+
+    #[test]
+    fn discovery_falls_back_to_srv_records() {
+        let starttls_only = config_xml(&[
+            server("incomingServer", "imap", "imap.example.com", 143, "STARTTLS"),
+            server("outgoingServer", "smtp", "smtp.example.com", 587, "STARTTLS"),
+        ]);
+        let fetcher = FakeFetcher {
+            responses: HashMap::from([(
+                "https://autoconfig.example.com/mail?emailaddress=alice%40example.com".to_string(),
+                starttls_only,
+            )]),
+            requested: Arc::new(Mutex::new(Vec::new())),
+        };
+        let resolver = FakeResolver(HashMap::from([
+            (
+                "_imaps._tcp.example.com.".to_string(),
+                vec![srv(20, 0, "backup.example.com", 993), srv(10, 5, "imap.example.com", 993), srv(10, 1, "other.example.com", 993)],
+            ),
+            ("_submissions._tcp.example.com.".to_string(), vec![srv(0, 0, ".", 0)]),
+            ("_submission._tcp.example.com.".to_string(), vec![srv(0, 0, "smtp.example.com", 587)]),
+        ]));
+
+        let config = discovery(fetcher).with_resolver(resolver).discover("alice@example.com").unwrap();
+        assert_eq!(config.source, ConfigSource::Srv);
+        assert_eq!(config.imap, ServerSetting { host: "imap.example.com".into(), port: 993, socket: SocketType::Ssl });
+        assert_eq!(config.smtp, ServerSetting { host: "smtp.example.com".into(), port: 587, socket: SocketType::StartTls });
+    }
+
+    #[test]
+    fn discovery_guesses_reachable_servers_in_order() {
+        let fetcher = FakeFetcher { responses: HashMap::new(), requested: Arc::new(Mutex::new(Vec::new())) };
+        let prober = FakeProber(vec![
+            ("mail.example.com".into(), 993),
+            ("smtp.example.com".into(), 587),
+            ("mail.example.com".into(), 465),
+        ]);
+
+        let config = discovery(fetcher).with_prober(prober).discover("alice@example.com").unwrap();
+        assert_eq!(config.source, ConfigSource::Guess);
+        assert_eq!((config.imap.host.as_str(), config.imap.port), ("mail.example.com", 993));
+        assert_eq!((config.smtp.host.as_str(), config.smtp.port), ("smtp.example.com", 587));
+    }
+
+    #[test]
+    fn builtin_providers_are_case_insensitive() {
+        let config = builtin_config("QQ.com").unwrap();
+        assert_eq!(config.imap.host, "imap.qq.com");
+        assert_eq!(config.smtp.socket, SocketType::Ssl);
+        assert!(builtin_config("example.com").is_none());
+    }
+}
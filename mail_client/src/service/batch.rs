@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use crate::models::{Email, EmailAccount};
+use crate::service::filter::TRASH_FOLDER;
+use crate::service::imap_client;
+use crate::service::label;
+use crate::service::local_storage::LocalStorage;
+use crate::service::mail_db::{FLAG_FLAGGED, FLAG_SEEN};
+
+type BatchResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 归档操作移到的本地文件夹
+pub const ARCHIVE_FOLDER: &str = "Archive";
+const FLAG_DELETED: &str = "\\Deleted";
+
+// 对勾选的多封邮件执行的操作
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchAction {
+    MarkSeen(bool),
+    Flag(bool),
+    Move(String),
+    Archive,
+    Delete, // 移到已删除
+    Label(String, bool), // 标签关键字，添加或移除
+}
+
+impl BatchAction {
+    // 移动类操作的目标文件夹
+    fn target(&self) -> Option<&str> {
+        match self {
+            BatchAction::Move(folder) => Some(folder),
+            BatchAction::Archive => Some(ARCHIVE_FOLDER),
+            BatchAction::Delete => Some(TRASH_FOLDER),
+            _ => None,
+        }
+    }
+
+    // 该操作是否会改变这封邮件，已经是目标状态的邮件跳过
+    fn changes(&self, email: &Email) -> bool {
+        match self {
+            BatchAction::MarkSeen(seen) => email.seen != *seen,
+            BatchAction::Flag(flagged) => email.flagged != *flagged,
+            BatchAction::Label(keyword, set) => email.labels.contains(keyword) != *set,
+            _ => self.target().is_some_and(|target| folder_of(email) != target),
+        }
+    }
+}
+
+fn folder_of(email: &Email) -> &str {
+    if email.folder.is_empty() { "INBOX" } else { &email.folder }
+}
+
+// 在本地执行操作，返回实际修改的邮件数；emails 为操作前的状态
+pub fn apply_local(storage: &LocalStorage, accounts: &[EmailAccount], emails: &[Email], action: &BatchAction)
+    -> BatchResult<usize>
+{
+    let emails: Vec<Email> = emails.iter().filter(|e| action.changes(e)).cloned().collect();
+    if let BatchAction::Label(keyword, set) = action {
+        return Ok(label::apply_label(storage, accounts, &emails, keyword, *set)?.len());
+    }
+
+    // 按 (账户, 文件夹) 分组，移动时每组只需读写一次
+    let mut groups: BTreeMap<(&str, &str), Vec<Email>> = BTreeMap::new();
+    for email in &emails {
+        groups.entry((email.account.as_str(), folder_of(email))).or_default().push(email.clone());
+    }
+
+    let mut changed = 0;
+    for ((address, folder), group) in groups {
+        let Some(account) = accounts.iter().find(|a| a.address == address) else {
+            continue;
+        };
+        match action {
+            BatchAction::MarkSeen(seen) => {
+                for email in &group {
+                    storage.mark_seen(account, folder, &email.id, *seen)?;
+                }
+                changed += group.len();
+            }
+            BatchAction::Flag(flagged) => {
+                for email in &group {
+                    storage.set_flag(account, folder, &email.id, FLAG_FLAGGED, *flagged)?;
+                }
+                changed += group.len();
+            }
+            _ => {
+                if let Some(target) = action.target() {
+                    changed += storage.move_emails(account, folder, target, &group)?;
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+// 把操作同步到服务器上邮件所在的文件夹，每个文件夹只发一条 UID 命令；emails 与 apply_local 相同，为操作前的状态
+// 移动、归档和删除在服务器上移到同名文件夹（删除移到 Trash），移动后新的 UID 未知，本地副本不再对应服务器上的邮件；
+// 旧版本删除时只在服务器上加了 \Deleted，从已删除移出时去掉。只在本地的邮件不处理
+// 返回服务器不允许保存该标记、修改只在本地的账户
+pub async fn apply_remote(storage: &LocalStorage, accounts: &[EmailAccount], emails: &[Email], action: &BatchAction)
+    -> Vec<String>
+{
+    let emails: Vec<Email> = emails.iter().filter(|e| action.changes(e)).cloned().collect();
+    let (flag, set) = match action {
+        BatchAction::Label(keyword, set) => {
+            return label::store_label(accounts, &emails, keyword, *set).await;
+        }
+        BatchAction::MarkSeen(seen) => (FLAG_SEEN, *seen),
+        BatchAction::Flag(flagged) => (FLAG_FLAGGED, *flagged),
+        BatchAction::Move(_) | BatchAction::Archive | BatchAction::Delete => (FLAG_DELETED, false),
+    };
+
+    // 只有从已删除移出时需要去掉 \Deleted
+    let flagged: Vec<Email> = emails
+        .iter()
+        .filter(|e| !(flag == FLAG_DELETED && folder_of(e) != TRASH_FOLDER))
+        .cloned()
+        .collect();
+    let mut local_only = Vec::new();
+    for ((address, mailbox), uids) in imap_client::group_by_mailbox(&flagged) {
+        let Some(account) = accounts.iter().find(|a| a.address == address) else {
+            continue;
+        };
+        let (add, remove): (&[&str], &[&str]) = if set { (&[flag], &[]) } else { (&[], &[flag]) };
+        match imap_client::store_flags(account, &mailbox, &uids, add, remove).await {
+            Ok(true) => println!("{}: 已在服务器上修改 {} 封邮件的 {}", address, uids.len(), flag),
+            Ok(false) => {
+                println!("{}: 服务器不允许保存 {}", address, flag);
+                if !local_only.contains(&address) {
+                    local_only.push(address);
+                }
+            }
+            Err(e) => println!("{}: 同步 {} 失败: {}", address, flag, e),
+        }
+    }
+
+    // 去掉 \Deleted 之后再移动；服务器上已在目标文件夹的邮件（如移回收件箱）不需要移动
+    if let Some(target) = action.target() {
+        for ((address, mailbox), uids) in imap_client::group_by_mailbox(&emails) {
+            let Some(account) = accounts.iter().find(|a| a.address == address) else {
+                continue;
+            };
+            if mailbox == target {
+                continue;
+            }
+            match imap_client::move_messages(account, &mailbox, target, &uids).await {
+                Ok(()) => {
+                    if let Err(e) = storage.forget_server_location(account, &mailbox, &uids) {
+                        println!("{}: 更新邮件的服务器位置失败: {}", address, e);
+                    }
+                }
+                Err(e) => println!("{}: 在服务器上移动邮件失败: {}", address, e),
+            }
+        }
+    }
+    local_only
+}
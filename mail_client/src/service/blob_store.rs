@@ -0,0 +1,193 @@
+use std::fs::{self, File, create_dir_all};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use sha2::{Digest, Sha256};
+
+// 写入临时文件时区分同一进程内的并发写入
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// 按内容寻址的附件存储，文件名为内容的 SHA-256
+// 目录结构为 blobs/ab/cdef...，相同内容的附件只保存一份，引用计数记录在数据库中
+#[derive(Clone, Debug)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn path(&self, hash: &str) -> PathBuf {
+        let (prefix, rest) = hash.split_at(2.min(hash.len()));
+        self.root.join(prefix).join(rest)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path(hash).is_file()
+    }
+
+    // 保存内容，返回 (哈希, 大小)，已存在时不重复写入
+    pub fn put(&self, data: &[u8]) -> io::Result<(String, u64)> {
+        self.put_reader(data)
+    }
+
+    // 从读取器边读边计算哈希写入临时文件，完成后按哈希重命名，不需要把整个内容放在内存中
+    pub fn put_reader<R: Read>(&self, mut reader: R) -> io::Result<(String, u64)> {
+        let tmp_dir = self.root.join("tmp");
+        create_dir_all(&tmp_dir)?;
+        let tmp_path = tmp_dir.join(format!(
+            "{}-{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        {
+            let mut file = File::create(&tmp_path)?;
+            let mut buffer = [0u8; 64 * 1024];
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                file.write_all(&buffer[..read])?;
+                size += read as u64;
+            }
+            file.sync_all()?;
+        }
+
+        let hash = format!("{:x}", hasher.finalize());
+        let path = self.path(&hash);
+        if path.is_file() {
+            fs::remove_file(&tmp_path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                create_dir_all(parent)?;
+            }
+            fs::rename(&tmp_path, &path)?;
+        }
+        Ok((hash, size))
+    }
+
+    // 打开附件用于流式读取
+    pub fn open(&self, hash: &str) -> io::Result<File> {
+        File::open(self.path(hash))
+    }
+
+    pub fn remove(&self, hash: &str) -> io::Result<u64> {
+        let path = self.path(hash);
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(size),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    // 列出所有已保存的哈希（不含未完成的临时文件）
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let mut hashes = Vec::new();
+        if !self.root.is_dir() {
+            return Ok(hashes);
+        }
+        for prefix_entry in fs::read_dir(&self.root)? {
+            let prefix_path = prefix_entry?.path();
+            let Some(prefix) = prefix_path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if !prefix_path.is_dir() || prefix == "tmp" {
+                continue;
+            }
+            for entry in fs::read_dir(&prefix_path)? {
+                if let Some(rest) = entry?.file_name().to_str() {
+                    hashes.push(format!("{}{}", prefix, rest));
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    // 文件是否在指定时间之前写入，清理时跳过可能正在写入的新文件
+    pub fn is_older_than(&self, hash: &str, age: Duration) -> bool {
+        is_older_than(&self.path(hash), age)
+    }
+
+    // 清理中断写入留下的临时文件
+    pub fn clear_tmp(&self, age: Duration) -> io::Result<()> {
+        let tmp_dir = self.root.join("tmp");
+        if tmp_dir.is_dir() {
+            for entry in fs::read_dir(&tmp_dir)? {
+                let path = entry?.path();
+                if is_older_than(&path, age) {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_older_than(path: &Path, age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|elapsed| elapsed > age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> BlobStore {
+        let root = std::env::temp_dir().join(format!("rustmail-blobs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        BlobStore::new(root)
+    }
+
+    // 相同内容只保存一份，文件名为内容的 SHA-256
+    #[test]
+    fn put_deduplicates_by_content() {
+        let store = temp_store("put");
+        let (hash, size) = store.put(b"hello").unwrap();
+        assert_eq!(hash, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        assert_eq!(size, 5);
+        assert!(store.path(&hash).ends_with("2c/f24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"));
+
+        let (again, _) = store.put_reader(&b"hello"[..]).unwrap();
+        assert_eq!(again, hash);
+        let (other, _) = store.put(b"world").unwrap();
+        let mut listed = store.list().unwrap();
+        listed.sort();
+        let mut expected = vec![hash.clone(), other];
+        expected.sort();
+        assert_eq!(listed, expected);
+        // 临时文件写完后都已重命名或删除
+        assert_eq!(fs::read_dir(store.root.join("tmp")).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&store.root);
+    }
+
+    // 流式读取的内容与写入的一致，删除不存在的文件不报错
+    #[test]
+    fn open_and_remove() {
+        let store = temp_store("open");
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let (hash, size) = store.put(&data).unwrap();
+        assert_eq!(size, data.len() as u64);
+
+        let mut read = Vec::new();
+        store.open(&hash).unwrap().read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        assert_eq!(store.remove(&hash).unwrap(), size);
+        assert!(!store.contains(&hash));
+        assert_eq!(store.remove(&hash).unwrap(), 0);
+        assert!(store.open(&hash).is_err());
+        let _ = fs::remove_dir_all(&store.root);
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use crate::models::{Attachment, EmailAccount};
+use crate::service::address_book::AddressBook;
+use crate::service::dav::{self, DavClient, DavResult};
+use crate::service::vcard::{fold, split_property, unescape, unfold};
+
+const CALENDAR_TYPE: &str = "text/calendar; charset=utf-8";
+
+// 邮件附带的会议邀请（iTIP）
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Invitation {
+    pub uid: String,
+    pub method: String, // REQUEST 为邀请，CANCEL、REPLY 等不能接受
+    pub summary: String,
+    pub start: String, // 已格式化为本地时间
+    pub location: String,
+    pub organizer: String,
+}
+
+impl Invitation {
+    // 解析日历文件中的第一个事件
+    pub fn parse(ics: &str) -> Option<Self> {
+        let mut invitation = Invitation::default();
+        let mut in_event = false;
+        for line in unfold(ics) {
+            let Some((name, params, value)) = split_property(&line) else {
+                continue;
+            };
+            match name.as_str() {
+                "METHOD" => invitation.method = value.trim().to_uppercase(),
+                "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => in_event = true,
+                "END" if value.eq_ignore_ascii_case("VEVENT") => break,
+                _ if !in_event => {}
+                "UID" => invitation.uid = value.trim().to_string(),
+                "SUMMARY" => invitation.summary = unescape(&value),
+                "LOCATION" => invitation.location = unescape(&value),
+                "DTSTART" => invitation.start = format_start(&params, value.trim()),
+                "ORGANIZER" => {
+                    invitation.organizer = param(&params, "CN").unwrap_or_else(|| strip_mailto(&value).to_string());
+                }
+                _ => {}
+            }
+        }
+        (in_event && !invitation.uid.is_empty()).then_some(invitation)
+    }
+
+    // 没有 METHOD 的日历文件也按邀请处理
+    pub fn is_request(&self) -> bool {
+        self.method.is_empty() || self.method == "REQUEST"
+    }
+}
+
+// 附件是否为日历文件（text/calendar 或 .ics 文件）
+pub fn is_calendar_attachment(attachment: &Attachment) -> bool {
+    attachment.content_type.eq_ignore_ascii_case("text/calendar")
+        || attachment.content_type.eq_ignore_ascii_case("application/ics")
+        || attachment.filename.to_lowercase().ends_with(".ics")
+}
+
+// 生成接受邀请后保存到日历的内容：去掉 METHOD，把自己的参与状态设为已接受
+// 日历服务器支持日程调度（RFC 6638）时会据此通知组织者
+pub fn accepted_event(ics: &str, attendee: &str) -> String {
+    let mut out = String::new();
+    for line in unfold(ics) {
+        let Some((name, params, value)) = split_property(&line) else {
+            fold(&mut out, &line);
+            continue;
+        };
+        match name.as_str() {
+            "METHOD" => {}
+            "ATTENDEE" if strip_mailto(&value).eq_ignore_ascii_case(attendee) => {
+                let mut parts: Vec<String> = split_params(&params)
+                    .into_iter()
+                    .filter(|p| {
+                        let key = p.split('=').next().unwrap_or_default().to_uppercase();
+                        key != "PARTSTAT" && key != "RSVP"
+                    })
+                    .collect();
+                parts.push("PARTSTAT=ACCEPTED".to_string());
+                fold(&mut out, &format!("ATTENDEE;{}:{}", parts.join(";"), value));
+            }
+            _ => fold(&mut out, &line),
+        }
+    }
+    out
+}
+
+// 把邀请加入账户的默认日历，返回日历名称
+pub fn accept_invitation(book: &AddressBook, account: &EmailAccount, ics: &str) -> DavResult<String> {
+    let invitation = Invitation::parse(ics).ok_or("无法识别的会议邀请")?;
+    let calendar = book
+        .default_calendar(&account.address)?
+        .ok_or("还没有设置 CalDAV 日历，请先在设置中发现日历")?;
+    let client = DavClient::new(&account.address, &account.password);
+    let href = dav::resource_url(&calendar.url, &format!("{}.ics", invitation.uid))?;
+    let body = accepted_event(ics, &account.address);
+
+    let mut response = client.put(&href, CALENDAR_TYPE, &body, None)?;
+    // 已经接受过的邀请被更新时覆盖日历中的事件
+    if response.status == 412 {
+        let current = client.get(&href)?;
+        let etag = current.etag().ok_or("无法读取日历中已有的事件")?;
+        response = client.put(&href, CALENDAR_TYPE, &body, Some(&etag))?;
+    }
+    if !response.is_success() {
+        return Err(format!("保存到日历失败: HTTP {}", response.status).into());
+    }
+    Ok(calendar.name)
+}
+
+// DTSTART 转为本地时间显示，带 TZID 的时间按原样显示
+fn format_start(params: &str, value: &str) -> String {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return date.format("%Y年%-m月%-d日").to_string();
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        if let Ok(time) = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S") {
+            return Utc.from_utc_datetime(&time).with_timezone(&Local).format("%Y年%-m月%-d日 %H:%M").to_string();
+        }
+    }
+    match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(time) => match param(params, "TZID") {
+            Some(tz) => format!("{} ({})", time.format("%Y年%-m月%-d日 %H:%M"), tz),
+            None => time.format("%Y年%-m月%-d日 %H:%M").to_string(),
+        },
+        Err(_) => value.to_string(),
+    }
+}
+
+fn strip_mailto(value: &str) -> &str {
+    let value = value.trim();
+    if value.len() > 7 && value[..7].eq_ignore_ascii_case("mailto:") {
+        &value[7..]
+    } else {
+        value
+    }
+}
+
+// 按分号拆分参数，引号中的分号不作为分隔
+fn split_params(params: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in params.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+fn param(params: &str, key: &str) -> Option<String> {
+    split_params(params).into_iter().find_map(|p| {
+        let (name, value) = p.split_once('=')?;
+        name.eq_ignore_ascii_case(key).then(|| value.trim_matches('"').to_string())
+    })
+}
@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+use crate::models::{Contact, EmailAccount};
+use crate::service::address_book::AddressBook;
+use crate::service::dav::{self, DavClient, DavResource, DavResult, DavService};
+use crate::service::local_storage::LocalStorage;
+use crate::service::vcard::{self, VCardEntry, VCardVersion};
+
+// addressbook-multiget 每次取回的联系人数量
+const MULTIGET_BATCH: usize = 50;
+// 上传使用 3.0，所有 CardDAV 服务器都支持
+const UPLOAD_VERSION: VCardVersion = VCardVersion::V3;
+const VCARD_TYPE: &str = "text/vcard; charset=utf-8";
+
+// 一次同步的结果，用于在界面上提示
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContactSyncReport {
+    pub downloaded: usize,
+    pub uploaded: usize,
+    pub deleted: usize,
+    pub conflicts: usize, // 服务器和本地都修改过、已合并的联系人数
+}
+
+impl ContactSyncReport {
+    fn add(&mut self, other: &ContactSyncReport) {
+        self.downloaded += other.downloaded;
+        self.uploaded += other.uploaded;
+        self.deleted += other.deleted;
+        self.conflicts += other.conflicts;
+    }
+}
+
+// 同步一个通讯录：先取回服务器的变化，再上传本地的修改和删除
+// include_new 为 true 时把还没有上传过的本地联系人上传到这个通讯录
+pub fn sync_contacts(book: &AddressBook, client: &DavClient, collection: &str, include_new: bool) -> DavResult<ContactSyncReport> {
+    let mut report = ContactSyncReport::default();
+    let known = book.dav_etags(collection)?;
+    let token = book.dav_sync_token(collection)?;
+
+    // 有令牌时增量同步，令牌失效或服务器不支持时取完整列表
+    let mut changes = match token.as_deref() {
+        Some(token) => client.sync_collection(collection, Some(token))?,
+        None => None,
+    };
+    let incremental = changes.is_some();
+    if changes.is_none() {
+        changes = client.sync_collection(collection, None)?;
+    }
+    let (resources, new_token) = match changes {
+        Some(status) => (status.responses, status.sync_token),
+        None => (client.propfind(collection, "1", "<d:getetag/><d:resourcetype/>")?.responses, None),
+    };
+
+    let resources: Vec<DavResource> = resources
+        .into_iter()
+        .filter(|r| !dav::same_url(&r.href, collection) && !r.resource_types.iter().any(|t| t == "collection"))
+        .collect();
+
+    // 新增或修改过的资源
+    let changed: Vec<String> = resources
+        .iter()
+        .filter(|r| r.status / 100 == 2)
+        .filter(|r| r.etag.is_none() || known.get(&r.href) != r.etag.as_ref())
+        .map(|r| r.href.clone())
+        .collect();
+    // 增量同步中报告为 404 的资源已删除，完整列表中没有出现的已同步资源也已删除
+    let mut deleted: Vec<String> = resources.iter().filter(|r| r.status == 404).map(|r| r.href.clone()).collect();
+    if !incremental {
+        let listed: HashSet<&str> = resources.iter().map(|r| r.href.as_str()).collect();
+        deleted.extend(known.keys().filter(|href| !listed.contains(href.as_str())).cloned());
+    }
+
+    for batch in changed.chunks(MULTIGET_BATCH) {
+        let status = client.multiget(collection, DavService::CardDav, batch)?;
+        for resource in status.responses.into_iter().filter(|r| r.status / 100 == 2) {
+            let Some(remote) = resource.data.as_deref().and_then(first_contact) else {
+                continue;
+            };
+            let etag = resource.etag.clone().unwrap_or_default();
+            // 本地删除后服务器上又被修改：保留服务器的版本
+            if book.has_dav_tombstone(&resource.href)? {
+                book.clear_dav_tombstone(&resource.href)?;
+                report.conflicts += 1;
+            }
+            match book.dav_contact(&resource.href)? {
+                Some((local, true)) => {
+                    book.save_dav_contact(collection, &resource.href, &etag, &merge_contacts(&local, &remote), true)?;
+                    report.conflicts += 1;
+                }
+                _ => book.save_dav_contact(collection, &resource.href, &etag, &remote, false)?,
+            }
+            report.downloaded += 1;
+        }
+    }
+
+    for href in &deleted {
+        match book.dav_contact(href)? {
+            // 服务器上删除但本地修改过：保留本地的联系人，之后重新上传
+            Some((_, true)) => {
+                book.forget_dav_resource(href)?;
+                report.conflicts += 1;
+            }
+            Some(_) => {
+                book.remove_dav_contact(href)?;
+                report.deleted += 1;
+            }
+            None => book.clear_dav_tombstone(href)?,
+        }
+    }
+
+    // 本地删除的联系人，服务器上已修改时保留服务器的版本，下次完整同步时重新下载
+    let mut keep_token = true;
+    for (href, etag) in book.dav_tombstones(collection)? {
+        let response = client.delete(&href, &etag)?;
+        match response.status {
+            412 => {
+                keep_token = false;
+                report.conflicts += 1;
+            }
+            status if status / 100 != 2 && status != 404 => {
+                println!("删除联系人 {} 失败: HTTP {}", href, status);
+                continue;
+            }
+            _ => report.deleted += 1,
+        }
+        book.clear_dav_tombstone(&href)?;
+    }
+
+    for (contact, resource) in book.dirty_contacts(collection, include_new)? {
+        let (href, etag) = match resource {
+            Some((href, etag)) => (href, Some(etag).filter(|e| !e.is_empty())),
+            None => (dav::resource_url(collection, &format!("{}.vcf", contact.uid))?, None),
+        };
+        match upload_contact(client, &href, &contact, etag.as_deref())? {
+            Upload::Done(etag) => book.mark_dav_uploaded(&contact.uid, collection, &href, &etag)?,
+            Upload::Conflict(remote, remote_etag) => {
+                // 上传时服务器上的版本已被修改：合并后按服务器的 ETag 再上传一次
+                let merged = Contact { uid: contact.uid.clone(), ..merge_contacts(&contact, &remote) };
+                report.conflicts += 1;
+                match upload_contact(client, &href, &merged, Some(&remote_etag))? {
+                    Upload::Done(etag) => {
+                        book.save_dav_contact(collection, &href, &etag, &merged, false)?;
+                    }
+                    Upload::Conflict(..) => {
+                        println!("联系人 {} 在服务器上持续变化，下次同步时重试", href);
+                        keep_token = false;
+                        continue;
+                    }
+                }
+            }
+        }
+        report.uploaded += 1;
+    }
+
+    let token = if keep_token { new_token } else { None };
+    book.set_dav_sync_token(collection, token.as_deref())?;
+    Ok(report)
+}
+
+enum Upload {
+    Done(String),              // 新的 ETag，服务器没有返回时为空
+    Conflict(Contact, String), // 服务器上的版本及其 ETag
+}
+
+fn upload_contact(client: &DavClient, href: &str, contact: &Contact, etag: Option<&str>) -> DavResult<Upload> {
+    let body = vcard::write_vcards(std::slice::from_ref(contact), &[], UPLOAD_VERSION);
+    let response = client.put(href, VCARD_TYPE, &body, etag)?;
+    if response.is_success() {
+        return Ok(Upload::Done(response.etag().unwrap_or_default()));
+    }
+    if response.status != 412 {
+        return Err(format!("上传联系人 {} 失败: HTTP {}", href, response.status).into());
+    }
+    let current = client.get(href)?;
+    if !current.is_success() {
+        return Err(format!("读取联系人 {} 失败: HTTP {}", href, current.status).into());
+    }
+    let remote = first_contact(&current.body).unwrap_or_default();
+    Ok(Upload::Conflict(remote, current.etag().unwrap_or_default()))
+}
+
+fn first_contact(data: &str) -> Option<Contact> {
+    vcard::parse_vcards(data).into_iter().find_map(|entry| match entry {
+        VCardEntry::Contact(contact) => Some(contact),
+        VCardEntry::Group { .. } => None,
+    })
+}
+
+// 本地和服务器都修改了同一个联系人：地址和电话取两边的并集，姓名等字段以本地为准
+pub fn merge_contacts(local: &Contact, remote: &Contact) -> Contact {
+    let pick = |local: &str, remote: &str| if local.trim().is_empty() { remote.to_string() } else { local.to_string() };
+    let union = |local: &[String], remote: &[String]| {
+        let mut values = local.to_vec();
+        for value in remote {
+            if !values.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+                values.push(value.clone());
+            }
+        }
+        values
+    };
+    Contact {
+        uid: local.uid.clone(),
+        name: pick(&local.name, &remote.name),
+        emails: union(&local.emails, &remote.emails),
+        phones: union(&local.phones, &remote.phones),
+        organization: pick(&local.organization, &remote.organization),
+        note: pick(&local.note, &remote.note),
+        harvested: false,
+    }
+}
+
+// 同步账户的所有通讯录，新建的联系人上传到第一个通讯录
+pub fn sync_account_contacts(book: &AddressBook, account: &EmailAccount) -> DavResult<ContactSyncReport> {
+    let client = DavClient::new(&account.address, &account.password);
+    let mut report = ContactSyncReport::default();
+    for (index, collection) in book.dav_collections(&account.address, DavService::CardDav)?.iter().enumerate() {
+        report.add(&sync_contacts(book, &client, &collection.url, index == 0)?);
+    }
+    Ok(report)
+}
+
+// 在后台线程同步所有已设置 CardDAV 的账户，邮件同步完成后调用
+pub async fn sync_all_contacts(storage: LocalStorage, accounts: Vec<EmailAccount>) {
+    async_std::task::spawn_blocking(move || {
+        for account in &accounts {
+            match sync_account_contacts(storage.address_book(), account) {
+                Ok(report) if report != ContactSyncReport::default() => println!(
+                    "{}: 通讯录同步完成，下载 {}，上传 {}，删除 {}，冲突 {}",
+                    account.address, report.downloaded, report.uploaded, report.deleted, report.conflicts
+                ),
+                Ok(_) => {}
+                Err(e) => println!("{}: 同步通讯录失败: {}", account.address, e),
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str, emails: &[&str], phones: &[&str]) -> Contact {
+        Contact {
+            uid: "remote-uid".into(),
+            name: name.into(),
+            emails: emails.iter().map(|e| e.to_string()).collect(),
+            phones: phones.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_keeps_local_fields_and_unions_lists() {
+        let local = Contact { uid: "local-uid".into(), note: "本地备注".into(), ..contact("张三", &["a@x.com"], &["123"]) };
+        let remote = Contact { organization: "ACME".into(), note: "服务器备注".into(), ..contact("Zhang San", &["A@X.com", "b@x.com"], &["456"]) };
+
+        let merged = merge_contacts(&local, &remote);
+        assert_eq!(merged.uid, "local-uid");
+        assert_eq!(merged.name, "张三");
+        assert_eq!(merged.emails, vec!["a@x.com", "b@x.com"]);
+        assert_eq!(merged.phones, vec!["123", "456"]);
+        assert_eq!(merged.organization, "ACME");
+        assert_eq!(merged.note, "本地备注");
+        assert!(!merged.harvested);
+    }
+
+    #[test]
+    fn merge_fills_blank_local_name() {
+        let local = contact("  ", &["a@x.com"], &[]);
+        let remote = contact("Remote", &[], &[]);
+        assert_eq!(merge_contacts(&local, &remote).name, "Remote");
+    }
+}
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use url::Url;
+use crate::models::EmailAccount;
+use crate::service::address_book::AddressBook;
+use crate::service::autoconfig::{DnsSrvResolver, SrvResolver};
+
+// 单次请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+// 手动跟随重定向的次数，ureq 会把 PROPFIND 的 301 重定向改为 GET，因此不用它的重定向
+const MAX_REDIRECTS: usize = 5;
+
+const NS_DAV: &str = "DAV:";
+const NS_CARDDAV: &str = "urn:ietf:params:xml:ns:carddav";
+const NS_CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+
+pub type DavResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DavRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DavResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl DavResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    // 服务器返回的 ETag，弱 ETag 也按原样比较
+    pub fn etag(&self) -> Option<String> {
+        self.header("ETag").map(str::to_string)
+    }
+}
+
+// 发送 HTTP 请求，测试时可替换为本地的 Radicale 等服务或模拟实现
+pub trait DavTransport: Send + Sync {
+    fn send(&self, request: &DavRequest) -> DavResult<DavResponse>;
+}
+
+// 基于 ureq 的实现，使用 Basic 认证，非 2xx 的响应也作为结果返回
+pub struct HttpTransport {
+    agent: ureq::Agent,
+    authorization: String,
+}
+
+impl HttpTransport {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).redirects(0).build(),
+            authorization: format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", username, password))),
+        }
+    }
+}
+
+impl DavTransport for HttpTransport {
+    fn send(&self, request: &DavRequest) -> DavResult<DavResponse> {
+        let mut builder = self
+            .agent
+            .request(&request.method, &request.url)
+            .set("Authorization", &self.authorization);
+        for (name, value) in &request.headers {
+            builder = builder.set(name, value);
+        }
+        let result = match &request.body {
+            Some(body) => builder.send_string(body),
+            None => builder.call(),
+        };
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.into()),
+        };
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| response.header(&name).map(|value| (name.clone(), value.to_string())))
+            .collect();
+        Ok(DavResponse { status: response.status(), headers, body: response.into_string()? })
+    }
+}
+
+// 服务类型，决定发现时使用的 well-known 地址、SRV 记录和集合类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DavService {
+    CardDav,
+    CalDav,
+}
+
+impl DavService {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DavService::CardDav => "carddav",
+            DavService::CalDav => "caldav",
+        }
+    }
+
+    fn home_set(&self) -> (&'static str, &'static str) {
+        match self {
+            DavService::CardDav => (NS_CARDDAV, "addressbook-home-set"),
+            DavService::CalDav => (NS_CALDAV, "calendar-home-set"),
+        }
+    }
+
+    fn collection_type(&self) -> &'static str {
+        match self {
+            DavService::CardDav => "addressbook",
+            DavService::CalDav => "calendar",
+        }
+    }
+}
+
+// 发现的通讯录或日历
+#[derive(Clone, Debug, PartialEq)]
+pub struct DavCollection {
+    pub url: String,
+    pub name: String,
+    pub service: DavService,
+    pub components: Vec<String>, // 日历支持的组件（VEVENT、VTODO），为空表示未声明
+}
+
+impl DavCollection {
+    // 能否保存会议邀请
+    pub fn supports_events(&self) -> bool {
+        self.components.is_empty() || self.components.iter().any(|c| c.eq_ignore_ascii_case("VEVENT"))
+    }
+}
+
+// multistatus 中的一个资源，属性只包含状态为 200 的部分
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DavResource {
+    pub href: String, // 解析为绝对地址
+    pub status: u16,  // 响应级别的状态，sync-collection 中 404 表示已删除
+    pub etag: Option<String>,
+    pub display_name: Option<String>,
+    pub resource_types: Vec<String>,
+    pub hrefs: HashMap<String, Vec<String>>, // 值为地址的属性，如 current-user-principal
+    pub data: Option<String>,                // address-data 或 calendar-data
+    pub components: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultiStatus {
+    pub responses: Vec<DavResource>,
+    pub sync_token: Option<String>,
+}
+
+// WebDAV 客户端，实现 CardDAV/CalDAV 需要的请求
+pub struct DavClient {
+    transport: Box<dyn DavTransport>,
+    resolver: Option<Box<dyn SrvResolver>>,
+}
+
+impl DavClient {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            transport: Box::new(HttpTransport::new(username, password)),
+            resolver: DnsSrvResolver::from_system_conf()
+                .ok()
+                .map(|resolver| Box::new(resolver) as Box<dyn SrvResolver>),
+        }
+    }
+
+    // 发送请求并跟随重定向，返回最终地址和响应
+    fn send(&self, method: &str, url: &str, headers: &[(&str, &str)], body: Option<&str>) -> DavResult<(String, DavResponse)> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let request = DavRequest {
+                method: method.to_string(),
+                url: url.clone(),
+                headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                body: body.map(str::to_string),
+            };
+            let response = self.transport.send(&request)?;
+            if response.status == 401 {
+                return Err(format!("服务器拒绝了用户名或密码: {}", url).into());
+            }
+            match (response.status, response.header("Location")) {
+                (301 | 302 | 303 | 307 | 308, Some(location)) => url = Url::parse(&url)?.join(location)?.to_string(),
+                _ => return Ok((url, response)),
+            }
+        }
+        Err(format!("重定向次数过多: {}", url).into())
+    }
+
+    // PROPFIND，props 为 <d:prop> 中的内容
+    pub fn propfind(&self, url: &str, depth: &str, props: &str) -> DavResult<MultiStatus> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><d:propfind {}><d:prop>{}</d:prop></d:propfind>"#,
+            XMLNS, props
+        );
+        let (url, response) = self.send(
+            "PROPFIND",
+            url,
+            &[("Depth", depth), ("Content-Type", "application/xml; charset=utf-8")],
+            Some(&body),
+        )?;
+        if response.status != 207 {
+            return Err(format!("PROPFIND {} 失败: HTTP {}", url, response.status).into());
+        }
+        parse_multistatus(&response.body, &url)
+    }
+
+    // REPORT 请求，返回 multistatus
+    pub fn report(&self, url: &str, depth: &str, body: &str) -> DavResult<MultiStatus> {
+        let (url, response) = self.report_raw(url, depth, body)?;
+        if response.status != 207 {
+            return Err(format!("REPORT {} 失败: HTTP {}", url, response.status).into());
+        }
+        parse_multistatus(&response.body, &url)
+    }
+
+    fn report_raw(&self, url: &str, depth: &str, body: &str) -> DavResult<(String, DavResponse)> {
+        let body = format!(r#"<?xml version="1.0" encoding="utf-8"?>{}"#, body);
+        self.send(
+            "REPORT",
+            url,
+            &[("Depth", depth), ("Content-Type", "application/xml; charset=utf-8")],
+            Some(&body),
+        )
+    }
+
+    // RFC 6578 增量同步，token 为空时返回全部资源
+    // 服务器不支持或令牌已失效时返回 None，由调用方重新完整同步
+    pub fn sync_collection(&self, url: &str, token: Option<&str>) -> DavResult<Option<MultiStatus>> {
+        let body = format!(
+            "<d:sync-collection {}><d:sync-token>{}</d:sync-token><d:sync-level>1</d:sync-level>\
+             <d:prop><d:getetag/></d:prop></d:sync-collection>",
+            XMLNS,
+            escape_xml(token.unwrap_or_default())
+        );
+        let (url, response) = self.report_raw(url, "0", &body)?;
+        if response.status == 207 {
+            return parse_multistatus(&response.body, &url).map(Some);
+        }
+        println!("{} 不支持增量同步或同步令牌已失效: HTTP {}", url, response.status);
+        Ok(None)
+    }
+
+    // 一次取回多个资源的内容（addressbook-multiget / calendar-multiget）
+    pub fn multiget(&self, url: &str, service: DavService, hrefs: &[String]) -> DavResult<MultiStatus> {
+        let (report, data) = match service {
+            DavService::CardDav => ("card:addressbook-multiget", "card:address-data"),
+            DavService::CalDav => ("cal:calendar-multiget", "cal:calendar-data"),
+        };
+        let mut body = format!("<{} {}><d:prop><d:getetag/><{}/></d:prop>", report, XMLNS, data);
+        for href in hrefs {
+            // 请求中使用路径，部分服务器不接受完整地址
+            let path = Url::parse(href).map(|u| u.path().to_string()).unwrap_or_else(|_| href.clone());
+            body.push_str(&format!("<d:href>{}</d:href>", escape_xml(&path)));
+        }
+        body.push_str(&format!("</{}>", report));
+        self.report(url, "1", &body)
+    }
+
+    pub fn get(&self, url: &str) -> DavResult<DavResponse> {
+        Ok(self.send("GET", url, &[], None)?.1)
+    }
+
+    // 上传资源，etag 为 None 时只在资源不存在时创建，否则只在 ETag 一致时覆盖
+    // 返回 412 表示服务器上的资源已被修改，由调用方处理冲突
+    pub fn put(&self, url: &str, content_type: &str, body: &str, etag: Option<&str>) -> DavResult<DavResponse> {
+        let precondition = match etag {
+            Some(etag) => ("If-Match", etag),
+            None => ("If-None-Match", "*"),
+        };
+        Ok(self.send("PUT", url, &[("Content-Type", content_type), precondition], Some(body))?.1)
+    }
+
+    pub fn delete(&self, url: &str, etag: &str) -> DavResult<DavResponse> {
+        let headers: &[(&str, &str)] = if etag.is_empty() { &[] } else { &[("If-Match", etag)] };
+        Ok(self.send("DELETE", url, headers, None)?.1)
+    }
+
+    // RFC 6764 服务发现：指定地址、SRV 记录或 well-known 地址 -> 当前用户主体 -> 主目录 -> 集合
+    // server 为空时根据邮箱域名查找
+    pub fn discover(&self, server: &str, address: &str, service: DavService) -> DavResult<Vec<DavCollection>> {
+        let mut errors = Vec::new();
+        for start in self.start_urls(server, address, service) {
+            match self.discover_from(&start, service) {
+                Ok(collections) if !collections.is_empty() => return Ok(collections),
+                Ok(_) => errors.push(format!("{}: 没有找到{}", start, service_label(service))),
+                Err(e) => errors.push(format!("{}: {}", start, e)),
+            }
+        }
+        Err(errors.join("\n").into())
+    }
+
+    fn start_urls(&self, server: &str, address: &str, service: DavService) -> Vec<String> {
+        let well_known = format!("/.well-known/{}", service.as_str());
+        let server = server.trim();
+        if !server.is_empty() {
+            let url = if server.contains("://") { server.to_string() } else { format!("https://{}", server) };
+            let mut urls = vec![url.clone()];
+            if let Ok(origin) = Url::parse(&url).and_then(|u| u.join(&well_known)) {
+                urls.push(origin.to_string());
+            }
+            return urls;
+        }
+
+        let Some((_, domain)) = address.trim().rsplit_once('@') else {
+            return Vec::new();
+        };
+        let domain = domain.to_lowercase();
+        let mut urls = Vec::new();
+        if let Some(resolver) = &self.resolver {
+            let name = format!("_{}s._tcp.{}.", service.as_str(), domain);
+            match resolver.lookup_srv(&name) {
+                Ok(mut records) => {
+                    records.retain(|r| !r.target.is_empty() && r.target != ".");
+                    records.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)));
+                    urls.extend(records.iter().map(|r| format!("https://{}:{}{}", r.target, r.port, well_known)));
+                }
+                Err(e) => println!("查询 SRV 记录失败 {}: {}", name, e),
+            }
+        }
+        urls.push(format!("https://{}{}", domain, well_known));
+        urls
+    }
+
+    fn discover_from(&self, start: &str, service: DavService) -> DavResult<Vec<DavCollection>> {
+        let (home_ns, home_name) = service.home_set();
+        let props = "<d:resourcetype/><d:displayname/><d:current-user-principal/>\
+                     <card:addressbook-home-set/><cal:calendar-home-set/><cal:supported-calendar-component-set/>";
+
+        // 起始地址本身就是通讯录或日历时直接使用
+        let status = self.propfind(start, "0", props)?;
+        let Some(first) = status.responses.into_iter().next() else {
+            return Ok(Vec::new());
+        };
+        if first.resource_types.iter().any(|t| t == service.collection_type()) {
+            return Ok(vec![to_collection(first, service)]);
+        }
+
+        let principal = first
+            .hrefs
+            .get("current-user-principal")
+            .and_then(|h| h.first().cloned())
+            .unwrap_or_else(|| first.href.clone());
+        let mut homes = first.hrefs.get(home_name).cloned().unwrap_or_default();
+        if homes.is_empty() {
+            let status = self.propfind(&principal, "0", &format!("<{}:{}/>", prefix(home_ns), home_name))?;
+            homes = status
+                .responses
+                .into_iter()
+                .flat_map(|r| r.hrefs.get(home_name).cloned().unwrap_or_default())
+                .collect();
+        }
+        if homes.is_empty() {
+            homes.push(principal);
+        }
+
+        let mut collections = Vec::new();
+        for home in homes {
+            let status = self.propfind(
+                &home,
+                "1",
+                "<d:resourcetype/><d:displayname/><cal:supported-calendar-component-set/>",
+            )?;
+            collections.extend(
+                status
+                    .responses
+                    .into_iter()
+                    .filter(|r| r.resource_types.iter().any(|t| t == service.collection_type()))
+                    .map(|r| to_collection(r, service)),
+            );
+        }
+        Ok(collections)
+    }
+}
+
+const XMLNS: &str = r#"xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav" xmlns:cal="urn:ietf:params:xml:ns:caldav""#;
+
+fn prefix(namespace: &str) -> &'static str {
+    match namespace {
+        NS_CARDDAV => "card",
+        NS_CALDAV => "cal",
+        _ => "d",
+    }
+}
+
+fn service_label(service: DavService) -> &'static str {
+    match service {
+        DavService::CardDav => "通讯录",
+        DavService::CalDav => "日历",
+    }
+}
+
+fn to_collection(resource: DavResource, service: DavService) -> DavCollection {
+    // 没有显示名时使用地址的最后一段
+    let name = resource.display_name.clone().filter(|n| !n.trim().is_empty()).unwrap_or_else(|| {
+        resource.href.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string()
+    });
+    DavCollection { url: resource.href, name, service, components: resource.components }
+}
+
+// 解析 207 Multi-Status 响应，href 按请求地址解析为绝对地址
+pub fn parse_multistatus(xml: &str, base: &str) -> DavResult<MultiStatus> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let base = Url::parse(base)?;
+    let resolve = |href: &str| base.join(href.trim()).map(|u| u.to_string()).unwrap_or_else(|_| href.trim().to_string());
+
+    let root = doc.root_element();
+    let sync_token = root
+        .children()
+        .find(|n| is_dav(n, "sync-token"))
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string());
+
+    let mut responses = Vec::new();
+    for node in root.children().filter(|n| is_dav(n, "response")) {
+        let mut resource = DavResource { status: 200, ..Default::default() };
+        for child in node.children().filter(|n| n.is_element()) {
+            if is_dav(&child, "href") && resource.href.is_empty() {
+                resource.href = resolve(child.text().unwrap_or_default());
+            } else if is_dav(&child, "status") {
+                resource.status = parse_status(child.text().unwrap_or_default());
+            } else if is_dav(&child, "propstat") {
+                let ok = child
+                    .children()
+                    .find(|n| is_dav(n, "status"))
+                    .is_none_or(|n| parse_status(n.text().unwrap_or_default()) / 100 == 2);
+                if !ok {
+                    continue;
+                }
+                for prop in child.children().filter(|n| is_dav(n, "prop")).flat_map(|n| n.children()) {
+                    if !prop.is_element() {
+                        continue;
+                    }
+                    let text = || prop.text().map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+                    match prop.tag_name().name() {
+                        "getetag" => resource.etag = text(),
+                        "displayname" => resource.display_name = text(),
+                        "resourcetype" => {
+                            resource.resource_types =
+                                prop.children().filter(|n| n.is_element()).map(|n| n.tag_name().name().to_string()).collect();
+                        }
+                        "address-data" | "calendar-data" => resource.data = prop.text().map(str::to_string),
+                        "supported-calendar-component-set" => {
+                            resource.components = prop
+                                .children()
+                                .filter_map(|n| n.attribute("name"))
+                                .map(str::to_string)
+                                .collect();
+                        }
+                        name => {
+                            let hrefs: Vec<String> = prop
+                                .children()
+                                .filter(|n| is_dav(n, "href"))
+                                .filter_map(|n| n.text())
+                                .map(resolve)
+                                .collect();
+                            if !hrefs.is_empty() {
+                                resource.hrefs.insert(name.to_string(), hrefs);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if !resource.href.is_empty() {
+            responses.push(resource);
+        }
+    }
+    Ok(MultiStatus { responses, sync_token })
+}
+
+fn is_dav(node: &roxmltree::Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(NS_DAV)
+}
+
+// "HTTP/1.1 404 Not Found" -> 404
+fn parse_status(line: &str) -> u16 {
+    line.split_whitespace().nth(1).and_then(|code| code.parse().ok()).unwrap_or(0)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// 两个地址是否指向同一资源，忽略末尾的斜杠
+pub fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+// 在集合下为新资源生成地址，文件名中的特殊字符会被转义
+pub fn resource_url(collection: &str, name: &str) -> DavResult<String> {
+    let mut url = Url::parse(collection)?;
+    url.path_segments_mut()
+        .map_err(|_| format!("无效的集合地址: {}", collection))?
+        .pop_if_empty()
+        .push(name);
+    Ok(url.to_string())
+}
+
+// 发现账户的通讯录和日历并保存，服务器只提供其中一种时也算成功
+pub fn discover_account(book: &AddressBook, account: &EmailAccount, server: &str) -> DavResult<(Vec<DavCollection>, Vec<DavCollection>)> {
+    let client = DavClient::new(&account.address, &account.password);
+    let address_books = client.discover(server, &account.address, DavService::CardDav);
+    let calendars = client.discover(server, &account.address, DavService::CalDav);
+    if let (Err(card_error), Err(cal_error)) = (&address_books, &calendars) {
+        return Err(format!("{}\n{}", card_error, cal_error).into());
+    }
+    let address_books = address_books.unwrap_or_default();
+    let calendars = calendars.unwrap_or_default();
+    book.set_dav_server(&account.address, server)?;
+    book.set_dav_collections(&account.address, DavService::CardDav, &address_books)?;
+    book.set_dav_collections(&account.address, DavService::CalDav, &calendars)?;
+    Ok((address_books, calendars))
+}
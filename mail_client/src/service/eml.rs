@@ -0,0 +1,38 @@
+use mailparse::{parse_headers, MailHeaderMap};
+use crate::models::{Attachment, Email};
+use crate::service::imap_client::parse_email;
+use crate::service::mbox::content_hash;
+
+// 打开单独的 .eml 文件或邮件中附带的邮件，与同步邮件使用相同的解析流程
+// ID 由内容计算，打开的不同邮件不会混淆
+pub fn parse_eml(raw: &[u8]) -> Result<Email, Box<dyn std::error::Error + Send + Sync>> {
+    let mut email = parse_email(format!("eml-{:016x}", content_hash(raw)), raw)?;
+    email.seen = true;
+    Ok(email)
+}
+
+// 附件是否为一封完整的邮件（message/rfc822 或 .eml 文件）
+pub fn is_message_attachment(attachment: &Attachment) -> bool {
+    attachment.content_type.eq_ignore_ascii_case("message/rfc822")
+        || attachment.filename.to_lowercase().ends_with(".eml")
+}
+
+// 附带邮件没有文件名时，使用其主题作为文件名
+pub fn message_file_name(raw: &[u8]) -> String {
+    let subject = parse_headers(raw)
+        .ok()
+        .and_then(|(headers, _)| headers.get_first_value("Subject"))
+        .unwrap_or_default();
+    file_name_for(&subject)
+}
+
+// 保存邮件时的默认文件名，去掉文件系统不允许的字符
+pub fn file_name_for(subject: &str) -> String {
+    let name: String = subject
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .take(80)
+        .collect();
+    let name = name.trim();
+    if name.is_empty() { "邮件.eml".to_string() } else { format!("{}.eml", name) }
+}
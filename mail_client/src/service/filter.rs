@@ -0,0 +1,297 @@
+use chrono::Local;
+use crate::models::{format_address_list, ActionKind, Address, Email, EmailAccount, EmailStatus, FilterRule, Mailbox};
+use crate::service::local_storage::LocalStorage;
+use crate::service::smtp_client;
+use crate::service::spam::{SpamModel, JUNK_FOLDER, KEYWORD_JUNK};
+
+type FilterResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 删除动作把邮件移到这个本地文件夹，邮件 ID 仍然保留，下次同步不会重新下载
+pub const TRASH_FOLDER: &str = "Trash";
+const INBOX: &str = "INBOX";
+
+// 规则对一封邮件的处理结果
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterOutcome {
+    pub matched: Vec<String>,   // 匹配的规则名称
+    pub folder: Option<String>, // 移动到的文件夹，None 时留在原文件夹
+    pub seen: bool,
+    pub flags: Vec<String>,
+    pub forward: Vec<String>,
+}
+
+// 按顺序检查所有规则；多个规则移动邮件时以第一个为准，删除优先于移动
+pub fn evaluate(rules: &[FilterRule], email: &Email) -> FilterOutcome {
+    let mut outcome = FilterOutcome::default();
+    let mut deleted = false;
+    for rule in rules.iter().filter(|rule| rule.matches(email)) {
+        outcome.matched.push(rule.name.clone());
+        for action in &rule.actions {
+            let value = action.value.trim();
+            match action.kind {
+                ActionKind::MoveTo if !value.is_empty() && outcome.folder.is_none() => {
+                    outcome.folder = Some(value.to_string())
+                }
+                ActionKind::AddFlag if !value.is_empty() && !outcome.flags.iter().any(|f| f == value) => {
+                    outcome.flags.push(value.to_string())
+                }
+                ActionKind::MarkRead => outcome.seen = true,
+                ActionKind::Forward if !value.is_empty() && !outcome.forward.iter().any(|f| f == value) => {
+                    outcome.forward.push(value.to_string())
+                }
+                ActionKind::Delete => deleted = true,
+                _ => {}
+            }
+        }
+        if rule.stop {
+            break;
+        }
+    }
+    if deleted {
+        outcome.folder = Some(TRASH_FOLDER.to_string());
+    }
+    outcome
+}
+
+// 保存新同步的邮件：按规则分到各个文件夹并设置标记，规则没有移动的邮件再由垃圾邮件模型判断，
+// 返回需要转发的 (地址, 邮件)
+pub fn save_new_emails(
+    storage: &mut LocalStorage,
+    account: &EmailAccount,
+    rules: &[FilterRule],
+    spam: Option<&SpamModel>,
+    emails: Vec<Email>,
+) -> FilterResult<Vec<(String, Email)>> {
+    // 收件箱总是写入一次，以更新同步时间
+    let mut folders: Vec<(String, Vec<Email>)> = vec![(INBOX.to_string(), Vec::new())];
+    let mut flags = Vec::new();
+    let mut forwards = Vec::new();
+
+    for mut email in emails {
+        let mut outcome = evaluate(rules, &email);
+        if outcome.folder.is_none() && spam.is_some_and(|model| model.is_spam(&email)) {
+            outcome.folder = Some(JUNK_FOLDER.to_string());
+            outcome.flags.push(KEYWORD_JUNK.to_string());
+        }
+        email.seen |= outcome.seen;
+        let folder = outcome.folder.unwrap_or_else(|| INBOX.to_string());
+        flags.extend(outcome.flags.into_iter().map(|flag| (folder.clone(), email.id.clone(), flag)));
+        // 自己发出的邮件（包括转发出去的）不再转发，避免转发给自己时循环
+        if !email.from.address.eq_ignore_ascii_case(&account.address) {
+            forwards.extend(outcome.forward.into_iter().map(|to| (to, email.clone())));
+        }
+        match folders.iter_mut().find(|(name, _)| *name == folder) {
+            Some((_, list)) => list.push(email),
+            None => folders.push((folder, vec![email])),
+        }
+    }
+
+    for (folder, list) in &folders {
+        storage.save_emails(account, folder, list)?;
+    }
+    for (folder, id, flag) in flags {
+        storage.set_flag(account, &folder, &id, &flag, true)?;
+    }
+    Ok(forwards)
+}
+
+// 对文件夹中已有的邮件执行规则，返回匹配的邮件数；转发只对新邮件执行，这里跳过
+pub fn apply_to_folder(storage: &LocalStorage, account: &EmailAccount, folder: &str, rules: &[FilterRule])
+    -> FilterResult<usize>
+{
+    let mut matched = 0;
+    let mut moves: Vec<(String, Vec<Email>)> = Vec::new();
+    let mut flags = Vec::new();
+
+    for mut email in storage.load_emails(account, folder)? {
+        // 检查邮件头需要原文
+        email.raw = storage.raw_source(account, folder, &email)?;
+        let outcome = evaluate(rules, &email);
+        if outcome.matched.is_empty() {
+            continue;
+        }
+        matched += 1;
+
+        if outcome.seen && !email.seen {
+            storage.mark_seen(account, folder, &email.id, true)?;
+            email.seen = true;
+        }
+        let target = outcome.folder.unwrap_or_else(|| folder.to_string());
+        flags.extend(outcome.flags.into_iter().map(|flag| (target.clone(), email.id.clone(), flag)));
+        if target != folder {
+            match moves.iter_mut().find(|(name, _)| *name == target) {
+                Some((_, list)) => list.push(email),
+                None => moves.push((target, vec![email])),
+            }
+        }
+    }
+
+    for (target, list) in &moves {
+        storage.move_emails(account, folder, target, list)?;
+    }
+    for (target, id, flag) in flags {
+        storage.set_flag(account, &target, &id, &flag, true)?;
+    }
+    Ok(matched)
+}
+
+// 生成转发的邮件，原邮件的头信息放在正文开头，附件一并转发
+pub fn forwarded_email(account: &EmailAccount, to: &str, email: &Email) -> FilterResult<Email> {
+    let to = Mailbox::parse(to).ok_or_else(|| format!("转发地址无效: {}", to))?;
+    let body = format!(
+        "---------- 转发的邮件 ----------\n发件人: {}\n日期: {}\n主题: {}\n收件人: {}\n\n{}",
+        email.from,
+        email.date,
+        email.subject,
+        format_address_list(&email.to),
+        email.body
+    );
+    Ok(Email {
+        id: String::new(),
+        from: Mailbox::new(account.address.clone()),
+        to: vec![Address::Mailbox(to)],
+        cc: Vec::new(),
+        bcc: Vec::new(),
+        subject: format!("Fwd: {}", email.subject),
+        body,
+        html: None,
+        body_view: None,
+        date: Local::now().to_rfc3339(),
+        attachments: email.attachments.clone(),
+        status: EmailStatus::Draft,
+        seen: true,
+        account: account.address.clone(),
+        folder: String::new(),
+        labels: Vec::new(),
+        flagged: false,
+        answered: false,
+        snippet: String::new(),
+        raw: None,
+        received: None,
+        server_folder: None,
+    })
+}
+
+// 在后台线程发送规则产生的转发
+pub async fn send_forwards(account: &EmailAccount, forwards: Vec<(String, Email)>) {
+    if forwards.is_empty() {
+        return;
+    }
+    let account = account.clone();
+    async_std::task::spawn_blocking(move || {
+        for (to, email) in &forwards {
+            let result = forwarded_email(&account, to, email).and_then(|message| {
+                smtp_client::send_email(
+                    &account.smtp_server,
+                    account.smtp_port,
+                    &account.address,
+                    &account.password,
+                    &message,
+                    account.smtp_tls(),
+                )
+            });
+            match result {
+                Ok(()) => println!("{}: 已按规则把「{}」转发到 {}", account.address, email.subject, to),
+                Err(e) => println!("{}: 转发「{}」到 {} 失败: {}", account.address, email.subject, to, e),
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{RuleAction, RuleCondition, RuleField, RuleOp};
+    use crate::service::imap_client::parse_email;
+
+    fn email(id: &str, from: &str, subject: &str) -> Email {
+        let raw = format!("From: {}\r\nTo: me@example.com\r\nSubject: {}\r\n\r\nbody", from, subject);
+        let mut email = parse_email(id.into(), raw.as_bytes()).unwrap();
+        email.account = "me@example.com".into();
+        email
+    }
+
+    fn rule(name: &str, subject: &str, actions: &[(ActionKind, &str)]) -> FilterRule {
+        FilterRule {
+            name: name.into(),
+            conditions: vec![RuleCondition {
+                field: RuleField::Subject,
+                op: RuleOp::Contains,
+                value: subject.into(),
+                ..Default::default()
+            }],
+            actions: actions.iter().map(|(kind, value)| RuleAction { kind: *kind, value: value.to_string() }).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_move_wins_and_actions_accumulate() {
+        let rules = vec![
+            rule("a", "report", &[(ActionKind::MoveTo, "Reports"), (ActionKind::AddFlag, "\\Flagged")]),
+            rule("b", "weekly", &[(ActionKind::MoveTo, "Other"), (ActionKind::AddFlag, "\\Flagged"), (ActionKind::MarkRead, "")]),
+            rule("c", "nothing", &[(ActionKind::Delete, "")]),
+        ];
+        let outcome = evaluate(&rules, &email("1", "a@x.com", "Weekly report"));
+        assert_eq!(outcome.matched, vec!["a", "b"]);
+        assert_eq!(outcome.folder.as_deref(), Some("Reports"));
+        assert_eq!(outcome.flags, vec!["\\Flagged"]);
+        assert!(outcome.seen);
+    }
+
+    #[test]
+    fn delete_overrides_move_and_stop_ends_evaluation() {
+        let mut first = rule("a", "report", &[(ActionKind::MoveTo, "Reports"), (ActionKind::Delete, "")]);
+        let rules = vec![first.clone(), rule("b", "report", &[(ActionKind::Forward, "x@y.com")])];
+        let outcome = evaluate(&rules, &email("1", "a@x.com", "report"));
+        assert_eq!(outcome.folder.as_deref(), Some(TRASH_FOLDER));
+        assert_eq!(outcome.forward, vec!["x@y.com"]);
+
+        first.stop = true;
+        let outcome = evaluate(&[first, rules[1].clone()], &email("1", "a@x.com", "report"));
+        assert_eq!(outcome.matched, vec!["a"]);
+        assert!(outcome.forward.is_empty());
+    }
+
+    #[test]
+    fn new_emails_are_filed_and_forwarded() {
+        let dir = std::env::temp_dir().join(format!("rustmail-filter-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut storage = LocalStorage::open(dir.clone()).unwrap();
+        let account = EmailAccount { address: "me@example.com".into(), ..Default::default() };
+        let rules = vec![rule("a", "report", &[(ActionKind::MoveTo, "Reports"), (ActionKind::AddFlag, "\\Flagged"), (ActionKind::Forward, "boss@x.com")])];
+
+        let forwards = save_new_emails(
+            &mut storage,
+            &account,
+            &rules,
+            None,
+            vec![
+                email("1", "a@x.com", "Weekly report"),
+                email("2", "me@example.com", "My report"),
+                email("3", "a@x.com", "Hello"),
+            ],
+        )
+        .unwrap();
+
+        // 自己发出的邮件不转发
+        assert_eq!(forwards.iter().map(|(to, e)| (to.as_str(), e.id.as_str())).collect::<Vec<_>>(), vec![("boss@x.com", "1")]);
+        let inbox: Vec<String> = storage.load_emails(&account, INBOX).unwrap().into_iter().map(|e| e.id).collect();
+        assert_eq!(inbox, vec!["3"]);
+        let reports = storage.load_emails(&account, "Reports").unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|e| e.flagged));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn forwarded_email_quotes_the_original() {
+        let account = EmailAccount { address: "me@example.com".into(), ..Default::default() };
+        let forwarded = forwarded_email(&account, "Boss <boss@x.com>", &email("1", "a@x.com", "Report")).unwrap();
+        assert_eq!(forwarded.subject, "Fwd: Report");
+        assert_eq!(forwarded.from.address, "me@example.com");
+        assert!(forwarded.body.contains("发件人: a@x.com"));
+        assert!(forwarded_email(&account, "", &forwarded).is_err());
+    }
+}
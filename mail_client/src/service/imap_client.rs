@@ -1,21 +1,154 @@
-use crate::models::{Email, Attachment};
+use crate::models::{is_label_keyword, parse_address_list, sort_newest_first, Address, Attachment, Email, EmailAccount, Mailbox, MimePart};
 use async_std::net::TcpStream;
 use async_imap::{self, Client};
 use async_native_tls::{TlsStream, TlsConnector};
-use mailparse::{parse_mail, ParsedMail, MailHeaderMap}; 
-use async_std::task;
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use mailparse::{addrparse_header, parse_mail, MailHeader, MailHeaderMap};
 use futures_util::stream::StreamExt;
 use std::boxed::Box;
+use std::collections::BTreeMap;
 use chrono::{DateTime, Utc};
 
-use crate::models::EmailStatus;
+use crate::service::eml;
 
-pub async fn fetch_emails(
+// 根据 FLAGS 判断邮件是否已读
+fn is_seen(fetch: &async_imap::types::Fetch) -> bool {
+    fetch.flags().any(|flag| flag == async_imap::types::Flag::Seen)
+}
+
+fn is_flagged(fetch: &async_imap::types::Fetch) -> bool {
+    fetch.flags().any(|flag| flag == async_imap::types::Flag::Flagged)
+}
+
+fn is_answered(fetch: &async_imap::types::Fetch) -> bool {
+    fetch.flags().any(|flag| flag == async_imap::types::Flag::Answered)
+}
+
+// FLAGS 中作为标签使用的自定义关键字
+fn label_keywords(fetch: &async_imap::types::Fetch) -> Vec<String> {
+    fetch
+        .flags()
+        .filter_map(|flag| match flag {
+            async_imap::types::Flag::Custom(keyword) if is_label_keyword(&keyword) => Some(keyword.into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+// 解析原始 RFC822 邮件，IMAP 同步和 Maildir 导入共用
+pub fn parse_email(id: String, raw: &[u8]) -> Result<Email, mailparse::MailParseError> {
+    let parsed = parse_mail(raw)?;
+    let headers = &parsed.headers;
+
+    // 无法解析的 From 头保留原文作为显示名
+    let from = headers.get_first_header("From")
+        .map(|h| {
+            address_header(h)
+                .iter()
+                .flat_map(|a| a.mailboxes().to_vec())
+                .next()
+                .unwrap_or_else(|| Mailbox { name: Some(h.get_value()), address: String::new() })
+        })
+        .unwrap_or_default();
+
+    // 未编码的 GBK 等主题也能正确显示
+    let root = MimePart::from(&parsed);
+    let subject = root.header("Subject")
+        .map(str::to_string)
+        .unwrap_or_else(|| String::from("无主题"));
+
+    let date = headers.get_first_header("Date")
+        .map(|h| h.get_value())
+        .unwrap_or_else(|| String::from("未知日期"));
+
+    let (body, html) = root.bodies();
+    let attachments = root
+        .attachments()
+        .into_iter()
+        .enumerate()
+        .map(|(index, part)| Attachment::new(attachment_name(part, index + 1), part.content_type.clone(), part.body.clone()))
+        .collect();
+
+    // 一个头中可能有多个地址，同名的头也可能出现多次
+    let to = headers.get_all_headers("To")
+        .iter()
+        .flat_map(|h| address_header(h))
+        .collect();
+
+    let cc = headers.get_all_headers("Cc")
+        .iter()
+        .flat_map(|h| address_header(h))
+        .collect();
+
+    let mut email = Email {
+        id,
+        from,
+        to,
+        cc,
+        bcc: vec![],
+        subject,
+        body,
+        html,
+        body_view: None,
+        date,
+        attachments,
+        status: crate::models::EmailStatus::Draft,
+        seen: false,
+        account: String::new(),
+        folder: String::new(),
+        labels: Vec::new(),
+        flagged: false,
+        answered: false,
+        snippet: String::new(),
+        raw: Some(raw.to_vec()),
+        received: None,
+        server_folder: None,
+    };
+    email.snippet = email.make_snippet();
+    Ok(email)
+}
+
+// 解析地址头，显示名中的 RFC 2047 编码会被解码
+fn address_header(header: &MailHeader) -> Vec<Address> {
+    match addrparse_header(header) {
+        Ok(list) => list.iter().cloned().map(Address::from).collect(),
+        Err(_) => parse_address_list(&header.get_value()),
+    }
+}
+
+// 附件文件名，没有文件名时按类型命名：附带的邮件用其主题，会议邀请为 invite.ics
+fn attachment_name(part: &MimePart, index: usize) -> String {
+    if let Some(name) = &part.filename {
+        return name.clone();
+    }
+    if part.is_message() {
+        return eml::message_file_name(&part.body);
+    }
+    let extension = match part.content_type.as_str() {
+        "text/calendar" => return "invite.ics".to_string(),
+        "text/plain" | "text/rfc822-headers" | "message/delivery-status" | "message/disposition-notification" => "txt",
+        "text/html" => "html",
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "application/pdf" => "pdf",
+        "application/pgp-encrypted" | "application/pgp-keys" => "asc",
+        _ => return format!("附件{}", index),
+    };
+    format!("附件{}.{}", index, extension)
+}
+
+// 添加到 imap_client.rs 中的适当位置
+
+// 添加一个新函数，支持从特定日期之后获取邮件
+pub async fn fetch_new_emails(
     imap_server: &str,
     imap_port: u16,
     username: &str,
     password: &str,
     use_tls: bool,
+    since_date: Option<DateTime<Utc>>, // 添加一个参数，只获取该日期之后的邮件
+    exclude_ids: Vec<String>,         // 排除已有的邮件ID
 ) -> Result<Vec<Email>, Box<dyn std::error::Error + Send + Sync>> {
     println!("连接到 IMAP 服务器: {}:{}", imap_server, imap_port);
     
@@ -25,7 +158,7 @@ pub async fn fetch_emails(
         Tls(async_imap::Session<TlsStream<TcpStream>>),
     }
     
-    // 创建会话
+    // 创建会话 - 复用 fetch_emails 中的连接代码
     let session = if use_tls {
         println!("使用 TLS 加密连接");
         let tcp_stream = TcpStream::connect((imap_server, imap_port)).await?;
@@ -71,7 +204,18 @@ pub async fn fetch_emails(
             let mut emails = Vec::new();
             
             println!("获取邮件列表...");
-            let sequence = match $session.search("ALL").await {
+            
+            // 构建搜索命令
+            let search_cmd = if let Some(date) = since_date {
+                // 格式化日期为 IMAP 搜索格式 (DD-MMM-YYYY)
+                let date_str = date.format("%d-%b-%Y").to_string();
+                format!("SINCE {}", date_str)
+            } else {
+                "ALL".to_string()
+            };
+            
+            println!("搜索邮件: {}", search_cmd);
+            let sequence = match $session.search(&search_cmd).await {
                 Ok(seq) => {
                     if seq.is_empty() {
                         println!("没有找到邮件");
@@ -101,316 +245,316 @@ pub async fn fetch_emails(
             };
             
             println!("获取邮件内容: {}", sequence);
-            let mut fetches = $session.fetch(sequence, "RFC822").await?;
+            let mut fetches = $session.fetch(sequence, "(UID FLAGS INTERNALDATE BODY.PEEK[])").await?;
             
             while let Some(fetch) = fetches.next().await {
                 match fetch {
                     Ok(fetch) => {
-                        if let Some(body) = fetch.body() {
-                            match parse_mail(body) {
-                                Ok(parsed) => {
-                                    // 修复：不要移动parsed.headers，而是使用引用
-                                    let headers = &parsed.headers;
-                                    
-                                    let from = headers.get_first_header("From")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("未知发件人"));
-                                        
-                                    let subject = headers.get_first_header("Subject")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("无主题"));
-                                        
-                                    let date = headers.get_first_header("Date")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("未知日期"));
-                                    
-                                    // 现在 parsed 没有被部分移动，可以安全地借用
-                                    let (body, attachments) = extract_body_and_attachments(&parsed);
-                                    
-                                    let to = headers.get_all_headers("To")
-                                        .iter()
-                                        .map(|h| h.get_value())
-                                        .collect();
-                                        
-                                    let cc = headers.get_all_headers("Cc")
-                                        .iter()
-                                        .map(|h| h.get_value())
-                                        .collect();
-                                    
-                                    let id = format!("{}", fetch.message);
-                                    
-                                    emails.push(Email {
-                                        id,
-                                        from,
-                                        to,
-                                        cc,
-                                        bcc: vec![],
-                                        subject,
-                                        body,
-                                        date,
-                                        attachments,
-                                        status: crate::models::EmailStatus::Draft,
-                                    });
-                                },
-                                Err(e) => println!("解析邮件失败: {}", e),
-                            }
+                        // 邮件 ID 使用 UID，序号会随其他邮件的删除而改变
+                        let (Some(body), Some(uid)) = (fetch.body(), fetch.uid) else {
+                            continue;
+                        };
+                        let id = uid.to_string();
+                        // 检查是否在排除列表中
+                        if exclude_ids.contains(&id) {
+                            continue;
+                        }
+                        match parse_email(id, body) {
+                            Ok(mut email) => {
+                                email.seen = is_seen(&fetch);
+                                email.flagged = is_flagged(&fetch);
+                                email.answered = is_answered(&fetch);
+                                email.labels = label_keywords(&fetch);
+                                email.received = fetch.internal_date().map(|d| d.with_timezone(&Utc));
+                                email.account = username.to_string();
+                                email.server_folder = Some("INBOX".to_string());
+                                emails.push(email);
+                            },
+                            Err(e) => println!("解析邮件失败: {}", e),
                         }
                     },
                     Err(e) => println!("获取邮件错误: {}", e),
                 }
             }
             
-            // 修复: 确保在使用fetches后再调用logout，防止多次可变借用
-            // 明确丢弃fetches，确保其生命周期结束
+            // 确保在使用fetches后再调用logout，防止多次可变借用
             drop(fetches);
             
             // 现在可以安全地调用logout
             let _ = $session.logout().await;
             
             // 按日期排序，最新的邮件在前面
-            println!("成功获取 {} 封邮件", emails.len());
-            emails.sort_by(|a, b| b.date.cmp(&a.date));
+            println!("成功获取 {} 封新邮件", emails.len());
+            sort_newest_first(&mut emails);
             
             Ok(emails)
         }}
     }
     
-    // 修复: 将session声明为mut，以便在宏中可变借用
+    // 根据会话类型调用相应的处理逻辑
     match session {
         ImapSession::Plain(mut session) => handle_session!(session),
         ImapSession::Tls(mut session) => handle_session!(session),
     }
 }
+// 把邮件上传到服务器的文件夹（IMAP APPEND），返回成功上传的数量
+pub async fn append_emails(
+    imap_server: &str,
+    imap_port: u16,
+    username: &str,
+    password: &str,
+    use_tls: bool,
+    mailbox: &str,
+    messages: &[Vec<u8>],
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    println!("连接到 IMAP 服务器上传邮件: {}:{}", imap_server, imap_port);
 
-// extract_body_and_attachments 函数保持不变
-fn extract_body_and_attachments(parsed: &ParsedMail) -> (String, Vec<Attachment>) {
-    let mut body = String::new();
-    let mut attachments = Vec::new();
-    
-    if parsed.ctype.mimetype.starts_with("text/") {
-        if let Ok(text) = parsed.get_body() {
-            body = text;
-        }
-    } else if parsed.ctype.mimetype == "multipart/alternative" || parsed.ctype.mimetype == "multipart/mixed" {
-        for subpart in &parsed.subparts {
-            if subpart.ctype.mimetype == "text/plain" {
-                if let Ok(text) = subpart.get_body() {
-                    body = text;
-                }
-            } else if subpart.ctype.mimetype == "text/html" {
-                if let Ok(text) = subpart.get_body() {
-                    body = text;
+    enum ImapSession {
+        Plain(async_imap::Session<TcpStream>),
+        Tls(async_imap::Session<TlsStream<TcpStream>>),
+    }
+
+    let tcp_stream = TcpStream::connect((imap_server, imap_port)).await?;
+    let session = if use_tls {
+        let tls = TlsConnector::new();
+        let tls_stream = TlsConnector::connect(&tls, imap_server, tcp_stream).await?;
+        let session = Client::new(tls_stream).login(username, password).await.map_err(|e| e.0)?;
+        ImapSession::Tls(session)
+    } else {
+        let session = Client::new(tcp_stream).login(username, password).await.map_err(|e| e.0)?;
+        ImapSession::Plain(session)
+    };
+
+    macro_rules! handle_session {
+        ($session:expr) => {{
+            let mut uploaded = 0;
+            for message in messages {
+                match $session.append(mailbox, message).await {
+                    Ok(()) => uploaded += 1,
+                    Err(e) => {
+                        // 一封失败时停止，避免连接异常后继续发送
+                        let _ = $session.logout().await;
+                        return Err(format!("上传第 {} 封邮件失败: {}", uploaded + 1, e).into());
+                    }
                 }
-            } else if !subpart.ctype.mimetype.starts_with("multipart/") {
-                let filename = subpart.ctype.params.get("name")
-                    .or_else(|| subpart.ctype.params.get("filename"))
-                    .unwrap_or(&"未命名附件".to_string()).clone();
-                
-                if let Ok(content) = subpart.get_body_raw() {
-                    attachments.push(Attachment {
-                        filename,
-                        content_type: subpart.ctype.mimetype.clone(),
-                        data: content,
-                    });
+            }
+            let _ = $session.logout().await;
+            println!("已上传 {} 封邮件到 {}", uploaded, mailbox);
+            Ok(uploaded)
+        }};
+    }
+
+    match session {
+        ImapSession::Plain(mut session) => handle_session!(session),
+        ImapSession::Tls(mut session) => handle_session!(session),
+    }
+}
+
+// 给服务器文件夹中的邮件添加/移除标记（如 \Seen、$Junk、标签），uids 为邮件在 mailbox 中的 UID
+// 所有邮件在一条 UID STORE 命令中修改；服务器不允许保存新的关键字时不做修改，返回 false
+pub async fn store_flags(
+    account: &EmailAccount,
+    mailbox: &str,
+    uids: &[u32],
+    add: &[&str],
+    remove: &[&str],
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if uids.is_empty() {
+        return Ok(true);
+    }
+
+    enum ImapSession {
+        Plain(async_imap::Session<TcpStream>),
+        Tls(async_imap::Session<TlsStream<TcpStream>>),
+    }
+
+    let tcp_stream = TcpStream::connect((account.imap_server.as_str(), account.imap_port)).await?;
+    let session = if account.use_tls {
+        let tls = TlsConnector::new();
+        let tls_stream = TlsConnector::connect(&tls, &account.imap_server, tcp_stream).await?;
+        let session = Client::new(tls_stream).login(&account.address, &account.password).await.map_err(|e| e.0)?;
+        ImapSession::Tls(session)
+    } else {
+        let session = Client::new(tcp_stream).login(&account.address, &account.password).await.map_err(|e| e.0)?;
+        ImapSession::Plain(session)
+    };
+
+    macro_rules! handle_session {
+        ($session:expr) => {{
+            let selected = $session.select(encode_mailbox_name(mailbox)).await?;
+            // 没有 PERMANENTFLAGS 时所有标记都可以保存；有则需要 \* 或已包含要添加的标记
+            let permanent = &selected.permanent_flags;
+            let allowed = permanent.is_empty()
+                || permanent.contains(&async_imap::types::Flag::MayCreate)
+                || add.iter().all(|k| permanent.contains(&async_imap::types::Flag::from(*k)));
+            if !allowed {
+                let _ = $session.logout().await;
+                return Ok(false);
+            }
+            let set = uid_set(uids);
+            for (sign, keywords) in [("+", add), ("-", remove)] {
+                if keywords.is_empty() {
+                    continue;
                 }
-            } else if subpart.ctype.mimetype.starts_with("multipart/") {
-                let (inner_body, inner_attachments) = extract_body_and_attachments(subpart);
-                if body.is_empty() {
-                    body = inner_body;
+                let query = format!("{}FLAGS.SILENT ({})", sign, keywords.join(" "));
+                let updates: Vec<_> = $session.uid_store(&set, &query).await?.collect().await;
+                for update in updates {
+                    update?;
                 }
-                attachments.extend(inner_attachments);
             }
-        }
+            let _ = $session.logout().await;
+            Ok(true)
+        }};
+    }
+
+    match session {
+        ImapSession::Plain(mut session) => handle_session!(session),
+        ImapSession::Tls(mut session) => handle_session!(session),
     }
-    
-    (body, attachments)
 }
 
-// 添加到 imap_client.rs 中的适当位置
+// 把服务器文件夹 from 中的邮件移到 to，目标文件夹不存在时先创建
+// 支持 MOVE 时用一条 UID MOVE；否则 UID COPY 后加上 \Deleted，支持 UIDPLUS 时再用 UID EXPUNGE 只清除这些邮件，
+// 不支持时不执行 EXPUNGE，以免清除其他标记为删除的邮件
+pub async fn move_messages(
+    account: &EmailAccount,
+    from: &str,
+    to: &str,
+    uids: &[u32],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if uids.is_empty() || from == to {
+        return Ok(());
+    }
 
-// 添加一个新函数，支持从特定日期之后获取邮件
-pub async fn fetch_new_emails(
-    imap_server: &str,
-    imap_port: u16,
-    username: &str,
-    password: &str,
-    use_tls: bool,
-    since_date: Option<DateTime<Utc>>, // 添加一个参数，只获取该日期之后的邮件
-    exclude_ids: Vec<String>,         // 排除已有的邮件ID
-) -> Result<Vec<Email>, Box<dyn std::error::Error + Send + Sync>> {
-    println!("连接到 IMAP 服务器: {}:{}", imap_server, imap_port);
-    
-    // 使用枚举包装不同类型的会话
     enum ImapSession {
         Plain(async_imap::Session<TcpStream>),
         Tls(async_imap::Session<TlsStream<TcpStream>>),
     }
-    
-    // 创建会话 - 复用 fetch_emails 中的连接代码
-    let session = if use_tls {
-        println!("使用 TLS 加密连接");
-        let tcp_stream = TcpStream::connect((imap_server, imap_port)).await?;
+
+    let tcp_stream = TcpStream::connect((account.imap_server.as_str(), account.imap_port)).await?;
+    let session = if account.use_tls {
         let tls = TlsConnector::new();
-        let tls_stream = TlsConnector::connect(&tls, imap_server, tcp_stream).await?;
-        let client = Client::new(tls_stream);
-        
-        println!("尝试登录...");
-        let session = client
-            .login(username, password)
-            .await
-            .map_err(|e| {
-                println!("登录失败: {:?}", e);
-                e.0
-            })?;
-        
+        let tls_stream = TlsConnector::connect(&tls, &account.imap_server, tcp_stream).await?;
+        let session = Client::new(tls_stream).login(&account.address, &account.password).await.map_err(|e| e.0)?;
         ImapSession::Tls(session)
     } else {
-        println!("使用非加密连接");
-        let tcp_stream = TcpStream::connect((imap_server, imap_port)).await?;
-        let client = Client::new(tcp_stream);
-        
-        println!("尝试登录...");
-        let session = client
-            .login(username, password)
-            .await
-            .map_err(|e| {
-                println!("登录失败: {:?}", e);
-                e.0
-            })?;
-        
+        let session = Client::new(tcp_stream).login(&account.address, &account.password).await.map_err(|e| e.0)?;
         ImapSession::Plain(session)
     };
-    
-    println!("登录成功，选择收件箱...");
-    
-    // 创建一个宏来处理不同类型的会话，避免代码重复
+
+    // 命令中的文件夹名使用修改版 UTF-7
+    let (from_name, to_name) = (encode_mailbox_name(from), encode_mailbox_name(to));
+
     macro_rules! handle_session {
         ($session:expr) => {{
-            // 选择收件箱
-            $session.select("INBOX").await?;
-            
-            let mut emails = Vec::new();
-            
-            println!("获取邮件列表...");
-            
-            // 构建搜索命令
-            let search_cmd = if let Some(date) = since_date {
-                // 格式化日期为 IMAP 搜索格式 (DD-MMM-YYYY)
-                let date_str = date.format("%d-%b-%Y").to_string();
-                format!("SINCE {}", date_str)
-            } else {
-                "ALL".to_string()
-            };
-            
-            println!("搜索邮件: {}", search_cmd);
-            let sequence = match $session.search(&search_cmd).await {
-                Ok(seq) => {
-                    if seq.is_empty() {
-                        println!("没有找到邮件");
-                        return Ok(emails);
-                    }
-                    
-                    let count = seq.len();
-                    println!("找到 {} 封邮件", count);
-                    
-                    // 因为 seq 是 HashSet<u32>，我们需要将其转换为有序列表
-                    let mut seq_vec: Vec<_> = seq.into_iter().collect();
-                    seq_vec.sort_unstable(); // 排序，以便获取正确的序列号范围
-                    
-                    // 只获取最近的30封邮件
-                    let start_idx = if count > 30 { count - 30 } else { 0 };
-                    let range = if start_idx < count && start_idx < seq_vec.len() {
-                        format!("{}:*", seq_vec[start_idx])
+            let capabilities = $session.capabilities().await?;
+            let can_move = capabilities.has_str("MOVE");
+            let uidplus = capabilities.has_str("UIDPLUS");
+            $session.select(&from_name).await?;
+            let set = uid_set(uids);
+
+            macro_rules! transfer {
+                () => {
+                    if can_move {
+                        $session.uid_mv(&set, &to_name).await
                     } else {
-                        "1:*".to_string()
-                    };
-                    range
-                },
-                Err(e) => {
-                    println!("搜索邮件失败: {}", e);
-                    return Err(e.into());
+                        $session.uid_copy(&set, quote_mailbox(&to_name)).await
+                    }
+                };
+            }
+            // 目标文件夹不存在时服务器返回 NO，创建后重试一次
+            if transfer!().is_err() {
+                $session.create(&to_name).await?;
+                transfer!()?;
+            }
+
+            if !can_move {
+                let updates: Vec<_> = $session.uid_store(&set, "+FLAGS.SILENT (\\Deleted)").await?.collect().await;
+                for update in updates {
+                    update?;
                 }
-            };
-            
-            println!("获取邮件内容: {}", sequence);
-            let mut fetches = $session.fetch(sequence, "RFC822").await?;
-            
-            while let Some(fetch) = fetches.next().await {
-                match fetch {
-                    Ok(fetch) => {
-                        if let Some(body) = fetch.body() {
-                            match parse_mail(body) {
-                                Ok(parsed) => {
-                                    // 使用引用而不是移动
-                                    let headers = &parsed.headers;
-                                    
-                                    let from = headers.get_first_header("From")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("未知发件人"));
-                                        
-                                    let subject = headers.get_first_header("Subject")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("无主题"));
-                                        
-                                    let date = headers.get_first_header("Date")
-                                        .map(|h| h.get_value())
-                                        .unwrap_or_else(|| String::from("未知日期"));
-                                    
-                                    let (body, attachments) = extract_body_and_attachments(&parsed);
-                                    
-                                    let to = headers.get_all_headers("To")
-                                        .iter()
-                                        .map(|h| h.get_value())
-                                        .collect();
-                                        
-                                    let cc = headers.get_all_headers("Cc")
-                                        .iter()
-                                        .map(|h| h.get_value())
-                                        .collect();
-                                    
-                                    let id = format!("{}", fetch.message);
-                                    
-                                    // 检查是否在排除列表中
-                                    if !exclude_ids.contains(&id) {
-                                        emails.push(Email {
-                                            id,
-                                            from,
-                                            to,
-                                            cc,
-                                            bcc: vec![],
-                                            subject,
-                                            body,
-                                            date,
-                                            attachments,
-                                            status: crate::models::EmailStatus::Draft,
-                                        });
-                                    }
-                                },
-                                Err(e) => println!("解析邮件失败: {}", e),
-                            }
-                        }
-                    },
-                    Err(e) => println!("获取邮件错误: {}", e),
+                if uidplus {
+                    let expunged: Vec<_> = $session.uid_expunge(&set).await?.collect().await;
+                    for uid in expunged {
+                        uid?;
+                    }
                 }
             }
-            
-            // 确保在使用fetches后再调用logout，防止多次可变借用
-            drop(fetches);
-            
-            // 现在可以安全地调用logout
             let _ = $session.logout().await;
-            
-            // 按日期排序，最新的邮件在前面
-            println!("成功获取 {} 封新邮件", emails.len());
-            emails.sort_by(|a, b| b.date.cmp(&a.date));
-            
-            Ok(emails)
-        }}
+            println!("{}: 已在服务器上把 {} 封邮件从 {} 移到 {}", account.address, uids.len(), from, to);
+            Ok(())
+        }};
     }
-    
-    // 根据会话类型调用相应的处理逻辑
+
     match session {
         ImapSession::Plain(mut session) => handle_session!(session),
         ImapSession::Tls(mut session) => handle_session!(session),
     }
-}
\ No newline at end of file
+}
+
+// 按 (账户, 服务器文件夹) 分组邮件的 UID，只在本地的邮件不发送到服务器
+pub fn group_by_mailbox(emails: &[Email]) -> BTreeMap<(String, String), Vec<u32>> {
+    let mut groups: BTreeMap<(String, String), Vec<u32>> = BTreeMap::new();
+    for email in emails {
+        if let Some((mailbox, uid)) = email.server_location() {
+            groups.entry((email.account.clone(), mailbox.to_string())).or_default().push(uid);
+        }
+    }
+    groups
+}
+
+// UID 集合，如 "3,5,8"
+fn uid_set(uids: &[u32]) -> String {
+    uids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+}
+
+// 命令中的文件夹名使用带引号的字符串
+fn quote_mailbox(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// 文件夹名按 IMAP 的修改版 UTF-7 编码（RFC 3501 5.1.3），如「工作」编码为 &XeVPXA-
+// 可打印的 ASCII 字符保持不变，& 写作 &-，其他字符按 UTF-16 做 base64（/ 换成 ,）后放在 & 和 - 之间
+fn encode_mailbox_name(name: &str) -> String {
+    fn flush(pending: &mut Vec<u16>, encoded: &mut String) {
+        if pending.is_empty() {
+            return;
+        }
+        let bytes: Vec<u8> = pending.drain(..).flat_map(u16::to_be_bytes).collect();
+        encoded.push('&');
+        encoded.push_str(&STANDARD_NO_PAD.encode(bytes).replace('/', ","));
+        encoded.push('-');
+    }
+
+    let mut encoded = String::new();
+    let mut pending = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush(&mut pending, &mut encoded);
+            if c == '&' {
+                encoded.push_str("&-");
+            } else {
+                encoded.push(c);
+            }
+        } else {
+            pending.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        }
+    }
+    flush(&mut pending, &mut encoded);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailbox_names_use_modified_utf7() {
+        assert_eq!(encode_mailbox_name("INBOX"), "INBOX");
+        assert_eq!(encode_mailbox_name("工作"), "&XeVPXA-");
+        assert_eq!(encode_mailbox_name("~peter/mail/台北/日本語"), "~peter/mail/&U,BTFw-/&ZeVnLIqe-");
+        assert_eq!(encode_mailbox_name("R&D 报告"), "R&-D &YqVUSg-");
+        // 基本平面以外的字符按代理对编码
+        assert_eq!(encode_mailbox_name("📁"), "&2D3cwQ-");
+    }
+}
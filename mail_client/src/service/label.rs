@@ -0,0 +1,51 @@
+use crate::models::{Email, EmailAccount};
+use crate::service::imap_client;
+use crate::service::local_storage::LocalStorage;
+
+type LabelResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 在本地给邮件添加或去掉标签，每封邮件在它所在的文件夹中更新，返回修改后的邮件
+pub fn apply_label(storage: &LocalStorage, accounts: &[EmailAccount], emails: &[Email], keyword: &str, set: bool)
+    -> LabelResult<Vec<Email>>
+{
+    let mut updated = Vec::new();
+    for email in emails {
+        let Some(account) = accounts.iter().find(|a| a.address == email.account) else {
+            continue;
+        };
+        let folder = if email.folder.is_empty() { "INBOX" } else { email.folder.as_str() };
+        storage.set_flag(account, folder, &email.id, keyword, set)?;
+
+        let mut email = email.clone();
+        email.labels.retain(|l| l != keyword);
+        if set {
+            email.labels.push(keyword.to_string());
+            email.labels.sort();
+        }
+        updated.push(email);
+    }
+    Ok(updated)
+}
+
+// 把标签作为关键字同步到服务器上邮件所在的文件夹，每个文件夹设置一次，只在本地的邮件不处理
+// 返回服务器不允许自定义关键字、只保存在本地的账户
+pub async fn store_label(accounts: &[EmailAccount], emails: &[Email], keyword: &str, set: bool) -> Vec<String> {
+    let mut local_only = Vec::new();
+    for ((address, mailbox), uids) in imap_client::group_by_mailbox(emails) {
+        let Some(account) = accounts.iter().find(|a| a.address == address) else {
+            continue;
+        };
+        let (add, remove): (&[&str], &[&str]) = if set { (&[keyword], &[]) } else { (&[], &[keyword]) };
+        match imap_client::store_flags(account, &mailbox, &uids, add, remove).await {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("{}: 服务器不允许自定义关键字，标签只保存在本地", address);
+                if !local_only.contains(&address) {
+                    local_only.push(address);
+                }
+            }
+            Err(e) => println!("{}: 同步标签失败: {}", address, e),
+        }
+    }
+    local_only
+}
@@ -1,10 +1,39 @@
 use std::path::{Path, PathBuf};
 use std::fs::{self, File, create_dir_all};
 use std::io::{Read, Write};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
-use crate::models::{Email, EmailAccount};
+use crate::models::{Attachment, BodyView, Email, EmailAccount, FilterRule, Label, SavedSearch, SearchQuery};
+use crate::service::mail_db::{MailDb, MessageQuery, MessageScope, SortField, FLAG_ANSWERED, FLAG_FLAGGED, FLAG_SEEN};
+use crate::service::address_book::AddressBook;
+use crate::service::maildir::{self, Maildir, MaildirEntry};
+use crate::service::mbox::{self, MboxFormat};
+use crate::service::spam::SpamModel;
+use crate::service::imap_client::parse_email;
+use crate::service::smtp_client::{build_message, to_lettre_mailbox};
+
+// 每个用户配置目录下的邮件数据库文件名
+const DB_FILE_NAME: &str = "mail.db";
+// 通讯录数据库文件名
+const CONTACTS_FILE_NAME: &str = "contacts.db";
+// 邮件过滤规则文件名
+const FILTER_RULES_FILE_NAME: &str = "filter_rules.json";
+// 账户目录下的垃圾邮件模型文件名
+const SPAM_MODEL_FILE_NAME: &str = "spam_model.json";
+// 标签定义，所有账户共用
+const LABELS_FILE_NAME: &str = "labels.json";
+// 保存的搜索（虚拟文件夹）
+const SAVED_SEARCHES_FILE_NAME: &str = "saved_searches.json";
+// 各文件夹的排序与筛选
+const LIST_VIEWS_FILE_NAME: &str = "list_views.json";
+
+// 每个 (账户, Maildir 文件夹目录) 上次扫描时 cur/new 的修改时间
+static MAILDIR_SCANS: LazyLock<Mutex<HashMap<(String, PathBuf), MaildirStamp>>> = LazyLock::new(Default::default);
+
+type MaildirStamp = (SystemTime, SystemTime);
 
 // 布局设置结构体
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -29,18 +58,137 @@ impl Default for LayoutSettings {
     }
 }
 
+// 邮件列表的排序与快速筛选，按文件夹分别记住
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ListView {
+    pub sort: SortField,
+    pub descending: bool,
+    #[serde(default)]
+    pub unread: bool, // 只显示未读
+    #[serde(default)]
+    pub flagged: bool, // 只显示星标
+    #[serde(default)]
+    pub attachment: bool, // 只显示带附件的
+    #[serde(default)]
+    pub contacts: bool, // 只显示通讯录中联系人发来的
+}
+
+impl Default for ListView {
+    fn default() -> Self {
+        ListView {
+            sort: SortField::Date,
+            descending: true,
+            unread: false,
+            flagged: false,
+            attachment: false,
+            contacts: false,
+        }
+    }
+}
+
+impl ListView {
+    pub fn is_filtered(&self) -> bool {
+        self.unread || self.flagged || self.attachment || self.contacts
+    }
+
+    // 数据库查询条件：search 为列表上方搜索框的条件，contacts 为联系人地址（小写）
+    pub fn query(&self, search: SearchQuery, contacts: &HashSet<String>) -> MessageQuery {
+        MessageQuery {
+            search,
+            unread: self.unread,
+            flagged: self.flagged,
+            attachment: self.attachment,
+            senders: self.contacts.then(|| contacts.iter().cloned().collect()),
+            ..MessageQuery::default().sorted_by(self.sort, self.descending)
+        }
+    }
+}
+
+// 邮件列表的显示密度：舒适模式显示头像和正文摘要，紧凑模式每行更矮
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ListDensity {
+    #[default]
+    Comfortable,
+    Compact,
+}
+
+impl ListDensity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListDensity::Comfortable => "comfortable",
+            ListDensity::Compact => "compact",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "comfortable" => Some(ListDensity::Comfortable),
+            "compact" => Some(ListDensity::Compact),
+            _ => None,
+        }
+    }
+}
+
+// 邮件存储方式
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum StorageBackend {
+    // 只保存在数据库中
+    #[default]
+    Database,
+    // 同时保存为 Maildir，根目录下每个账户一个子目录，可与 mutt、notmuch 等工具共用
+    Maildir(PathBuf),
+}
+
 // 记录邮件同步状态和应用设置的结构体
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct AppState {
     pub last_sync: Option<DateTime<Utc>>,
-    pub uid_map: HashMap<String, Vec<String>>, // 邮箱 -> 已同步的UID列表
+    pub uid_map: HashMap<String, Vec<String>>, // 旧版本记录的已同步UID，邮件已迁移到数据库
     pub layout: LayoutSettings, // 添加布局设置
+    #[serde(default)]
+    pub last_sync_map: HashMap<String, DateTime<Utc>>, // 邮箱 -> 最后同步时间
+    #[serde(default)]
+    pub backend: StorageBackend,
+    #[serde(default)]
+    pub body_view: BodyView, // 默认的正文显示方式
+    #[serde(default)]
+    pub density: ListDensity, // 邮件列表的行距
+    #[serde(default)]
+    pub json_import_pending: bool, // 旧版 JSON 邮件文件尚未成功导入数据库
+}
+
+// 已保存的账户列表，多个账户一起保存
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct AccountSettings {
+    pub accounts: Vec<EmailAccount>,
+    pub active: Option<String>, // 当前使用的账户地址
+}
+
+impl AccountSettings {
+    // 获取当前账户，未设置时返回第一个账户
+    pub fn active_account(&self) -> Option<EmailAccount> {
+        self.active
+            .as_ref()
+            .and_then(|addr| self.accounts.iter().find(|a| &a.address == addr))
+            .or_else(|| self.accounts.first())
+            .cloned()
+    }
+
+    // 添加或更新账户（按地址匹配）
+    pub fn upsert(&mut self, account: EmailAccount) {
+        match self.accounts.iter_mut().find(|a| a.address == account.address) {
+            Some(existing) => *existing = account,
+            None => self.accounts.push(account),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct LocalStorage {
     base_path: PathBuf,
     app_state: AppState,
+    db: MailDb,
+    contacts: AddressBook,
 }
 
 impl LocalStorage {
@@ -50,29 +198,61 @@ impl LocalStorage {
             .ok_or("无法获取本地数据目录")?
             .join("RustMail");
         
+        Self::open(base_path)
+    }
+
+    // 在指定目录打开存储（每个目录对应一个用户配置）
+    pub fn open(base_path: PathBuf) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         // 确保目录存在
         create_dir_all(&base_path)?;
         
         // 加载或创建应用状态
         let app_state = Self::load_app_state(&base_path).unwrap_or_default();
+
+        // 打开邮件数据库，首次创建时导入旧版的 JSON 邮件文件
+        let (db, previous_version) = MailDb::open(&base_path.join(DB_FILE_NAME))?;
+        let contacts = AddressBook::open(&base_path.join(CONTACTS_FILE_NAME))?;
         
-        Ok(Self {
+        let mut storage = Self {
             base_path,
             app_state,
-        })
+            db,
+            contacts,
+        };
+
+        // 新建数据库时导入旧版 JSON 邮件，导入成功前保留待导入标记，失败时下次启动重试
+        if previous_version == 0 {
+            storage.app_state.json_import_pending = true;
+        }
+        if storage.app_state.json_import_pending {
+            match storage.import_json_folders() {
+                Ok(count) => {
+                    if count > 0 {
+                        println!("已从 JSON 文件导入 {} 封邮件", count);
+                    }
+                    storage.app_state.json_import_pending = false;
+                }
+                Err(e) => println!("导入 JSON 邮件失败，下次启动时重试: {}", e),
+            }
+            if let Err(e) = storage.save_app_state() {
+                println!("保存导入状态失败: {}", e);
+            }
+        }
+
+        // 启动时清理没有引用的附件文件和中断写入留下的临时文件
+        if let Err(e) = storage.db.collect_garbage() {
+            println!("清理附件文件失败: {}", e);
+        }
+
+        Ok(storage)
     }
     
-    // 获取账户的邮件存储目录
+    // 获取账户的本地数据目录
     fn get_account_path(&self, account: &EmailAccount) -> PathBuf {
         let safe_address = account.address.replace("@", "_at_").replace(".", "_dot_");
         self.base_path.join(&safe_address)
     }
     
-    // 获取指定文件夹的路径
-    fn get_folder_path(&self, account: &EmailAccount, folder: &str) -> PathBuf {
-        self.get_account_path(account).join(folder)
-    }
-    
     // 加载应用状态
     fn load_app_state(base_path: &Path) -> Result<AppState, Box<dyn std::error::Error + Send + Sync>> {
         let state_file_path = base_path.join("app_state.json");
@@ -99,115 +279,842 @@ impl LocalStorage {
         
         Ok(())
     }
+
+    // 导入旧版本按 账户目录/文件夹/ID.json 保存的邮件
+    // 只处理已保存的账户或按地址命名的目录，跳过附件存储（blobs）、Maildir 根目录等其他目录
+    fn import_json_folders(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        // 账户目录名 -> 邮箱地址，优先使用已保存的账户列表
+        let known_accounts: Vec<EmailAccount> = self
+            .load_accounts()
+            .ok()
+            .flatten()
+            .map(|settings| settings.accounts)
+            .unwrap_or_default();
+        let maildir_root = match &self.app_state.backend {
+            StorageBackend::Maildir(root) => Some(root.clone()),
+            StorageBackend::Database => None,
+        };
+
+        let mut imported = 0;
+        for account_entry in fs::read_dir(&self.base_path)? {
+            let account_path = account_entry?.path();
+            if !account_path.is_dir() || maildir_root.as_ref() == Some(&account_path) {
+                continue;
+            }
+            let dir_name = account_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            let address = match known_accounts.iter().find(|acc| self.get_account_path(acc) == account_path) {
+                Some(account) => account.address.clone(),
+                None if dir_name.contains("_at_") => dir_name.replace("_at_", "@").replace("_dot_", "."),
+                None => continue,
+            };
+
+            let mut found = false;
+            for folder_entry in fs::read_dir(&account_path)? {
+                let folder_path = folder_entry?.path();
+                if !folder_path.is_dir() {
+                    continue;
+                }
+                let folder = folder_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+
+                let mut files = Vec::new();
+                for entry in fs::read_dir(&folder_path)? {
+                    let path = entry?.path();
+                    if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                        files.push(path);
+                    }
+                }
+                // 没有邮件 JSON 的目录（如 Maildir 的文件夹）不是旧版本的文件夹
+                if files.is_empty() {
+                    continue;
+                }
+                found = true;
+
+                let mut emails = Vec::new();
+                for path in files {
+                    match fs::read_to_string(&path).map_err(|e| e.to_string())
+                        .and_then(|contents| serde_json::from_str::<Email>(&contents).map_err(|e| e.to_string()))
+                    {
+                        Ok(email) => emails.push(email),
+                        Err(e) => println!("跳过无法读取的邮件文件 {:?}: {}", path, e),
+                    }
+                }
+                imported += self.db.insert_emails(&address, &folder, &emails)?;
+            }
+
+            // 保留旧版记录的同步时间
+            if let (true, Some(last_sync)) = (found, self.app_state.last_sync_map.get(&address)) {
+                self.db.set_last_sync(&address, "INBOX", *last_sync)?;
+            }
+        }
+
+        Ok(imported)
+    }
     
-    // 保存邮件到本地
+    // 保存邮件到本地，已存在的邮件会被跳过
     pub fn save_emails(&mut self, account: &EmailAccount, folder: &str, emails: &[Email]) 
         -> Result<(), Box<dyn std::error::Error + Send + Sync>> 
     {
-        let folder_path = self.get_folder_path(account, folder);
-        create_dir_all(&folder_path)?;
-        
-        // 获取该账户的UID映射，如果不存在则创建
-        let account_key = account.address.clone();
-        let uid_map = self.app_state.uid_map
-            .entry(account_key)
-            .or_insert_with(Vec::new);
-            
-        // 只保存新邮件
-        for email in emails {
-            if !uid_map.contains(&email.id) {
-                let email_path = folder_path.join(format!("{}.json", &email.id));
-                let json = serde_json::to_string_pretty(email)?;
-                
-                let mut file = File::create(email_path)?;
-                file.write_all(json.as_bytes())?;
-                
-                // 添加到已同步列表
-                uid_map.push(email.id.clone());
+        let existing = self.db.folder_uids(&account.address, folder)?;
+        self.db.insert_emails(&account.address, folder, emails)?;
+
+        // 新邮件的发件人加入通讯录
+        let new_emails: Vec<Email> = emails.iter().filter(|e| !existing.contains(&e.id)).cloned().collect();
+        if let Err(e) = self.contacts.record_received(&new_emails) {
+            println!("更新通讯录失败: {}", e);
+        }
+
+        // Maildir 存储时同时写入邮件文件
+        if let Some(md) = self.account_maildir(account) {
+            for email in emails.iter().filter(|e| !existing.contains(&e.id)) {
+                if let Err(e) = write_to_maildir(&self.db, &md, account, folder, email) {
+                    println!("写入 Maildir 失败 ({}): {}", email.id, e);
+                }
             }
         }
         
         // 更新同步状态
-        self.app_state.last_sync = Some(Utc::now());
-        self.save_app_state()?;
+        self.db.set_last_sync(&account.address, folder, Utc::now())?;
         
         Ok(())
     }
     
-    // 从本地加载邮件
+    // 从本地加载邮件，最新的邮件在前面
     pub fn load_emails(&self, account: &EmailAccount, folder: &str) 
         -> Result<Vec<Email>, Box<dyn std::error::Error + Send + Sync>> 
     {
-        let folder_path = self.get_folder_path(account, folder);
-        
-        if !folder_path.exists() {
-            return Ok(Vec::new());
-        }
-        
-        let mut emails = Vec::new();
-        
-        for entry in fs::read_dir(folder_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.extension().map_or(false, |ext| ext == "json") {
-                let mut file = File::open(&path)?;
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)?;
-                
-                let email: Email = serde_json::from_str(&contents)?;
-                emails.push(email);
+        self.query_emails(account, folder, &MessageQuery::default())
+    }
+
+    // 按排序与分页条件加载邮件
+    pub fn query_emails(&self, account: &EmailAccount, folder: &str, query: &MessageQuery)
+        -> Result<Vec<Email>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        // 先读取其他工具对 Maildir 的修改
+        if let Some(md) = self.account_maildir(account) {
+            if let Err(e) = self.refresh_from_maildir(&md, account, folder) {
+                println!("读取 Maildir 失败: {}", e);
             }
         }
-        
-        // 按日期排序，最新的邮件在前面
-        emails.sort_by(|a, b| b.date.cmp(&a.date));
-        
-        Ok(emails)
+        self.db.query_emails(&account.address, folder, query)
     }
+
     
-    // 获取最后同步时间 (兼容现有代码)
-    pub fn get_last_sync(&self) -> Option<DateTime<Utc>> {
-        self.app_state.last_sync
+    // 获取账户收件箱的最后同步时间
+    pub fn get_last_sync(&self, account: &EmailAccount) -> Option<DateTime<Utc>> {
+        self.db.last_sync(&account.address, "INBOX").ok().flatten()
+    }
+
+    // 标记邮件已读/未读
+    pub fn mark_seen(&self, account: &EmailAccount, folder: &str, id: &str, seen: bool)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.set_flag(&account.address, folder, id, FLAG_SEEN, seen)?;
+
+        if let Some(md) = self.account_maildir(account) {
+            if let Some(entry) = md.find(folder, id)? {
+                md.set_flags(&entry, &maildir::with_flag(&entry.flags, 'S', seen))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 默认的正文显示方式
+    pub fn body_view(&self) -> BodyView {
+        self.app_state.body_view
+    }
+
+    pub fn set_body_view(&mut self, view: BodyView) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.app_state.body_view = view;
+        self.save_app_state()
+    }
+
+    pub fn density(&self) -> ListDensity {
+        self.app_state.density
+    }
+
+    pub fn set_density(&mut self, density: ListDensity) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.app_state.density = density;
+        self.save_app_state()
+    }
+
+    // 单封邮件的正文显示方式，None 时使用默认设置
+    pub fn set_message_body_view(&self, account: &EmailAccount, folder: &str, id: &str, view: Option<BodyView>)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.set_body_view(&account.address, folder, id, view)
+    }
+
+    // 设置或清除邮件标记，\Flagged 和 \Answered 同时写入 Maildir 文件名
+    pub fn set_flag(&self, account: &EmailAccount, folder: &str, id: &str, flag: &str, set: bool)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        if flag == FLAG_SEEN {
+            return self.mark_seen(account, folder, id, set);
+        }
+        self.db.set_flag(&account.address, folder, id, flag, set)?;
+
+        let maildir_flag = match flag {
+            FLAG_FLAGGED => Some('F'),
+            FLAG_ANSWERED => Some('R'),
+            _ => None,
+        };
+        if let (Some(c), Some(md)) = (maildir_flag, self.account_maildir(account)) {
+            if let Some(entry) = md.find(folder, id)? {
+                md.set_flags(&entry, &maildir::with_flag(&entry.flags, c, set))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 把邮件移到同一账户的其他文件夹，原文和附件随之移动，返回移动的数量
+    pub fn move_emails(&self, account: &EmailAccount, from: &str, to: &str, emails: &[Email])
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let mut moving = Vec::new();
+        for email in emails {
+            let mut email = email.clone();
+            email.raw = self.raw_source(account, from, &email)?;
+            moving.push(email);
+        }
+        self.db.insert_emails(&account.address, to, &moving)?;
+
+        if let Some(md) = self.account_maildir(account) {
+            md.create_folder(to)?;
+            for email in &moving {
+                if let Some(entry) = md.find(from, &email.id)? {
+                    md.deliver(to, &entry.unique, &fs::read(&entry.path)?, &entry.flags)?;
+                    fs::remove_file(&entry.path)?;
+                }
+            }
+        }
+
+        let ids: Vec<String> = moving.iter().map(|e| e.id.clone()).collect();
+        self.db.delete_messages(&account.address, from, &ids)
+    }
+
+    // 统计文件夹中的未读邮件数
+    pub fn unread_count(&self, account: &EmailAccount, folder: &str) -> usize {
+        self.db.count_unread(&account.address, folder).unwrap_or(0)
+    }
+
+    // 删除账户的全部本地数据和同步记录
+    pub fn remove_account_data(&mut self, account: &EmailAccount)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.delete_account(&account.address)?;
+
+        // 清理账户目录（垃圾邮件模型和旧版本留下的 JSON 文件）
+        let account_path = self.get_account_path(account);
+        if account_path.exists() {
+            fs::remove_dir_all(&account_path)?;
+        }
+
+        if let Some(md) = self.account_maildir(account) {
+            if md.root().exists() {
+                fs::remove_dir_all(md.root())?;
+            }
+        }
+
+        self.app_state.uid_map.remove(&account.address);
+        self.app_state.last_sync_map.remove(&account.address);
+        self.save_app_state()
     }
     
+    // 账户下已保存的文件夹
+    pub fn list_folders(&self, account: &EmailAccount) -> Vec<String> {
+        self.db.folders(&account.address).unwrap_or_default()
+    }
+
+    pub fn storage_backend(&self) -> &StorageBackend {
+        &self.app_state.backend
+    }
+
+    // 切换存储方式，切换到 Maildir 时把已有邮件写入 Maildir，返回写入的邮件数
+    pub fn set_storage_backend(&mut self, backend: StorageBackend)
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.app_state.backend = backend;
+
+        let mut written = 0;
+        if let StorageBackend::Maildir(_) = &self.app_state.backend {
+            let accounts = self.load_accounts()?.map(|s| s.accounts).unwrap_or_default();
+            for account in &accounts {
+                let Some(md) = self.account_maildir(account) else { continue };
+                for folder in self.list_folders(account) {
+                    let present: HashSet<String> = md.list(&folder)?.iter().map(|e| e.message_id()).collect();
+                    md.create_folder(&folder)?;
+                    for mut email in self.db.query_emails(&account.address, &folder, &MessageQuery::default())? {
+                        if present.contains(&email.id) {
+                            continue;
+                        }
+                        email.raw = self.db.source(&account.address, &folder, &email.id)?;
+                        match write_to_maildir(&self.db, &md, account, &folder, &email) {
+                            Ok(()) => written += 1,
+                            Err(e) => println!("写入 Maildir 失败 ({}): {}", email.id, e),
+                        }
+                    }
+                }
+            }
+        }
+
+        self.save_app_state()?;
+        Ok(written)
+    }
+
+    // 账户对应的 Maildir，未使用 Maildir 存储时为 None
+    fn account_maildir(&self, account: &EmailAccount) -> Option<Maildir> {
+        match &self.app_state.backend {
+            StorageBackend::Database => None,
+            StorageBackend::Maildir(root) => {
+                let safe_address = account.address.replace("@", "_at_").replace(".", "_dot_");
+                Some(Maildir::new(root.join(safe_address)))
+            }
+        }
+    }
+
+    // 以 Maildir 为准更新数据库：导入新文件、删除已被移除的邮件、同步已读标记
+    fn refresh_from_maildir(&self, md: &Maildir, account: &EmailAccount, folder: &str)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        // 文件夹目录还不存在时（如刚切换存储方式）不做任何修改
+        let folder_path = md.folder_path(folder);
+        if !folder_path.join("cur").is_dir() {
+            return Ok(());
+        }
+
+        // 增删邮件和修改标记都会改变 cur/new 目录的修改时间，没有变化时不必重新扫描
+        let stamp = maildir_stamp(&folder_path);
+        let scan_key = (account.address.clone(), folder_path);
+        if stamp.is_some() && MAILDIR_SCANS.lock().unwrap_or_else(|e| e.into_inner()).get(&scan_key) == stamp.as_ref() {
+            return Ok(());
+        }
+
+        let entries: Vec<(String, MaildirEntry)> = md.list(folder)?.into_iter().map(|e| (e.message_id(), e)).collect();
+        let known = self.db.folder_uids(&account.address, folder)?;
+
+        let mut new_emails = Vec::new();
+        for (id, entry) in entries.iter().filter(|(id, _)| !known.contains(id)) {
+            let raw = fs::read(&entry.path)?;
+            match parse_email(id.clone(), &raw) {
+                Ok(mut email) => {
+                    email.seen = entry.is_seen();
+                    email.account = account.address.clone();
+                    new_emails.push(email);
+                }
+                Err(e) => println!("解析 Maildir 邮件失败 {:?}: {}", entry.path, e),
+            }
+        }
+        self.db.insert_emails(&account.address, folder, &new_emails)?;
+
+        let present: HashSet<&String> = entries.iter().map(|(id, _)| id).collect();
+        let removed: Vec<String> = known.into_iter().filter(|id| !present.contains(id)).collect();
+        if !removed.is_empty() {
+            self.db.delete_messages(&account.address, folder, &removed)?;
+        }
+
+        // 只更新已读状态有变化的邮件
+        let seen = self.db.folder_uids_with_flag(&account.address, folder, FLAG_SEEN)?;
+        for (id, entry) in &entries {
+            if seen.contains(id) != entry.is_seen() {
+                self.db.set_flag(&account.address, folder, id, FLAG_SEEN, entry.is_seen())?;
+            }
+        }
+
+        if let Some(stamp) = stamp {
+            MAILDIR_SCANS.lock().unwrap_or_else(|e| e.into_inner()).insert(scan_key, stamp);
+        }
+        Ok(())
+    }
+
+    // 把 Maildir 目录树导入到账户中，文件夹结构保持不变，返回导入的邮件数
+    pub fn import_maildir(&mut self, account: &EmailAccount, source: &Path)
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let source = Maildir::new(source);
+        let folders = source.list_folders()?;
+        if folders.is_empty() {
+            return Err(format!("{} 不是 Maildir 目录", source.root().display()).into());
+        }
+
+        let target = self.account_maildir(account);
+        let mut imported = 0;
+        for folder in folders {
+            let known = self.db.folder_uids(&account.address, &folder)?;
+            let mut emails = Vec::new();
+            for entry in source.list(&folder)? {
+                let id = entry.message_id();
+                if known.contains(&id) {
+                    continue;
+                }
+                let raw = fs::read(&entry.path)?;
+                let mut email = match parse_email(id, &raw) {
+                    Ok(email) => email,
+                    Err(e) => {
+                        println!("跳过无法解析的邮件 {:?}: {}", entry.path, e);
+                        continue;
+                    }
+                };
+                email.seen = entry.is_seen();
+                email.account = account.address.clone();
+
+                // 使用 Maildir 存储时保留原始文件内容和标记
+                if let Some(md) = &target {
+                    if md.root() != source.root() {
+                        md.deliver(&folder, &entry.unique, &raw, &entry.flags)?;
+                    }
+                }
+                emails.push(email);
+            }
+            imported += self.db.insert_emails(&account.address, &folder, &emails)?;
+            println!("从 Maildir 导入 {}: {} 封邮件", folder, emails.len());
+        }
+
+        Ok(imported)
+    }
+
+    // 把账户的一个文件夹导出到 Maildir 目录树中的同名文件夹，已存在的邮件会被跳过
+    pub fn export_maildir(&self, account: &EmailAccount, folder: &str, destination: &Path)
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let destination = Maildir::new(destination);
+        destination.create_folder(folder)?;
+        let present: HashSet<String> = destination.list(folder)?.iter().map(|e| e.message_id()).collect();
+        let source = self.account_maildir(account);
+
+        let mut exported = 0;
+        for mut email in self.query_emails(account, folder, &MessageQuery::default())? {
+            if present.contains(&email.id) {
+                continue;
+            }
+            email.raw = self.db.source(&account.address, folder, &email.id)?;
+            // 优先使用 Maildir 中的原始文件
+            let original = match &source {
+                Some(md) => md.find(folder, &email.id)?,
+                None => None,
+            };
+            let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = match original {
+                Some(entry) => fs::read(&entry.path)
+                    .and_then(|raw| destination.deliver(folder, &entry.unique, &raw, &entry.flags))
+                    .map(|_| ())
+                    .map_err(|e| e.into()),
+                None => write_to_maildir(&self.db, &destination, account, folder, &email),
+            };
+            match result {
+                Ok(()) => exported += 1,
+                Err(e) => println!("导出邮件失败 ({}): {}", email.id, e),
+            }
+        }
+
+        Ok(exported)
+    }
+
+    // 邮件原文：优先读取数据库中保存的原文，其次是 Maildir 中的文件，都没有时返回 None
+    pub fn raw_source(&self, account: &EmailAccount, folder: &str, email: &Email)
+        -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        if let Some(raw) = &email.raw {
+            return Ok(Some(raw.clone()));
+        }
+        if let Some(raw) = self.db.source(&account.address, folder, &email.id)? {
+            return Ok(Some(raw));
+        }
+        if let Some(md) = self.account_maildir(account) {
+            if let Some(entry) = md.find(folder, &email.id)? {
+                return Ok(Some(fs::read(&entry.path)?));
+            }
+        }
+        Ok(None)
+    }
+
+    // 邮件的 RFC822 内容，没有原文时（旧版本同步的邮件）根据保存的内容重新生成
+    pub fn message_source(&self, account: &EmailAccount, folder: &str, email: &Email)
+        -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        match self.raw_source(account, folder, email)? {
+            Some(raw) => Ok(raw),
+            None => render_message(&self.db, account, email),
+        }
+    }
+
+    // 打开附件用于流式读取
+    pub fn open_attachment<'a>(&self, attachment: &'a Attachment)
+        -> Result<Box<dyn Read + Send + 'a>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.open_attachment(attachment)
+    }
+
+    // 读取附件的全部内容
+    pub fn attachment_data(&self, attachment: &Attachment)
+        -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.attachment_data(attachment)
+    }
+
+    // 把附件保存到指定文件，返回写入的字节数
+    pub fn save_attachment(&self, attachment: &Attachment, destination: &Path)
+        -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.save_attachment(attachment, destination)
+    }
+
+    // 把 mbox 文件中的邮件导入到账户的文件夹，返回新导入邮件的原始内容（用于上传到服务器）
+    pub fn import_mbox(&mut self, account: &EmailAccount, folder: &str, data: &[u8])
+        -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let known = self.db.folder_uids(&account.address, folder)?;
+        let target = self.account_maildir(account);
+
+        let mut emails = Vec::new();
+        let mut imported = Vec::new();
+        for message in mbox::parse_mbox(data) {
+            let id = mbox::content_id(&message.raw);
+            if known.contains(&id) || emails.iter().any(|e: &Email| e.id == id) {
+                continue;
+            }
+            let mut email = match parse_email(id, &message.raw) {
+                Ok(email) => email,
+                Err(e) => {
+                    println!("跳过无法解析的 mbox 邮件: {}", e);
+                    continue;
+                }
+            };
+            email.seen = message.seen;
+            email.account = account.address.clone();
+
+            if let Some(md) = &target {
+                let flags = maildir::flags_for(&email);
+                md.deliver(folder, &maildir::unique_name(Some(&email.id)), &message.raw, &flags)?;
+            }
+            emails.push(email);
+            imported.push(message.raw);
+        }
+
+        self.db.insert_emails(&account.address, folder, &emails)?;
+        println!("从 mbox 导入到 {}: {} 封邮件", folder, emails.len());
+        Ok(imported)
+    }
+
+    // 把一组邮件（文件夹或搜索结果）导出为一个 mbox 文件，返回导出的数量
+    pub fn export_mbox(&self, accounts: &[EmailAccount], folder: &str, emails: &[Email], path: &Path, format: MboxFormat)
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        let mut exported = 0;
+        for email in emails {
+            let Some(account) = accounts.iter().find(|a| a.address == email.account) else {
+                continue;
+            };
+            // 标签视图中的邮件来自不同文件夹
+            let folder = if email.folder.is_empty() { folder } else { email.folder.as_str() };
+            let raw = match self.message_source(account, folder, email) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    println!("导出邮件失败 ({}): {}", email.id, e);
+                    continue;
+                }
+            };
+            // From_ 行使用纯地址
+            let sender = if email.from.address.is_empty() {
+                account.address.clone()
+            } else {
+                email.from.ascii_address()
+            };
+            let date = email.timestamp().unwrap_or_else(Utc::now);
+            mbox::write_message(&mut out, &sender, date, &raw, format)?;
+            exported += 1;
+        }
+        out.flush()?;
+        Ok(exported)
+    }
+
+    // 通讯录
+    pub fn address_book(&self) -> &AddressBook {
+        &self.contacts
+    }
+
     // 获取已同步的邮件ID列表
-    pub fn get_synced_ids(&self, account: &EmailAccount) -> Vec<String> {
-        self.app_state.uid_map
-            .get(&account.address)
-            .cloned()
-            .unwrap_or_default()
+    pub fn get_synced_ids(&self, account: &EmailAccount, mailbox: &str) -> Vec<String> {
+        self.db.server_uids(&account.address, mailbox).unwrap_or_default()
     }
-    
-    // 获取布局设置文件路径
-    fn get_layout_settings_path(&self) -> PathBuf {
-        self.base_path.join("layout_settings.json")
+
+    // 同步到的邮件如果是旧版本按序号保存过的，改为记录 UID，返回这些邮件的 ID
+    pub fn adopt_synced_emails(&self, account: &EmailAccount, mailbox: &str, emails: &[Email])
+        -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.adopt_legacy_messages(&account.address, mailbox, emails)
     }
-    
-    // 保存布局设置
-    pub fn save_layout_settings(&self, settings: &LayoutSettings) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let layout_file = self.get_layout_settings_path();
-        let json = serde_json::to_string_pretty(&settings)?;
-        
-        let mut file = File::create(layout_file)?;
+
+    // 邮件在服务器上被移走后，本地副本不再对应服务器上的 UID
+    pub fn forget_server_location(&self, account: &EmailAccount, mailbox: &str, uids: &[u32])
+        -> Result<usize, Box<dyn std::error::Error + Send + Sync>>
+    {
+        self.db.forget_server_location(&account.address, mailbox, uids)
+    }
+
+    // 获取账户列表文件路径
+    fn get_accounts_path(&self) -> PathBuf {
+        self.base_path.join("accounts.json")
+    }
+
+    // 保存账户列表
+    pub fn save_accounts(&self, settings: &AccountSettings) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let json = serde_json::to_string_pretty(settings)?;
+
+        let mut file = File::create(self.get_accounts_path())?;
         file.write_all(json.as_bytes())?;
-        
+
         Ok(())
     }
+
+    // 加载账户列表，文件不存在时返回 None
+    pub fn load_accounts(&self) -> Result<Option<AccountSettings>, Box<dyn std::error::Error + Send + Sync>> {
+        let accounts_file = self.get_accounts_path();
+
+        if !accounts_file.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(accounts_file)?;
+        let settings: AccountSettings = serde_json::from_str(&contents)?;
+        Ok(Some(settings))
+    }
     
-    // 加载布局设置
-    pub fn load_layout_settings(&self) -> Result<LayoutSettings, Box<dyn std::error::Error + Send + Sync>> {
-        let layout_file = self.get_layout_settings_path();
-        
-        if !layout_file.exists() {
-            return Ok(LayoutSettings::default());
+    // 加载邮件过滤规则，文件不存在时为空
+    pub fn filter_rules(&self) -> Result<Vec<FilterRule>, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.base_path.join(FILTER_RULES_FILE_NAME);
+        if !path.exists() {
+            return Ok(Vec::new());
         }
-        
-        let mut file = File::open(layout_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        
-        let settings: LayoutSettings = serde_json::from_str(&contents)?;
-        Ok(settings)
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save_filter_rules(&self, rules: &[FilterRule]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let json = serde_json::to_string_pretty(rules)?;
+        fs::write(self.base_path.join(FILTER_RULES_FILE_NAME), json)?;
+        Ok(())
+    }
+
+    // 加载账户的垃圾邮件模型，还没有训练过时为空模型
+    pub fn spam_model(&self, account: &EmailAccount) -> Result<SpamModel, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.get_account_path(account).join(SPAM_MODEL_FILE_NAME);
+        if !path.exists() {
+            return Ok(SpamModel::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save_spam_model(&self, account: &EmailAccount, model: &SpamModel)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let account_path = self.get_account_path(account);
+        create_dir_all(&account_path)?;
+        fs::write(account_path.join(SPAM_MODEL_FILE_NAME), serde_json::to_string(model)?)?;
+        Ok(())
+    }
+
+    pub fn labels(&self) -> Result<Vec<Label>, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.base_path.join(LABELS_FILE_NAME);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save_labels(&self, labels: &[Label]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let json = serde_json::to_string_pretty(labels)?;
+        fs::write(self.base_path.join(LABELS_FILE_NAME), json)?;
+        Ok(())
+    }
+
+    // 删除标签定义，并从各账户的邮件上去掉它
+    pub fn delete_label(&self, accounts: &[EmailAccount], keyword: &str)
+        -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let mut labels = self.labels()?;
+        labels.retain(|l| l.keyword != keyword);
+        self.save_labels(&labels)?;
+        for account in accounts {
+            self.db.clear_flag(&account.address, keyword)?;
+        }
+        Ok(())
+    }
+
+    // 邮件列表的行 ID，按 query 排序和筛选；读取前先同步 Maildir 中的修改
+    pub fn list_keys(&self, accounts: &[EmailAccount], scope: &MessageScope, query: &MessageQuery)
+        -> Result<Vec<i64>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        for account in accounts {
+            let Some(md) = self.account_maildir(account) else { continue };
+            let folders = match scope {
+                MessageScope::Folder(folder) => vec![folder.clone()],
+                _ => self.list_folders(account),
+            };
+            for folder in folders {
+                if let Err(e) = self.refresh_from_maildir(&md, account, &folder) {
+                    println!("读取 Maildir 失败: {}", e);
+                }
+            }
+        }
+        let addresses: Vec<&str> = accounts.iter().map(|a| a.address.as_str()).collect();
+        self.db.list_keys(&addresses, scope, query)
+    }
+
+    // 列表中显示的邮件字段（不含正文），按 rows 的顺序
+    pub fn list_headers(&self, rows: &[i64]) -> Result<Vec<(i64, Email)>, Box<dyn std::error::Error + Send + Sync>> {
+        self.db.headers(rows)
+    }
+
+    // 列表中邮件的完整内容，用于打开邮件和批量操作
+    pub fn load_messages(&self, rows: &[i64]) -> Result<Vec<Email>, Box<dyn std::error::Error + Send + Sync>> {
+        self.db.messages(rows)
+    }
+
+    // 各标记在这些邮件中出现的次数
+    pub fn flag_counts(&self, rows: &[i64]) -> HashMap<String, usize> {
+        self.db.flag_counts(rows).unwrap_or_default()
     }
-}
\ No newline at end of file
+
+    // 账户中符合搜索条件的未读邮件数，没有用 in: 指定文件夹时不包括 excluded 中的文件夹
+    pub fn search_unread_count(&self, account: &EmailAccount, query: &SearchQuery, excluded: &[&str]) -> usize {
+        self.db.count_unread_matching(&account.address, query, excluded).unwrap_or(0)
+    }
+
+    pub fn saved_searches(&self) -> Result<Vec<SavedSearch>, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.base_path.join(SAVED_SEARCHES_FILE_NAME);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save_saved_searches(&self, searches: &[SavedSearch]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let json = serde_json::to_string_pretty(searches)?;
+        fs::write(self.base_path.join(SAVED_SEARCHES_FILE_NAME), json)?;
+        Ok(())
+    }
+
+    fn list_views(&self) -> Result<HashMap<String, ListView>, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.base_path.join(LIST_VIEWS_FILE_NAME);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    // 某个文件夹（或标签、搜索）的排序与筛选，没有保存过时为默认值
+    pub fn list_view(&self, key: &str) -> ListView {
+        self.list_views()
+            .ok()
+            .and_then(|mut views| views.remove(key))
+            .unwrap_or_default()
+    }
+
+    pub fn save_list_view(&self, key: &str, view: &ListView) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut views = self.list_views().unwrap_or_default();
+        if *view == ListView::default() {
+            views.remove(key);
+        } else {
+            views.insert(key.to_string(), view.clone());
+        }
+        let json = serde_json::to_string_pretty(&views)?;
+        fs::write(self.base_path.join(LIST_VIEWS_FILE_NAME), json)?;
+        Ok(())
+    }
+}
+
+// 把邮件转换为 RFC822 格式
+fn render_message(db: &MailDb, account: &EmailAccount, email: &Email) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    // 发件人无法识别（如“未知发件人”）时使用账户地址
+    let from = to_lettre_mailbox(&email.from).or_else(|_| account.address.parse())?;
+    // lettre 生成邮件需要完整的附件内容，从附件存储中逐个读入
+    let mut email = email.clone();
+    for attachment in &mut email.attachments {
+        attachment.data = db.attachment_data(attachment)?;
+    }
+    Ok(build_message(from, &email)?.formatted())
+}
+
+// 文件夹 cur 和 new 目录的修改时间，文件系统不支持时返回 None
+fn maildir_stamp(folder_path: &Path) -> Option<MaildirStamp> {
+    let modified = |sub: &str| fs::metadata(folder_path.join(sub)).and_then(|m| m.modified()).ok();
+    Some((modified("cur")?, modified("new")?))
+}
+
+// 把邮件转换为 RFC822 格式写入 Maildir
+fn write_to_maildir(db: &MailDb, md: &Maildir, account: &EmailAccount, folder: &str, email: &Email)
+    -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+{
+    let raw = match &email.raw {
+        Some(raw) => raw.clone(),
+        None => render_message(db, account, email)?,
+    };
+    md.deliver(folder, &maildir::unique_name(Some(&email.id)), &raw, &maildir::flags_for(email))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_storage(name: &str) -> (PathBuf, LocalStorage) {
+        let dir = std::env::temp_dir().join(format!("rustmail-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let storage = LocalStorage::open(dir.clone()).unwrap();
+        (dir, storage)
+    }
+
+    // 只从账户目录导入旧版本的 JSON 邮件，附件存储和 Maildir 目录不当作账户或文件夹
+    #[test]
+    fn json_import_skips_non_account_directories() {
+        let dir = std::env::temp_dir().join(format!("rustmail-json-import-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let email = parse_email("7".into(), b"From: a@example.com\r\nSubject: legacy\r\n\r\nbody").unwrap();
+        let inbox = dir.join("me_at_example_dot_com").join("INBOX");
+        create_dir_all(&inbox).unwrap();
+        fs::write(inbox.join("7.json"), serde_json::to_string(&email).unwrap()).unwrap();
+        create_dir_all(dir.join("me_at_example_dot_com").join("Maildir").join("cur")).unwrap();
+        create_dir_all(dir.join("blobs").join("ab")).unwrap();
+        fs::write(dir.join("blobs").join("ab").join("cdef.json"), "{}").unwrap();
+        create_dir_all(dir.join("Mail").join("me_at_example_dot_com").join("INBOX").join("cur")).unwrap();
+
+        let storage = LocalStorage::open(dir.clone()).unwrap();
+        assert_eq!(storage.db.folders("me@example.com").unwrap(), vec!["INBOX"]);
+        for other in ["blobs", "Mail"] {
+            assert!(storage.db.folders(other).unwrap().is_empty(), "{}", other);
+        }
+        assert!(!storage.app_state.json_import_pending);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 没有原文的邮件导出时由完整的邮件重新生成，正文、收件人和附件都不能丢
+    #[test]
+    fn export_renders_messages_without_source() {
+        let (dir, mut storage) = temp_storage("export");
+        let account = EmailAccount { address: "me@example.com".into(), ..Default::default() };
+        let body = "第一段。\n".repeat(400);
+        let raw = format!("From: a@x.com\r\nTo: me@example.com, b@x.com\r\nCc: c@x.com\r\nSubject: Report\r\n\r\n{}", body);
+        let mut email = parse_email("1".into(), raw.as_bytes()).unwrap();
+        email.raw = None;
+        email.account = account.address.clone();
+        email.attachments.push(Attachment::new("a.txt".into(), "text/plain".into(), b"attached".to_vec()));
+        storage.save_emails(&account, "INBOX", &[email]).unwrap();
+
+        let keys = storage
+            .list_keys(std::slice::from_ref(&account), &MessageScope::Folder("INBOX".into()), &MessageQuery::default())
+            .unwrap();
+        let emails = storage.load_messages(&keys).unwrap();
+        let path = dir.join("out.mbox");
+        assert_eq!(storage.export_mbox(&[account], "INBOX", &emails, &path, MboxFormat::Mboxrd).unwrap(), 1);
+
+        let exported = mbox::parse_mbox(&fs::read(&path).unwrap());
+        assert_eq!(exported.len(), 1);
+        let parsed = parse_email("1".into(), &exported[0].raw).unwrap();
+        assert_eq!(parsed.body.trim().replace("\r\n", "\n"), body.trim());
+        assert_eq!(parsed.to.len(), 2);
+        assert_eq!(parsed.cc.len(), 1);
+        assert_eq!(parsed.attachments.len(), 1);
+        assert_eq!(parsed.attachments[0].filename, "a.txt");
+        assert_eq!(parsed.attachments[0].data.trim_ascii_end(), b"attached");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,1505 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::time::Duration;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use crate::models::{html_to_text, is_label_keyword, parse_address_list, parse_date, Address, Attachment, BodyView, Email, EmailStatus, Mailbox, MimePart, SearchQuery};
+use crate::models::search::{self, Term};
+use crate::service::blob_store::BlobStore;
+
+pub(crate) type DbResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 已读标记，与 IMAP 的 \Seen 保持一致
+pub const FLAG_SEEN: &str = "\\Seen";
+// 星标，与 IMAP 的 \Flagged 保持一致
+pub const FLAG_FLAGGED: &str = "\\Flagged";
+// 已回复，与 IMAP 的 \Answered 保持一致
+pub const FLAG_ANSWERED: &str = "\\Answered";
+
+// 数据库结构迁移，按顺序执行，当前版本记录在 PRAGMA user_version 中
+// 只能在末尾追加新的迁移，不能修改已发布的迁移
+const MIGRATIONS: &[&str] = &[
+    // 1: 初始结构
+    "CREATE TABLE folders (
+        id          INTEGER PRIMARY KEY,
+        account     TEXT NOT NULL,
+        name        TEXT NOT NULL,
+        last_sync   TEXT,
+        UNIQUE (account, name)
+    );
+    CREATE TABLE messages (
+        id          INTEGER PRIMARY KEY,
+        folder_id   INTEGER NOT NULL REFERENCES folders(id) ON DELETE CASCADE,
+        uid         TEXT NOT NULL,
+        subject     TEXT NOT NULL,
+        date        TEXT NOT NULL,
+        body        TEXT NOT NULL,
+        status      TEXT NOT NULL,
+        size        INTEGER NOT NULL DEFAULT 0,
+        UNIQUE (folder_id, uid)
+    );
+    CREATE INDEX idx_messages_folder_date ON messages(folder_id, date);
+    CREATE TABLE flags (
+        message_id  INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+        flag        TEXT NOT NULL,
+        PRIMARY KEY (message_id, flag)
+    );
+    CREATE TABLE addresses (
+        message_id  INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+        kind        TEXT NOT NULL,
+        position    INTEGER NOT NULL,
+        address     TEXT NOT NULL
+    );
+    CREATE INDEX idx_addresses_message ON addresses(message_id);
+    CREATE INDEX idx_addresses_address ON addresses(address);
+    CREATE TABLE attachments (
+        id          INTEGER PRIMARY KEY,
+        message_id  INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        filename    TEXT NOT NULL,
+        content_type TEXT NOT NULL,
+        data        BLOB NOT NULL
+    );
+    CREATE INDEX idx_attachments_message ON attachments(message_id);",
+    // 2: 保存邮件原文
+    "CREATE TABLE sources (
+        message_id  INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+        raw         BLOB NOT NULL
+    );",
+    // 3: 附件内容移到按哈希保存的文件中，引用计数由触发器维护
+    "CREATE TABLE blobs (
+        hash        TEXT PRIMARY KEY,
+        size        INTEGER NOT NULL,
+        refcount    INTEGER NOT NULL DEFAULT 0
+    );
+    ALTER TABLE attachments ADD COLUMN blob_hash TEXT;
+    ALTER TABLE attachments ADD COLUMN size INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX idx_attachments_blob ON attachments(blob_hash);
+    CREATE INDEX idx_blobs_refcount ON blobs(refcount);
+    CREATE TRIGGER attachments_blob_insert AFTER INSERT ON attachments
+    WHEN NEW.blob_hash IS NOT NULL
+    BEGIN
+        UPDATE blobs SET refcount = refcount + 1 WHERE hash = NEW.blob_hash;
+    END;
+    CREATE TRIGGER attachments_blob_delete AFTER DELETE ON attachments
+    WHEN OLD.blob_hash IS NOT NULL
+    BEGIN
+        UPDATE blobs SET refcount = refcount - 1 WHERE hash = OLD.blob_hash;
+    END;
+    CREATE TRIGGER attachments_blob_update AFTER UPDATE OF blob_hash ON attachments
+    BEGIN
+        UPDATE blobs SET refcount = refcount - 1 WHERE hash = OLD.blob_hash;
+        UPDATE blobs SET refcount = refcount + 1 WHERE hash = NEW.blob_hash;
+    END;",
+    // 4: 解析后的邮件时间（UTC 秒），按时间排序不再比较日期字符串
+    "ALTER TABLE messages ADD COLUMN timestamp INTEGER;
+    ALTER TABLE messages ADD COLUMN received INTEGER;
+    CREATE INDEX idx_messages_folder_timestamp ON messages(folder_id, timestamp);",
+    // 5: 地址拆分为显示名和邮箱，每个邮箱一行，地址组的成员记录组名
+    "ALTER TABLE addresses ADD COLUMN name TEXT;
+    ALTER TABLE addresses ADD COLUMN group_name TEXT;",
+    // 6: HTML 正文单独保存，body 只保存纯文本
+    "ALTER TABLE messages ADD COLUMN html TEXT;",
+    // 7: 单封邮件选择的正文显示方式，NULL 表示使用默认设置
+    "ALTER TABLE messages ADD COLUMN body_view TEXT;",
+    // 8: 列表中显示的正文摘要，同步时生成；旧邮件为 NULL，加载时由正文计算
+    "ALTER TABLE messages ADD COLUMN snippet TEXT;",
+    // 9: 邮件在服务器上所在的文件夹，uid 为该文件夹中的 UID；只在本地的邮件和旧版本按序号同步的邮件为 NULL
+    "ALTER TABLE messages ADD COLUMN server_folder TEXT;
+    CREATE INDEX idx_messages_server_folder ON messages(server_folder, uid);",
+];
+
+// 列表查询共用的字段，?2 为已读标记
+const SELECT_EMAILS: &str = "SELECT m.id, m.uid, m.subject, m.date, m.body, m.status,
+        COALESCE(NULLIF(s.name, ''), s.address) AS sender,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = ?2) AS seen,
+        m.received, s.name, s.address, m.html, m.body_view,
+        (SELECT name FROM folders WHERE id = m.folder_id) AS folder,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = '\\Flagged') AS flagged,
+        m.snippet,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = '\\Answered') AS answered,
+        m.server_folder
+     FROM messages m
+     LEFT JOIN addresses s ON s.message_id = m.id AND s.kind = 'from'";
+
+// 列表中显示的字段，不读取正文（只在没有摘要时读取开头一段），最后一列为账户
+const SELECT_HEADERS: &str = "SELECT m.id, m.uid, m.subject, m.date,
+        CASE WHEN m.snippet IS NULL THEN substr(m.body, 1, 1000) ELSE '' END AS body, m.status,
+        COALESCE(NULLIF(s.name, ''), s.address) AS sender,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = '\\Seen') AS seen,
+        m.received, s.name, s.address, NULL AS html, m.body_view,
+        fo.name AS folder,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = '\\Flagged') AS flagged,
+        m.snippet,
+        EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = '\\Answered') AS answered,
+        m.server_folder, fo.account
+     FROM messages m
+     JOIN folders fo ON fo.id = m.folder_id
+     LEFT JOIN addresses s ON s.message_id = m.id AND s.kind = 'from'";
+
+// 按顺序执行未完成的迁移，返回执行前的版本，通讯录数据库也使用
+pub(crate) fn run_migrations(conn: &mut Connection, migrations: &[&str]) -> DbResult<u32> {
+    run_migrations_with(conn, migrations, |_, _| Ok(()))
+}
+
+// 同上，每个版本的结构变更之后调用 backfill(事务, 版本号) 转换已有数据
+// 转换与版本号的更新在同一事务中，中途失败时下次打开会完整重做这一步
+fn run_migrations_with(
+    conn: &mut Connection,
+    migrations: &[&str],
+    backfill: impl Fn(&Transaction, u32) -> DbResult<()>,
+) -> DbResult<u32> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version as usize > migrations.len() {
+        return Err(format!("数据库版本 {} 高于当前程序支持的版本 {}", version, migrations.len()).into());
+    }
+
+    for (index, migration) in migrations.iter().enumerate().skip(version as usize) {
+        let target = (index + 1) as u32;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute_batch(migration)?;
+        backfill(&tx, target)?;
+        tx.pragma_update(None, "user_version", target)?;
+        tx.commit()?;
+        println!("数据库已迁移到版本 {}", target);
+    }
+
+    Ok(version)
+}
+
+// 批量加载收件人、附件和标签时每次查询的邮件数，低于 SQLite 的参数个数上限
+const DETAIL_BATCH: usize = 500;
+
+// 新写入的附件文件在这段时间内不会被当作无引用文件清理，避免与正在进行的写入冲突
+const ORPHAN_GRACE: Duration = Duration::from_secs(3600);
+
+// 邮件列表的排序字段
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SortField {
+    Date,
+    From,
+    Subject,
+    Size,
+}
+
+impl SortField {
+    pub const ALL: [SortField; 4] = [SortField::Date, SortField::From, SortField::Subject, SortField::Size];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortField::Date => "日期",
+            SortField::From => "发件人",
+            SortField::Subject => "主题",
+            SortField::Size => "大小",
+        }
+    }
+}
+
+// 邮件列表查询条件：排序、筛选与分页
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageQuery {
+    pub sort: SortField,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub search: SearchQuery, // 列表上方搜索框的条件
+    pub unread: bool,
+    pub flagged: bool,
+    pub attachment: bool,
+    pub senders: Option<Vec<String>>, // 只包括这些地址（小写）发来的邮件
+}
+
+impl Default for MessageQuery {
+    fn default() -> Self {
+        MessageQuery {
+            sort: SortField::Date,
+            descending: true,
+            offset: 0,
+            limit: None,
+            search: SearchQuery::default(),
+            unread: false,
+            flagged: false,
+            attachment: false,
+            senders: None,
+        }
+    }
+}
+
+// 邮件列表的范围
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageScope {
+    Folder(String),
+    // 所有文件夹中带有该标记（如标签关键字）的邮件
+    Flag(String),
+    // 保存的搜索，没有用 in: 指定文件夹时不包括 excluded 中的文件夹
+    Search(SearchQuery, Vec<String>),
+}
+
+impl MessageQuery {
+    // 第 page 页（从 0 开始），每页 page_size 封
+    #[cfg(test)]
+    pub fn page(page: usize, page_size: usize) -> Self {
+        MessageQuery {
+            offset: page * page_size,
+            limit: Some(page_size),
+            ..Default::default()
+        }
+    }
+
+    pub fn sorted_by(mut self, sort: SortField, descending: bool) -> Self {
+        self.sort = sort;
+        self.descending = descending;
+        self
+    }
+
+    fn order_clause(&self) -> String {
+        let column = match self.sort {
+            SortField::Date => "m.timestamp",
+            SortField::From => "sender COLLATE NOCASE",
+            SortField::Subject => "m.subject COLLATE NOCASE",
+            SortField::Size => "m.size",
+        };
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        // 相同排序值时按插入顺序，保证分页稳定
+        format!("{} {}, m.id {}", column, direction, direction)
+    }
+
+    // 筛选条件，参数追加到 values 末尾；查询中 m 为邮件，s 为发件人地址
+    fn conditions(&self, values: &mut Vec<Value>) -> Vec<String> {
+        let mut conditions = Vec::new();
+        if !self.search.is_empty() {
+            conditions.push(search_condition(&self.search, &[], values));
+        }
+        if self.unread {
+            conditions.push(format!(
+                "NOT EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {})",
+                bind(values, FLAG_SEEN.to_string())
+            ));
+        }
+        if self.flagged {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {})",
+                bind(values, FLAG_FLAGGED.to_string())
+            ));
+        }
+        if self.attachment {
+            conditions.push("EXISTS (SELECT 1 FROM attachments t WHERE t.message_id = m.id)".to_string());
+        }
+        if let Some(senders) = &self.senders {
+            let list = serde_json::to_string(senders).unwrap_or_default();
+            conditions.push(format!("lower(s.address) IN (SELECT value FROM json_each({}))", bind(values, list)));
+        }
+        conditions
+    }
+
+    fn limit_clause(&self, values: &mut Vec<Value>) -> String {
+        let limit = self.limit.map_or(-1, |l| l as i64);
+        format!("LIMIT {} OFFSET {}", bind(values, limit), bind(values, self.offset as i64))
+    }
+}
+
+// 每个用户配置目录下的 SQLite 邮件数据库
+// 附件内容保存在数据库旁边的 blobs 目录中
+#[derive(Clone)]
+pub struct MailDb {
+    conn: Arc<Mutex<Connection>>,
+    blobs: BlobStore,
+}
+
+impl MailDb {
+    // 打开数据库并执行未完成的迁移，返回打开前的结构版本（0 表示新建）
+    pub fn open(path: &Path) -> DbResult<(Self, u32)> {
+        let mut conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        register_search_functions(&conn)?;
+
+        let blob_root = path.parent().unwrap_or(Path::new(".")).join("blobs");
+        let blobs = BlobStore::new(blob_root);
+        let previous_version = Self::migrate(&mut conn, &blobs)?;
+
+        Ok((Self { conn: Arc::new(Mutex::new(conn)), blobs }, previous_version))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory(blob_root: std::path::PathBuf) -> DbResult<Self> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        register_search_functions(&conn)?;
+        let blobs = BlobStore::new(blob_root);
+        Self::migrate(&mut conn, &blobs)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), blobs })
+    }
+
+    // 版本 3：把旧版本保存在数据库中的附件内容移到附件存储
+    fn move_attachments_to_blobs(tx: &Transaction, blobs: &BlobStore) -> DbResult<()> {
+        let pending: Vec<i64> = tx
+            .prepare("SELECT id FROM attachments WHERE blob_hash IS NULL AND length(data) > 0")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for id in &pending {
+            let data: Vec<u8> = tx.query_row("SELECT data FROM attachments WHERE id = ?1", params![id], |row| row.get(0))?;
+            let hash = Self::store_blob(tx, blobs, &data)?;
+            tx.execute(
+                "UPDATE attachments SET blob_hash = ?1, size = ?2, data = X'' WHERE id = ?3",
+                params![hash, data.len() as i64, id],
+            )?;
+        }
+        if !pending.is_empty() {
+            println!("已将 {} 个附件移到附件存储", pending.len());
+        }
+        Ok(())
+    }
+
+    // 版本 4：为旧版本写入的邮件解析日期
+    fn fill_timestamps(tx: &Transaction) -> DbResult<()> {
+        let pending: Vec<(i64, String)> = tx
+            .prepare("SELECT id, date FROM messages WHERE timestamp IS NULL")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut update = tx.prepare_cached("UPDATE messages SET timestamp = ?1 WHERE id = ?2")?;
+        for (id, date) in &pending {
+            if let Some(time) = parse_date(date) {
+                update.execute(params![time.timestamp(), id])?;
+            }
+        }
+        Ok(())
+    }
+
+    // 版本 5：旧版本每个头保存为一个字符串，拆分为单独的邮箱
+    fn split_addresses(tx: &Transaction) -> DbResult<()> {
+        let rows: Vec<(i64, String, String)> = tx
+            .prepare("SELECT message_id, kind, address FROM addresses ORDER BY message_id, kind, position")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut lists: Vec<(i64, String, Vec<Address>)> = Vec::new();
+        for (message_id, kind, text) in rows {
+            let parsed = match kind.as_str() {
+                "from" => Mailbox::parse(&text).map(Address::Mailbox).into_iter().collect(),
+                _ => parse_address_list(&text),
+            };
+            match lists.last_mut() {
+                Some((id, k, list)) if *id == message_id && *k == kind => list.extend(parsed),
+                _ => lists.push((message_id, kind, parsed)),
+            }
+        }
+
+        tx.execute("DELETE FROM addresses", [])?;
+        for (message_id, kind, list) in &lists {
+            Self::insert_addresses(tx, *message_id, kind, list)?;
+        }
+        Ok(())
+    }
+
+    // 版本 6：旧版本的 body 可能是 HTML，有原文时重新解析正文，没有时按内容判断
+    fn split_bodies(tx: &Transaction) -> DbResult<()> {
+        let rows: Vec<(i64, String, Option<Vec<u8>>)> = tx
+            .prepare("SELECT m.id, m.body, s.raw FROM messages m LEFT JOIN sources s ON s.message_id = m.id")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut update = tx.prepare_cached("UPDATE messages SET body = ?1, html = ?2 WHERE id = ?3")?;
+        for (id, body, raw) in rows {
+            let (text, html) = match raw.as_deref().map(MimePart::parse) {
+                Some(Ok(root)) => root.bodies(),
+                _ if looks_like_html(&body) => (html_to_text(&body), Some(body)),
+                _ => continue,
+            };
+            update.execute(params![text, html, id])?;
+        }
+        Ok(())
+    }
+
+    // 写入一个地址头，地址组没有成员时写入一行空地址以保留组名
+    fn insert_addresses(conn: &Connection, message_id: i64, kind: &str, list: &[Address]) -> rusqlite::Result<()> {
+        let mut insert = conn.prepare_cached(
+            "INSERT INTO addresses (message_id, kind, position, address, name, group_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut position = 0i64;
+        for address in list {
+            let group = match address {
+                Address::Group { name, .. } => Some(name),
+                Address::Mailbox(_) => None,
+            };
+            if address.mailboxes().is_empty() {
+                insert.execute(params![message_id, kind, position, "", None::<String>, group])?;
+                position += 1;
+            }
+            for mailbox in address.mailboxes() {
+                insert.execute(params![message_id, kind, position, mailbox.address, mailbox.name, group])?;
+                position += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // 写入附件文件并登记，引用计数在写入 attachments 时由触发器增加
+    fn store_blob(tx: &Transaction, blobs: &BlobStore, data: &[u8]) -> DbResult<String> {
+        let (hash, size) = blobs.put(data)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO blobs (hash, size, refcount) VALUES (?1, ?2, 0)",
+            params![hash, size as i64],
+        )?;
+        Ok(hash)
+    }
+
+    fn migrate(conn: &mut Connection, blobs: &BlobStore) -> DbResult<u32> {
+        run_migrations_with(conn, MIGRATIONS, |tx, version| match version {
+            3 => Self::move_attachments_to_blobs(tx, blobs),
+            4 => Self::fill_timestamps(tx),
+            5 => Self::split_addresses(tx),
+            6 => Self::split_bodies(tx),
+            _ => Ok(()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        // 其他线程 panic 不影响数据库本身，继续使用
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn folder_id(conn: &Connection, account: &str, folder: &str) -> rusqlite::Result<Option<i64>> {
+        conn.query_row(
+            "SELECT id FROM folders WHERE account = ?1 AND name = ?2",
+            params![account, folder],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    fn ensure_folder(tx: &Transaction, account: &str, folder: &str) -> rusqlite::Result<i64> {
+        tx.execute(
+            "INSERT OR IGNORE INTO folders (account, name) VALUES (?1, ?2)",
+            params![account, folder],
+        )?;
+        tx.query_row(
+            "SELECT id FROM folders WHERE account = ?1 AND name = ?2",
+            params![account, folder],
+            |row| row.get(0),
+        )
+    }
+
+    // 写入邮件，已存在的 (文件夹, ID) 会被跳过，返回新写入的数量
+    pub fn insert_emails(&self, account: &str, folder: &str, emails: &[Email]) -> DbResult<usize> {
+        let mut conn = self.lock();
+        // 立即取得写锁，写入附件文件和清理附件在不同连接之间也不会交错
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let folder_id = Self::ensure_folder(&tx, account, folder)?;
+        let mut inserted = 0;
+
+        for email in emails {
+            let size = email.size();
+            // 写邮件、转发等不经过解析的邮件在这里生成摘要
+            let snippet = if email.snippet.is_empty() { email.make_snippet() } else { email.snippet.clone() };
+            let changed = tx.execute(
+                "INSERT OR IGNORE INTO messages (folder_id, uid, subject, date, body, html, status, size, timestamp, received, snippet, server_folder)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    folder_id,
+                    email.id,
+                    email.subject,
+                    email.date,
+                    email.body,
+                    email.html,
+                    serde_json::to_string(&email.status)?,
+                    size as i64,
+                    email.timestamp().map(|t| t.timestamp()),
+                    email.received.map(|t| t.timestamp()),
+                    snippet,
+                    email.server_folder,
+                ],
+            )?;
+            if changed == 0 {
+                continue;
+            }
+            let message_id = tx.last_insert_rowid();
+            inserted += 1;
+
+            Self::insert_addresses(&tx, message_id, "from", &[Address::Mailbox(email.from.clone())])?;
+            for (kind, list) in [("to", &email.to), ("cc", &email.cc), ("bcc", &email.bcc)] {
+                Self::insert_addresses(&tx, message_id, kind, list)?;
+            }
+
+            for (position, attachment) in email.attachments.iter().enumerate() {
+                // 已在附件存储中的附件（如从其他文件夹复制）直接增加引用
+                // 附件文件丢失时保留原来的哈希，不能把空内容当作附件保存
+                let hash = match (&attachment.blob, attachment.data.is_empty()) {
+                    (Some(hash), true) => {
+                        if !self.blobs.contains(hash) {
+                            println!("附件文件不存在，保留原来的引用: {}", hash);
+                        }
+                        tx.execute(
+                            "INSERT OR IGNORE INTO blobs (hash, size, refcount) VALUES (?1, ?2, 0)",
+                            params![hash, attachment.size as i64],
+                        )?;
+                        hash.clone()
+                    }
+                    _ => Self::store_blob(&tx, &self.blobs, &attachment.data)?,
+                };
+                tx.prepare_cached(
+                    "INSERT INTO attachments (message_id, position, filename, content_type, data, blob_hash, size)
+                     VALUES (?1, ?2, ?3, ?4, X'', ?5, ?6)",
+                )?
+                .execute(params![
+                    message_id,
+                    position as i64,
+                    attachment.filename,
+                    attachment.content_type,
+                    hash,
+                    attachment.byte_len() as i64,
+                ])?;
+            }
+
+            if let Some(raw) = &email.raw {
+                tx.execute(
+                    "INSERT INTO sources (message_id, raw) VALUES (?1, ?2)",
+                    params![message_id, raw],
+                )?;
+            }
+
+            let system = [
+                email.seen.then_some(FLAG_SEEN),
+                email.flagged.then_some(FLAG_FLAGGED),
+                email.answered.then_some(FLAG_ANSWERED),
+            ];
+            for flag in system.into_iter().flatten().chain(email.labels.iter().map(String::as_str)) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO flags (message_id, flag) VALUES (?1, ?2)",
+                    params![message_id, flag],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    // 按排序、筛选和分页条件查询文件夹中的邮件
+    pub fn query_emails(&self, account: &str, folder: &str, query: &MessageQuery) -> DbResult<Vec<Email>> {
+        let conn = self.lock();
+        let Some(folder_id) = Self::folder_id(&conn, account, folder)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut values = vec![Value::from(folder_id), Value::from(FLAG_SEEN.to_string())];
+        let mut conditions = vec!["m.folder_id = ?1".to_string()];
+        conditions.extend(query.conditions(&mut values));
+        let sql = format!(
+            "{} WHERE {} ORDER BY {} {}",
+            SELECT_EMAILS,
+            conditions.join(" AND "),
+            query.order_clause(),
+            query.limit_clause(&mut values)
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), |row| Self::email_from_row(row, account))?;
+        Self::collect_emails(&conn, rows)
+    }
+
+    // 邮件列表中各邮件的行 ID，按查询条件排序；列表只保存行 ID，显示时再按需读取
+    pub fn list_keys(&self, accounts: &[&str], scope: &MessageScope, query: &MessageQuery) -> DbResult<Vec<i64>> {
+        if accounts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.lock();
+        let mut values = Vec::new();
+        let placeholders: Vec<String> = accounts.iter().map(|a| bind(&mut values, a.to_string())).collect();
+        let mut conditions = vec![format!("fo.account IN ({})", placeholders.join(", "))];
+        conditions.push(match scope {
+            MessageScope::Folder(folder) => format!("fo.name = {}", bind(&mut values, folder.clone())),
+            MessageScope::Flag(flag) => format!(
+                "EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {})",
+                bind(&mut values, flag.clone())
+            ),
+            MessageScope::Search(search, excluded) => {
+                let excluded: Vec<&str> = excluded.iter().map(String::as_str).collect();
+                search_condition(search, &excluded, &mut values)
+            }
+        });
+        conditions.extend(query.conditions(&mut values));
+        let sql = format!(
+            "SELECT m.id, COALESCE(NULLIF(s.name, ''), s.address) AS sender FROM messages m
+             JOIN folders fo ON fo.id = m.folder_id
+             LEFT JOIN addresses s ON s.message_id = m.id AND s.kind = 'from'
+             WHERE {} ORDER BY {} {}",
+            conditions.join(" AND "),
+            query.order_clause(),
+            query.limit_clause(&mut values)
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let keys = stmt
+            .query_map(params_from_iter(values), |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+        Ok(keys)
+    }
+
+    // 按行 ID 读取列表显示需要的字段，按 rows 的顺序返回 (行 ID, 邮件)，已删除的邮件不返回
+    pub fn headers(&self, rows: &[i64]) -> DbResult<Vec<(i64, Email)>> {
+        let conn = self.lock();
+        let mut ids = Vec::new();
+        let mut emails = Vec::new();
+        for chunk in rows.chunks(DETAIL_BATCH) {
+            let sql = format!("{} WHERE m.id IN ({})", SELECT_HEADERS, vec!["?"; chunk.len()].join(","));
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let found = stmt.query_map(params_from_iter(chunk), |row| {
+                let (id, mut email) = Self::email_from_row(row, "")?;
+                email.account = row.get(18)?;
+                Ok((id, email))
+            })?;
+            for row in found {
+                let (id, email) = row?;
+                ids.push(id);
+                emails.push(email);
+            }
+        }
+        Self::load_details(&conn, &ids, &mut emails)?;
+
+        let mut by_id: HashMap<i64, Email> = ids.into_iter().zip(emails).collect();
+        Ok(rows.iter().filter_map(|id| Some((*id, by_id.remove(id)?))).collect())
+    }
+
+    // 按行 ID 读取完整的邮件，用于打开邮件和批量操作，按 rows 的顺序，已删除的邮件不返回
+    pub fn messages(&self, rows: &[i64]) -> DbResult<Vec<Email>> {
+        let conn = self.lock();
+        let mut ids = Vec::new();
+        let mut emails = Vec::new();
+        for chunk in rows.chunks(DETAIL_BATCH) {
+            // ?1 为行 ID 的 JSON 数组
+            let list = serde_json::to_string(chunk)?;
+            let accounts: HashMap<i64, String> = conn
+                .prepare_cached(
+                    "SELECT m.id, fo.account FROM messages m JOIN folders fo ON fo.id = m.folder_id
+                     WHERE m.id IN (SELECT value FROM json_each(?1))",
+                )?
+                .query_map(params![list], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            let sql = format!("{} WHERE m.id IN (SELECT value FROM json_each(?1))", SELECT_EMAILS);
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let found = stmt.query_map(params![list, FLAG_SEEN], |row| Self::email_from_row(row, ""))?;
+            for row in found {
+                let (id, mut email) = row?;
+                email.account = accounts.get(&id).cloned().unwrap_or_default();
+                ids.push(id);
+                emails.push(email);
+            }
+        }
+        Self::load_details(&conn, &ids, &mut emails)?;
+
+        let mut by_id: HashMap<i64, Email> = ids.into_iter().zip(emails).collect();
+        Ok(rows.iter().filter_map(|id| by_id.remove(id)).collect())
+    }
+
+    // 选中的邮件中各标记出现的次数，用于批量操作工具栏
+    pub fn flag_counts(&self, rows: &[i64]) -> DbResult<HashMap<String, usize>> {
+        let conn = self.lock();
+        let mut counts = HashMap::new();
+        for chunk in rows.chunks(DETAIL_BATCH) {
+            let sql = format!(
+                "SELECT flag, COUNT(*) FROM flags WHERE message_id IN ({}) GROUP BY flag",
+                vec!["?"; chunk.len()].join(",")
+            );
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let found = stmt.query_map(params_from_iter(chunk), |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+            for row in found {
+                let (flag, count) = row?;
+                *counts.entry(flag).or_insert(0) += count as usize;
+            }
+        }
+        Ok(counts)
+    }
+
+    // 账户中符合搜索条件的未读邮件数，没有用 in: 指定文件夹时不包括 excluded 中的文件夹
+    pub fn count_unread_matching(&self, account: &str, query: &SearchQuery, excluded: &[&str]) -> DbResult<usize> {
+        let conn = self.lock();
+        let mut values = vec![Value::from(account.to_string()), Value::from(FLAG_SEEN.to_string())];
+        let condition = search_condition(query, excluded, &mut values);
+        let sql = format!(
+            "SELECT COUNT(*) FROM messages m JOIN folders fo ON fo.id = m.folder_id
+             WHERE fo.account = ?1
+               AND NOT EXISTS (SELECT 1 FROM flags f WHERE f.message_id = m.id AND f.flag = ?2) AND {}",
+            condition
+        );
+        let count: i64 = conn.query_row(&sql, params_from_iter(values), |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    // SELECT_EMAILS 的一行，返回 (消息行 ID, 邮件)
+    fn email_from_row(row: &rusqlite::Row, account: &str) -> rusqlite::Result<(i64, Email)> {
+        let mut email = Email {
+            id: row.get(1)?,
+            subject: row.get(2)?,
+            date: row.get(3)?,
+            body: row.get(4)?,
+            html: row.get(11)?,
+            body_view: row.get::<_, Option<String>>(12)?.as_deref().and_then(BodyView::parse),
+            status: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or(EmailStatus::Draft),
+            from: Mailbox {
+                name: row.get(9)?,
+                address: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+            },
+            seen: row.get(7)?,
+            flagged: row.get(14)?,
+            answered: row.get(16)?,
+            snippet: row.get::<_, Option<String>>(15)?.unwrap_or_default(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            attachments: Vec::new(),
+            account: account.to_string(),
+            folder: row.get(13)?,
+            labels: Vec::new(),
+            raw: None,
+            received: row.get::<_, Option<i64>>(8)?.and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            server_folder: row.get(17)?,
+        };
+        // 迁移前保存的邮件没有摘要
+        if email.snippet.is_empty() {
+            email.snippet = email.make_snippet();
+        }
+        Ok((row.get::<_, i64>(0)?, email))
+    }
+
+    fn collect_emails(
+        conn: &Connection,
+        rows: impl Iterator<Item = rusqlite::Result<(i64, Email)>>,
+    ) -> DbResult<Vec<Email>> {
+        let mut ids = Vec::new();
+        let mut emails = Vec::new();
+        for row in rows {
+            let (message_id, email) = row?;
+            ids.push(message_id);
+            emails.push(email);
+        }
+        Self::load_details(conn, &ids, &mut emails)?;
+        Ok(emails)
+    }
+
+    // 补充收件人列表、附件和标签，按批查询，emails 与 ids 一一对应
+    fn load_details(conn: &Connection, ids: &[i64], emails: &mut [Email]) -> DbResult<()> {
+        let index: HashMap<i64, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+        for chunk in ids.chunks(DETAIL_BATCH) {
+            let placeholders = vec!["?"; chunk.len()].join(",");
+
+            let mut addresses = conn.prepare_cached(&format!(
+                "SELECT message_id, kind, address, name, group_name FROM addresses
+                 WHERE message_id IN ({}) AND kind != 'from' ORDER BY message_id, kind, position",
+                placeholders
+            ))?;
+            let rows = addresses.query_map(params_from_iter(chunk), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    Mailbox { address: row.get(2)?, name: row.get(3)? },
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?;
+            for row in rows {
+                let (message_id, kind, mailbox, group) = row?;
+                let email = &mut emails[index[&message_id]];
+                let list = match kind.as_str() {
+                    "to" => &mut email.to,
+                    "cc" => &mut email.cc,
+                    "bcc" => &mut email.bcc,
+                    _ => continue,
+                };
+                // 同一地址组的成员是连续的几行
+                match (group, list.last_mut()) {
+                    (Some(group), Some(Address::Group { name, members })) if *name == group => {
+                        if !mailbox.address.is_empty() {
+                            members.push(mailbox);
+                        }
+                    }
+                    (Some(group), _) => list.push(Address::Group {
+                        name: group,
+                        members: if mailbox.address.is_empty() { Vec::new() } else { vec![mailbox] },
+                    }),
+                    (None, _) => list.push(Address::Mailbox(mailbox)),
+                }
+            }
+
+            // 只加载附件信息，内容在需要时从附件存储读取
+            let mut attachments = conn.prepare_cached(&format!(
+                "SELECT message_id, filename, content_type, blob_hash, size FROM attachments
+                 WHERE message_id IN ({}) ORDER BY message_id, position",
+                placeholders
+            ))?;
+            let rows = attachments.query_map(params_from_iter(chunk), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    Attachment {
+                        filename: row.get(1)?,
+                        content_type: row.get(2)?,
+                        data: Vec::new(),
+                        blob: row.get(3)?,
+                        size: row.get::<_, i64>(4)? as u64,
+                    },
+                ))
+            })?;
+            for row in rows {
+                let (message_id, attachment) = row?;
+                emails[index[&message_id]].attachments.push(attachment);
+            }
+
+            let mut flags = conn.prepare_cached(&format!(
+                "SELECT message_id, flag FROM flags WHERE message_id IN ({}) ORDER BY message_id, flag",
+                placeholders
+            ))?;
+            let rows = flags.query_map(params_from_iter(chunk), |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            for row in rows {
+                let (message_id, flag) = row?;
+                if is_label_keyword(&flag) {
+                    emails[index[&message_id]].labels.push(flag);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // 邮件原文，旧版本同步的邮件没有保存原文
+    pub fn source(&self, account: &str, folder: &str, uid: &str) -> DbResult<Option<Vec<u8>>> {
+        let conn = self.lock();
+        let raw = conn
+            .query_row(
+                "SELECT s.raw FROM sources s
+                 JOIN messages m ON m.id = s.message_id
+                 JOIN folders f ON f.id = m.folder_id
+                 WHERE f.account = ?1 AND f.name = ?2 AND m.uid = ?3",
+                params![account, folder, uid],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(raw)
+    }
+
+    pub fn count_unread(&self, account: &str, folder: &str) -> DbResult<usize> {
+        let conn = self.lock();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM messages m JOIN folders f ON f.id = m.folder_id
+             WHERE f.account = ?1 AND f.name = ?2
+               AND NOT EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = ?3)",
+            params![account, folder, FLAG_SEEN],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    // 账户下已保存的、位于服务器文件夹 mailbox 中的邮件 UID，本地移到其他文件夹的邮件也包括在内
+    pub fn server_uids(&self, account: &str, mailbox: &str) -> DbResult<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT m.uid FROM messages m JOIN folders f ON f.id = m.folder_id
+             WHERE f.account = ?1 AND m.server_folder = ?2",
+        )?;
+        let uids = stmt
+            .query_map(params![account, mailbox], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(uids)
+    }
+
+    // 旧版本按序号保存的邮件没有服务器位置，同步到日期和主题相同的邮件时改为记录它的 UID，
+    // 返回这样认领的邮件 ID，这些邮件不需要再次保存
+    pub fn adopt_legacy_messages(&self, account: &str, mailbox: &str, emails: &[Email]) -> DbResult<HashSet<String>> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut adopted = HashSet::new();
+        {
+            let mut stmt = tx.prepare_cached(
+                "UPDATE OR IGNORE messages SET uid = ?1, server_folder = ?2
+                 WHERE id = (SELECT m.id FROM messages m JOIN folders f ON f.id = m.folder_id
+                             WHERE f.account = ?3 AND m.server_folder IS NULL AND m.uid GLOB '[0-9]*'
+                               AND m.date = ?4 AND m.subject = ?5
+                             LIMIT 1)",
+            )?;
+            for email in emails {
+                if stmt.execute(params![email.id, mailbox, account, email.date, email.subject])? > 0 {
+                    adopted.insert(email.id.clone());
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(adopted)
+    }
+
+    // 邮件已在服务器上移到其他文件夹、新的 UID 未知时，不再记录它在服务器上的位置
+    pub fn forget_server_location(&self, account: &str, mailbox: &str, uids: &[u32]) -> DbResult<usize> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut changed = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "UPDATE messages SET server_folder = NULL
+                 WHERE server_folder = ?2 AND uid = ?3
+                   AND folder_id IN (SELECT id FROM folders WHERE account = ?1)",
+            )?;
+            for uid in uids {
+                changed += stmt.execute(params![account, mailbox, uid.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    // 文件夹中已保存邮件的 ID
+    pub fn folder_uids(&self, account: &str, folder: &str) -> DbResult<HashSet<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT m.uid FROM messages m JOIN folders f ON f.id = m.folder_id
+             WHERE f.account = ?1 AND f.name = ?2",
+        )?;
+        let uids = stmt
+            .query_map(params![account, folder], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(uids)
+    }
+
+    // 文件夹中带有某个标记的邮件 ID
+    pub fn folder_uids_with_flag(&self, account: &str, folder: &str, flag: &str) -> DbResult<HashSet<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT m.uid FROM messages m JOIN folders f ON f.id = m.folder_id
+             WHERE f.account = ?1 AND f.name = ?2
+               AND EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = ?3)",
+        )?;
+        let uids = stmt
+            .query_map(params![account, folder, flag], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(uids)
+    }
+
+    // 账户下的所有文件夹名称
+    pub fn folders(&self, account: &str) -> DbResult<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare("SELECT name FROM folders WHERE account = ?1 ORDER BY name")?;
+        let names = stmt
+            .query_map(params![account], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(names)
+    }
+
+    // 删除文件夹中指定 ID 的邮件
+    pub fn delete_messages(&self, account: &str, folder: &str, uids: &[String]) -> DbResult<usize> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let Some(folder_id) = Self::folder_id(&tx, account, folder)? else {
+            return Ok(0);
+        };
+        let mut deleted = 0;
+        let mut hashes = HashSet::new();
+        {
+            let mut blobs = tx.prepare_cached(
+                "SELECT a.blob_hash FROM attachments a JOIN messages m ON m.id = a.message_id
+                 WHERE m.folder_id = ?1 AND m.uid = ?2 AND a.blob_hash IS NOT NULL",
+            )?;
+            let mut stmt = tx.prepare_cached("DELETE FROM messages WHERE folder_id = ?1 AND uid = ?2")?;
+            for uid in uids {
+                for hash in blobs.query_map(params![folder_id, uid], |row| row.get(0))? {
+                    hashes.insert(hash?);
+                }
+                deleted += stmt.execute(params![folder_id, uid])?;
+            }
+        }
+        self.release_blobs(&tx, &hashes)?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    // 记录单封邮件的正文显示方式，None 恢复为默认设置
+    pub fn set_body_view(&self, account: &str, folder: &str, uid: &str, view: Option<BodyView>) -> DbResult<()> {
+        self.lock().execute(
+            "UPDATE messages SET body_view = ?4
+             WHERE uid = ?3 AND folder_id = (SELECT id FROM folders WHERE account = ?1 AND name = ?2)",
+            params![account, folder, uid, view.map(|v| v.as_str())],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_flag(&self, account: &str, folder: &str, uid: &str, flag: &str, set: bool) -> DbResult<()> {
+        let conn = self.lock();
+        let message_id: Option<i64> = conn
+            .query_row(
+                "SELECT m.id FROM messages m JOIN folders f ON f.id = m.folder_id
+                 WHERE f.account = ?1 AND f.name = ?2 AND m.uid = ?3",
+                params![account, folder, uid],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(message_id) = message_id else {
+            return Ok(());
+        };
+
+        if set {
+            conn.execute(
+                "INSERT OR IGNORE INTO flags (message_id, flag) VALUES (?1, ?2)",
+                params![message_id, flag],
+            )?;
+        } else {
+            conn.execute(
+                "DELETE FROM flags WHERE message_id = ?1 AND flag = ?2",
+                params![message_id, flag],
+            )?;
+        }
+        Ok(())
+    }
+
+    // 从账户的所有邮件上去掉某个标记，如删除标签时
+    pub fn clear_flag(&self, account: &str, flag: &str) -> DbResult<usize> {
+        let conn = self.lock();
+        let removed = conn.execute(
+            "DELETE FROM flags WHERE flag = ?1 AND message_id IN
+                (SELECT m.id FROM messages m JOIN folders f ON f.id = m.folder_id WHERE f.account = ?2)",
+            params![flag, account],
+        )?;
+        Ok(removed)
+    }
+
+    pub fn last_sync(&self, account: &str, folder: &str) -> DbResult<Option<DateTime<Utc>>> {
+        let conn = self.lock();
+        let value: Option<Option<String>> = conn
+            .query_row(
+                "SELECT last_sync FROM folders WHERE account = ?1 AND name = ?2",
+                params![account, folder],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value
+            .flatten()
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|t| t.with_timezone(&Utc)))
+    }
+
+    pub fn set_last_sync(&self, account: &str, folder: &str, time: DateTime<Utc>) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let folder_id = Self::ensure_folder(&tx, account, folder)?;
+        tx.execute(
+            "UPDATE folders SET last_sync = ?1 WHERE id = ?2",
+            params![time.to_rfc3339(), folder_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 删除账户的所有文件夹和邮件
+    pub fn delete_account(&self, account: &str) -> DbResult<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let hashes = tx
+            .prepare(
+                "SELECT DISTINCT a.blob_hash FROM attachments a
+                 JOIN messages m ON m.id = a.message_id JOIN folders f ON f.id = m.folder_id
+                 WHERE f.account = ?1 AND a.blob_hash IS NOT NULL",
+            )?
+            .query_map(params![account], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        tx.execute("DELETE FROM folders WHERE account = ?1", params![account])?;
+        self.release_blobs(&tx, &hashes)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 打开附件用于流式读取，附件存储中的内容从文件读取，刚解析的附件直接读取内存中的内容
+    pub fn open_attachment<'a>(&self, attachment: &'a Attachment) -> DbResult<Box<dyn Read + Send + 'a>> {
+        match &attachment.blob {
+            Some(hash) if attachment.data.is_empty() => Ok(Box::new(self.blobs.open(hash)?)),
+            _ => Ok(Box::new(attachment.data.as_slice())),
+        }
+    }
+
+    // 读取附件的全部内容，只用于需要完整内容的地方（如解析附带的邮件）
+    pub fn attachment_data(&self, attachment: &Attachment) -> DbResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(attachment.byte_len() as usize);
+        self.open_attachment(attachment)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // 把附件保存到指定文件，不整体读入内存
+    pub fn save_attachment(&self, attachment: &Attachment, destination: &Path) -> DbResult<u64> {
+        let mut reader = self.open_attachment(attachment)?;
+        Ok(io::copy(&mut reader, &mut File::create(destination)?)?)
+    }
+
+    // 删除邮件后只清理引用数刚降为 0 的附件，不扫描整个附件目录
+    // 与删除邮件在同一个写事务中进行，其他连接此时无法写入相同内容的附件
+    fn release_blobs(&self, tx: &Transaction, hashes: &HashSet<String>) -> DbResult<()> {
+        let mut stmt = tx.prepare_cached("DELETE FROM blobs WHERE hash = ?1 AND refcount <= 0")?;
+        for hash in hashes {
+            if stmt.execute(params![hash])? > 0 {
+                self.blobs.remove(hash)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 删除没有引用的附件文件，返回 (文件数, 释放的字节数)
+    // 扫描整个附件目录，在启动时或手动清理时调用
+    // 在写事务中先删除登记再删文件，其他连接此时无法写入附件；之后写入相同内容时会重新保存文件
+    pub fn collect_garbage(&self) -> DbResult<(usize, u64)> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let unused: Vec<String> = tx
+            .prepare("SELECT hash FROM blobs WHERE refcount <= 0")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut removed = 0;
+        let mut freed = 0;
+        for hash in &unused {
+            if tx.execute("DELETE FROM blobs WHERE hash = ?1 AND refcount <= 0", params![hash])? > 0 {
+                freed += self.blobs.remove(hash)?;
+                removed += 1;
+            }
+        }
+
+        // 没有登记的文件（写入后事务失败等情况留下的）
+        {
+            let mut known = tx.prepare_cached("SELECT 1 FROM blobs WHERE hash = ?1")?;
+            for hash in self.blobs.list()? {
+                if !known.exists(params![hash])? && self.blobs.is_older_than(&hash, ORPHAN_GRACE) {
+                    freed += self.blobs.remove(&hash)?;
+                    removed += 1;
+                }
+            }
+        }
+        tx.commit()?;
+        self.blobs.clear_tmp(ORPHAN_GRACE)?;
+
+        if removed > 0 {
+            println!("清理了 {} 个附件文件，释放 {} 字节", removed, freed);
+        }
+        Ok((removed, freed))
+    }
+}
+
+// 搜索时比较文字的 SQL 函数，与 SearchQuery::matches 使用相同的规则
+fn register_search_functions(conn: &Connection) -> rusqlite::Result<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("search_contains", 2, flags, |ctx| {
+        let haystack = ctx.get::<Option<String>>(0)?.unwrap_or_default();
+        Ok(search::contains(&haystack, &ctx.get::<String>(1)?))
+    })?;
+    conn.create_scalar_function("search_mailbox", 3, flags, |ctx| {
+        let mailbox = Mailbox {
+            name: ctx.get(0)?,
+            address: ctx.get::<Option<String>>(1)?.unwrap_or_default(),
+        };
+        Ok(search::mailbox_matches(&mailbox, &ctx.get::<String>(2)?))
+    })?;
+    Ok(())
+}
+
+// 搜索条件转换为 SQL 条件，参数追加到 values 末尾；没有用 in: 指定文件夹时排除 excluded
+fn search_condition(query: &SearchQuery, excluded: &[&str], values: &mut Vec<Value>) -> String {
+    let mut conditions: Vec<String> = query.terms().iter().map(|term| term_condition(term, values)).collect();
+    if query.folders().is_none() {
+        for folder in excluded {
+            conditions.push(format!("fo.name != {}", bind(values, folder.to_string())));
+        }
+    }
+    if conditions.is_empty() {
+        return "1".to_string();
+    }
+    conditions.join(" AND ")
+}
+
+fn term_condition(term: &Term, values: &mut Vec<Value>) -> String {
+    match term {
+        Term::Text(text) => format!(
+            "(search_contains(m.subject, {0}) OR search_contains(m.body, {0})
+              OR EXISTS (SELECT 1 FROM addresses a WHERE a.message_id = m.id AND a.kind IN ('from', 'to', 'cc')
+                         AND (search_mailbox(a.name, a.address, {0}) OR search_contains(a.group_name, {0}))))",
+            bind(values, text.clone())
+        ),
+        Term::From(text) => format!(
+            "EXISTS (SELECT 1 FROM addresses a WHERE a.message_id = m.id AND a.kind = 'from'
+                     AND search_mailbox(a.name, a.address, {}))",
+            bind(values, text.clone())
+        ),
+        Term::To(text) => format!(
+            "EXISTS (SELECT 1 FROM addresses a WHERE a.message_id = m.id AND a.kind IN ('to', 'cc')
+                     AND (search_mailbox(a.name, a.address, {0}) OR search_contains(a.group_name, {0})))",
+            bind(values, text.clone())
+        ),
+        Term::Subject(text) => format!("search_contains(m.subject, {})", bind(values, text.clone())),
+        Term::Unread(unread) => format!(
+            "EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {}) = {}",
+            bind(values, FLAG_SEEN.to_string()),
+            bind(values, !*unread)
+        ),
+        Term::Flagged => format!(
+            "EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {})",
+            bind(values, FLAG_FLAGGED.to_string())
+        ),
+        Term::HasAttachment => "EXISTS (SELECT 1 FROM attachments t WHERE t.message_id = m.id)".to_string(),
+        // 没有时间的邮件不满足日期条件
+        Term::After(date) => format!("COALESCE(m.timestamp >= {}, 0)", bind(values, local_day_start(*date))),
+        Term::Before(date) => format!("COALESCE(m.timestamp < {}, 0)", bind(values, local_day_start(*date))),
+        Term::Label(keyword) => format!(
+            "EXISTS (SELECT 1 FROM flags g WHERE g.message_id = m.id AND g.flag = {} COLLATE NOCASE)",
+            bind(values, keyword.clone())
+        ),
+        Term::Folder(folder) => format!("fo.name = {} COLLATE NOCASE", bind(values, folder.clone())),
+        Term::Not(inner) => format!("NOT ({})", term_condition(inner, values)),
+    }
+}
+
+// 追加一个参数，返回它的占位符
+fn bind(values: &mut Vec<Value>, value: impl Into<Value>) -> String {
+    values.push(value.into());
+    format!("?{}", values.len())
+}
+
+// 本地日期开始时刻的 UTC 秒；当天 0 点因夏令时不存在时取之后第一个存在的整点
+fn local_day_start(date: NaiveDate) -> i64 {
+    let midnight = date.and_time(NaiveTime::MIN);
+    (0..3)
+        .find_map(|hour| Local.from_local_datetime(&(midnight + chrono::Duration::hours(hour))).earliest())
+        .map_or_else(|| midnight.and_utc().timestamp(), |time| time.timestamp())
+}
+
+// 旧版本保存的正文是否为 HTML
+fn looks_like_html(body: &str) -> bool {
+    let lower = body.to_ascii_lowercase();
+    ["<html", "<body", "<div", "<p>", "<br", "<table"].iter().any(|tag| lower.contains(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::imap_client::parse_email;
+
+    fn email(id: &str, headers: &str, body: &str) -> Email {
+        let raw = format!("{}\r\n\r\n{}", headers, body);
+        parse_email(id.into(), raw.as_bytes()).unwrap()
+    }
+
+    // 数据库中的搜索结果与内存中的 SearchQuery::matches 一致
+    #[test]
+    fn search_in_database_matches_search_in_memory() {
+        let db = MailDb::open_in_memory(std::env::temp_dir().join("rustmail-search-blobs")).unwrap();
+        let inbox = vec![
+            email("1", "From: =?UTF-8?B?5byg5LiJ?= <Boss@Example.com>\r\nTo: Team: a@example.com, b@example.com;\r\nSubject: Quarterly Report\r\nDate: Mon, 15 Jan 2024 12:00:00 +0000", "Numbers inside."),
+            email("2", "From: carol@example.com\r\nTo: me@example.com\r\nCc: Ünal <unal@example.com>\r\nSubject: ÉTÉ plans\r\nDate: Tue, 20 Feb 2024 12:00:00 +0000", "see attached"),
+            email("3", "From: news@list.example\r\nTo: Empty:;\r\nSubject: Weekly", "no date here"),
+        ];
+        db.insert_emails("me@example.com", "INBOX", &inbox).unwrap();
+        db.insert_emails("me@example.com", "Junk", &[email("4", "From: spam@x.com\r\nSubject: report", "")]).unwrap();
+        db.set_flag("me@example.com", "INBOX", "1", FLAG_SEEN, true).unwrap();
+        db.set_flag("me@example.com", "INBOX", "2", FLAG_FLAGGED, true).unwrap();
+        db.set_flag("me@example.com", "INBOX", "2", "$Work", true).unwrap();
+
+        let mut all = db.query_emails("me@example.com", "INBOX", &MessageQuery::default()).unwrap();
+        all.extend(db.query_emails("me@example.com", "Junk", &MessageQuery::default()).unwrap());
+        for text in [
+            "", "report", "张三", "boss@example", "team", "empty", "to:b@example.com", "ünal", "été", "inside",
+            "from:carol subject:plans", "-from:carol", "is:unread", "is:read", "is:starred", "label:$work",
+            "-label:$WORK", "after:2024-02-01", "-after:2024-02-01", "before:2024-02-01", "in:junk report", "in:INBOX -weekly",
+        ] {
+            let query = SearchQuery::parse(text);
+            let excluded = ["Junk"];
+            let mut expected: Vec<&str> = all
+                .iter()
+                .filter(|e| query.matches(e) && (query.folders().is_some() || e.folder != "Junk"))
+                .map(|e| e.id.as_str())
+                .collect();
+            expected.sort();
+            let scope = MessageScope::Search(query.clone(), vec!["Junk".to_string()]);
+            let keys = db.list_keys(&["me@example.com"], &scope, &MessageQuery::default()).unwrap();
+            let mut found: Vec<String> = db.headers(&keys).unwrap().into_iter().map(|(_, e)| e.id).collect();
+            found.sort();
+            assert_eq!(found, expected, "{}", text);
+            let unread = all.iter().filter(|e| expected.contains(&e.id.as_str()) && !e.seen).count();
+            assert_eq!(db.count_unread_matching("me@example.com", &query, &excluded).unwrap(), unread, "{}", text);
+        }
+    }
+
+    // 列表按行 ID 分页读取，筛选和排序在数据库中完成
+    #[test]
+    fn list_keys_sort_filter_and_page() {
+        let db = MailDb::open_in_memory(std::env::temp_dir().join("rustmail-list-blobs")).unwrap();
+        let emails: Vec<Email> = (0..30)
+            .map(|i| {
+                let date = format!("Date: Mon, {} Jan 2024 12:00:00 +0000", i + 1);
+                email(&i.to_string(), &format!("From: user{}@example.com\r\nSubject: message {}\r\n{}", i % 3, i, date), "text")
+            })
+            .collect();
+        db.insert_emails("a@example.com", "INBOX", &emails[..20]).unwrap();
+        db.insert_emails("b@example.com", "INBOX", &emails[20..]).unwrap();
+        for i in (0..20).step_by(2) {
+            db.set_flag("a@example.com", "INBOX", &i.to_string(), FLAG_SEEN, true).unwrap();
+        }
+        let inbox = MessageScope::Folder("INBOX".to_string());
+        let ids = |rows: &[i64]| -> Vec<String> { db.headers(rows).unwrap().into_iter().map(|(_, e)| e.id).collect() };
+
+        // 两个账户合并，最新的在前
+        let all = db.list_keys(&["a@example.com", "b@example.com"], &inbox, &MessageQuery::default()).unwrap();
+        assert_eq!(all.len(), 30);
+        assert_eq!(ids(&all[..3]), vec!["29", "28", "27"]);
+        let page = db.list_keys(&["a@example.com", "b@example.com"], &inbox, &MessageQuery::page(1, 5)).unwrap();
+        assert_eq!(page, all[5..10]);
+
+        // 行 ID 的顺序与列表一致，表头不包含完整正文
+        let headers = db.headers(&[all[2], all[0]]).unwrap();
+        assert_eq!(headers.iter().map(|(row, _)| *row).collect::<Vec<_>>(), vec![all[2], all[0]]);
+        assert_eq!(headers[1].1.account, "b@example.com");
+        assert_eq!(headers[1].1.snippet, "text");
+        assert!(headers[1].1.body.is_empty());
+        assert_eq!(db.messages(&all[..1]).unwrap()[0].body.trim(), "text");
+
+        let query = MessageQuery {
+            unread: true,
+            senders: Some(vec!["user1@example.com".to_string()]),
+            search: SearchQuery::parse("message"),
+            ..MessageQuery::default().sorted_by(SortField::Subject, false)
+        };
+        let unread = db.list_keys(&["a@example.com"], &inbox, &query).unwrap();
+        assert_eq!(ids(&unread), vec!["1", "13", "19", "7"]);
+        assert_eq!(db.flag_counts(&all[10..14]).unwrap().get(FLAG_SEEN), Some(&2));
+    }
+
+    // 同步到的邮件记录服务器上的位置，旧版本按序号保存的邮件按日期和主题认领 UID
+    #[test]
+    fn server_locations_and_legacy_adoption() {
+        let db = MailDb::open_in_memory(std::env::temp_dir().join("rustmail-server-blobs")).unwrap();
+        let headers = |subject: &str| format!("From: a@example.com\r\nSubject: {}\r\nDate: Mon, 15 Jan 2024 12:00:00 +0000", subject);
+        let legacy = email("7", &headers("old"), "");
+        let local = email("mbox-1", &headers("old"), "");
+        db.insert_emails("me@example.com", "INBOX", &[legacy, local]).unwrap();
+        let mut synced = email("1042", &headers("new"), "");
+        synced.server_folder = Some("INBOX".to_string());
+        db.insert_emails("me@example.com", "Reports", &[synced]).unwrap();
+
+        // 本地移到其他文件夹的邮件仍在服务器的收件箱中
+        assert_eq!(db.server_uids("me@example.com", "INBOX").unwrap(), vec!["1042"]);
+        let loaded = db.query_emails("me@example.com", "Reports", &MessageQuery::default()).unwrap();
+        assert_eq!(loaded[0].server_location(), Some(("INBOX", 1042)));
+
+        // 只认领数字 ID 的旧邮件，本地导入的邮件保持不变
+        let fetched = [email("2001", &headers("old"), ""), email("2002", &headers("old"), ""), email("2003", &headers("other"), "")];
+        let adopted = db.adopt_legacy_messages("me@example.com", "INBOX", &fetched).unwrap();
+        assert_eq!(adopted, HashSet::from(["2001".to_string()]));
+        let mut uids = db.server_uids("me@example.com", "INBOX").unwrap();
+        uids.sort();
+        assert_eq!(uids, vec!["1042", "2001"]);
+        let inbox = db.query_emails("me@example.com", "INBOX", &MessageQuery::default()).unwrap();
+        assert!(inbox.iter().any(|e| e.id == "mbox-1" && e.server_location().is_none()));
+
+        // 在服务器上移走后不再对应服务器上的 UID
+        assert_eq!(db.forget_server_location("me@example.com", "INBOX", &[1042]).unwrap(), 1);
+        assert_eq!(db.server_uids("me@example.com", "INBOX").unwrap(), vec!["2001"]);
+    }
+
+    // 附件文件丢失时保存邮件仍引用原来的哈希，不会变成空附件
+    #[test]
+    fn missing_blob_keeps_reference() {
+        let root = std::env::temp_dir().join(format!("rustmail-missing-blobs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let db = MailDb::open_in_memory(root.clone()).unwrap();
+        let mut message = email("1", "From: a@example.com\r\nSubject: lost", "");
+        let missing = "ab".repeat(32);
+        message.attachments.push(Attachment { blob: Some(missing.clone()), size: 42, ..Attachment::new("a.pdf".into(), "application/pdf".into(), Vec::new()) });
+        db.insert_emails("me@example.com", "INBOX", &[message]).unwrap();
+
+        let loaded = db.query_emails("me@example.com", "INBOX", &MessageQuery::default()).unwrap();
+        assert_eq!(loaded[0].attachments[0].blob.as_deref(), Some(missing.as_str()));
+        assert_eq!(loaded[0].attachments[0].size, 42);
+        assert!(db.blobs.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    // 相同的附件只保存一份，删除最后一封引用它的邮件后才删除文件
+    #[test]
+    fn attachment_refcount_and_garbage_collection() {
+        let root = std::env::temp_dir().join(format!("rustmail-refcount-blobs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let db = MailDb::open_in_memory(root.clone()).unwrap();
+        let with_attachment = |id: &str, data: &[u8]| {
+            let mut message = email(id, "From: a@example.com\r\nSubject: report", "");
+            message.attachments.push(Attachment::new("report.pdf".into(), "application/pdf".into(), data.to_vec()));
+            message
+        };
+        db.insert_emails("me@example.com", "INBOX", &[with_attachment("1", b"shared"), with_attachment("2", b"shared")]).unwrap();
+        db.insert_emails("me@example.com", "Archive", &[with_attachment("3", b"only")]).unwrap();
+        let refcount = |hash: &str| -> i64 {
+            db.lock().query_row("SELECT refcount FROM blobs WHERE hash = ?1", params![hash], |row| row.get(0)).unwrap()
+        };
+
+        let inbox = db.query_emails("me@example.com", "INBOX", &MessageQuery::default()).unwrap();
+        let shared = inbox[0].attachments[0].clone();
+        let hash = shared.blob.clone().unwrap();
+        assert_eq!(inbox[1].attachments[0].blob.as_ref(), Some(&hash));
+        assert_eq!(refcount(&hash), 2);
+        assert_eq!(db.blobs.list().unwrap().len(), 2);
+
+        // 从存储中流式读取附件
+        let mut data = Vec::new();
+        db.open_attachment(&shared).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"shared");
+
+        // 复制到其他文件夹只增加引用
+        db.insert_emails("me@example.com", "Archive", &inbox[..1]).unwrap();
+        assert_eq!(refcount(&hash), 3);
+        assert_eq!(db.blobs.list().unwrap().len(), 2);
+
+        // 删除邮件只清理刚失去引用的附件，其他未引用的文件留到完整清理时处理
+        let (unused, _) = db.blobs.put(b"unused").unwrap();
+        db.lock().execute("INSERT INTO blobs (hash, size, refcount) VALUES (?1, 6, 0)", params![unused]).unwrap();
+
+        db.delete_messages("me@example.com", "INBOX", &["1".to_string(), "2".to_string()]).unwrap();
+        assert_eq!(refcount(&hash), 1);
+        assert!(db.blobs.contains(&hash));
+        db.delete_messages("me@example.com", "Archive", &[inbox[0].id.clone()]).unwrap();
+        assert!(!db.blobs.contains(&hash));
+        assert_eq!(db.blobs.list().unwrap().len(), 2);
+
+        db.delete_account("me@example.com").unwrap();
+        assert_eq!(db.blobs.list().unwrap(), vec![unused.clone()]);
+        assert_eq!(db.collect_garbage().unwrap(), (1, 6));
+        assert!(db.blobs.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    // 10 万封邮件时列表的加载时间：cargo test --release -- --ignored list_keys_for_100k
+    #[test]
+    #[ignore]
+    fn list_keys_for_100k_messages() {
+        let db = MailDb::open_in_memory(std::env::temp_dir().join("rustmail-bench-blobs")).unwrap();
+        let emails: Vec<Email> = (0..100_000)
+            .map(|i| {
+                let date = format!("Date: {}", (DateTime::from_timestamp(1_600_000_000 + i * 600, 0).unwrap()).to_rfc2822());
+                email(&i.to_string(), &format!("From: User {} <user{}@example.com>\r\nTo: me@example.com\r\nSubject: message {}\r\n{}", i % 500, i % 500, i, date), "body text")
+            })
+            .collect();
+        for chunk in emails.chunks(5000) {
+            db.insert_emails("me@example.com", "INBOX", chunk).unwrap();
+        }
+        let inbox = MessageScope::Folder("INBOX".to_string());
+
+        for query in [
+            MessageQuery::default(),
+            MessageQuery::default().sorted_by(SortField::From, false),
+            MessageQuery { unread: true, search: SearchQuery::parse("from:user42"), ..MessageQuery::default() },
+        ] {
+            let start = std::time::Instant::now();
+            let keys = db.list_keys(&["me@example.com"], &inbox, &query).unwrap();
+            let listed = start.elapsed();
+            let start = std::time::Instant::now();
+            let window = db.headers(&keys[keys.len() / 2..keys.len() / 2 + 40]).unwrap();
+            let loaded = start.elapsed();
+            println!("{:?}: {} 封，列表 {:?}，一屏 {:?}", query.sort, keys.len(), listed, loaded);
+            assert_eq!(window.len(), 40);
+            assert!(listed < Duration::from_secs(2), "{:?}", listed);
+            assert!(loaded < Duration::from_millis(100), "{:?}", loaded);
+        }
+    }
+}
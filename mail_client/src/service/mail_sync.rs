@@ -0,0 +1,85 @@
+use futures_util::future::join_all;
+use crate::models::{EmailAccount, SearchQuery};
+use crate::service::{filter, imap_client, local_storage::LocalStorage, spam::{self, SpamModel}};
+
+// 单个账户的同步结果
+#[derive(Clone, Debug)]
+pub struct SyncOutcome {
+    pub address: String,
+    pub result: Result<usize, String>, // 成功时为新邮件数量
+}
+
+// 同时同步所有账户的收件箱
+// 网络请求并发进行，写入根组件共享的本地存储按顺序完成
+pub async fn sync_all_accounts(mut storage: LocalStorage, accounts: &[EmailAccount]) -> Vec<SyncOutcome> {
+    println!("开始同步 {} 个账户...", accounts.len());
+    let fetches = accounts.iter().map(|account| {
+        let synced_ids = storage.get_synced_ids(account, "INBOX");
+        let since_date = storage.get_last_sync(account);
+        async move {
+            imap_client::fetch_new_emails(
+                &account.imap_server,
+                account.imap_port,
+                &account.address,
+                &account.password,
+                account.use_tls,
+                since_date,
+                synced_ids,
+            )
+            .await
+        }
+    });
+    let results = join_all(fetches).await;
+
+    // 新邮件按过滤规则分到各个文件夹
+    let rules = storage.filter_rules().unwrap_or_else(|e| {
+        println!("读取过滤规则失败: {}", e);
+        Vec::new()
+    });
+
+    let mut outcomes = Vec::new();
+    for (account, result) in accounts.iter().zip(results) {
+        let result = match result {
+            Ok(mut new_emails) => {
+                println!("{}: 从服务器获取到 {} 封新邮件", account.address, new_emails.len());
+                for email in new_emails.iter_mut() {
+                    email.account = account.address.clone();
+                }
+                // 旧版本按序号保存过的邮件只改为记录 UID，不再重复保存
+                match storage.adopt_synced_emails(account, "INBOX", &new_emails) {
+                    Ok(adopted) => new_emails.retain(|e| !adopted.contains(&e.id)),
+                    Err(e) => println!("{}: 更新旧邮件的 UID 失败: {}", account.address, e),
+                }
+                let count = new_emails.len();
+                let spam_model = storage.spam_model(account).unwrap_or_else(|e| {
+                    println!("{}: 读取垃圾邮件模型失败: {}", account.address, e);
+                    SpamModel::default()
+                });
+                match filter::save_new_emails(&mut storage, account, &rules, Some(&spam_model), new_emails) {
+                    Ok(forwards) => {
+                        filter::send_forwards(account, forwards).await;
+                        Ok(count)
+                    }
+                    Err(e) => Err(format!("保存邮件到本地失败: {}", e)),
+                }
+            }
+            Err(e) => Err(format!("从服务器获取邮件失败: {}", e)),
+        };
+        if let Err(e) = &result {
+            println!("{}: {}", account.address, e);
+        }
+        outcomes.push(SyncOutcome { address: account.address.clone(), result });
+    }
+    outcomes
+}
+
+// 没有用 in: 指定文件夹时，搜索不包括垃圾邮件和已删除
+pub const SEARCH_EXCLUDED: [&str; 2] = [spam::JUNK_FOLDER, filter::TRASH_FOLDER];
+
+// 各账户中符合搜索条件的未读邮件数
+pub fn count_search_unread(storage: &LocalStorage, accounts: &[EmailAccount], query: &SearchQuery) -> usize {
+    accounts
+        .iter()
+        .map(|account| storage.search_unread_count(account, query, &SEARCH_EXCLUDED))
+        .sum()
+}
@@ -0,0 +1,338 @@
+use std::fs::{self, File, create_dir_all};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::models::Email;
+
+// 本程序写入的文件名中的主机名部分，用于识别自己保存的邮件
+const HOST_TAG: &str = "rustmail";
+
+// 文件名中唯一部分和标记之间的分隔符，Windows 的文件名不能包含 ":"，改用 "!"
+const INFO_SEPARATOR: &str = if cfg!(windows) { "!2," } else { ":2," };
+
+// 同一进程内生成唯一文件名的计数器
+static DELIVERY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Maildir 中的一封邮件
+#[derive(Clone, Debug)]
+pub struct MaildirEntry {
+    pub path: PathBuf,
+    pub unique: String, // 文件名中 ":2," 之前的部分，移动或改标记时保持不变
+    pub flags: String,  // 按字母排序的标记，如 "FS"
+}
+
+impl MaildirEntry {
+    pub fn is_seen(&self) -> bool {
+        self.flags.contains('S')
+    }
+
+    // 邮件在应用中的 ID：本程序写入的文件带有服务器 ID，其他工具写入的使用文件名
+    pub fn message_id(&self) -> String {
+        uid_from_unique(&self.unique).unwrap_or_else(|| format!("md-{}", self.unique))
+    }
+}
+
+// 标准 Maildir 目录（Maildir++ 布局）
+// 收件箱为根目录本身，其他文件夹为 ".名称"，多级文件夹用 "." 连接，如 ".Archive.2024"
+#[derive(Clone, Debug)]
+pub struct Maildir {
+    root: PathBuf,
+}
+
+impl Maildir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // 文件夹对应的目录
+    pub fn folder_path(&self, folder: &str) -> PathBuf {
+        if folder.eq_ignore_ascii_case("INBOX") {
+            self.root.clone()
+        } else {
+            self.root.join(format!(".{}", folder.replace('/', ".")))
+        }
+    }
+
+    // 创建文件夹的 cur/new/tmp 子目录
+    pub fn create_folder(&self, folder: &str) -> io::Result<PathBuf> {
+        let path = self.folder_path(folder);
+        for sub in ["cur", "new", "tmp"] {
+            create_dir_all(path.join(sub))?;
+        }
+        Ok(path)
+    }
+
+    // 列出所有文件夹，同时支持 Maildir++ 的 ".A.B" 和按目录嵌套的 "A/B/cur" 两种布局
+    pub fn list_folders(&self) -> io::Result<Vec<String>> {
+        let mut folders = Vec::new();
+        if is_maildir(&self.root) {
+            folders.push("INBOX".to_string());
+        }
+        self.collect_nested(&self.root, "", &mut folders)?;
+        folders.sort();
+        folders.dedup();
+        Ok(folders)
+    }
+
+    fn collect_nested(&self, dir: &Path, prefix: &str, folders: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if prefix.is_empty() && matches!(name.as_str(), "cur" | "new" | "tmp") {
+                continue;
+            }
+
+            if prefix.is_empty() && name.starts_with('.') && name.len() > 1 {
+                // Maildir++ 子文件夹
+                if is_maildir(&path) {
+                    folders.push(name[1..].replace('.', "/"));
+                }
+                continue;
+            }
+            if matches!(name.as_str(), "cur" | "new" | "tmp") {
+                continue;
+            }
+
+            let folder = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+            if is_maildir(&path) {
+                folders.push(folder.clone());
+            }
+            self.collect_nested(&path, &folder, folders)?;
+        }
+        Ok(())
+    }
+
+    // 嵌套布局下的文件夹目录，Maildir++ 目录不存在时使用
+    fn resolve_folder(&self, folder: &str) -> PathBuf {
+        let path = self.folder_path(folder);
+        if is_maildir(&path) {
+            return path;
+        }
+        let nested = folder.split('/').fold(self.root.clone(), |p, part| p.join(part));
+        if is_maildir(&nested) { nested } else { path }
+    }
+
+    // 列出文件夹中的邮件，new 中的邮件视为没有任何标记
+    pub fn list(&self, folder: &str) -> io::Result<Vec<MaildirEntry>> {
+        let path = self.resolve_folder(folder);
+        let mut entries = Vec::new();
+        for sub in ["new", "cur"] {
+            let dir = path.join(sub);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let file_path = entry?.path();
+                if !file_path.is_file() {
+                    continue;
+                }
+                let Some(name) = file_path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                // 跳过隐藏文件（如部分工具写入的索引）
+                if name.starts_with('.') {
+                    continue;
+                }
+                let (unique, flags) = parse_file_name(name);
+                entries.push(MaildirEntry { path: file_path.clone(), unique, flags });
+            }
+        }
+        entries.sort_by(|a, b| a.unique.cmp(&b.unique));
+        Ok(entries)
+    }
+
+    // 按应用中的邮件 ID 查找
+    pub fn find(&self, folder: &str, message_id: &str) -> io::Result<Option<MaildirEntry>> {
+        Ok(self.list(folder)?.into_iter().find(|entry| entry.message_id() == message_id))
+    }
+
+    // 投递一封邮件：先写入 tmp，再移动到 cur（已有标记）或 new（未读且无标记）
+    pub fn deliver(&self, folder: &str, unique: &str, raw: &[u8], flags: &str) -> io::Result<MaildirEntry> {
+        let path = self.create_folder(folder)?;
+        let tmp_path = path.join("tmp").join(unique);
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(raw)?;
+            file.sync_all()?;
+        }
+
+        let flags = normalize_flags(flags);
+        let final_path = if flags.is_empty() {
+            path.join("new").join(unique)
+        } else {
+            path.join("cur").join(format!("{}{}{}", unique, INFO_SEPARATOR, flags))
+        };
+        fs::rename(&tmp_path, &final_path)?;
+
+        Ok(MaildirEntry { path: final_path, unique: unique.to_string(), flags })
+    }
+
+    // 修改邮件标记，重命名文件并移入 cur
+    pub fn set_flags(&self, entry: &MaildirEntry, flags: &str) -> io::Result<MaildirEntry> {
+        let flags = normalize_flags(flags);
+        let folder_path = entry
+            .path
+            .parent()
+            .and_then(|p| p.parent())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无效的 Maildir 路径"))?;
+        let new_path = folder_path.join("cur").join(format!("{}{}{}", entry.unique, INFO_SEPARATOR, flags));
+        if new_path != entry.path {
+            fs::rename(&entry.path, &new_path)?;
+        }
+        Ok(MaildirEntry { path: new_path, unique: entry.unique.clone(), flags })
+    }
+}
+
+// 目录下同时存在 cur、new、tmp 时视为 Maildir
+fn is_maildir(path: &Path) -> bool {
+    ["cur", "new", "tmp"].iter().all(|sub| path.join(sub).is_dir())
+}
+
+// 拆分文件名为唯一部分和标记，如 "123.M1P2.host:2,RS" -> ("123.M1P2.host", "RS")
+pub fn parse_file_name(name: &str) -> (String, String) {
+    // Windows 上部分工具用 "!" 代替 ":"
+    for separator in [":2,", "!2,"] {
+        if let Some(index) = name.rfind(separator) {
+            return (name[..index].to_string(), normalize_flags(&name[index + separator.len()..]));
+        }
+    }
+    (name.to_string(), String::new())
+}
+
+// 标记只保留大写字母，去重并按字母排序（Maildir 规范要求）
+fn normalize_flags(flags: &str) -> String {
+    let mut chars: Vec<char> = flags.chars().filter(|c| c.is_ascii_uppercase()).collect();
+    chars.sort();
+    chars.dedup();
+    chars.into_iter().collect()
+}
+
+// 根据邮件状态生成 Maildir 标记
+pub fn flags_for(email: &Email) -> String {
+    let mut flags = String::new();
+    if email.flagged {
+        flags.push('F');
+    }
+    if email.answered {
+        flags.push('R');
+    }
+    if email.seen {
+        flags.push('S');
+    }
+    flags
+}
+
+// 在已有标记上设置或清除某个标记
+pub fn with_flag(flags: &str, flag: char, set: bool) -> String {
+    let mut result: String = flags.chars().filter(|c| *c != flag).collect();
+    if set {
+        result.push(flag);
+    }
+    normalize_flags(&result)
+}
+
+// 生成新的唯一文件名，带服务器 ID 时以 "U<ID>" 记录，便于同步时对应回数据库
+pub fn unique_name(message_id: Option<&str>) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let counter = DELIVERY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let base = format!("{}.M{}P{}Q{}", now.as_secs(), now.subsec_micros(), std::process::id(), counter);
+    match message_id {
+        // ID 中的 "."、"/" 等字符会破坏文件名结构，按百分号编码，读取时可以还原
+        Some(id) => format!("{}.U{}.{}", base, encode_uid(id), HOST_TAG),
+        None => format!("{}.{}", base, HOST_TAG),
+    }
+}
+
+// 从本程序生成的唯一文件名中取出服务器 ID
+fn uid_from_unique(unique: &str) -> Option<String> {
+    let rest = unique.strip_suffix(&format!(".{}", HOST_TAG))?;
+    let index = rest.rfind(".U")?;
+    Some(decode_uid(&rest[index + 2..]))
+}
+
+// 文件名中不能出现的字符和 "%" 本身编码为 "%XX"
+fn encode_uid(id: &str) -> String {
+    let mut encoded = String::with_capacity(id.len());
+    for c in id.chars() {
+        if matches!(c, '%' | '.' | '/' | '\\' | ':' | '!') || c.is_control() {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+// 还原 encode_uid，旧版本用 "_" 替换的文件名原样返回
+fn decode_uid(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_name_keeps_message_id() {
+        for id in ["42", "mbox-3f2a.1", "a/b:c!d%2E", "md-1.2_3", "邮件.1"] {
+            let unique = unique_name(Some(id));
+            assert!(!unique.contains(['/', ':', '!']));
+            assert_eq!(uid_from_unique(&unique).as_deref(), Some(id));
+        }
+        // 旧版本用 "_" 替换的文件名
+        assert_eq!(uid_from_unique("1.M2P3Q4.Umbox-3f2a_1.rustmail").as_deref(), Some("mbox-3f2a_1"));
+        assert_eq!(uid_from_unique("1.M2P3Q4.other"), None);
+    }
+
+    // 写入的文件名使用当前系统允许的分隔符，两种分隔符都能读取
+    #[test]
+    fn flags_round_trip_through_file_names() {
+        let root = std::env::temp_dir().join(format!("rustmail-maildir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let md = Maildir::new(root.clone());
+        let unique = unique_name(Some("7"));
+
+        let entry = md.deliver("INBOX", &unique, b"Subject: hi\r\n\r\nbody", "SF").unwrap();
+        let name = entry.path.file_name().unwrap().to_str().unwrap().to_string();
+        assert_eq!(name, format!("{}{}FS", unique, INFO_SEPARATOR));
+        assert!(!cfg!(windows) || !name.contains(':'));
+        assert_eq!(parse_file_name(&name), (unique.clone(), "FS".to_string()));
+
+        let entry = md.set_flags(&entry, "RS").unwrap();
+        assert!(entry.path.ends_with(format!("cur/{}{}RS", unique, INFO_SEPARATOR)));
+        assert_eq!(entry.message_id(), "7");
+
+        assert_eq!(parse_file_name("123.M1P2.host!2,SR"), ("123.M1P2.host".to_string(), "RS".to_string()));
+        assert_eq!(parse_file_name("123.M1P2.host:2,"), ("123.M1P2.host".to_string(), String::new()));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,437 @@
+use std::time::Duration;
+use async_std::io;
+use async_std::net::TcpStream;
+use async_native_tls::TlsConnector;
+use base64::Engine;
+use futures_util::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use crate::models::EmailAccount;
+
+type SieveResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// ManageSieve（RFC 5804）的默认端口
+pub const DEFAULT_PORT: u16 = 4190;
+
+// 每次读写的超时时间
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// 服务器上的一个脚本，同一时间只有一个脚本处于启用状态
+#[derive(Clone, Debug, PartialEq)]
+pub struct SieveScript {
+    pub name: String,
+    pub active: bool,
+}
+
+// 服务器返回 NO 或 BYE 时的错误，message 为服务器给出的说明
+#[derive(Clone, Debug, PartialEq)]
+pub struct SieveError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for SieveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "[{}] {}", code, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SieveError {}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+// 响应中的一项：原子、字符串（带引号或字面量）或括号中的响应码
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Atom(String),
+    Text(String),
+    Code(String),
+}
+
+impl Token {
+    fn as_str(&self) -> &str {
+        match self {
+            Token::Atom(s) | Token::Text(s) | Token::Code(s) => s,
+        }
+    }
+}
+
+pub struct SieveClient {
+    stream: BufReader<Box<dyn Stream>>,
+    capabilities: Vec<(String, String)>,
+}
+
+impl SieveClient {
+    // 连接并登录；use_tls 时使用 STARTTLS，服务器不支持则报错
+    pub async fn connect(host: &str, port: u16, username: &str, password: &str, use_tls: bool) -> SieveResult<Self> {
+        println!("连接到 ManageSieve 服务器: {}:{}", host, port);
+        let tcp_stream = io::timeout(TIMEOUT, TcpStream::connect((host, port))).await?;
+        let mut client = SieveClient::new(Box::new(tcp_stream.clone()));
+        client.capabilities = client.read_capabilities().await?;
+
+        if use_tls {
+            if !client.has_capability("STARTTLS") {
+                return Err("服务器不支持 STARTTLS".into());
+            }
+            client.command("STARTTLS").await?;
+            let tls_stream = TlsConnector::new().connect(host, tcp_stream).await?;
+            client = SieveClient::new(Box::new(tls_stream));
+            // TLS 建立后服务器会重新发送能力列表
+            client.capabilities = client.read_capabilities().await?;
+        }
+
+        let credentials = format!("\0{}\0{}", username, password);
+        let initial = base64::engine::general_purpose::STANDARD.encode(credentials);
+        client.command(&format!("AUTHENTICATE \"PLAIN\" \"{}\"", initial)).await?;
+        // 登录后能力列表可能变化
+        client.capabilities = client.fetch_capabilities().await.unwrap_or(client.capabilities);
+        Ok(client)
+    }
+
+    // 使用账户的 IMAP 服务器地址和登录信息连接
+    pub async fn connect_account(account: &EmailAccount, port: u16) -> SieveResult<Self> {
+        Self::connect(&account.imap_server, port, &account.address, &account.password, account.use_tls).await
+    }
+
+    fn new(stream: Box<dyn Stream>) -> Self {
+        SieveClient { stream: BufReader::new(stream), capabilities: Vec::new() }
+    }
+
+    fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    // 服务器支持的 Sieve 扩展，如 fileinto、vacation
+    pub fn extensions(&self) -> Vec<String> {
+        self.capabilities
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("SIEVE"))
+            .map(|(_, value)| value.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn list_scripts(&mut self) -> SieveResult<Vec<SieveScript>> {
+        let lines = self.command("LISTSCRIPTS").await?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| {
+                let name = line.first()?.as_str().to_string();
+                let active = line.get(1).is_some_and(|t| t.as_str().eq_ignore_ascii_case("ACTIVE"));
+                Some(SieveScript { name, active })
+            })
+            .collect())
+    }
+
+    pub async fn get_script(&mut self, name: &str) -> SieveResult<String> {
+        let lines = self.command(&format!("GETSCRIPT {}", quote(name))).await?;
+        lines
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|token| token.as_str().to_string())
+            .ok_or_else(|| format!("服务器没有返回脚本 {}", name).into())
+    }
+
+    // 上传脚本，服务器会先检查语法，有误时返回 SieveError
+    pub async fn put_script(&mut self, name: &str, content: &str) -> SieveResult<()> {
+        self.command(&format!("PUTSCRIPT {} {}", quote(name), literal(content))).await?;
+        Ok(())
+    }
+
+    // 只检查语法不保存，返回服务器给出的警告
+    pub async fn check_script(&mut self, content: &str) -> SieveResult<Option<String>> {
+        self.send(&format!("CHECKSCRIPT {}", literal(content))).await?;
+        let (_, status, code, message) = self.read_response().await?;
+        match status.as_str() {
+            "OK" if code.as_deref().is_some_and(|c| c.eq_ignore_ascii_case("WARNINGS")) => Ok(Some(message)),
+            "OK" => Ok(None),
+            _ => Err(Box::new(SieveError { code, message })),
+        }
+    }
+
+    // 启用指定脚本；名称为空时停用所有脚本
+    pub async fn set_active(&mut self, name: &str) -> SieveResult<()> {
+        self.command(&format!("SETACTIVE {}", quote(name))).await?;
+        Ok(())
+    }
+
+    pub async fn delete_script(&mut self, name: &str) -> SieveResult<()> {
+        self.command(&format!("DELETESCRIPT {}", quote(name))).await?;
+        Ok(())
+    }
+
+    pub async fn logout(mut self) {
+        let _ = self.command("LOGOUT").await;
+    }
+
+    async fn fetch_capabilities(&mut self) -> SieveResult<Vec<(String, String)>> {
+        self.send("CAPABILITY").await?;
+        self.read_capabilities().await
+    }
+
+    async fn read_capabilities(&mut self) -> SieveResult<Vec<(String, String)>> {
+        let (lines, status, code, message) = self.read_response().await?;
+        if status != "OK" {
+            return Err(Box::new(SieveError { code, message }));
+        }
+        Ok(lines
+            .iter()
+            .filter_map(|line| {
+                let name = line.first()?.as_str().to_string();
+                let value = line.get(1).map(|t| t.as_str().to_string()).unwrap_or_default();
+                Some((name, value))
+            })
+            .collect())
+    }
+
+    // 发送命令并等待完成，NO/BYE 转为错误，返回结果之前的数据行
+    async fn command(&mut self, command: &str) -> SieveResult<Vec<Vec<Token>>> {
+        self.send(command).await?;
+        let (lines, status, code, message) = self.read_response().await?;
+        if status != "OK" {
+            return Err(Box::new(SieveError { code, message }));
+        }
+        Ok(lines)
+    }
+
+    async fn send(&mut self, command: &str) -> SieveResult<()> {
+        let stream = self.stream.get_mut();
+        io::timeout(TIMEOUT, async {
+            stream.write_all(command.as_bytes()).await?;
+            stream.write_all(b"\r\n").await?;
+            stream.flush().await
+        })
+        .await?;
+        Ok(())
+    }
+
+    // 读取到 OK/NO/BYE 为止，返回 (数据行, 状态, 响应码, 说明)
+    async fn read_response(&mut self) -> SieveResult<(Vec<Vec<Token>>, String, Option<String>, String)> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            let status = match line.first() {
+                Some(Token::Atom(atom)) => atom.to_ascii_uppercase(),
+                _ => {
+                    lines.push(line);
+                    continue;
+                }
+            };
+            if !matches!(status.as_str(), "OK" | "NO" | "BYE") {
+                lines.push(line);
+                continue;
+            }
+            let code = line.iter().find_map(|t| match t {
+                Token::Code(code) => Some(code.clone()),
+                _ => None,
+            });
+            let message = line
+                .iter()
+                .find_map(|t| match t {
+                    Token::Text(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            return Ok((lines, status, code, message));
+        }
+    }
+
+    // 读取一行响应，行中的字面量 {n} 会接着读取 n 个字节
+    async fn read_line(&mut self) -> SieveResult<Vec<Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            let mut buf = Vec::new();
+            let read = io::timeout(TIMEOUT, self.stream.read_until(b'\n', &mut buf)).await?;
+            if read == 0 {
+                return Err("服务器关闭了连接".into());
+            }
+            let text = String::from_utf8_lossy(&buf);
+            let text = text.trim_end_matches(['\r', '\n']);
+            match tokenize(text, &mut tokens) {
+                Some(size) => {
+                    let mut data = vec![0u8; size];
+                    io::timeout(TIMEOUT, self.stream.read_exact(&mut data)).await?;
+                    tokens.push(Token::Text(String::from_utf8_lossy(&data).into_owned()));
+                }
+                None => return Ok(tokens),
+            }
+        }
+    }
+}
+
+// 解析一行中的各项，行尾是字面量时返回其长度
+fn tokenize(line: &str, tokens: &mut Vec<Token>) -> Option<usize> {
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            ' ' => {}
+            '"' => {
+                let mut text = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => text.extend(chars.next().map(|(_, c)| c)),
+                        '"' => break,
+                        c => text.push(c),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '(' => {
+                let mut depth = 1;
+                let mut quoted = false;
+                let mut end = line.len();
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '"' => quoted = !quoted,
+                        '(' if !quoted => depth += 1,
+                        ')' if !quoted => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = i;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                tokens.push(Token::Code(line[start + 1..end].to_string()));
+            }
+            '{' if line.ends_with('}') => {
+                let size = line[start + 1..line.len() - 1].trim_end_matches('+');
+                return size.parse().ok();
+            }
+            _ => {
+                let mut end = line.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c == ' ' {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token::Atom(line[start..end].to_string()));
+            }
+        }
+    }
+    None
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// 脚本内容用非同步字面量发送，不需要等待服务器的继续响应
+fn literal(value: &str) -> String {
+    format!("{{{}+}}\r\n{}", value.len(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::sieve::{Vacation, VACATION_SCRIPT};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    // 按预先写好的响应回复，记录客户端发出的内容
+    struct FakeServer {
+        replies: futures_util::io::Cursor<Vec<u8>>,
+        sent: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl AsyncRead for FakeServer {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut self.replies).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for FakeServer {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            self.sent.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn client(replies: &str) -> (SieveClient, Arc<Mutex<Vec<u8>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let server = FakeServer { replies: futures_util::io::Cursor::new(replies.as_bytes().to_vec()), sent: sent.clone() };
+        let client = SieveClient { stream: BufReader::new(Box::new(server)), capabilities: Vec::new() };
+        (client, sent)
+    }
+
+    #[test]
+    fn tokenizes_response_lines() {
+        let mut tokens = Vec::new();
+        assert_eq!(tokenize("NO (QUOTA/MAXSIZE) \"脚本 \\\"太大\\\"\"", &mut tokens), None);
+        assert_eq!(
+            tokens,
+            vec![Token::Atom("NO".into()), Token::Code("QUOTA/MAXSIZE".into()), Token::Text("脚本 \"太大\"".into())]
+        );
+
+        let mut tokens = Vec::new();
+        assert_eq!(tokenize("\"vacation\" {42}", &mut tokens), Some(42));
+        assert_eq!(tokens, vec![Token::Text("vacation".into())]);
+    }
+
+    #[async_std::test]
+    async fn vacation_script_round_trips() {
+        let script = Vacation {
+            subject: "外出".into(),
+            message: "我下周回来\n\"谢谢\"".into(),
+            start: chrono::NaiveDate::from_ymd_opt(2024, 10, 1),
+            ..Default::default()
+        }
+        .to_script();
+
+        // 服务器把上传的脚本原样返回
+        let replies = format!(
+            "OK\r\nOK\r\n{{{}}}\r\n{}\r\nOK \"GETSCRIPT completed\"\r\n\"vacation\" ACTIVE\r\n\"other\"\r\nOK\r\n",
+            script.len(),
+            script
+        );
+        let (mut client, sent) = client(&replies);
+        client.put_script(VACATION_SCRIPT, &script).await.unwrap();
+        client.set_active(VACATION_SCRIPT).await.unwrap();
+        let downloaded = client.get_script(VACATION_SCRIPT).await.unwrap();
+        let scripts = client.list_scripts().await.unwrap();
+
+        assert_eq!(downloaded, script);
+        assert_eq!(Vacation::from_script(&downloaded).unwrap().subject, "外出");
+        assert_eq!(
+            scripts,
+            vec![
+                SieveScript { name: "vacation".into(), active: true },
+                SieveScript { name: "other".into(), active: false }
+            ]
+        );
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            sent,
+            format!(
+                "PUTSCRIPT \"vacation\" {{{}+}}\r\n{}\r\nSETACTIVE \"vacation\"\r\nGETSCRIPT \"vacation\"\r\nLISTSCRIPTS\r\n",
+                script.len(),
+                script
+            )
+        );
+    }
+
+    #[async_std::test]
+    async fn server_errors_carry_code_and_message() {
+        let (mut client, _) = client("NO (QUOTA/MAXSIZE) \"脚本太大\"\r\nOK (WARNINGS) \"line 3: 未使用的扩展\"\r\n");
+        let error = client.put_script("big", "keep;").await.unwrap_err();
+        let error = error.downcast_ref::<SieveError>().unwrap();
+        assert_eq!(error.code.as_deref(), Some("QUOTA/MAXSIZE"));
+        assert_eq!(error.message, "脚本太大");
+        assert_eq!(client.check_script("keep;").await.unwrap().as_deref(), Some("line 3: 未使用的扩展"));
+    }
+}
@@ -0,0 +1,363 @@
+use std::io::{self, Write};
+use chrono::{DateTime, Utc};
+use mailparse::{parse_headers, MailHeaderMap};
+
+// 导出时使用的 mbox 变体
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MboxFormat {
+    // 正文中以 ">*From " 开头的行多加一个 ">"，导入时可以无损还原
+    Mboxrd,
+    // 不转义正文，用 Content-Length 头记录邮件长度
+    Mboxcl2,
+}
+
+// 从 mbox 中读出的一封邮件
+#[derive(Clone, Debug)]
+pub struct MboxMessage {
+    pub raw: Vec<u8>,
+    pub seen: bool, // 根据 Status / X-Mozilla-Status 头判断
+}
+
+// 拆分 mbox 文件
+// 带 Content-Length 的邮件按长度读取（mboxcl/mboxcl2），其余按 "From " 分隔行拆分并还原 mboxrd 转义
+pub fn parse_mbox(data: &[u8]) -> Vec<MboxMessage> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+
+    // 跳过文件开头可能存在的空行
+    while pos < data.len() && (data[pos] == b'\n' || data[pos] == b'\r') {
+        pos += 1;
+    }
+
+    while pos < data.len() {
+        if !data[pos..].starts_with(b"From ") {
+            // 不是标准 mbox，整个文件当作一封邮件
+            if messages.is_empty() {
+                messages.push(to_message(data[pos..].to_vec()));
+            }
+            break;
+        }
+
+        // 跳过 From_ 分隔行
+        let body_start = next_line(data, pos);
+        let header_end = find_header_end(data, body_start);
+
+        let content_length = parse_headers(&data[body_start..header_end])
+            .ok()
+            .and_then(|(headers, _)| headers.get_first_value("Content-Length"))
+            .and_then(|v| v.trim().parse::<usize>().ok());
+
+        // Content-Length 指向下一封邮件的分隔行时才采用，避免错误的长度截断邮件
+        let content_start = next_line(data, header_end.min(data.len().saturating_sub(1)));
+        let by_length = content_length.and_then(|length| {
+            let end = content_start + length;
+            let next = skip_blank_lines(data, end.min(data.len()));
+            (end <= data.len() && (next >= data.len() || data[next..].starts_with(b"From "))).then_some((end, next))
+        });
+
+        let (raw, next) = match by_length {
+            Some((end, next)) => (data[body_start..end].to_vec(), next),
+            None => {
+                let end = find_next_separator(data, header_end);
+                (unescape_from_lines(trim_separator(&data[body_start..end])), end)
+            }
+        };
+
+        messages.push(to_message(raw));
+        pos = next;
+    }
+
+    messages
+}
+
+// 写入一封邮件到 mbox
+pub fn write_message<W: Write>(
+    out: &mut W,
+    sender: &str,
+    date: DateTime<Utc>,
+    raw: &[u8],
+    format: MboxFormat,
+) -> io::Result<()> {
+    // mbox 使用 LF 换行
+    let raw = to_lf(raw);
+    let sender = if sender.trim().is_empty() { "MAILER-DAEMON" } else { sender.trim() };
+    writeln!(out, "From {} {}", sender, date.format("%a %b %e %H:%M:%S %Y"))?;
+
+    match format {
+        MboxFormat::Mboxrd => {
+            for line in raw.split_inclusive(|b| *b == b'\n') {
+                let stripped = line.iter().skip_while(|b| **b == b'>').copied().collect::<Vec<u8>>();
+                if stripped.starts_with(b"From ") {
+                    out.write_all(b">")?;
+                }
+                out.write_all(line)?;
+            }
+            if !raw.ends_with(b"\n") {
+                out.write_all(b"\n")?;
+            }
+        }
+        MboxFormat::Mboxcl2 => {
+            let header_end = find_header_end(&raw, 0);
+            let (headers, body) = raw.split_at(header_end);
+            // 去掉原有的 Content-Length，避免与实际长度不符
+            for line in split_header_fields(headers) {
+                if !line.to_ascii_lowercase().starts_with(b"content-length:") {
+                    out.write_all(line)?;
+                }
+            }
+            // body 以分隔头部的空行开头，长度不包括这一行
+            writeln!(out, "Content-Length: {}", body.len().saturating_sub(1))?;
+            if body.is_empty() {
+                out.write_all(b"\n")?;
+            }
+            out.write_all(body)?;
+            if !body.ends_with(b"\n") {
+                out.write_all(b"\n")?;
+            }
+        }
+    }
+
+    // 邮件之间空一行
+    out.write_all(b"\n")
+}
+
+// 导入的邮件 ID 由内容计算，重复导入同一个文件不会产生重复邮件
+pub fn content_id(raw: &[u8]) -> String {
+    format!("mbox-{:016x}", content_hash(raw))
+}
+
+// FNV-1a，结果在不同版本和平台上保持一致
+pub fn content_hash(raw: &[u8]) -> u64 {
+    raw.iter().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// IMAP APPEND 要求 CRLF 换行
+pub fn to_crlf(raw: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(raw.len() + raw.len() / 40);
+    let mut previous = 0u8;
+    for b in raw {
+        if *b == b'\n' && previous != b'\r' {
+            result.push(b'\r');
+        }
+        result.push(*b);
+        previous = *b;
+    }
+    result
+}
+
+fn to_lf(raw: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(raw.len());
+    for (i, b) in raw.iter().enumerate() {
+        if *b == b'\r' && raw.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        result.push(*b);
+    }
+    result
+}
+
+fn to_message(raw: Vec<u8>) -> MboxMessage {
+    let seen = parse_headers(&raw)
+        .map(|(headers, _)| {
+            let status = headers.get_first_value("Status").unwrap_or_default();
+            // Thunderbird 的 X-Mozilla-Status 为十六进制，最低位表示已读
+            let mozilla_read = headers
+                .get_first_value("X-Mozilla-Status")
+                .and_then(|v| u32::from_str_radix(v.trim(), 16).ok())
+                .is_some_and(|v| v & 0x0001 != 0);
+            status.contains('R') || mozilla_read
+        })
+        .unwrap_or(false);
+    MboxMessage { raw, seen }
+}
+
+fn next_line(data: &[u8], pos: usize) -> usize {
+    data[pos..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(data.len(), |i| pos + i + 1)
+}
+
+// 头部结束位置（指向分隔头部和正文的空行）
+fn find_header_end(data: &[u8], start: usize) -> usize {
+    let mut pos = start;
+    while pos < data.len() {
+        let line_end = next_line(data, pos);
+        let line = &data[pos..line_end];
+        if line == b"\n" || line == b"\r\n" {
+            return pos;
+        }
+        pos = line_end;
+    }
+    data.len()
+}
+
+// 下一封邮件的 "From " 分隔行位置，分隔行前面必须是空行或文件开头
+fn find_next_separator(data: &[u8], start: usize) -> usize {
+    let mut pos = start;
+    while pos < data.len() {
+        let line_end = next_line(data, pos);
+        if data[line_end..].starts_with(b"From ") {
+            let line = &data[pos..line_end];
+            if line == b"\n" || line == b"\r\n" {
+                return line_end;
+            }
+        }
+        pos = line_end;
+    }
+    data.len()
+}
+
+fn skip_blank_lines(data: &[u8], mut pos: usize) -> usize {
+    while pos < data.len() && (data[pos] == b'\n' || data[pos] == b'\r') {
+        pos += 1;
+    }
+    pos
+}
+
+// 去掉邮件末尾用于分隔的空行
+fn trim_separator(raw: &[u8]) -> &[u8] {
+    if raw.ends_with(b"\r\n\r\n") {
+        &raw[..raw.len() - 2]
+    } else if raw.ends_with(b"\n\n") {
+        &raw[..raw.len() - 1]
+    } else {
+        raw
+    }
+}
+
+// mboxrd：去掉 ">*From " 行开头的一个 ">"
+fn unescape_from_lines(raw: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(raw.len());
+    for line in raw.split_inclusive(|b| *b == b'\n') {
+        let quotes = line.iter().take_while(|b| **b == b'>').count();
+        if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            result.extend_from_slice(&line[1..]);
+        } else {
+            result.extend_from_slice(line);
+        }
+    }
+    result
+}
+
+// 按头部字段拆分（续行属于同一个字段）
+fn split_header_fields(headers: &[u8]) -> Vec<&[u8]> {
+    let mut fields: Vec<&[u8]> = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < headers.len() {
+        let line_end = next_line(headers, pos);
+        let is_continuation = pos > start && matches!(headers.get(pos), Some(b' ') | Some(b'\t'));
+        if pos > start && !is_continuation {
+            fields.push(&headers[start..pos]);
+            start = pos;
+        }
+        pos = line_end;
+    }
+    if start < headers.len() {
+        fields.push(&headers[start..]);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(messages: &[&[u8]], format: MboxFormat) -> Vec<u8> {
+        let date = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut out = Vec::new();
+        for raw in messages {
+            write_message(&mut out, "a@example.com", date, raw, format).unwrap();
+        }
+        out
+    }
+
+    // mboxrd：写入时 ">*From " 行多加一个 ">"，读取时去掉，正文原样还原
+    #[test]
+    fn mboxrd_escapes_from_lines_both_ways() {
+        let raw: &[u8] = b"Subject: quoting\n\nFrom here\n>From there\n>>From deep\nFromage\n";
+        let out = export(&[raw, b"Subject: second\n\nbody\n"], MboxFormat::Mboxrd);
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.starts_with("From a@example.com Tue Nov 14 22:13:20 2023\nSubject: quoting\n"));
+        assert!(text.contains("\n>From here\n>>From there\n>>>From deep\nFromage\n\nFrom a@example.com "));
+
+        let messages = parse_mbox(&out);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].raw, raw);
+        assert_eq!(messages[1].raw, b"Subject: second\n\nbody\n");
+        assert_eq!(unescape_from_lines(b">From a\n>>From b\n>not from\nFrom c\n"), b"From a\n>From b\n>not from\nFrom c\n");
+    }
+
+    // mboxcl2：不转义正文，按 Content-Length 读取，正文中以 "From " 开头的行不会拆分邮件
+    #[test]
+    fn mboxcl2_round_trips_by_content_length() {
+        let body: &[u8] = b"first\n\nFrom the middle of a paragraph\n>From quoted\n";
+        let raw = [b"Subject: long\nContent-Length: 3\n\n".as_slice(), body].concat();
+        let out = export(&[&raw, b"Subject: empty\n"], MboxFormat::Mboxcl2);
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.contains(&format!("Content-Length: {}\n\n", body.len())));
+        assert!(!text.contains("Content-Length: 3"));
+        assert!(text.contains("\nFrom the middle of a paragraph\n>From quoted\n"));
+
+        let messages = parse_mbox(&out);
+        assert_eq!(messages.len(), 2);
+        let expected = [b"Subject: long\nContent-Length: ".as_slice(), body.len().to_string().as_bytes(), b"\n\n", body].concat();
+        assert_eq!(messages[0].raw, expected);
+        assert_eq!(messages[1].raw, b"Subject: empty\nContent-Length: 0\n\n");
+    }
+
+    // 长度不对的 Content-Length 不采用，改按分隔行拆分
+    #[test]
+    fn wrong_content_length_falls_back_to_separators() {
+        let data = b"From a Mon Jan  1 00:00:00 2024\nContent-Length: 2\n\nbody text\n\nFrom b Mon Jan  1 00:00:00 2024\nSubject: b\n\nsecond\n";
+        let messages = parse_mbox(data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].raw, b"Content-Length: 2\n\nbody text\n");
+        assert_eq!(messages[1].raw, b"Subject: b\n\nsecond\n");
+    }
+
+    // CRLF 换行的 mbox 也能拆分，写入时统一为 LF
+    #[test]
+    fn crlf_input() {
+        let data = b"\r\nFrom a Mon Jan  1 00:00:00 2024\r\nSubject: x\r\nStatus: RO\r\n\r\nbody\r\n\r\n\
+                     From b Mon Jan  1 00:00:00 2024\r\nSubject: y\r\nX-Mozilla-Status: 0000\r\n\r\nbody2\r\n";
+        let messages = parse_mbox(data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].raw, b"Subject: x\r\nStatus: RO\r\n\r\nbody\r\n");
+        assert!(messages[0].seen);
+        assert_eq!(messages[1].raw, b"Subject: y\r\nX-Mozilla-Status: 0000\r\n\r\nbody2\r\n");
+        assert!(!messages[1].seen);
+
+        let out = export(&[&messages[0].raw], MboxFormat::Mboxrd);
+        assert!(!out.contains(&b'\r'));
+        assert_eq!(parse_mbox(&out)[0].raw, b"Subject: x\nStatus: RO\n\nbody\n");
+    }
+
+    // 最后一封邮件没有结尾的换行
+    #[test]
+    fn trailing_message_without_final_newline() {
+        let data = b"From a Mon Jan  1 00:00:00 2024\nSubject: x\n\nbody\n\nFrom b Mon Jan  1 00:00:00 2024\nSubject: y\n\nlast line";
+        let messages = parse_mbox(data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].raw, b"Subject: y\n\nlast line");
+
+        // 写入时补上换行；mboxcl2 按长度读取，能还原没有换行的结尾
+        let out = export(&[b"Subject: y\n\nlast line"], MboxFormat::Mboxrd);
+        assert!(out.ends_with(b"last line\n\n"));
+        assert_eq!(parse_mbox(&out)[0].raw, b"Subject: y\n\nlast line\n");
+        let out = export(&[b"Subject: y\n\nlast line"], MboxFormat::Mboxcl2);
+        assert!(out.ends_with(b"last line\n\n"));
+        assert_eq!(parse_mbox(&out)[0].raw, b"Subject: y\nContent-Length: 9\n\nlast line");
+    }
+
+    // 不是 mbox 格式的文件当作一封邮件
+    #[test]
+    fn plain_message_is_single_entry() {
+        let messages = parse_mbox(b"Subject: x\n\nFrom here\n");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].raw, b"Subject: x\n\nFrom here\n");
+    }
+}
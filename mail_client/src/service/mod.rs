@@ -1,8 +1,36 @@
 pub mod imap_client;
+pub mod managesieve;
+pub mod sieve;
 pub mod smtp_client;
 pub mod local_storage;
+pub mod mail_db;
+pub mod blob_store;
+pub mod maildir;
+pub mod mbox;
+pub mod eml;
+pub mod account_check;
+pub mod autoconfig;
+pub mod mail_sync;
+pub mod address_book;
+pub mod vcard;
+pub mod dav;
+pub mod carddav;
+pub mod caldav;
+pub mod filter;
+pub mod spam;
+pub mod label;
+pub mod batch;
 
 
-pub use imap_client::fetch_emails;
-pub use smtp_client::send_email;
-pub use local_storage::{LocalStorage, LayoutSettings};
\ No newline at end of file
+pub use local_storage::{LocalStorage, AccountSettings, StorageBackend, ListView, ListDensity};
+pub use account_check::verify_account;
+pub use autoconfig::discover_settings;
+pub use mail_sync::{sync_all_accounts, count_search_unread, SEARCH_EXCLUDED};
+pub use mail_db::{MessageScope, SortField};
+pub use mbox::MboxFormat;
+pub use address_book::Suggestion;
+pub use vcard::VCardVersion;
+pub use dav::DavService;
+pub use carddav::sync_all_contacts;
+pub use caldav::Invitation;
+pub use batch::BatchAction;
\ No newline at end of file
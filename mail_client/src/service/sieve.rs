@@ -0,0 +1,312 @@
+use chrono::NaiveDate;
+
+// 休假自动回复保存的脚本名称
+pub const VACATION_SCRIPT: &str = "vacation";
+
+// 休假自动回复的设置，对应 Sieve vacation 扩展（RFC 5230）
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vacation {
+    pub subject: String,
+    pub message: String,
+    pub days: u32,               // 同一发件人多少天内只回复一次
+    pub addresses: Vec<String>,  // 账户的其他地址，发到这些地址的邮件也会回复
+    pub start: Option<NaiveDate>, // 生效日期，需要 date 扩展
+    pub end: Option<NaiveDate>,
+}
+
+impl Default for Vacation {
+    fn default() -> Self {
+        Vacation {
+            subject: String::new(),
+            message: String::new(),
+            days: 7,
+            addresses: Vec::new(),
+            start: None,
+            end: None,
+        }
+    }
+}
+
+impl Vacation {
+    // 生成 Sieve 脚本
+    pub fn to_script(&self) -> String {
+        let mut require = vec!["\"vacation\""];
+        if self.start.is_some() || self.end.is_some() {
+            require.extend(["\"date\"", "\"relational\""]);
+        }
+
+        let mut action = format!("vacation :days {}", self.days.max(1));
+        if !self.subject.trim().is_empty() {
+            action.push_str(&format!(" :subject {}", quote(self.subject.trim())));
+        }
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .map(quote)
+            .collect();
+        if !addresses.is_empty() {
+            action.push_str(&format!(" :addresses [{}]", addresses.join(", ")));
+        }
+        action.push_str(&format!(" {};", quote(&self.message)));
+
+        let mut dates = Vec::new();
+        if let Some(start) = self.start {
+            dates.push(format!("currentdate :value \"ge\" \"date\" \"{}\"", start.format("%Y-%m-%d")));
+        }
+        if let Some(end) = self.end {
+            dates.push(format!("currentdate :value \"le\" \"date\" \"{}\"", end.format("%Y-%m-%d")));
+        }
+
+        let mut script = format!("# 休假自动回复\nrequire [{}];\n\n", require.join(", "));
+        if dates.is_empty() {
+            script.push_str(&action);
+        } else {
+            script.push_str(&format!("if allof ({}) {{\n    {}\n}}", dates.join(",\n          "), action));
+        }
+        script.push('\n');
+        script
+    }
+
+    // 从脚本中读出第一个 vacation 动作，用来填充设置表单
+    pub fn from_script(script: &str) -> Option<Vacation> {
+        let tokens = tokenize(script);
+        let position = tokens.iter().position(|t| *t == Token::Identifier("vacation".to_string()))?;
+
+        let mut vacation = Vacation { days: 0, ..Default::default() };
+        let mut args = tokens[position + 1..].iter();
+        while let Some(token) = args.next() {
+            match token {
+                Token::Semicolon => break,
+                Token::Tag(tag) => match tag.as_str() {
+                    "days" => vacation.days = args.next().and_then(Token::number).unwrap_or(0),
+                    "seconds" => {
+                        let seconds = args.next().and_then(Token::number).unwrap_or(0);
+                        vacation.days = seconds.div_ceil(86400);
+                    }
+                    "subject" => vacation.subject = args.next().and_then(Token::string).unwrap_or_default(),
+                    "addresses" => vacation.addresses = string_list(&mut args),
+                    "from" | "handle" => {
+                        args.next();
+                    }
+                    _ => {}
+                },
+                Token::String(message) => vacation.message = message.clone(),
+                _ => {}
+            }
+        }
+        if vacation.days == 0 {
+            vacation.days = Vacation::default().days;
+        }
+
+        // currentdate :value "ge"/"le" "date" "YYYY-MM-DD"
+        for window in tokens[..position].windows(5) {
+            if window[0] != Token::Identifier("currentdate".to_string())
+                || window[1] != Token::Tag("value".to_string())
+                || window[3].string().as_deref() != Some("date")
+            {
+                continue;
+            }
+            let date = window[4].string().and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+            match window[2].string().as_deref() {
+                Some("ge") | Some("gt") => vacation.start = date,
+                Some("le") | Some("lt") => vacation.end = date,
+                _ => {}
+            }
+        }
+        Some(vacation)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Tag(String),
+    String(String),
+    Number(u64),
+    Punct(char),
+    Semicolon,
+}
+
+impl Token {
+    fn number(&self) -> Option<u32> {
+        match self {
+            Token::Number(n) => u32::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    fn string(&self) -> Option<String> {
+        match self {
+            Token::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+// 字符串或字符串列表
+fn string_list<'a>(args: &mut impl Iterator<Item = &'a Token>) -> Vec<String> {
+    let mut list = Vec::new();
+    match args.next() {
+        Some(Token::String(s)) => list.push(s.clone()),
+        Some(Token::Punct('[')) => {
+            for token in args.by_ref() {
+                match token {
+                    Token::String(s) => list.push(s.clone()),
+                    Token::Punct(']') => break,
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    list
+}
+
+// Sieve 词法分析（RFC 5228 第 8.1 节），注释被忽略
+fn tokenize(script: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = script.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+                    text.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push(Token::String(text.replace("\r\n", "\n")));
+            }
+            ':' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Tag(chars[start..i].iter().collect::<String>().to_lowercase()));
+                continue;
+            }
+            ';' => tokens.push(Token::Semicolon),
+            '[' | ']' | '(' | ')' | '{' | '}' | ',' => tokens.push(Token::Punct(c)),
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let mut number: u64 = chars[start..i].iter().collect::<String>().parse().unwrap_or(0);
+                match chars.get(i).map(|c| c.to_ascii_uppercase()) {
+                    Some('K') => number <<= 10,
+                    Some('M') => number <<= 20,
+                    Some('G') => number <<= 30,
+                    _ => i -= 1,
+                }
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                // text: 多行字符串，以单独一行的 "." 结束，".." 开头的行去掉一个点
+                if word == "text" && chars.get(i) == Some(&':') {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                    let rest: String = chars[(i + 1).min(chars.len())..].iter().collect();
+                    let mut text = Vec::new();
+                    let mut consumed = 0;
+                    for line in rest.split_inclusive('\n') {
+                        consumed += line.chars().count();
+                        let line = line.trim_end_matches(['\r', '\n']);
+                        if line == "." {
+                            break;
+                        }
+                        text.push(line.strip_prefix('.').filter(|l| l.starts_with('.')).unwrap_or(line));
+                    }
+                    tokens.push(Token::String(text.join("\n")));
+                    i += 1 + consumed;
+                    continue;
+                }
+                tokens.push(Token::Identifier(word));
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    tokens
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vacation_round_trips_through_script() {
+        let vacation = Vacation {
+            subject: "外出 \"休假\"".into(),
+            message: "您好，\n我 10 月 1 日返回。\\ 谢谢".into(),
+            days: 3,
+            addresses: vec!["me@example.com".into(), " alias@example.com ".into(), "".into()],
+            start: NaiveDate::from_ymd_opt(2024, 10, 1),
+            end: NaiveDate::from_ymd_opt(2024, 10, 7),
+        };
+        let script = vacation.to_script();
+        assert!(script.contains("require [\"vacation\", \"date\", \"relational\"];"));
+
+        let parsed = Vacation::from_script(&script).unwrap();
+        assert_eq!(parsed.addresses, vec!["me@example.com", "alias@example.com"]);
+        assert_eq!(parsed, Vacation { addresses: parsed.addresses.clone(), ..vacation });
+    }
+
+    #[test]
+    fn vacation_without_dates_is_unconditional() {
+        let vacation = Vacation { message: "不在".into(), ..Default::default() };
+        let script = vacation.to_script();
+        assert!(!script.contains("currentdate"));
+        assert_eq!(Vacation::from_script(&script), Some(vacation));
+    }
+
+    #[test]
+    fn reads_scripts_written_by_other_clients() {
+        // 多行 text:、:seconds、注释和其他规则
+        let script = "require [\"fileinto\", \"vacation\"];\r\n\
+            /* 其他客户端生成 */\r\n\
+            if header :contains \"subject\" \"spam\" { fileinto \"Junk\"; }\r\n\
+            vacation :seconds 90000 :from \"me@example.com\" :addresses \"a@example.com\" text:\r\n\
+            第一行\r\n\
+            ..点开头\r\n\
+            .\r\n\
+            ;\r\n";
+        let vacation = Vacation::from_script(script).unwrap();
+        assert_eq!(vacation.days, 2);
+        assert_eq!(vacation.addresses, vec!["a@example.com"]);
+        assert_eq!(vacation.message, "第一行\n.点开头");
+        assert_eq!(vacation.subject, "");
+        assert_eq!(Vacation::from_script("keep;"), None);
+    }
+}
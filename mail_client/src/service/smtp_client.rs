@@ -1,9 +1,10 @@
-use crate::models::{Email, Attachment};
+use crate::models::{Address, Email};
 use lettre::{
-    Message, SmtpTransport, Transport,
+    Message, SmtpTransport, Transport, address::Envelope,
     transport::smtp::authentication::Credentials,
-    message::{header, MultiPart, SinglePart, Attachment as LettreAttachment, Mailbox}
+    message::{MultiPart, SinglePart, Attachment as LettreAttachment, Mailbox}
 };
+use std::time::{Duration, UNIX_EPOCH};
 
 pub fn send_email(
     smtp_server: &str,
@@ -15,20 +16,58 @@ pub fn send_email(
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let creds = Credentials::new(username.to_string(), password.to_string());
 
-    let mut builder = Message::builder().from(username.parse()?);
+    let email_builder = build_message(username.parse()?, email)?;
 
-    for addr in &email.to {
-        builder = builder.to(addr.parse::<Mailbox>()?);
+    let mailer = if use_tls && uses_implicit_tls(smtp_port) {
+        SmtpTransport::relay(smtp_server)?
+            .port(smtp_port)
+            .credentials(creds)
+            .build()
+    } else if use_tls {
+        SmtpTransport::starttls_relay(smtp_server)?
+            .port(smtp_port)
+            .credentials(creds)
+            .build()
+    } else {
+        SmtpTransport::builder_dangerous(smtp_server)
+            .port(smtp_port)
+            .credentials(creds)
+            .build()
+    };
+
+    mailer.send(&email_builder)?;
+    Ok(())
+}
+
+// 根据 Email 生成 RFC822 邮件，发送和导出 Maildir 共用
+pub fn build_message(from: Mailbox, email: &Email) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = Message::builder().from(from.clone());
+
+    // 没有收件人的邮件（如导出没有 To 头的邮件）只能用发件人作为 SMTP 信封收件人，不影响邮件头
+    if email.to.iter().chain(&email.cc).chain(&email.bcc).all(|a| a.mailboxes().is_empty()) {
+        builder = builder.envelope(Envelope::new(Some(from.email.clone()), vec![from.email])?);
+    }
+
+    // 地址组展开为组内的邮箱，中文显示名由 lettre 按 RFC 2047 编码
+    for mailbox in email.to.iter().flat_map(Address::mailboxes) {
+        builder = builder.to(to_lettre_mailbox(mailbox)?);
     }
-    for addr in &email.cc {
-        builder = builder.cc(addr.parse::<Mailbox>()?);
+    for mailbox in email.cc.iter().flat_map(Address::mailboxes) {
+        builder = builder.cc(to_lettre_mailbox(mailbox)?);
     }
-    for addr in &email.bcc {
-        builder = builder.bcc(addr.parse::<Mailbox>()?);
+    for mailbox in email.bcc.iter().flat_map(Address::mailboxes) {
+        builder = builder.bcc(to_lettre_mailbox(mailbox)?);
     }
 
     builder = builder.subject(&email.subject);
 
+    // 保留原邮件的日期，无法识别时使用当前时间
+    if let Some(time) = email.timestamp() {
+        if time.timestamp() >= 0 {
+            builder = builder.date(UNIX_EPOCH + Duration::from_secs(time.timestamp() as u64));
+        }
+    }
+
     let mut multipart = MultiPart::mixed().singlepart(
         SinglePart::plain(email.body.clone())
     );
@@ -40,20 +79,16 @@ pub fn send_email(
         );
     }
 
-    let email_builder = builder.multipart(multipart)?;
+    Ok(builder.multipart(multipart)?)
+}
 
-    let mailer = if use_tls {
-        SmtpTransport::relay(smtp_server)?
-            .port(smtp_port)
-            .credentials(creds)
-            .build()
-    } else {
-        SmtpTransport::builder_dangerous(smtp_server)
-            .port(smtp_port)
-            .credentials(creds)
-            .build()
-    };
+// 转换为 lettre 的邮箱，国际化域名转换为 punycode
+pub fn to_lettre_mailbox(mailbox: &crate::models::Mailbox) -> Result<Mailbox, lettre::address::AddressError> {
+    let name = mailbox.name.clone().filter(|name| !name.trim().is_empty());
+    Ok(Mailbox::new(name, mailbox.ascii_address().parse()?))
+}
 
-    mailer.send(&email_builder)?;
-    Ok(())
+// 465 端口使用 SSL 直连，其余端口（如 587、25）先明文连接再 STARTTLS
+pub fn uses_implicit_tls(smtp_port: u16) -> bool {
+    smtp_port == 465
 }
\ No newline at end of file
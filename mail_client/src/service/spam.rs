@@ -0,0 +1,319 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::{Serialize, Deserialize};
+use crate::models::{html_to_text, Email, EmailAccount};
+use crate::service::imap_client;
+use crate::service::local_storage::LocalStorage;
+
+type SpamResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 判为垃圾邮件后移到的本地文件夹
+pub const JUNK_FOLDER: &str = "Junk";
+// 服务器上表示垃圾/非垃圾邮件的关键字（RFC 5788 注册）
+pub const KEYWORD_JUNK: &str = "$Junk";
+pub const KEYWORD_NOT_JUNK: &str = "$NotJunk";
+
+// 得分达到该值才当作垃圾邮件，宁可漏判也不误判
+const SPAM_THRESHOLD: f64 = 0.9;
+// 两类邮件都训练到这个数量后才开始自动判断
+const MIN_TRAINED: u32 = 5;
+// 只用偏离 0.5 最多的这些词计算得分
+const INTERESTING_TOKENS: usize = 15;
+// 正文只取前面这部分，避免超长邮件拖慢同步
+const MAX_BODY_CHARS: usize = 20_000;
+
+// 每个账户一个的朴素贝叶斯模型
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct SpamModel {
+    pub spam_count: u32,
+    pub ham_count: u32,
+    tokens: HashMap<String, [u32; 2]>, // 词 -> [出现在垃圾邮件中的次数, 出现在正常邮件中的次数]
+    #[serde(default)]
+    trained: HashMap<String, bool>, // 已训练的邮件 ID -> 是否为垃圾邮件，重复标记时不重复计数
+}
+
+impl SpamModel {
+    // 用户标记后训练；之前按另一类训练过时先撤销，返回模型是否有变化
+    pub fn train(&mut self, email: &Email, spam: bool) -> bool {
+        let previous = self.trained.get(&email.id).copied();
+        if previous == Some(spam) {
+            return false;
+        }
+        let tokens = tokenize(email);
+        if let Some(previous) = previous {
+            self.adjust(&tokens, previous, false);
+        }
+        self.adjust(&tokens, spam, true);
+        self.trained.insert(email.id.clone(), spam);
+        true
+    }
+
+    fn adjust(&mut self, tokens: &BTreeSet<String>, spam: bool, add: bool) {
+        let index = if spam { 0 } else { 1 };
+        let count = if spam { &mut self.spam_count } else { &mut self.ham_count };
+        *count = if add { *count + 1 } else { count.saturating_sub(1) };
+        for token in tokens {
+            let counts = self.tokens.entry(token.clone()).or_default();
+            counts[index] = if add { counts[index] + 1 } else { counts[index].saturating_sub(1) };
+            if *counts == [0, 0] {
+                self.tokens.remove(token);
+            }
+        }
+    }
+
+    // 是否已经训练了足够的邮件
+    pub fn is_ready(&self) -> bool {
+        self.spam_count >= MIN_TRAINED && self.ham_count >= MIN_TRAINED
+    }
+
+    // 垃圾邮件的概率（0 到 1），训练不足时为 None
+    pub fn score(&self, email: &Email) -> Option<f64> {
+        if !self.is_ready() {
+            return None;
+        }
+        let mut probabilities: Vec<f64> = tokenize(email)
+            .iter()
+            .filter_map(|token| self.token_probability(token))
+            .collect();
+        if probabilities.is_empty() {
+            return Some(0.5);
+        }
+        probabilities.sort_by(|a, b| (b - 0.5).abs().total_cmp(&(a - 0.5).abs()));
+        probabilities.truncate(INTERESTING_TOKENS);
+
+        // 在对数空间合并，避免连乘下溢
+        let (spam, ham) = probabilities
+            .iter()
+            .fold((0.0, 0.0), |(spam, ham), p| (spam + p.ln(), ham + (1.0 - p).ln()));
+        Some(1.0 / (1.0 + (ham - spam).exp()))
+    }
+
+    pub fn is_spam(&self, email: &Email) -> bool {
+        self.score(email).is_some_and(|score| score >= SPAM_THRESHOLD)
+    }
+
+    // 单个词的垃圾概率，出现次数少时向 0.5 靠拢（Robinson 的修正）
+    fn token_probability(&self, token: &str) -> Option<f64> {
+        let [spam, ham] = *self.tokens.get(token)?;
+        let spam_freq = spam as f64 / self.spam_count.max(1) as f64;
+        let ham_freq = ham as f64 / self.ham_count.max(1) as f64;
+        if spam_freq + ham_freq == 0.0 {
+            return None;
+        }
+        let p = spam_freq / (spam_freq + ham_freq);
+        let n = (spam + ham) as f64;
+        Some(((0.5 + n * p) / (1.0 + n)).clamp(0.01, 0.99))
+    }
+}
+
+// 提取邮件中的词：发件人、主题、正文、链接域名和附件类型，每个词只计一次
+pub fn tokenize(email: &Email) -> BTreeSet<String> {
+    let mut tokens = BTreeSet::new();
+
+    let address = email.from.address.to_lowercase();
+    if let Some((_, domain)) = address.rsplit_once('@') {
+        tokens.insert(format!("from-domain:{}", domain));
+    }
+    tokens.insert(format!("from:{}", address));
+
+    for word in words(&email.subject) {
+        tokens.insert(format!("subject:{}", word));
+    }
+
+    let body = match (&email.html, email.body.trim().is_empty()) {
+        (Some(html), true) => html_to_text(html),
+        _ => email.body.clone(),
+    };
+    let body: String = body.chars().take(MAX_BODY_CHARS).collect();
+    for host in link_hosts(&body).into_iter().chain(email.html.as_deref().map(link_hosts).unwrap_or_default()) {
+        tokens.insert(format!("url:{}", host));
+    }
+    tokens.extend(words(&body));
+
+    for attachment in &email.attachments {
+        if let Some((_, ext)) = attachment.filename.rsplit_once('.') {
+            tokens.insert(format!("attach:{}", ext.to_lowercase()));
+        }
+    }
+    if email.html.is_some() && email.body.trim().is_empty() {
+        tokens.insert("html-only".to_string());
+    }
+    tokens
+}
+
+// 中日韩文字没有空格分词，连续的字取相邻两字组成词；其他文字按字母数字连续的部分分词
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+
+    let flush_cjk = |cjk: &mut Vec<char>, words: &mut Vec<String>| {
+        match cjk.len() {
+            0 => {}
+            1 => words.push(cjk[0].to_string()),
+            _ => words.extend(cjk.windows(2).map(|pair| pair.iter().collect::<String>())),
+        }
+        cjk.clear();
+    };
+    let flush_word = |word: &mut String, words: &mut Vec<String>| {
+        let length = word.chars().count();
+        if (2..=24).contains(&length) && !word.chars().all(|c| c.is_ascii_digit()) {
+            words.push(word.to_lowercase());
+        }
+        word.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut words);
+            cjk.push(c);
+        } else if c.is_alphanumeric() || matches!(c, '$' | '\'' | '-') {
+            flush_cjk(&mut cjk, &mut words);
+            word.push(c);
+        } else {
+            flush_cjk(&mut cjk, &mut words);
+            flush_word(&mut word, &mut words);
+        }
+    }
+    flush_cjk(&mut cjk, &mut words);
+    flush_word(&mut word, &mut words);
+    words
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF   // 平假名、片假名
+        | 0x3400..=0x4DBF // 扩展 A
+        | 0x4E00..=0x9FFF // 基本汉字
+        | 0xAC00..=0xD7AF // 韩文音节
+        | 0xF900..=0xFAFF)
+}
+
+// 文本中链接的域名
+fn link_hosts(text: &str) -> Vec<String> {
+    let lower = text.to_lowercase();
+    let mut hosts = Vec::new();
+    for prefix in ["http://", "https://"] {
+        for (start, _) in lower.match_indices(prefix) {
+            let host: String = lower[start + prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '-'))
+                .collect();
+            if !host.is_empty() {
+                hosts.push(host);
+            }
+        }
+    }
+    hosts
+}
+
+// 用户把邮件标记为垃圾邮件或正常邮件：训练模型、设置本地关键字，并在收件箱和垃圾邮件文件夹之间移动，
+// 返回邮件所在的新文件夹
+pub fn mark_spam(storage: &LocalStorage, account: &EmailAccount, folder: &str, email: &Email, spam: bool)
+    -> SpamResult<String>
+{
+    let mut model = storage.spam_model(account)?;
+    if model.train(email, spam) {
+        storage.save_spam_model(account, &model)?;
+    }
+
+    let (set, unset, target) = if spam {
+        (KEYWORD_JUNK, KEYWORD_NOT_JUNK, JUNK_FOLDER)
+    } else {
+        (KEYWORD_NOT_JUNK, KEYWORD_JUNK, "INBOX")
+    };
+    // 只在收件箱和垃圾邮件之间移动，其他文件夹中的邮件留在原处
+    let target = if folder == "INBOX" || folder == JUNK_FOLDER { target } else { folder };
+    if target != folder {
+        storage.move_emails(account, folder, target, std::slice::from_ref(email))?;
+    }
+    storage.set_flag(account, target, &email.id, set, true)?;
+    storage.set_flag(account, target, &email.id, unset, false)?;
+    Ok(target.to_string())
+}
+
+// 在服务器上邮件所在的文件夹中设置 $Junk / $NotJunk，本地移动不影响服务器上的位置；只在本地的邮件不处理
+pub async fn store_keywords(account: &EmailAccount, email: &Email, spam: bool) -> SpamResult<()> {
+    let Some((mailbox, uid)) = email.server_location() else {
+        return Ok(());
+    };
+    let (set, unset) = if spam { (KEYWORD_JUNK, KEYWORD_NOT_JUNK) } else { (KEYWORD_NOT_JUNK, KEYWORD_JUNK) };
+    imap_client::store_flags(account, mailbox, &[uid], &[set], &[unset]).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::imap_client::parse_email;
+
+    fn email(id: &str, from: &str, subject: &str, body: &str) -> Email {
+        let raw = format!("From: {}\r\nSubject: {}\r\n\r\n{}", from, subject, body);
+        parse_email(id.into(), raw.as_bytes()).unwrap()
+    }
+
+    // 重复标记同一封邮件不重复计数，改为另一类时先撤销之前的训练
+    #[test]
+    fn train_is_idempotent_and_retraining_undoes() {
+        let mut model = SpamModel::default();
+        let message = email("1", "win@prize.example", "Free money", "Click http://prize.example/now");
+        assert!(model.train(&message, true));
+        let trained = model.clone();
+        assert!(!model.train(&message, true));
+        assert_eq!(model, trained);
+        assert_eq!((model.spam_count, model.ham_count), (1, 0));
+
+        assert!(model.train(&message, false));
+        assert_eq!((model.spam_count, model.ham_count), (0, 1));
+        assert!(model.tokens.values().all(|counts| counts[0] == 0 && counts[1] == 1));
+        assert!(model.train(&message, true));
+        assert_eq!(model.tokens, trained.tokens);
+    }
+
+    // 两类邮件都训练够数量之前不做判断
+    #[test]
+    fn scores_only_when_ready() {
+        let mut model = SpamModel::default();
+        let spam = |i: u32| email(&format!("s{}", i), "win@prize.example", "Free money prize", "Claim your free prize at http://prize.example");
+        let ham = |i: u32| email(&format!("h{}", i), "boss@work.example", "Meeting notes", "The project meeting is moved to Monday");
+        for i in 0..MIN_TRAINED {
+            model.train(&spam(i), true);
+            if i + 1 < MIN_TRAINED {
+                model.train(&ham(i), false);
+            }
+        }
+        assert!(!model.is_ready());
+        assert_eq!(model.score(&spam(99)), None);
+        assert!(!model.is_spam(&spam(99)));
+
+        model.train(&ham(MIN_TRAINED), false);
+        assert!(model.is_ready());
+        assert!(model.is_spam(&spam(99)));
+        assert!(!model.is_spam(&ham(99)));
+        assert!(model.score(&ham(99)).unwrap() < 0.5);
+    }
+
+    // 中文按相邻两字分词，其他文字按连续的字母数字分词
+    #[test]
+    fn words_split_cjk_into_bigrams() {
+        assert_eq!(words("免费领取奖品"), vec!["免费", "费领", "领取", "取奖", "奖品"]);
+        assert_eq!(words("点击 Free-Money 赢"), vec!["点击", "free-money", "赢"]);
+        assert_eq!(words("中文English混排"), vec!["中文", "english", "混排"]);
+        // 单个字母和纯数字不作为词
+        assert_eq!(words("a 12345 ok 2024年"), vec!["ok", "年"]);
+        assert_eq!(words("カタカナ"), vec!["カタ", "タカ", "カナ"]);
+    }
+
+    #[test]
+    fn link_hosts_from_text_and_html() {
+        assert_eq!(
+            link_hosts("see HTTPS://Shop.Example.com/deal?x=1 and http://a-b.example:8080/"),
+            vec!["a-b.example", "shop.example.com"]
+        );
+        assert!(link_hosts("no links, just https:// here").is_empty());
+
+        let mut message = email("1", "a@x.example", "hi", "");
+        message.html = Some("<a href=\"https://tracker.example/p\">link</a>".into());
+        assert!(tokenize(&message).contains("url:tracker.example"));
+        assert!(tokenize(&message).contains("html-only"));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::models::{Contact, ContactGroup, Mailbox};
+
+// 导出的 vCard 版本
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VCardVersion {
+    V3,
+    V4,
+}
+
+// vCard 文件中的一项：联系人或分组
+#[derive(Clone, Debug, PartialEq)]
+pub enum VCardEntry {
+    Contact(Contact),
+    // 以 urn:uuid: 引用的成员在导入时按联系人 UID 查找
+    Group { group: ContactGroup, member_uids: Vec<String> },
+}
+
+// 解析 vCard 3.0 / 4.0 文件，可包含多个联系人
+pub fn parse_vcards(text: &str) -> Vec<VCardEntry> {
+    let mut entries = Vec::new();
+    let mut card: Option<Card> = None;
+
+    for line in unfold(text) {
+        let Some((name, params, value)) = split_property(&line) else {
+            continue;
+        };
+        match (name.as_str(), card.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VCARD") => card = Some(Card::default()),
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(entry) = card.take().and_then(Card::finish) {
+                    entries.push(entry);
+                }
+            }
+            (_, Some(card)) => card.add(&name, &params, &value),
+            _ => {}
+        }
+    }
+    entries
+}
+
+// 把联系人和分组写为 vCard 文件
+pub fn write_vcards(contacts: &[Contact], groups: &[ContactGroup], version: VCardVersion) -> String {
+    let mut out = String::new();
+    for contact in contacts {
+        let mut lines = vec![format!("UID:{}", escape(&contact.uid)), format!("FN:{}", escape(contact.display_name()))];
+        lines.push(format!("N:{};;;;", escape(&contact.name)));
+        for email in &contact.emails {
+            lines.push(format!("EMAIL;TYPE=INTERNET:{}", email));
+        }
+        for phone in &contact.phones {
+            lines.push(format!("TEL:{}", escape(phone)));
+        }
+        if !contact.organization.is_empty() {
+            lines.push(format!("ORG:{}", escape(&contact.organization)));
+        }
+        if !contact.note.is_empty() {
+            lines.push(format!("NOTE:{}", escape(&contact.note)));
+        }
+        write_card(&mut out, version, &lines);
+    }
+
+    // 4.0 使用 KIND/MEMBER，3.0 使用 Apple 通讯录等常用的扩展属性
+    let (kind, member) = match version {
+        VCardVersion::V4 => ("KIND", "MEMBER"),
+        VCardVersion::V3 => ("X-ADDRESSBOOKSERVER-KIND", "X-ADDRESSBOOKSERVER-MEMBER"),
+    };
+    for group in groups {
+        let mut lines = vec![
+            format!("UID:{}", escape(&group.uid)),
+            format!("FN:{}", escape(&group.name)),
+            format!("N:{};;;;", escape(&group.name)),
+            format!("{}:group", kind),
+        ];
+        for mailbox in &group.members {
+            lines.push(format!("{}:mailto:{}", member, mailbox.address));
+        }
+        write_card(&mut out, version, &lines);
+    }
+    out
+}
+
+fn write_card(out: &mut String, version: VCardVersion, lines: &[String]) {
+    out.push_str("BEGIN:VCARD\r\n");
+    out.push_str(match version {
+        VCardVersion::V3 => "VERSION:3.0\r\n",
+        VCardVersion::V4 => "VERSION:4.0\r\n",
+    });
+    for line in lines {
+        fold(out, line);
+    }
+    out.push_str("END:VCARD\r\n");
+}
+
+// 解析中的一张名片
+#[derive(Default)]
+struct Card {
+    contact: Contact,
+    given: String,
+    family: String,
+    is_group: bool,
+    members: Vec<Mailbox>,
+    member_uids: Vec<String>,
+}
+
+impl Card {
+    fn add(&mut self, name: &str, params: &str, value: &str) {
+        match name {
+            "UID" => self.contact.uid = strip_urn(value).to_string(),
+            "FN" => self.contact.name = unescape(value),
+            "N" => {
+                let parts = split_unescaped(value, ';');
+                self.family = parts.first().cloned().unwrap_or_default();
+                self.given = parts.get(1).cloned().unwrap_or_default();
+            }
+            "EMAIL" => {
+                let email = unescape(value).trim().to_string();
+                if !email.is_empty() {
+                    // PREF 的地址放在最前面
+                    if params.to_uppercase().contains("PREF") {
+                        self.contact.emails.insert(0, email);
+                    } else {
+                        self.contact.emails.push(email);
+                    }
+                }
+            }
+            "TEL" => {
+                let phone = unescape(value.trim_start_matches("tel:")).trim().to_string();
+                if !phone.is_empty() {
+                    self.contact.phones.push(phone);
+                }
+            }
+            "ORG" => {
+                self.contact.organization = split_unescaped(value, ';').into_iter().next().unwrap_or_default();
+            }
+            "NOTE" => self.contact.note = unescape(value),
+            "KIND" | "X-ADDRESSBOOKSERVER-KIND" => self.is_group = value.eq_ignore_ascii_case("group"),
+            "MEMBER" | "X-ADDRESSBOOKSERVER-MEMBER" => {
+                let value = value.trim();
+                if value.len() > 7 && value[..7].eq_ignore_ascii_case("mailto:") {
+                    self.members.push(Mailbox::new(&value[7..]));
+                } else {
+                    self.member_uids.push(strip_urn(value).to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Option<VCardEntry> {
+        if self.contact.name.trim().is_empty() {
+            self.contact.name = [self.given.trim(), self.family.trim()]
+                .iter()
+                .filter(|part| !part.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        // 没有姓名的联系人导出时 FN 为地址，导入时还原为空
+        if self.contact.primary_email() == Some(self.contact.name.trim()) {
+            self.contact.name.clear();
+        }
+        if self.is_group {
+            let group = ContactGroup { uid: self.contact.uid, name: self.contact.name, members: self.members };
+            return Some(VCardEntry::Group { group, member_uids: self.member_uids });
+        }
+        if self.contact.name.is_empty() && self.contact.emails.is_empty() {
+            return None;
+        }
+        Some(VCardEntry::Contact(self.contact))
+    }
+}
+
+// 合并折行：以空格或制表符开头的行是上一行的延续，iCalendar 使用相同的规则
+pub(crate) fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// 按 RFC 6350 每行不超过 75 字节折行，不拆开多字节字符
+pub(crate) fn fold(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+// 拆分为 (属性名, 参数, 值)，去掉 item1. 这样的分组前缀，参数中引号内的冒号不作为分隔
+pub(crate) fn split_property(line: &str) -> Option<(String, String, String)> {
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    let name = name.rsplit('.').next().unwrap_or(name).trim().to_uppercase();
+    Some((name, params.to_string(), value.to_string()))
+}
+
+fn strip_urn(value: &str) -> &str {
+    let value = value.trim();
+    if value.len() > 9 && value[..9].eq_ignore_ascii_case("urn:uuid:") {
+        &value[9..]
+    } else {
+        value
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// 按未转义的分隔符拆分结构化的值（如 N、ORG）
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            current.push('\\');
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(unescape(&current));
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    parts.push(unescape(&current));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(uid: &str, name: &str, emails: &[&str]) -> Contact {
+        Contact {
+            uid: uid.into(),
+            name: name.into(),
+            emails: emails.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    // 折行不超过 75 字节且不拆开多字节字符，合并后与原文相同
+    #[test]
+    fn fold_and_unfold_multibyte_values() {
+        let line = format!("NOTE:{}", "多字节的备注😀，".repeat(20));
+        let mut out = String::new();
+        fold(&mut out, &line);
+        let physical: Vec<&str> = out.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(physical.len() > 5);
+        assert!(physical.iter().all(|l| l.len() <= 75), "{:?}", physical);
+        assert!(physical[1..].iter().all(|l| l.starts_with(' ')));
+        assert_eq!(unfold(&out), vec![line]);
+
+        // 导入时也接受 LF 换行和制表符开头的续行
+        assert_eq!(unfold("FN:张\n\t三\n\nNOTE:a\r\n  b\r\n"), vec!["FN:张三", "NOTE:a b"]);
+    }
+
+    // 写出后再解析得到相同的联系人和分组，3.0 和 4.0 都一样
+    #[test]
+    fn write_and_parse_round_trip() {
+        let mut zhang = contact("uid-1", "张三, 经理; 北京", &["zhang@example.com", "z@example.org"]);
+        zhang.phones = vec!["+86 10 1234 5678".into()];
+        zhang.organization = "示例公司".into();
+        zhang.note = "第一行\n第二行 \\ 反斜杠，".repeat(5);
+        let nameless = contact("uid-2", "", &["nobody@example.com"]);
+        let group = ContactGroup {
+            uid: "group-1".into(),
+            name: "项目组".into(),
+            members: vec![Mailbox::new("zhang@example.com"), Mailbox::new("nobody@example.com")],
+        };
+
+        for version in [VCardVersion::V3, VCardVersion::V4] {
+            let text = write_vcards(&[zhang.clone(), nameless.clone()], std::slice::from_ref(&group), version);
+            assert!(text.lines().all(|l| l.len() <= 75));
+            assert_eq!(
+                parse_vcards(&text),
+                vec![
+                    VCardEntry::Contact(zhang.clone()),
+                    VCardEntry::Contact(nameless.clone()),
+                    VCardEntry::Group { group: group.clone(), member_uids: Vec::new() },
+                ],
+                "{:?}",
+                version
+            );
+        }
+    }
+
+    // 其他程序导出的 vCard：分组前缀、PREF、只有 N、tel: 和 urn:uuid: 成员
+    #[test]
+    fn parse_other_clients() {
+        let text = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;John;;;\r\nitem1.EMAIL;TYPE=INTERNET:john@work.example\r\n\
+                    EMAIL;TYPE=\"HOME,PREF\":john@home.example\r\nTEL;VALUE=uri:tel:+1-555-0100\r\nORG:Acme\\, Inc.;R&D\r\nEND:VCARD\r\n\
+                    BEGIN:VCARD\r\nVERSION:4.0\r\nUID:urn:uuid:g-1\r\nFN:Team\r\nKIND:group\r\nMEMBER:urn:uuid:c-1\r\n\
+                    MEMBER:mailto:a@example.com\r\nEND:VCARD\r\nBEGIN:VCARD\r\nVERSION:4.0\r\nNOTE:empty\r\nEND:VCARD\r\n";
+        let entries = parse_vcards(text);
+        assert_eq!(entries.len(), 2);
+        let VCardEntry::Contact(john) = &entries[0] else { panic!("{:?}", entries[0]) };
+        assert_eq!(john.name, "John Doe");
+        assert_eq!(john.emails, vec!["john@home.example", "john@work.example"]);
+        assert_eq!(john.phones, vec!["+1-555-0100"]);
+        assert_eq!(john.organization, "Acme, Inc.");
+        assert_eq!(
+            entries[1],
+            VCardEntry::Group {
+                group: ContactGroup { uid: "g-1".into(), name: "Team".into(), members: vec![Mailbox::new("a@example.com")] },
+                member_uids: vec!["c-1".into()],
+            }
+        );
+    }
+}